@@ -0,0 +1,542 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Timelike, Utc};
+
+use crate::config::Preset;
+use crate::db::{Reg, SeasonInfo};
+use crate::ir::RaceGuideEntry;
+use crate::ir_watcher::{Announcement, AnnouncementType};
+
+// The rules for what's worth announcing, kept apart from the polling and sending so
+// they only depend on what they're given, including the time.
+
+// Transition is how a session changed between two looks at the race guide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Opened,
+    Count,
+    // announce is false if nobody had registered, there's nothing to say about that.
+    Closed { announce: bool },
+}
+impl Transition {
+    // the announcement for the transition, if there is one.
+    pub fn announcement(self) -> Option<AnnouncementType> {
+        match self {
+            Transition::Opened => Some(AnnouncementType::Open),
+            Transition::Count => Some(AnnouncementType::Count),
+            Transition::Closed { announce } => announce.then_some(AnnouncementType::Closed),
+        }
+    }
+}
+
+// how a session went from prev to curr, None if nothing changed that matters.
+pub fn transition(prev: &RaceGuideEntry, curr: &RaceGuideEntry) -> Option<Transition> {
+    match (prev.session_id, curr.session_id) {
+        (None, Some(_)) => Some(Transition::Opened),
+        (Some(_), Some(_))
+            if prev.entry_count != curr.entry_count
+                && (prev.entry_count > 0 || curr.entry_count > 0) =>
+        {
+            Some(Transition::Count)
+        }
+        (Some(_), None) => Some(Transition::Closed {
+            announce: prev.entry_count > 0,
+        }),
+        _ => None,
+    }
+}
+
+// a session that's dropped out of the guide has started, so registration for it has
+// closed, if it hadn't already.
+pub fn dropped(prev: &RaceGuideEntry) -> Option<Transition> {
+    prev.session_id.map(|_| Transition::Closed {
+        announce: prev.entry_count > 0,
+    })
+}
+
+// true if a count going from prev to curr is worth announcing for a min_reg..=max_reg
+// watch. Jumping right over the range, e.g. 2 to 10 for 3-5, still counts.
+pub fn count_in_range(min_reg: i64, max_reg: i64, prev: i64, curr: i64) -> bool {
+    (curr >= min_reg && curr <= max_reg) || (prev < min_reg && curr > max_reg)
+}
+
+// the min & max entries used for a watch when they're not given, from half way to
+// official to half way to splitting.
+pub fn default_reg_range(series: &SeasonInfo) -> (i64, i64) {
+    (
+        series.reg_official / 2,
+        ((series.reg_split - series.reg_official) / 2) + series.reg_official,
+    )
+}
+
+// checks the entry counts for a watch make sense, the error is the message for the user.
+pub fn check_reg_range(min_reg: i64, max_reg: i64) -> Result<(), &'static str> {
+    if min_reg > max_reg {
+        return Err("The minimum entries can't be more than the maximum.");
+    }
+    Ok(())
+}
+
+// true if the watch reg wants to hear about ann at time now.
+pub fn wants(reg: &Reg, ann: &Announcement, now: DateTime<Utc>) -> bool {
+    if reg.boost_until.is_some_and(|b| now < b) {
+        return wants(&boosted(reg), ann, now);
+    }
+    if reg.snoozed || reg.muted || reg.disabled || reg.blocked {
+        return false;
+    }
+    let session = matches!(
+        ann.ann_type,
+        AnnouncementType::Open
+            | AnnouncementType::Count
+            | AnnouncementType::Closed
+            | AnnouncementType::Predicted
+            | AnnouncementType::Results
+    );
+    if session && !races_at(reg, ann.curr.start_time) {
+        return false;
+    }
+    match ann.ann_type {
+        AnnouncementType::Open => match (ann.closed_at, ann.prev_open) {
+            // the next slot opening right after the last one closed isn't news.
+            (Some(closed), _) if now - closed < Duration::minutes(reg.reopen_mins) => false,
+            // already said it was open for an earlier race today.
+            (_, Some(prev)) if reg.first_open && same_day(reg, prev, ann.curr.start_time) => false,
+            _ => reg.open,
+        },
+        AnnouncementType::Closed => reg.close && ann.prev.entry_count >= reg.min_reg,
+        AnnouncementType::Count => {
+            if let Some(opened) = ann.opened_at {
+                if now - opened < Duration::minutes(reg.hold_mins) {
+                    return false;
+                }
+            }
+            if let Some(last) = reg.last_count_at {
+                if now - last < Duration::minutes(reg.cooldown_mins) {
+                    return false;
+                }
+            }
+            count_in_range(
+                reg.min_reg,
+                reg.max_reg,
+                ann.prev.entry_count,
+                ann.curr.entry_count,
+            ) || ann.splits_changed()
+        }
+        AnnouncementType::WeekOpen => reg.qualifying,
+        AnnouncementType::NewWeek => reg.week_change,
+        // only happens a few times a year, so everyone watching gets it.
+        AnnouncementType::NewSeason => true,
+        AnnouncementType::LastDay => reg.last_day,
+        AnnouncementType::Predicted => reg.predict,
+        AnnouncementType::Results => reg.results,
+    }
+}
+
+// the settings used while a watch is boosted for a special event, counts start at half
+// the usual min_reg, there's no hold or cooldown, and registration opening is always announced.
+fn boosted(reg: &Reg) -> Reg {
+    let mut b = Reg {
+        boost_until: None,
+        ..reg.clone()
+    };
+    Preset {
+        min_reg: Some(reg.min_reg / 2),
+        hold_mins: Some(0),
+        cooldown_mins: Some(0),
+        reopen_mins: Some(0),
+        open: Some(true),
+        ..Preset::default()
+    }
+    .apply(&mut b);
+    b
+}
+
+fn tz(reg: &Reg) -> FixedOffset {
+    FixedOffset::east_opt(reg.utc_offset_mins as i32 * 60).unwrap_or_else(|| FixedOffset::east(0))
+}
+
+// true if both times are on the same day in the server's timezone.
+fn same_day(reg: &Reg, a: DateTime<Utc>, b: DateTime<Utc>) -> bool {
+    let tz = tz(reg);
+    a.with_timezone(&tz).date_naive() == b.with_timezone(&tz).date_naive()
+}
+
+// true if a session starting at t is within the watch's time of day & days.
+fn races_at(reg: &Reg, t: DateTime<Utc>) -> bool {
+    let local = t.with_timezone(&tz(reg));
+    let day = 1 << local.weekday().num_days_from_monday();
+    if reg.race_days != 0 && reg.race_days & day == 0 {
+        return false;
+    }
+    let mins = (local.hour() * 60 + local.minute()) as i64;
+    match (reg.race_after, reg.race_before) {
+        (a, b) if a == b => true,
+        (a, b) if a < b => mins >= a && mins < b,
+        // the window wraps past midnight, e.g. 22:00 to 02:00
+        (a, b) => mins >= a || mins < b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serenity::model::prelude::ChannelId;
+
+    // a wednesday, in UTC.
+    fn now() -> DateTime<Utc> {
+        "2026-07-01T12:00:00Z".parse().unwrap()
+    }
+
+    fn series() -> SeasonInfo {
+        SeasonInfo {
+            reg_official: 8,
+            reg_split: 20,
+            ..crate::db::tests::series(1, "Test Series")
+        }
+    }
+
+    fn reg() -> Reg {
+        let mut reg = Reg::new(None, ChannelId(1), &series());
+        reg.min_reg = 4;
+        reg.max_reg = 12;
+        reg.open = true;
+        reg.close = true;
+        reg
+    }
+
+    fn entry(session_id: Option<i64>, entry_count: i64) -> RaceGuideEntry {
+        RaceGuideEntry {
+            season_id: 0,
+            // 30 minutes after now, 12:30 UTC.
+            start_time: now() + Duration::minutes(30),
+            super_session: false,
+            series_id: 1,
+            race_week_num: 0,
+            end_time: String::new(),
+            session_id,
+            entry_count,
+        }
+    }
+
+    fn ann(ann_type: AnnouncementType, prev: i64, curr: i64) -> Announcement {
+        Announcement {
+            series: series(),
+            prev: entry(Some(1), prev),
+            curr: entry(Some(1), curr),
+            ann_type,
+            opened_at: None,
+            closed_at: None,
+            prev_open: None,
+            predicted: None,
+            sof: None,
+            results: None,
+        }
+    }
+
+    #[test]
+    fn test_transition() {
+        let cases = [
+            (entry(None, 0), entry(Some(1), 0), Some(Transition::Opened)),
+            (
+                entry(Some(1), 2),
+                entry(Some(1), 3),
+                Some(Transition::Count),
+            ),
+            (entry(Some(1), 0), entry(Some(1), 0), None),
+            (entry(Some(1), 3), entry(Some(1), 3), None),
+            (
+                entry(Some(1), 3),
+                entry(None, 0),
+                Some(Transition::Closed { announce: true }),
+            ),
+            (
+                entry(Some(1), 0),
+                entry(None, 0),
+                Some(Transition::Closed { announce: false }),
+            ),
+            (entry(None, 0), entry(None, 0), None),
+        ];
+        for (i, (prev, curr, want)) in cases.iter().enumerate() {
+            assert_eq!(transition(prev, curr), *want, "case {}", i);
+        }
+    }
+
+    #[test]
+    fn test_dropped() {
+        assert_eq!(
+            dropped(&entry(Some(1), 5)),
+            Some(Transition::Closed { announce: true })
+        );
+        assert_eq!(
+            dropped(&entry(Some(1), 0)),
+            Some(Transition::Closed { announce: false })
+        );
+        assert_eq!(dropped(&entry(None, 5)), None);
+        assert!(Transition::Closed { announce: false }
+            .announcement()
+            .is_none());
+    }
+
+    #[test]
+    fn test_count_in_range() {
+        let cases = [
+            (2, 3, true),
+            (3, 5, true),
+            (5, 6, false),
+            (1, 2, false),
+            // jumped right over the range.
+            (2, 10, true),
+            // already past it.
+            (6, 10, false),
+            // going down into it.
+            (6, 5, true),
+        ];
+        for (prev, curr, want) in cases {
+            assert_eq!(
+                count_in_range(3, 5, prev, curr),
+                want,
+                "{} -> {}",
+                prev,
+                curr
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_reg_range() {
+        assert!(check_reg_range(3, 5).is_ok());
+        assert!(check_reg_range(5, 5).is_ok());
+        assert!(check_reg_range(6, 5).is_err());
+        assert_eq!(default_reg_range(&series()), (4, 14));
+    }
+
+    type Setup = Box<dyn Fn(&mut Reg, &mut Announcement)>;
+
+    #[test]
+    fn test_wants() {
+        let mins = Duration::minutes;
+        let cases: Vec<(&str, Setup, bool)> = vec![
+            ("count in range", Box::new(|_, _| {}), true),
+            (
+                "count below range",
+                Box::new(|_, a| {
+                    a.prev.entry_count = 1;
+                    a.curr.entry_count = 2;
+                }),
+                false,
+            ),
+            ("snoozed", Box::new(|r, _| r.snoozed = true), false),
+            ("muted", Box::new(|r, _| r.muted = true), false),
+            (
+                "held after opening",
+                Box::new(move |r, a| {
+                    r.hold_mins = 10;
+                    a.opened_at = Some(now() - mins(5));
+                }),
+                false,
+            ),
+            (
+                "hold over",
+                Box::new(move |r, a| {
+                    r.hold_mins = 10;
+                    a.opened_at = Some(now() - mins(15));
+                }),
+                true,
+            ),
+            (
+                "in cooldown",
+                Box::new(move |r, _| {
+                    r.cooldown_mins = 10;
+                    r.last_count_at = Some(now() - mins(5));
+                }),
+                false,
+            ),
+            (
+                "cooldown over",
+                Box::new(move |r, _| {
+                    r.cooldown_mins = 10;
+                    r.last_count_at = Some(now() - mins(10));
+                }),
+                true,
+            ),
+            (
+                "opened",
+                Box::new(|_, a| a.ann_type = AnnouncementType::Open),
+                true,
+            ),
+            (
+                "open not wanted",
+                Box::new(|r, a| {
+                    r.open = false;
+                    a.ann_type = AnnouncementType::Open;
+                }),
+                false,
+            ),
+            (
+                "reopened right after closing",
+                Box::new(move |r, a| {
+                    r.reopen_mins = 15;
+                    a.ann_type = AnnouncementType::Open;
+                    a.closed_at = Some(now() - mins(5));
+                }),
+                false,
+            ),
+            (
+                "reopened a while after closing",
+                Box::new(move |r, a| {
+                    r.reopen_mins = 15;
+                    a.ann_type = AnnouncementType::Open;
+                    a.closed_at = Some(now() - mins(20));
+                }),
+                true,
+            ),
+            (
+                "not the first open today",
+                Box::new(move |r, a| {
+                    r.first_open = true;
+                    a.ann_type = AnnouncementType::Open;
+                    a.prev_open = Some(now() - Duration::hours(2));
+                }),
+                false,
+            ),
+            (
+                "first open of a new day",
+                Box::new(move |r, a| {
+                    r.first_open = true;
+                    a.ann_type = AnnouncementType::Open;
+                    a.prev_open = Some(now() - Duration::hours(13));
+                }),
+                true,
+            ),
+            (
+                "first open of a new day in the server's timezone",
+                Box::new(move |r, a| {
+                    r.first_open = true;
+                    // 10:30 & 12:30 UTC are 22:30 & 00:30 the next day at UTC+12.
+                    r.utc_offset_mins = 12 * 60;
+                    a.ann_type = AnnouncementType::Open;
+                    a.prev_open = Some(now() - mins(90));
+                }),
+                true,
+            ),
+            (
+                "closed with enough entries",
+                Box::new(|_, a| {
+                    a.ann_type = AnnouncementType::Closed;
+                    a.prev.entry_count = 6;
+                }),
+                true,
+            ),
+            (
+                "closed with too few entries",
+                Box::new(|_, a| {
+                    a.ann_type = AnnouncementType::Closed;
+                    a.prev.entry_count = 3;
+                }),
+                false,
+            ),
+            (
+                "boost ignores the hold",
+                Box::new(move |r, a| {
+                    r.hold_mins = 10;
+                    r.boost_until = Some(now() + Duration::hours(1));
+                    a.opened_at = Some(now() - mins(5));
+                }),
+                true,
+            ),
+            (
+                "boost halves the min",
+                Box::new(move |r, a| {
+                    r.boost_until = Some(now() + Duration::hours(1));
+                    a.prev.entry_count = 1;
+                    a.curr.entry_count = 2;
+                }),
+                true,
+            ),
+            (
+                "boost announces opening",
+                Box::new(move |r, a| {
+                    r.open = false;
+                    r.boost_until = Some(now() + Duration::hours(1));
+                    a.ann_type = AnnouncementType::Open;
+                }),
+                true,
+            ),
+            (
+                "boost over",
+                Box::new(move |r, a| {
+                    r.boost_until = Some(now() - mins(1));
+                    a.prev.entry_count = 1;
+                    a.curr.entry_count = 2;
+                }),
+                false,
+            ),
+            (
+                "count past the range into another split",
+                Box::new(|_, a| {
+                    a.prev.entry_count = 20;
+                    a.curr.entry_count = 21;
+                }),
+                true,
+            ),
+            (
+                "count past the range, same number of splits",
+                Box::new(|_, a| {
+                    a.prev.entry_count = 22;
+                    a.curr.entry_count = 23;
+                }),
+                false,
+            ),
+            (
+                "race after the window",
+                Box::new(|r, _| {
+                    r.race_after = 8 * 60;
+                    r.race_before = 12 * 60;
+                }),
+                false,
+            ),
+            (
+                "race in the window",
+                Box::new(|r, _| {
+                    r.race_after = 12 * 60;
+                    r.race_before = 13 * 60;
+                }),
+                true,
+            ),
+            (
+                "race in a window past midnight",
+                Box::new(|r, _| {
+                    r.race_after = 22 * 60;
+                    r.race_before = 2 * 60;
+                    // 12:30 UTC is 00:30 at UTC+12.
+                    r.utc_offset_mins = 12 * 60;
+                }),
+                true,
+            ),
+            (
+                "race on a wednesday",
+                Box::new(|r, _| r.race_days = 1 << 2),
+                true,
+            ),
+            (
+                "races at the weekend only",
+                Box::new(|r, _| r.race_days = (1 << 5) | (1 << 6)),
+                false,
+            ),
+            (
+                "week change isn't a session",
+                Box::new(|r, a| {
+                    r.race_days = (1 << 5) | (1 << 6);
+                    r.week_change = true;
+                    a.ann_type = AnnouncementType::NewWeek;
+                }),
+                true,
+            ),
+        ];
+        for (name, setup, want) in cases {
+            let mut r = reg();
+            let mut a = ann(AnnouncementType::Count, 3, 4);
+            setup(&mut r, &mut a);
+            assert_eq!(wants(&r, &a, now()), want, "{}", name);
+        }
+    }
+}
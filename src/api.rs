@@ -0,0 +1,257 @@
+use axum::extract::{Path, Query};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{delete, get};
+use axum::{Extension, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use serenity::model::prelude::{Channel, ChannelId, GuildId};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::announce_rules::check_reg_range;
+use crate::config::WatchLimits;
+use crate::dashboard;
+use crate::db::{DbHandle, RegExport};
+use crate::error::BotError;
+use crate::HandlerState;
+
+// ApiState is what the handlers need, shared between all the requests.
+#[derive(Clone)]
+pub struct ApiState {
+    pub state: Arc<HandlerState>,
+    // for looking up which server a channel is in.
+    http: Arc<Http>,
+    token: Arc<String>,
+    dashboard_token: Arc<String>,
+    limits: Arc<WatchLimits>,
+}
+
+impl ApiState {
+    pub fn db(&self) -> DbHandle {
+        self.state.db()
+    }
+    pub fn check_token(&self, given: &str) -> Result<(), ApiError> {
+        check(given, &self.token)
+    }
+    // the dashboard's cookie has a hash of the dashboard token, so the token itself
+    // isn't kept in the browser.
+    pub fn dashboard_cookie(&self) -> String {
+        format!("{:x}", Sha256::digest(self.dashboard_token.as_bytes()))
+    }
+    pub fn check_dashboard_token(&self, given: &str) -> Result<(), ApiError> {
+        check(given, &self.dashboard_token)
+    }
+    pub fn check_dashboard_cookie(&self, given: &str) -> Result<(), ApiError> {
+        check(given, &self.dashboard_cookie())
+    }
+}
+
+fn check(given: &str, token: &str) -> Result<(), ApiError> {
+    // compare hashes so that how long it takes doesn't give away how much of the token was right.
+    if Sha256::digest(given.as_bytes()) == Sha256::digest(token.as_bytes()) {
+        Ok(())
+    } else {
+        Err((StatusCode::UNAUTHORIZED, "invalid token".to_string()))
+    }
+}
+
+pub type ApiError = (StatusCode, String);
+
+// serve runs the REST API for managing watches on addr, every request needs an
+// "Authorization: Bearer <token>" header. The read only dashboard is served alongside it
+// if there's a dashboard_token.
+pub async fn serve(
+    addr: String,
+    token: String,
+    dashboard_token: Option<String>,
+    state: Arc<HandlerState>,
+    http: Arc<Http>,
+    limits: WatchLimits,
+) {
+    let sock: SocketAddr = match addr.parse() {
+        Err(e) => {
+            println!("Invalid API address {} {:?}", addr, e);
+            return;
+        }
+        Ok(a) => a,
+    };
+    let mut app = Router::new()
+        .route("/watches", get(list_watches).put(put_watch))
+        .route("/watches/:channel_id/:series_id", delete(delete_watch))
+        .route("/series", get(list_series));
+    if dashboard_token.is_some() {
+        app = app.merge(dashboard::routes());
+    }
+    let app = app.layer(Extension(ApiState {
+        state,
+        http,
+        token: Arc::new(token),
+        dashboard_token: Arc::new(dashboard_token.unwrap_or_default()),
+        limits: Arc::new(limits),
+    }));
+    println!("API listening on {}", addr);
+    if let Err(e) = axum::Server::bind(&sock)
+        .serve(app.into_make_service())
+        .await
+    {
+        println!("API server failed {:?}", e);
+    }
+}
+
+fn authorize(headers: &HeaderMap, api: &ApiState) -> Result<(), ApiError> {
+    let given = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    api.check_token(given)
+}
+
+pub fn db_error(e: BotError) -> ApiError {
+    println!("API db error {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "database error".to_string(),
+    )
+}
+
+#[derive(Deserialize)]
+struct WatchQuery {
+    channel_id: Option<u64>,
+}
+
+// GET /watches?channel_id=x, every watch, or just the channel's ones.
+async fn list_watches(
+    Extension(api): Extension<ApiState>,
+    headers: HeaderMap,
+    Query(q): Query<WatchQuery>,
+) -> Result<Json<Vec<RegExport>>, ApiError> {
+    authorize(&headers, &api)?;
+    let regs = api
+        .db()
+        .call(move |db| match q.channel_id {
+            Some(ch) => db.channel_regs(ChannelId(ch)),
+            None => db.regs().map(|r| r.into_values().flatten().collect()),
+        })
+        .await
+        .map_err(db_error)?;
+    Ok(Json(regs.iter().map(RegExport::from).collect()))
+}
+
+// PUT /watches, adds the watch, or updates it if the channel's already watching the series.
+// The body is the same as a watch from /export, its guild_id is ignored in favour of the
+// channel's actual server so the server's limits and blocks apply.
+async fn put_watch(
+    Extension(api): Extension<ApiState>,
+    headers: HeaderMap,
+    Json(w): Json<RegExport>,
+) -> Result<Json<RegExport>, ApiError> {
+    authorize(&headers, &api)?;
+    let series = api.state.seasons().get(&w.series_id).cloned();
+    let series = series.ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("no current series {}", w.series_id),
+        )
+    })?;
+    check_reg_range(w.min_reg, w.max_reg).map_err(|m| (StatusCode::BAD_REQUEST, m.to_string()))?;
+    let guild = channel_guild(&api, ChannelId(w.channel_id)).await?;
+    let reg = w.to_reg(Some(guild), ChannelId(w.channel_id), &series);
+    let limits = api.limits.clone();
+    let r = reg.clone();
+    api.db()
+        .call(move |db| -> rusqlite::Result<Result<(), ApiError>> {
+            if db.is_series_blocked(r.guild, r.series_id)? {
+                return Ok(Err((
+                    StatusCode::CONFLICT,
+                    format!("the server has blocked {}", r.series_name),
+                )));
+            }
+            let (ch_count, guild_count) = db.watch_counts(&r)?;
+            if let Err(msg) = limits.check(r.guild.map(|g| g.0), ch_count, guild_count) {
+                return Ok(Err((StatusCode::CONFLICT, msg)));
+            }
+            db.upsert_reg(&r, "api", None)?;
+            Ok(Ok(()))
+        })
+        .await
+        .map_err(db_error)??;
+    Ok(Json(RegExport::from(&reg)))
+}
+
+// the server the channel is in, as discord sees it.
+async fn channel_guild(api: &ApiState, ch: ChannelId) -> Result<GuildId, ApiError> {
+    match api.http.get_channel(ch.0).await {
+        Ok(Channel::Guild(c)) => Ok(c.guild_id),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            format!("channel {} isn't in a server I'm in", ch),
+        )),
+    }
+}
+
+// DELETE /watches/:channel_id/:series_id
+async fn delete_watch(
+    Extension(api): Extension<ApiState>,
+    headers: HeaderMap,
+    Path((channel_id, series_id)): Path<(u64, i64)>,
+) -> Result<StatusCode, ApiError> {
+    authorize(&headers, &api)?;
+    let n = api
+        .db()
+        .call(move |db| db.delete_reg(ChannelId(channel_id), series_id))
+        .await
+        .map_err(db_error)?;
+    if n == 0 {
+        return Err((StatusCode::NOT_FOUND, "no such watch".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// SeriesState is a current series, with its next session from the race guide.
+#[derive(Serialize)]
+struct SeriesState {
+    series_id: i64,
+    name: String,
+    week: i64,
+    track: String,
+    reg_official: i64,
+    reg_split: i64,
+    next_session: Option<DateTime<Utc>>,
+    entry_count: Option<i64>,
+}
+
+// GET /series
+async fn list_series(
+    Extension(api): Extension<ApiState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<SeriesState>>, ApiError> {
+    authorize(&headers, &api)?;
+    let guide = api.state.guide();
+    let mut res: Vec<SeriesState> = api
+        .state
+        .seasons()
+        .values()
+        .map(|s| {
+            let next = guide
+                .iter()
+                .filter(|e| e.series_id == s.series_id)
+                .min_by_key(|e| e.start_time);
+            SeriesState {
+                series_id: s.series_id,
+                name: s.name.clone(),
+                week: s.week + 1,
+                track: s.track_name.clone(),
+                reg_official: s.reg_official,
+                reg_split: s.reg_split,
+                next_session: next.map(|e| e.start_time),
+                entry_count: next.map(|e| e.entry_count),
+            }
+        })
+        .collect();
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(res))
+}
@@ -0,0 +1,89 @@
+use anyhow::anyhow;
+use chrono::Utc;
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, DatabaseName};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::Config;
+
+const BACKUP_PREFIX: &str = "regbot-";
+const BACKUP_SUFFIX: &str = ".db";
+
+// backup_task takes a snapshot of the db every backup_interval_hours into backup_dir,
+// keeping the most recent backup_keep of them.
+pub async fn backup_task(config: Arc<Config>) {
+    let dir = match &config.backup_dir {
+        None => return,
+        Some(d) => PathBuf::from(d),
+    };
+    let interval = tokio::time::Duration::from_secs(config.backup_interval_hours * 60 * 60);
+    loop {
+        tokio::time::sleep(interval).await;
+        let (db_path, dir, keep) = (config.db_path.clone(), dir.clone(), config.backup_keep);
+        match tokio::task::spawn_blocking(move || backup(&db_path, &dir, keep)).await {
+            Ok(Ok(f)) => println!("Backed up db to {}", f.display()),
+            Ok(Err(e)) => println!("Failed to backup db {:?}", e),
+            Err(e) => println!("Backup task failed {:?}", e),
+        }
+    }
+}
+
+// backup writes a copy of the db into dir and removes all but the latest keep backups.
+// This uses its own connection, so it doesn't hold up the bot's use of the db.
+pub fn backup(db_path: &str, dir: &Path, keep: usize) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let file = dir.join(format!(
+        "{}{}{}",
+        BACKUP_PREFIX,
+        Utc::now().format("%Y%m%d-%H%M%S"),
+        BACKUP_SUFFIX
+    ));
+    let src = Connection::open(db_path)?;
+    let mut dst = Connection::open(&file)?;
+    Backup::new(&src, &mut dst)?.run_to_completion(
+        100,
+        std::time::Duration::from_millis(10),
+        None,
+    )?;
+
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(BACKUP_PREFIX) && n.ends_with(BACKUP_SUFFIX))
+        })
+        .collect();
+    // the timestamp in the name sorts oldest first.
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep.max(1));
+    for old in &backups[..excess] {
+        if let Err(e) = std::fs::remove_file(old) {
+            println!("Failed to remove old backup {} {:?}", old.display(), e);
+        }
+    }
+    Ok(file)
+}
+
+// restore replaces the contents of the db with the backup file. The bot shouldn't be
+// running while this happens.
+pub fn restore(db_path: &str, file: &Path) -> anyhow::Result<()> {
+    if !file.exists() {
+        return Err(anyhow!("backup {} doesn't exist", file.display()));
+    }
+    // make sure it looks like one of ours before we overwrite anything.
+    let check = Connection::open(file)?;
+    check
+        .query_row("SELECT count(*) FROM reg", [], |row| row.get::<_, i64>(0))
+        .map_err(|e| anyhow!("{} isn't a regbot db: {}", file.display(), e))?;
+    drop(check);
+
+    let mut dst = Connection::open(db_path)?;
+    dst.restore(
+        DatabaseName::Main,
+        file,
+        None::<fn(rusqlite::backup::Progress)>,
+    )?;
+    Ok(())
+}
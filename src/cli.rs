@@ -0,0 +1,390 @@
+use crate::backup;
+use crate::config::{Config, Preset};
+use crate::db::{Db, DbHandle, ExportFile, GuildExport, RegExport, EXPORT_TABLES};
+use crate::health::Health;
+use crate::ir_watcher;
+use crate::telegram::chat_reg;
+use crate::{all_commands, HandlerState};
+use anyhow::anyhow;
+use clap::{Parser, Subcommand};
+use serenity::http::Http;
+use serenity::model::prelude::command::Command;
+use serenity::model::prelude::{ChannelId, GuildId};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser, Debug)]
+#[clap(
+    version,
+    about = "A Discord bot that announces iRacing race registrations"
+)]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// Run the bot, this is the default
+    Run,
+    /// Check the config loads and the db can be opened
+    CheckConfig,
+    /// Write every watch out as JSON
+    ExportWatches {
+        /// File to write to, defaults to stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Add the watches from a JSON file made by export-watches or /export
+    ImportWatches {
+        /// The file to read
+        file: PathBuf,
+    },
+    /// Register the slash commands with Discord
+    RegisterCommands {
+        /// Register the commands globally for every server
+        #[clap(long)]
+        global: bool,
+        /// Register the commands for just this server
+        #[clap(long, required_unless_present = "global")]
+        guild: Option<u64>,
+    },
+    /// Create or update the db schema
+    MigrateDb,
+    /// Delete everything stored for a server
+    PurgeGuild {
+        /// The id of the server
+        guild: u64,
+    },
+    /// Export the history tables as CSV files for offline analysis
+    ExportData {
+        /// Directory to write the files to
+        #[clap(short, long, default_value = ".")]
+        dir: PathBuf,
+        /// Only rows from this date on (YYYY-MM-DD)
+        #[clap(long)]
+        from: Option<String>,
+        /// Only rows up to and including this date (YYYY-MM-DD)
+        #[clap(long)]
+        to: Option<String>,
+    },
+    /// Replace the db with a backup, don't do this while the bot is running
+    Restore {
+        /// The backup file to restore
+        file: PathBuf,
+    },
+    /// Replay a directory of race guide fixtures through the watcher and list the announcements
+    Replay {
+        /// The fixtures directory, laid out as for ir_fixtures
+        dir: PathBuf,
+        /// Mark the announcements a watch with this preset would get, rather than the defaults
+        #[clap(long)]
+        preset: Option<String>,
+        /// Seconds between race guides that don't say when they're from
+        #[clap(long, default_value_t = 61)]
+        step: i64,
+        /// Compare the announcements with this file instead of printing them
+        #[clap(long)]
+        expect: Option<PathBuf>,
+    },
+    /// Show how often each slash command was used and how long they took
+    CommandStats {
+        /// How many days back to look
+        #[clap(short, long, default_value_t = 7)]
+        days: i64,
+    },
+}
+
+pub fn check_config(config: &Config) -> anyhow::Result<()> {
+    println!("iRacing user:      {}", config.ir_user);
+    println!("db:                {}", config.db_path);
+    println!(
+        "poll interval:     {}s ({}s near a start, {}s {:02}:00-{:02}:00 UTC)",
+        config.poll_interval_secs,
+        config.poll_fast_secs,
+        config.poll_quiet_secs,
+        config.quiet_start_hour,
+        config.quiet_end_hour
+    );
+    println!("max message len:   {}", config.max_message_len);
+    println!(
+        "max messages:      {} per channel",
+        config.max_channel_messages
+    );
+    match config.shards {
+        0 => println!("shards:            as many as discord recommends"),
+        n => println!("shards:            {}", n),
+    }
+    println!(
+        "health endpoint:   {}",
+        config.health_addr.as_deref().unwrap_or("off")
+    );
+    println!(
+        "commands:          {}",
+        if config.global_commands {
+            "global"
+        } else {
+            "per guild"
+        }
+    );
+    match &config.backup_dir {
+        Some(d) => println!(
+            "backups:           {} every {}h, keeping {}",
+            d, config.backup_interval_hours, config.backup_keep
+        ),
+        None => println!("backups:           off"),
+    }
+    match &config.record_dir {
+        Some(d) => println!("recording:         {} upto {}MB", d, config.record_max_mb),
+        None => println!("recording:         off"),
+    }
+    println!(
+        "watch limits:      {} per channel, {} per server, {} unlimited servers",
+        config.max_channel_watches,
+        config.max_guild_watches,
+        config.unlimited_guilds.len()
+    );
+    println!(
+        "send failures:     watches disabled after {} refused sends",
+        config.max_send_failures
+    );
+    for (f, on) in &config.features {
+        println!("feature {:<10} {}", f, if *on { "on" } else { "off" });
+    }
+    Db::new(&config.db_path)?;
+    println!("config ok");
+    Ok(())
+}
+
+pub fn export_watches(config: &Config, output: Option<PathBuf>) -> anyhow::Result<()> {
+    let db = Db::new(&config.db_path)?;
+    let regs: Vec<RegExport> = db.regs()?.values().flatten().map(RegExport::from).collect();
+    let json = serde_json::to_string_pretty(&regs)?;
+    match output {
+        Some(f) => {
+            std::fs::write(&f, json)?;
+            println!("exported {} watches to {}", regs.len(), f.display());
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+pub fn import_watches(config: &Config, file: PathBuf) -> anyhow::Result<()> {
+    let mut db = Db::new(&config.db_path)?;
+    // this takes the watches from a server's /export as well.
+    let export: ExportFile = serde_json::from_str(&std::fs::read_to_string(&file)?)?;
+    let regs = GuildExport::from(export).watches;
+    let series = db.get_series()?;
+    let mut count = 0;
+    for r in &regs {
+        match series.get(&r.series_id) {
+            Some(s) => {
+                let reg = r.to_reg(r.guild_id.map(GuildId), ChannelId(r.channel_id), s);
+                db.upsert_reg(&reg, "import", None)?;
+                count += 1;
+            }
+            None => println!("skipping series {}, it's not active", r.series_name),
+        }
+    }
+    println!("imported {} of {} watches", count, regs.len());
+    Ok(())
+}
+
+pub async fn register_commands(
+    config: &Config,
+    global: bool,
+    guild: Option<u64>,
+) -> anyhow::Result<()> {
+    let state = Arc::new(HandlerState::new(DbHandle::open(&config.db_path)?));
+    let commands = all_commands(state, config, Arc::new(Health::default()));
+    let http = Http::new(&config.discord_token);
+    let app = http.get_current_application_info().await?;
+    http.set_application_id(app.id.0);
+    if global {
+        Command::set_global_application_commands(&http, |cmds| {
+            for c in &commands {
+                c.create(cmds);
+            }
+            cmds
+        })
+        .await?;
+        println!("registered commands globally");
+    }
+    if let Some(g) = guild {
+        GuildId(g)
+            .set_application_commands(&http, |cmds| {
+                for c in &commands {
+                    c.create(cmds);
+                }
+                cmds
+            })
+            .await?;
+        println!("registered commands for guild {}", g);
+    }
+    Ok(())
+}
+
+pub fn migrate_db(config: &Config) -> anyhow::Result<()> {
+    // Db::new takes care of bringing the schema up to date.
+    Db::new(&config.db_path)?;
+    println!("db {} is up to date", config.db_path);
+    Ok(())
+}
+
+pub fn purge_guild(config: &Config, guild: u64) -> anyhow::Result<()> {
+    let mut db = Db::new(&config.db_path)?;
+    for (table, n) in db.purge_guild(GuildId(guild))? {
+        println!("deleted {} rows from {}", n, table);
+    }
+    Ok(())
+}
+
+pub fn command_stats(config: &Config, days: i64) -> anyhow::Result<()> {
+    let db = Db::new(&config.db_path)?;
+    println!(
+        "{:<14} {:>7} {:>7} {:>8} {:>8} {:>8}",
+        "command", "count", "failed", "p50 ms", "p95 ms", "max ms"
+    );
+    for s in db.command_stats(days)? {
+        println!(
+            "{:<14} {:>7} {:>7} {:>8} {:>8} {:>8}",
+            s.command, s.count, s.failed, s.p50_ms, s.p95_ms, s.max_ms
+        );
+    }
+    Ok(())
+}
+
+pub fn export_data(
+    config: &Config,
+    dir: PathBuf,
+    from: Option<String>,
+    to: Option<String>,
+) -> anyhow::Result<()> {
+    let db = Db::new(&config.db_path)?;
+    for (table, _) in EXPORT_TABLES {
+        let (cols, rows) = db.export_rows(table, from.as_deref(), to.as_deref())?;
+        let mut csv = csv_line(&cols);
+        for r in &rows {
+            csv.push_str(&csv_line(r));
+        }
+        let f = dir.join(format!("{}.csv", table));
+        std::fs::write(&f, csv)?;
+        println!("exported {} rows to {}", rows.len(), f.display());
+    }
+    Ok(())
+}
+
+fn csv_line(fields: &[String]) -> String {
+    let quoted: Vec<String> = fields
+        .iter()
+        .map(|f| {
+            if f.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.clone()
+            }
+        })
+        .collect();
+    quoted.join(",") + "\n"
+}
+
+pub fn restore(config: &Config, file: PathBuf) -> anyhow::Result<()> {
+    backup::restore(&config.db_path, &file)?;
+    // bring the restored schema up to date.
+    Db::new(&config.db_path)?;
+    println!("restored {} from {}", config.db_path, file.display());
+    Ok(())
+}
+
+pub async fn replay(
+    config: &Config,
+    dir: PathBuf,
+    preset: Option<String>,
+    step: i64,
+    expect: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let preset = match preset {
+        None => Preset::default(),
+        Some(p) => config
+            .presets
+            .get(&p)
+            .cloned()
+            .ok_or_else(|| anyhow!("there's no preset called {}", p))?,
+    };
+    let lines = ir_watcher::replay(&dir, chrono::Duration::seconds(step), |si| {
+        chat_reg(si, &preset)
+    })
+    .await?;
+    let expect = match expect {
+        None => {
+            for l in &lines {
+                println!("{}", l);
+            }
+            return Ok(());
+        }
+        Some(f) => std::fs::read_to_string(f)?,
+    };
+    let expected: Vec<&str> = expect.lines().filter(|l| !l.trim().is_empty()).collect();
+    for (i, (got, want)) in lines.iter().zip(&expected).enumerate() {
+        if got.trim_end() != want.trim_end() {
+            return Err(anyhow!(
+                "announcement {} differs\nexpected: {}\n     got: {}",
+                i + 1,
+                want,
+                got
+            ));
+        }
+    }
+    if lines.len() != expected.len() {
+        return Err(anyhow!(
+            "expected {} announcements, got {}",
+            expected.len(),
+            lines.len()
+        ));
+    }
+    println!("{} announcements as expected", lines.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // fixtures/replay has a couple of sessions for one series going through opening,
+    // counts & closing, expected.txt is what the default settings get and
+    // expected_quiet.txt the quiet preset.
+    #[tokio::test]
+    async fn test_replay_fixtures() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/replay");
+        let config = Config::default();
+        replay(
+            &config,
+            dir.clone(),
+            None,
+            60,
+            Some(dir.join("expected.txt")),
+        )
+        .await
+        .unwrap();
+        replay(
+            &config,
+            dir.clone(),
+            Some("quiet".to_string()),
+            60,
+            Some(dir.join("expected_quiet.txt")),
+        )
+        .await
+        .unwrap();
+        // and it notices when they don't match.
+        assert!(replay(
+            &config,
+            dir.clone(),
+            Some("everything".to_string()),
+            60,
+            Some(dir.join("expected.txt")),
+        )
+        .await
+        .is_err());
+    }
+}
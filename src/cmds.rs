@@ -1,12 +1,20 @@
+use chrono::{DateTime, TimeZone, Utc};
 use serenity::async_trait;
+use serenity::model::prelude::component::{ActionRowComponent, InputTextStyle};
 use serenity::model::prelude::interaction::application_command::{
-    CommandDataOption, CommandDataOptionValue,
+    CommandDataOption, CommandDataOptionValue, ResolvedTarget,
 };
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::prelude::interaction::modal::ModalSubmitInteraction;
 use serenity::model::prelude::interaction::{InteractionResponseType, MessageFlags};
+use serenity::model::prelude::{
+    component::ButtonStyle, AttachmentType, ChannelId, ChannelType, GuildChannel, GuildId,
+    Permissions, User, UserId,
+};
 use serenity::{
-    builder::CreateApplicationCommands,
+    builder::{CreateApplicationCommandOption, CreateApplicationCommands, CreateComponents},
     model::prelude::{
-        command::CommandOptionType,
+        command::{CommandOptionType, CommandType},
         interaction::{
             application_command::ApplicationCommandInteraction,
             autocomplete::AutocompleteInteraction,
@@ -14,13 +22,32 @@ use serenity::{
     },
     prelude::Context,
 };
-use std::sync::{Arc, Mutex};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
-use crate::db::Reg;
-use crate::HandlerState;
+use crate::announce_rules::check_reg_range;
+use crate::config::{Preset, WatchLimits};
+use crate::db::{
+    Db, ExportFile, GuildExport, Reg, SeasonInfo, WatchDefaults, DAY_NAMES, DELETED_REG_DAYS,
+    LICENSES,
+};
+use crate::error::BotError;
+use crate::health::Health;
+use crate::i18n::{text, tr, Locale, Msg};
+use crate::ir::RaceGuideEntry;
+use crate::ir_watcher::Announcement;
+use crate::style::{Layout, Style};
+use crate::webhook::{WebhookFormat, WebhookTarget};
+use crate::{split_line, HandlerState};
 
 #[async_trait]
 pub trait ACommand: Send + Sync {
+    // the slash command name, or the menu item text for context menu commands
+    // (created with a kind of CommandType::Message or User).
     fn name(&self) -> &str;
 
     fn create(&self, _commands: &mut CreateApplicationCommands) {}
@@ -28,15 +55,367 @@ pub trait ACommand: Send + Sync {
     async fn autocomplete(&self, _ctx: Context, _a: AutocompleteInteraction) {}
 
     async fn execute(&self, _ctx: Context, _a: ApplicationCommandInteraction) {}
+
+    // called for button clicks etc on messages we sent, the component custom_id
+    // should be prefixed with the command name and a ':'
+    async fn component(&self, _ctx: Context, _c: MessageComponentInteraction) {}
+
+    // called when a modal we opened is submitted, the modal custom_id is prefixed
+    // the same way as components.
+    async fn modal(&self, _ctx: Context, _m: ModalSubmitInteraction) {}
+
+    // true if components & modals with this custom_id prefix are for this command.
+    fn handles(&self, prefix: &str) -> bool {
+        prefix == self.name()
+    }
+}
+
+// the top level command the everyday commands go under when the command_group feature
+// is on, and the subcommand name for each of them.
+pub const COMMAND_GROUP: &str = "reg";
+const GROUPED_COMMANDS: &[(&str, &str)] = &[
+    ("watch", "watch"),
+    ("watchmany", "watchmany"),
+    ("watching", "list"),
+    ("nomore", "remove"),
+    ("undo", "undo"),
+    ("snooze", "pause"),
+    ("boost", "boost"),
+    ("watchevent", "event"),
+    ("watchleague", "league"),
+    ("remindme", "remindme"),
+    ("now", "now"),
+    ("popular", "popular"),
+    ("history", "history"),
+];
+
+// moves the commands in GROUPED_COMMANDS under a single /reg command, e.g. /reg watch,
+// so they don't fill up the server's command list. Everything else, including the admin
+// commands, which discord can only restrict at the top level, stays as it is.
+pub fn group_commands(commands: Vec<Box<dyn ACommand>>) -> Vec<Box<dyn ACommand>> {
+    let (grouped, mut res): (Vec<_>, Vec<_>) = commands
+        .into_iter()
+        .partition(|c| GROUPED_COMMANDS.iter().any(|(name, _)| *name == c.name()));
+    let mut subs = Vec::with_capacity(grouped.len());
+    for c in grouped {
+        if let Some((_, sub)) = GROUPED_COMMANDS.iter().find(|(name, _)| *name == c.name()) {
+            subs.push((*sub, c));
+        }
+    }
+    res.insert(0, Box::new(CommandGroup { commands: subs }));
+    res
+}
+
+// CommandGroup is a slash command whose subcommands are other commands. Their
+// interactions are unwrapped so they look just like they would as a top level command.
+struct CommandGroup {
+    commands: Vec<(&'static str, Box<dyn ACommand>)>,
+}
+impl CommandGroup {
+    // the subcommand the options are for, and its own options.
+    fn subcommand(
+        &self,
+        opts: &[CommandDataOption],
+    ) -> Option<(&dyn ACommand, Vec<CommandDataOption>)> {
+        let sub = opts
+            .first()
+            .filter(|o| o.kind == CommandOptionType::SubCommand)?;
+        self.commands
+            .iter()
+            .find(|(name, _)| *name == sub.name)
+            .map(|(_, c)| (c.as_ref(), sub.options.clone()))
+    }
+    fn command(&self, prefix: &str) -> Option<&dyn ACommand> {
+        self.commands
+            .iter()
+            .map(|(_, c)| c.as_ref())
+            .find(|c| c.handles(prefix))
+    }
+}
+#[async_trait]
+impl ACommand for CommandGroup {
+    fn name(&self) -> &str {
+        COMMAND_GROUP
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        // have each command create itself as usual, then turn that into a subcommand.
+        let mut defs = CreateApplicationCommands::default();
+        for (_, c) in &self.commands {
+            c.create(&mut defs);
+        }
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Race registration announcements");
+            for ((sub, _), def) in self.commands.iter().zip(defs.0) {
+                let mut opt = CreateApplicationCommandOption::default();
+                opt.kind(CommandOptionType::SubCommand)
+                    .name(*sub)
+                    .description(def["description"].as_str().unwrap_or_default());
+                if let Some(options) = def.get("options") {
+                    opt.0.insert("options", options.clone());
+                }
+                command.add_option(opt);
+            }
+            command
+        });
+    }
+    async fn autocomplete(&self, ctx: Context, mut autocomp: AutocompleteInteraction) {
+        if let Some((c, options)) = self.subcommand(&autocomp.data.options) {
+            autocomp.data.name = c.name().to_string();
+            autocomp.data.options = options;
+            c.autocomplete(ctx, autocomp).await;
+        }
+    }
+    async fn execute(&self, ctx: Context, mut command: ApplicationCommandInteraction) {
+        if let Some((c, options)) = self.subcommand(&command.data.options) {
+            command.data.name = c.name().to_string();
+            command.data.options = options;
+            c.execute(ctx, command).await;
+        }
+    }
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        let prefix = comp.data.custom_id.split(':').next().unwrap_or_default();
+        if let Some(c) = self.command(prefix) {
+            c.component(ctx, comp).await;
+        }
+    }
+    async fn modal(&self, ctx: Context, m: ModalSubmitInteraction) {
+        let prefix = m.data.custom_id.split(':').next().unwrap_or_default();
+        if let Some(c) = self.command(prefix) {
+            c.modal(ctx, m).await;
+        }
+    }
+    fn handles(&self, prefix: &str) -> bool {
+        self.command(prefix).is_some()
+    }
+}
+
+tokio::task_local! {
+    // the outcome of the command currently executing, see with_outcome.
+    static OUTCOME: RefCell<&'static str>;
+    // true once the command currently executing has deferred its response, see defer.
+    static DEFERRED: Cell<bool>;
+}
+
+// runs a command execution and returns its outcome, which is "ok" unless the
+// command reported an error to the user or failed to respond.
+pub async fn with_outcome<F: Future<Output = ()>>(f: F) -> &'static str {
+    OUTCOME
+        .scope(
+            RefCell::new("ok"),
+            DEFERRED.scope(Cell::new(false), async move {
+                f.await;
+                OUTCOME.with(|o| *o.borrow())
+            }),
+        )
+        .await
+}
+
+fn is_deferred() -> bool {
+    DEFERRED.try_with(|d| d.get()).unwrap_or(false)
+}
+
+// tells discord we're working on the command, which gives us 15 minutes to respond
+// rather than 3 seconds. Call it before doing anything that might be slow, the respond
+// helpers then send their message as a followup. Anything sent after deferring is
+// visible to the whole channel.
+async fn defer(ctx: &Context, command: &ApplicationCommandInteraction) {
+    defer_as(ctx, command, false).await
+}
+
+// defers like defer, but the response is only visible to the user that ran the command,
+// discord decides that when the command is deferred rather than from the followup.
+async fn defer_private(ctx: &Context, command: &ApplicationCommandInteraction) {
+    defer_as(ctx, command, true).await
+}
+
+async fn defer_as(ctx: &Context, command: &ApplicationCommandInteraction, ephemeral: bool) {
+    if let Err(e) = command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|message| {
+                    if ephemeral {
+                        message.flags(MessageFlags::EPHEMERAL);
+                    }
+                    message
+                })
+        })
+        .await
+    {
+        println!("Failed to defer command {}", e);
+        return;
+    }
+    let _ = DEFERRED.try_with(|d| d.set(true));
+}
+
+// responds to the command, or sends a followup if the response was deferred.
+async fn respond(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    msg: &str,
+    ephemeral: bool,
+    components: Option<CreateComponents>,
+) -> bool {
+    let res = if is_deferred() {
+        command
+            .create_followup_message(&ctx.http, |message| {
+                if let Some(c) = components {
+                    message.components(|cc| {
+                        *cc = c;
+                        cc
+                    });
+                }
+                message.ephemeral(ephemeral).content(msg)
+            })
+            .await
+            .map(|_| ())
+    } else {
+        command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        if ephemeral {
+                            message.flags(MessageFlags::EPHEMERAL);
+                        }
+                        if let Some(c) = components {
+                            message.set_components(c);
+                        }
+                        message.content(msg)
+                    })
+            })
+            .await
+    };
+    if let Err(e) = res {
+        println!("Failed to respond to command {}", e);
+        set_outcome("respond_failed");
+        return false;
+    }
+    true
+}
+
+fn set_outcome(outcome: &'static str) {
+    let _ = OUTCOME.try_with(|o| *o.borrow_mut() = outcome);
 }
 
 pub struct RegCommand {
-    state: Arc<Mutex<HandlerState>>,
+    state: Arc<HandlerState>,
+    presets: BTreeMap<String, Preset>,
+    limits: WatchLimits,
+    // who to tell about requests for series we don't know about.
+    owner_id: Option<u64>,
 }
 impl RegCommand {
-    pub fn new(state: Arc<Mutex<HandlerState>>) -> Self {
-        RegCommand { state }
+    pub fn new(
+        state: Arc<HandlerState>,
+        presets: BTreeMap<String, Preset>,
+        limits: WatchLimits,
+        owner_id: Option<u64>,
+    ) -> Self {
+        RegCommand {
+            state,
+            presets,
+            limits,
+            owner_id,
+        }
+    }
+    // responds with a form for the main watch settings, prefilled from reg which
+    // has the options and series defaults. See RegCommand::modal.
+    async fn open_setup(&self, ctx: &Context, command: &ApplicationCommandInteraction, reg: &Reg) {
+        if let Err(e) = command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::Modal)
+                    .interaction_response_data(|modal| {
+                        modal
+                            .custom_id(format!("watch:setup:{}", reg.series_id))
+                            .title(truncate(&reg.series_name, 45))
+                            .components(|c| {
+                                watch_form(c, reg.min_reg, reg.max_reg, reg.open, reg.close)
+                            })
+                    })
+            })
+            .await
+        {
+            println!("Failed to respond to command {}", e);
+            set_outcome("respond_failed");
+        }
+    }
+    // records a request for a series we don't know about, and lets the owner know.
+    async fn request_series(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        query: String,
+    ) {
+        let (guild, user) = (command.guild_id, command.user.id);
+        let q = query.clone();
+        if let Err(e) = self
+            .state
+            .db()
+            .call(move |db| db.record_series_request(guild, user, &q))
+            .await
+        {
+            println!("Failed to record series request {:?}", e);
+        }
+        if let Some(owner) = self.owner_id {
+            let msg = format!(
+                "{} asked for a series I don't know about: {}",
+                command.user.name, query
+            );
+            let res = match UserId(owner).create_dm_channel(&ctx.http).await {
+                Ok(dm) => dm.say(&ctx.http, &msg).await.map(|_| ()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                println!("Failed to tell the owner about a series request {:?}", e);
+            }
+        }
+        respond_private(
+            ctx,
+            command,
+            &format!(
+                "I don't know about a series called {} yet, I've passed the request on.",
+                query
+            ),
+        )
+        .await;
+    }
+}
+
+// adds or updates the reg, as long as the server hasn't blocked the series and it doesn't
+// take the channel or server over its watch limits. The inner error is the message for
+// the user if it can't.
+fn upsert_reg_within(
+    db: &mut Db,
+    reg: &Reg,
+    created_by: &User,
+    perms: Option<Permissions>,
+    limits: &WatchLimits,
+) -> rusqlite::Result<Result<(), String>> {
+    let existing = db
+        .channel_regs(reg.channel)?
+        .into_iter()
+        .find(|r| r.series_id == reg.series_id);
+    if let Some(r) = existing {
+        if !can_change_watch(&r, created_by.id, perms) {
+            return Ok(Err(RESTRICTED_WATCH.to_string()));
+        }
+    }
+    if db.is_series_blocked(reg.guild, reg.series_id)? {
+        return Ok(Err(format!(
+            "This server has blocked {}, so I can't watch it.",
+            reg.series_name
+        )));
     }
+    let (ch_count, guild_count) = db.watch_counts(reg)?;
+    if let Err(msg) = limits.check(reg.guild.map(|g| g.0), ch_count, guild_count) {
+        return Ok(Err(msg));
+    }
+    db.upsert_reg(reg, &created_by.name, Some(created_by.id))?;
+    Ok(Ok(()))
 }
 #[async_trait]
 impl ACommand for RegCommand {
@@ -70,76 +449,160 @@ impl ACommand for RegCommand {
                             option.name("open").description("Always announce when registration opens").kind(CommandOptionType::Boolean).required(false)
                         }).create_option(|option| {
                             option.name("close").description("Always announce when registration closes").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("poll").description("Ask who's racing when registration opens").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("hold_mins").description("Wait this many minutes after registration opens before announcing counts").kind(CommandOptionType::Integer).required(false).min_int_value(0).max_int_value(120)
+                        }).create_option(|option| {
+                            option.name("qualifying").description("Announce when time trials & open qualifying open for each race week").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("reopen_mins").description("Don't announce registration opening within this many minutes of the last one closing").kind(CommandOptionType::Integer).required(false).min_int_value(0).max_int_value(120)
+                        }).create_option(|option| {
+                            option.name("last_day").description("Announce when it's the last day of each race week").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("week_change").description("Announce the track when each new race week starts").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("predict").description("Announce when a session looks like it'll go official, based on past sessions").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("results").description("Post the winners and SOF once an official race is over").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("first_open").description("Only announce registration opening for the first race of the day").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("cooldown_mins").description("Leave at least this many minutes between count announcements").kind(CommandOptionType::Integer).required(false).min_int_value(0).max_int_value(120)
+                        }).create_option(|option| {
+                            option.name("after").description("Only announce races starting after this time of day, e.g. 18:00 (server time)").kind(CommandOptionType::String).required(false)
+                        }).create_option(|option| {
+                            option.name("before").description("Only announce races starting before this time of day, e.g. 23:00 (server time)").kind(CommandOptionType::String).required(false)
+                        }).create_option(|option| {
+                            option.name("days").description("Only announce races on these days, e.g. sat,sun or weekdays").kind(CommandOptionType::String).required(false)
+                        }).create_option(|option| {
+                            option.name("setup_type").description("Only offer fixed or open setup series").kind(CommandOptionType::String).required(false)
+                                .add_string_choice("Fixed", "fixed").add_string_choice("Open", "open")
+                        }).create_option(|option| {
+                            option.name("car").description("Only offer series racing this car or class, e.g. GT3 or Mazda").kind(CommandOptionType::String).required(false)
+                        }).create_option(|option| {
+                            option.name("setup").description("Fill in the entry counts and open/close settings in a form instead").kind(CommandOptionType::Boolean).required(false)
+                        }).create_option(|option| {
+                            option.name("preset").description("Start from a preset combination of settings, any other options override it").kind(CommandOptionType::String).required(false);
+                            for name in self.presets.keys() {
+                                option.add_string_choice(name, name);
+                            }
+                            option
                         })
                 });
     }
 
     async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
-        for opt in &autocomp.data.options {
-            if opt.focused && opt.name == "series" {
-                if let Err(e) = autocomp
-                    .create_autocomplete_response(&ctx.http, |response| {
-                        let search_txt = match &autocomp.data.options[0].value {
-                            Some(serde_json::Value::String(s)) => s,
-                            _ => "",
-                        };
-                        let mut count = 0;
-                        let lc_txt = search_txt.to_lowercase();
-                        let state = self.state.lock().expect("unable to lock state");
-                        for season in state.seasons.values() {
-                            if season.lc_name.contains(&lc_txt) {
-                                response.add_string_choice(&season.name, season.series_id);
-                                count += 1;
-                                if count == 25 {
-                                    break;
-                                }
-                            }
-                        }
-                        response
-                    })
-                    .await
-                {
-                    println!("Failed to send autocomp response {:?}", e);
-                }
-            }
-        }
+        autocomplete_series(&self.state, &ctx, &autocomp, true).await;
     }
 
     async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if let Some(query) = resolve_option_str(&command.data.options, "series")
+            .and_then(|s| s.strip_prefix(SERIES_REQUEST_PREFIX).map(str::to_string))
+        {
+            self.request_series(&ctx, &command, query).await;
+            return;
+        }
         let series_id = match resolve_series_id(&ctx, &command).await {
             None => return,
             Some(i) => i,
         };
         let msg: String;
-        let open = resolve_option_bool(&command.data.options, "open").unwrap_or(false);
-        let close = resolve_option_bool(&command.data.options, "close").unwrap_or(false);
-        let maybe_min_reg = resolve_option_i64(&command.data.options, "min_reg");
-        let maybe_max_reg = resolve_option_i64(&command.data.options, "max_reg");
-        let dbr: rusqlite::Result<usize>;
-        {
-            let mut st = self.state.lock().expect("couldn't lock state");
-            let series = &st.seasons[&series_id];
-            let min_reg = maybe_min_reg.unwrap_or(series.reg_official / 2);
-            let max_reg = maybe_max_reg
-                .unwrap_or(((series.reg_split - series.reg_official) / 2) + series.reg_official);
-
-            let reg = Reg {
-                guild: command.guild_id,
-                channel: command.channel_id,
-                series_id,
-                series_name: series.name.clone(),
-                min_reg,
-                max_reg,
-                open,
-                close,
+        let preset = resolve_option_str(&command.data.options, "preset")
+            .and_then(|p| self.presets.get(&p).cloned())
+            .unwrap_or_default();
+        let channel = command.channel_id;
+        let defaults = self
+            .state
+            .db()
+            .call(move |db| db.watch_defaults(channel))
+            .await
+            .unwrap_or_default();
+        let opts = &command.data.options;
+        let (race_after, race_before, race_days) = match parse_race_window(opts) {
+            Ok(w) => w,
+            Err(msg) => {
+                respond_error(&ctx, &command, msg).await;
+                return;
+            }
+        };
+        let reg;
+        let db;
+        {
+            let seasons = self.state.seasons();
+            db = self.state.db();
+            let series = match seasons.get(&series_id) {
+                Some(s) => s,
+                None => {
+                    respond_error(
+                        &ctx,
+                        &command,
+                        "Please select one of the series from the autocomplete list.",
+                    )
+                    .await;
+                    return;
+                }
             };
+            if let Some(setup_type) = resolve_option_str(opts, "setup_type") {
+                if (setup_type == "fixed") != series.fixed_setup {
+                    let msg = format!("{} isn't a {} setup series.", series.name, setup_type);
+                    respond_error(&ctx, &command, &msg).await;
+                    return;
+                }
+            }
+            if let Some(car) = resolve_option_str(opts, "car") {
+                if !series.cars.contains(&car.trim().to_lowercase()) {
+                    let msg = format!("{} doesn't race anything called {}.", series.name, car);
+                    respond_error(&ctx, &command, &msg).await;
+                    return;
+                }
+            }
+            // the channel's defaults, then the preset, then what was given to /watch.
+            let mut r = Reg::new(command.guild_id, command.channel_id, series);
+            defaults.preset().apply(&mut r);
+            preset.apply(&mut r);
+            given_options(opts).apply(&mut r);
+            r.race_after = race_after;
+            r.race_before = race_before;
+            r.race_days = race_days;
+            if let Err(msg) = check_reg_range(r.min_reg, r.max_reg) {
+                respond_error(&ctx, &command, msg).await;
+                return;
+            }
+            reg = r;
             msg = format!(
                 "Okay, I will message this channel about race registrations for {}",
                 &reg
             );
-            dbr = st.db.upsert_reg(&reg, &command.user.name);
         }
-        match dbr {
+        if resolve_option_bool(&command.data.options, "setup").unwrap_or(false) {
+            self.open_setup(&ctx, &command, &reg).await;
+            return;
+        }
+        // if another channel already gets the same announcements, check they really
+        // want them in both places. This is before deferring, as only the user that ran
+        // the command should see the prompt, and that's fixed by the defer.
+        if let Some(guild_id) = command.guild_id {
+            let others = db
+                .read(move |db| db.guild_regs(guild_id))
+                .await
+                .unwrap_or_default();
+            if let Some(dupe) = others
+                .iter()
+                .find(|r| r.channel != reg.channel && r.same_settings(&reg))
+            {
+                respond_duplicate(&ctx, &command, &reg, dupe.channel).await;
+                return;
+            }
+        }
+        defer(&ctx, &command).await;
+        let created_by = command.user.clone();
+        let perms = command.member.as_ref().and_then(|m| m.permissions);
+        let limits = self.limits.clone();
+        match db
+            .call(move |db| upsert_reg_within(db, &reg, &created_by, perms, &limits))
+            .await
+        {
             Err(e) => {
                 println!("db failed to upsert reg {:?}", e);
                 respond_error(
@@ -149,250 +612,4887 @@ impl ACommand for RegCommand {
                 )
                 .await
             }
-            Ok(_) => respond_msg(&ctx, &command, &msg).await,
+            Ok(Err(limit)) => respond_error(&ctx, &command, &limit).await,
+            Ok(Ok(_)) => {
+                respond_msg(&ctx, &command, &msg).await;
+                if let Some(warning) = missing_permissions(&ctx, command.channel_id) {
+                    respond_private(&ctx, &command, &warning).await;
+                }
+            }
+        }
+    }
+
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        let parts: Vec<&str> = comp.data.custom_id.split(':').collect();
+        let msg = match parts[..] {
+            [_, "keep", ch] => format!("Okay, I'll leave that to <#{}>.", ch),
+            [_, "add", series_id, min_reg, max_reg, open, close, poll, hold_mins, qualifying, reopen_mins, last_day, first_open, cooldown_mins, week_change, predict, results, race_after, race_before, race_days] =>
+            {
+                let num = |s: &str| s.parse::<i64>().unwrap_or_default();
+                let series_id = num(series_id);
+                let mut reg = match self.state.seasons().get(&series_id) {
+                    Some(s) => Reg::new(comp.guild_id, comp.channel_id, s),
+                    None => return,
+                };
+                let flag = |s: &str| Some(s == "1");
+                Preset {
+                    min_reg: Some(num(min_reg)),
+                    max_reg: Some(num(max_reg)),
+                    open: flag(open),
+                    close: flag(close),
+                    poll: flag(poll),
+                    hold_mins: Some(num(hold_mins)),
+                    qualifying: flag(qualifying),
+                    reopen_mins: Some(num(reopen_mins)),
+                    last_day: flag(last_day),
+                    first_open: flag(first_open),
+                    cooldown_mins: Some(num(cooldown_mins)),
+                    week_change: flag(week_change),
+                    predict: flag(predict),
+                    results: flag(results),
+                }
+                .apply(&mut reg);
+                reg.race_after = num(race_after);
+                reg.race_before = num(race_before);
+                reg.race_days = num(race_days);
+                let msg = format!(
+                    "Okay, I will message this channel about race registrations for {}",
+                    &reg
+                );
+                let created_by = comp.user.clone();
+                let perms = comp.member.as_ref().and_then(|m| m.permissions);
+                let limits = self.limits.clone();
+                match self
+                    .state
+                    .db()
+                    .call(move |db| upsert_reg_within(db, &reg, &created_by, perms, &limits))
+                    .await
+                {
+                    Err(e) => {
+                        println!("db failed to upsert reg {:?}", e);
+                        "Sorry I appear to have lost my notepad, try again later.".to_string()
+                    }
+                    Ok(Err(limit)) => limit,
+                    Ok(Ok(_)) => msg,
+                }
+            }
+            _ => return,
+        };
+        if let Err(e) = comp
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| message.content(msg).components(|c| c))
+            })
+            .await
+        {
+            println!("Failed to respond to component {}", e);
+        }
+    }
+
+    async fn modal(&self, ctx: Context, m: ModalSubmitInteraction) {
+        let series_id = match m
+            .data
+            .custom_id
+            .strip_prefix("watch:setup:")
+            .and_then(|id| id.parse::<i64>().ok())
+        {
+            Some(id) => id,
+            None => return,
+        };
+        let msg = match parse_watch_edit(&modal_values(&m)) {
+            Err(msg) => msg.to_string(),
+            Ok((min_reg, max_reg, open, close)) => {
+                let mut reg = match self.state.seasons().get(&series_id) {
+                    Some(s) => Reg::new(m.guild_id, m.channel_id, s),
+                    None => return,
+                };
+                Preset {
+                    min_reg: Some(min_reg),
+                    max_reg: Some(max_reg),
+                    open: Some(open),
+                    close: Some(close),
+                    ..Preset::default()
+                }
+                .apply(&mut reg);
+                let msg = format!(
+                    "Okay, I will message this channel about race registrations for {}",
+                    &reg
+                );
+                let created_by = m.user.clone();
+                let perms = m.member.as_ref().and_then(|m| m.permissions);
+                let limits = self.limits.clone();
+                match self
+                    .state
+                    .db()
+                    .call(move |db| upsert_reg_within(db, &reg, &created_by, perms, &limits))
+                    .await
+                {
+                    Err(e) => {
+                        println!("db failed to upsert reg {:?}", e);
+                        "Sorry I appear to have lost my notepad, try again later.".to_string()
+                    }
+                    Ok(Err(limit)) => limit,
+                    Ok(Ok(_)) => msg,
+                }
+            }
+        };
+        if let Err(e) = m
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| message.content(msg))
+            })
+            .await
+        {
+            println!("Failed to respond to modal {}", e);
         }
     }
 }
 
-pub struct ListCommand {
-    state: Arc<Mutex<HandlerState>>,
+// how many series /watchmany can take in one go.
+const WATCH_MANY_MAX: usize = 10;
+
+// WatchManyCommand watches several series at once with the same preset, for setting up
+// a new channel without a /watch for each one.
+pub struct WatchManyCommand {
+    state: Arc<HandlerState>,
+    presets: BTreeMap<String, Preset>,
+    limits: WatchLimits,
 }
-impl ListCommand {
-    pub fn new(state: Arc<Mutex<HandlerState>>) -> Self {
-        Self { state }
+impl WatchManyCommand {
+    pub fn new(
+        state: Arc<HandlerState>,
+        presets: BTreeMap<String, Preset>,
+        limits: WatchLimits,
+    ) -> Self {
+        Self {
+            state,
+            presets,
+            limits,
+        }
     }
 }
 #[async_trait]
-impl ACommand for ListCommand {
+impl ACommand for WatchManyCommand {
     fn name(&self) -> &str {
-        "watching"
+        "watchmany"
     }
     fn create(&self, commands: &mut CreateApplicationCommands) {
         commands.create_application_command(|command| {
             command
                 .name(self.name())
-                .description("List the series that are being watched for this channel.")
+                .description("Watch several series at once, with the default settings or a preset");
+            for i in 1..=WATCH_MANY_MAX {
+                command.create_option(|option| {
+                    option
+                        .name(format!("series{}", i))
+                        .description("A series to announce")
+                        .set_autocomplete(true)
+                        .kind(CommandOptionType::String)
+                        .required(i == 1)
+                });
+            }
+            command.create_option(|option| {
+                option
+                    .name("preset")
+                    .description("Use a preset combination of settings for all of them")
+                    .kind(CommandOptionType::String)
+                    .required(false);
+                for name in self.presets.keys() {
+                    option.add_string_choice(name, name);
+                }
+                option
+            })
         });
     }
+
+    async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
+        autocomplete_series(&self.state, &ctx, &autocomp, false).await;
+    }
+
     async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
-        let regs: rusqlite::Result<Vec<Reg>>;
+        defer(&ctx, &command).await;
+        let opts = &command.data.options;
+        let preset = resolve_option_str(opts, "preset")
+            .and_then(|p| self.presets.get(&p).cloned())
+            .unwrap_or_default();
+        let channel = command.channel_id;
+        let defaults = self
+            .state
+            .db()
+            .call(move |db| db.watch_defaults(channel))
+            .await
+            .unwrap_or_default();
+        let mut regs = Vec::new();
+        let mut unknown = Vec::new();
         {
-            let st = self.state.lock().expect("Unable to lock state");
-            regs = st.db.channel_regs(command.channel_id);
+            let seasons = self.state.seasons();
+            for o in opts.iter().filter(|o| o.name.starts_with("series")) {
+                let txt = match &o.resolved {
+                    Some(CommandDataOptionValue::String(s)) => s.clone(),
+                    _ => continue,
+                };
+                match txt.parse::<i64>().ok().and_then(|id| seasons.get(&id)) {
+                    Some(series) if !regs.iter().any(|r: &Reg| r.series_id == series.series_id) => {
+                        regs.push(preset_reg(
+                            series,
+                            &preset,
+                            &defaults,
+                            command.guild_id,
+                            channel,
+                        ))
+                    }
+                    Some(_) => {}
+                    None => unknown.push(txt),
+                }
+            }
         }
-        match regs {
+        let created_by = command.user.clone();
+        let perms = command.member.as_ref().and_then(|m| m.permissions);
+        let limits = self.limits.clone();
+        let res = self
+            .state
+            .db()
+            .call(move |db| -> rusqlite::Result<_> {
+                let existing = db.channel_regs(channel)?;
+                let (mut added, mut skipped, mut problem) = (Vec::new(), Vec::new(), None);
+                for reg in regs {
+                    // /watchmany is for new watches, it leaves the settings of existing ones alone.
+                    if existing.iter().any(|r| r.series_id == reg.series_id) {
+                        skipped.push(reg.series_name);
+                        continue;
+                    }
+                    match upsert_reg_within(db, &reg, &created_by, perms, &limits)? {
+                        Ok(_) => added.push(reg.series_name),
+                        Err(msg) => {
+                            problem = Some(msg);
+                            break;
+                        }
+                    }
+                }
+                Ok((added, skipped, problem))
+            })
+            .await;
+        let (added, skipped, problem) = match res {
             Err(e) => {
-                println!("Failed to read watches {:?}", e);
+                println!("db failed to upsert regs {:?}", e);
                 respond_error(
                     &ctx,
                     &command,
-                    "Sorry, i can't find my notebook right how, try again later.",
+                    "Sorry I appear to have lost my notepad, try again later.",
                 )
                 .await;
+                return;
             }
-            Ok(r) => {
-                if r.is_empty() {
-                    respond_msg(
-                        &ctx,
-                        &command,
-                        "No registration announcements for this channel.",
-                    )
-                    .await;
-                } else {
-                    let mut msgs = Vec::new();
-                    msgs.push("Will post about race registrations for:".to_string());
-                    for cr in r {
-                        msgs.push(format!("\u{2981} {}", cr));
-                    }
-                    respond_msg(&ctx, &command, &msgs.join("\n")).await;
+            Ok(r) => r,
+        };
+        let mut lines = Vec::new();
+        if !added.is_empty() {
+            lines.push(format!(
+                "Okay, I will message this channel about race registrations for {}.",
+                added.join(", ")
+            ));
+        }
+        if !skipped.is_empty() {
+            lines.push(format!(
+                "This channel is already watching {}, use /watch to change their settings.",
+                skipped.join(", ")
+            ));
+        }
+        if !unknown.is_empty() {
+            lines.push(format!(
+                "I don't know about {}, please select series from the autocomplete list.",
+                unknown.join(", ")
+            ));
+        }
+        lines.extend(problem);
+        let msg = lines.join("\n");
+        if added.is_empty() {
+            respond_error(&ctx, &command, &msg).await;
+            return;
+        }
+        respond_msg(&ctx, &command, &msg).await;
+        if let Some(warning) = missing_permissions(&ctx, command.channel_id) {
+            respond_private(&ctx, &command, &warning).await;
+        }
+    }
+}
+
+// the watch on series from the preset, with the channel's defaults for anything the
+// preset doesn't set, the same way /watch fills them in.
+fn preset_reg(
+    series: &SeasonInfo,
+    preset: &Preset,
+    defaults: &WatchDefaults,
+    guild: Option<GuildId>,
+    channel: ChannelId,
+) -> Reg {
+    let mut reg = Reg::new(guild, channel, series);
+    defaults.preset().apply(&mut reg);
+    preset.apply(&mut reg);
+    reg
+}
+
+// offers the active series that match what's been typed so far for the series option.
+// With offer_request, if nothing matches the typed text is offered as a request for a
+// series we don't cover.
+async fn autocomplete_series(
+    state: &HandlerState,
+    ctx: &Context,
+    autocomp: &AutocompleteInteraction,
+    offer_request: bool,
+) {
+    let blocked = match autocomp.guild_id {
+        Some(g) => state
+            .db()
+            .read(move |db| db.blocked_series(g))
+            .await
+            .unwrap_or_default(),
+        None => HashSet::new(),
+    };
+    autocomplete_series_except(state, ctx, autocomp, &blocked, offer_request).await;
+}
+
+// the start of the series option value for a request for a series we don't know about.
+const SERIES_REQUEST_PREFIX: &str = "request:";
+
+// offers the series matching the typed text as choices, apart from those in skip.
+async fn autocomplete_series_except(
+    state: &HandlerState,
+    ctx: &Context,
+    autocomp: &AutocompleteInteraction,
+    skip: &HashSet<i64>,
+    offer_request: bool,
+) {
+    // /watch's setup_type & car narrow the choices to fixed or open setup series, and
+    // to series racing a car.
+    let opt_str = |name: &str| {
+        autocomp
+            .data
+            .options
+            .iter()
+            .find(|o| o.name == name)
+            .and_then(|o| o.value.as_ref())
+            .and_then(|v| v.as_str())
+    };
+    let fixed = opt_str("setup_type").map(|v| v == "fixed");
+    let car = opt_str("car").map(|c| c.trim().to_lowercase());
+    for opt in &autocomp.data.options {
+        // /watchmany has series1, series2 etc.
+        if opt.focused && opt.name.starts_with("series") {
+            let search_txt = match &opt.value {
+                Some(serde_json::Value::String(s)) => s.trim(),
+                _ => "",
+            };
+            let lc_txt = search_txt.to_lowercase();
+            let choices: Vec<(String, i64)> = {
+                let seasons = state.seasons();
+                // busier series go first when they match equally well.
+                let mut entries: HashMap<i64, i64> = HashMap::new();
+                for e in state.guide().iter() {
+                    let c = entries.entry(e.series_id).or_default();
+                    *c = (*c).max(e.entry_count);
                 }
+                let mut matches: Vec<(i64, i64, &SeasonInfo)> = seasons
+                    .values()
+                    .filter(|s| !skip.contains(&s.series_id))
+                    .filter(|s| fixed.is_none_or(|f| f == s.fixed_setup))
+                    .filter(|s| car.as_ref().is_none_or(|c| s.cars.contains(c.as_str())))
+                    .filter_map(|s| {
+                        let score = fuzzy_score(&lc_txt, &s.lc_name).or_else(|| {
+                            // or the cars, e.g. "gt3", these go after any name matches.
+                            let mut words = lc_txt.split_whitespace();
+                            (!lc_txt.is_empty() && words.all(|w| s.cars.contains(w))).then_some(0)
+                        });
+                        score.map(|score| {
+                            let busy = entries.get(&s.series_id).copied().unwrap_or_default();
+                            (score, busy, s)
+                        })
+                    })
+                    .collect();
+                matches.sort_by(|a, b| (b.0, b.1).cmp(&(a.0, a.1)).then(a.2.name.cmp(&b.2.name)));
+                matches
+                    .iter()
+                    .take(25)
+                    .map(|(_, _, s)| {
+                        let fixed = if s.fixed_setup { " (fixed)" } else { "" };
+                        let label = format!("{}{} @ {}", s.name, fixed, s.track_name);
+                        (truncate(&label, 100), s.series_id)
+                    })
+                    .collect()
+            };
+            if let Err(e) = autocomp
+                .create_autocomplete_response(&ctx.http, |response| {
+                    for (label, series_id) in &choices {
+                        response.add_string_choice(label, series_id);
+                    }
+                    if choices.is_empty() && offer_request && !search_txt.is_empty() {
+                        response.add_string_choice(
+                            truncate(&format!("request: {}", search_txt), 100),
+                            truncate(&format!("{}{}", SERIES_REQUEST_PREFIX, search_txt), 100),
+                        );
+                    }
+                    response
+                })
+                .await
+            {
+                println!("Failed to send autocomp response {:?}", e);
             }
+        }
+    }
+}
+
+// scores how well the typed text matches a series name, higher is better, None if it
+// doesn't match. Each word has to be in the name, or failing that its letters in order,
+// so "imsa pilot" finds "IMSA iRacing Series - Pilot Challenge". Both are lowercase.
+fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let mut score = 0;
+    if !query.is_empty() && name.starts_with(query) {
+        score += 100;
+    } else if !query.is_empty() && name.contains(query) {
+        score += 50;
+    }
+    for word in query.split_whitespace() {
+        score += match name.find(word) {
+            // at the start of a word in the name.
+            Some(i) if !name[..i].ends_with(char::is_alphanumeric) => 20,
+            Some(_) => 10,
+            None if is_subsequence(word, name) => 1,
+            None => return None,
         };
     }
+    Some(score)
 }
 
-pub struct RemoveCommand {
-    state: Arc<Mutex<HandlerState>>,
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut hay = haystack.chars();
+    needle.chars().all(|c| hay.any(|h| h == c))
 }
-impl RemoveCommand {
-    pub fn new(state: Arc<Mutex<HandlerState>>) -> Self {
+
+// returns the custom_id for a button that adds reg to the channel the button is in,
+// see RegCommand::component.
+fn watch_add_id(reg: &Reg) -> String {
+    let flag = |b: bool| if b { "1" } else { "0" };
+    format!(
+        "watch:add:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+        reg.series_id,
+        reg.min_reg,
+        reg.max_reg,
+        flag(reg.open),
+        flag(reg.close),
+        flag(reg.poll),
+        reg.hold_mins,
+        flag(reg.qualifying),
+        reg.reopen_mins,
+        flag(reg.last_day),
+        flag(reg.first_open),
+        reg.cooldown_mins,
+        flag(reg.week_change),
+        flag(reg.predict),
+        flag(reg.results),
+        reg.race_after,
+        reg.race_before,
+        reg.race_days
+    )
+}
+
+// tells the user that another channel already has the same watch, and offers to
+// leave it there or go ahead and add it to this channel too.
+async fn respond_duplicate(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    reg: &Reg,
+    other: ChannelId,
+) {
+    let add_id = watch_add_id(reg);
+    let content = format!(
+        "<#{}> is already watching {} with the same settings, do you want the announcements in both channels?",
+        other, reg.series_name
+    );
+    let mut components = CreateComponents::default();
+    components.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id(format!("watch:keep:{}", other))
+                .label("Leave it there")
+                .style(ButtonStyle::Primary)
+        })
+        .create_button(|b| {
+            b.custom_id(&add_id)
+                .label("Watch here too")
+                .style(ButtonStyle::Secondary)
+        })
+    });
+    respond(ctx, command, &content, true, Some(components)).await;
+}
+
+// WatchMessageCommand is a message context menu command, it looks for series names in
+// the message and offers to watch them with the default settings.
+pub struct WatchMessageCommand {
+    state: Arc<HandlerState>,
+}
+impl WatchMessageCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
         Self { state }
     }
 }
 #[async_trait]
-impl ACommand for RemoveCommand {
+impl ACommand for WatchMessageCommand {
     fn name(&self) -> &str {
-        "nomore"
+        "Watch this series"
     }
     fn create(&self, commands: &mut CreateApplicationCommands) {
         commands.create_application_command(|command| {
             command
                 .name(self.name())
-                .description("Stop reporting race registrations for a series.")
-                .create_option(
-                    |option| -> &mut serenity::builder::CreateApplicationCommandOption {
-                        option
-                            .name("series")
-                            .description("The series to announce")
-                            .set_autocomplete(true)
-                            .kind(CommandOptionType::String)
-                            .required(true)
-                    },
-                )
+                .kind(CommandType::Message)
+                .dm_permission(false)
         });
     }
-
-    async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
-        for opt in &autocomp.data.options {
-            if opt.focused && opt.name == "series" {
-                if let Err(e) = autocomp
-                    .create_autocomplete_response(&ctx.http, |response| {
-                        let search_txt = match &autocomp.data.options[0].value {
-                            Some(serde_json::Value::String(s)) => s,
-                            _ => "",
-                        };
-                        let mut count = 0;
-                        let lc_txt = search_txt.to_lowercase();
-
-                        let st = self.state.lock().expect("Unable to lock state");
-                        let regs = st
-                            .db
-                            .channel_regs(autocomp.channel_id)
-                            .expect("Failed to read db");
-                        for reg in regs {
-                            if reg.series_name.to_lowercase().contains(&lc_txt) {
-                                response.add_string_choice(&reg.series_name, reg.series_id);
-                                count += 1;
-                                if count == 25 {
-                                    break;
-                                }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let text = match command.data.target() {
+            Some(ResolvedTarget::Message(m)) => m.content,
+            _ => String::new(),
+        };
+        let matches: Vec<SeasonInfo> = series_in_text(self.state.seasons().values(), &text)
+            .into_iter()
+            .cloned()
+            .collect();
+        if matches.is_empty() {
+            respond_error(
+                &ctx,
+                &command,
+                "I couldn't find the name of a series in that message, try /watch instead.",
+            )
+            .await;
+            return;
+        }
+        let mut components = CreateComponents::default();
+        components.create_action_row(|row| {
+            for series in &matches {
+                let reg = Reg::new(command.guild_id, command.channel_id, series);
+                row.create_button(|b| {
+                    b.custom_id(watch_add_id(&reg))
+                        .label(truncate(&series.name, 80))
+                        .style(ButtonStyle::Primary)
+                });
+            }
+            row
+        });
+        respond(
+            &ctx,
+            &command,
+            "Which series should I watch in this channel? I'll use the default settings, use /watch if you want to change them.",
+            true,
+            Some(components),
+        )
+        .await;
+    }
+}
+
+// returns upto 5 active series that are mentioned in the text, most specific first.
+fn series_in_text<'a>(
+    seasons: impl Iterator<Item = &'a SeasonInfo>,
+    text: &str,
+) -> Vec<&'a SeasonInfo> {
+    let lc_text = text.trim().to_lowercase();
+    if lc_text.len() < 3 {
+        return Vec::new();
+    }
+    let mut found: Vec<&SeasonInfo> = seasons
+        .filter(|s| {
+            lc_text.contains(&s.lc_name)
+                || lc_text.contains(&s.short_name.to_lowercase())
+                || s.lc_name.contains(&lc_text)
+        })
+        .collect();
+    found.sort_by(|a, b| b.name.len().cmp(&a.name.len()).then(a.name.cmp(&b.name)));
+    found.truncate(5);
+    found
+}
+
+pub struct ListCommand {
+    state: Arc<HandlerState>,
+}
+impl ListCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+impl ListCommand {
+    async fn channel_watches(&self, ch: ChannelId) -> (Result<Vec<Reg>, BotError>, Vec<String>) {
+        let read = self
+            .state
+            .db()
+            .try_call(move |db| {
+                (
+                    db.channel_regs(ch),
+                    db.channel_mutes(ch).unwrap_or_default(),
+                )
+            })
+            .await;
+        match read {
+            Ok((regs, mutes)) => (regs.map_err(BotError::from), mutes),
+            Err(e) => (Err(e), Vec::new()),
+        }
+    }
+}
+
+// discord allows 5 rows of components, each watch on the page gets a row for its
+// edit button, leaving one for the page buttons and one for the page menu.
+const WATCHING_PAGE_SIZE: usize = 3;
+// discord's limit on a message, and how many options a menu can have.
+const MAX_MESSAGE_LEN: usize = 2000;
+const MAX_MENU_OPTIONS: usize = 25;
+
+#[async_trait]
+impl ACommand for ListCommand {
+    fn name(&self) -> &str {
+        "watching"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("List the series that are being watched for this channel.")
+                .create_option(|option| {
+                    option
+                        .name("private")
+                        .description("Only show the list to you")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let private = private_reply(&self.state, &command).await;
+        if private {
+            defer_private(&ctx, &command).await;
+        } else {
+            defer(&ctx, &command).await;
+        }
+        let (regs, mutes) = self.channel_watches(command.channel_id).await;
+        match regs {
+            Err(e) => {
+                println!("Failed to read watches {:?}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, i can't find my notebook right how, try again later.",
+                )
+                .await;
+            }
+            Ok(r) => {
+                if r.is_empty() {
+                    respond(
+                        &ctx,
+                        &command,
+                        "No registration announcements for this channel.",
+                        private,
+                        None,
+                    )
+                    .await;
+                } else {
+                    let (content, components) = watching_page(&r, &mutes, PageToken::default());
+                    respond(&ctx, &command, &content, private, Some(components)).await;
+                }
+            }
+        };
+    }
+
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        let parts: Vec<&str> = comp.data.custom_id.split(':').collect();
+        let res = match parts[..] {
+            [_, "page", token] | [_, "jump", token] => {
+                // the menu has the page in its value rather than its id.
+                let token = match (parts[1], comp.data.values.first()) {
+                    ("jump", Some(v)) => PageToken::parse(v),
+                    _ => PageToken::parse(token),
+                };
+                let (regs, mutes) = self.channel_watches(comp.channel_id).await;
+                let (content, components) = watching_page(&regs.unwrap_or_default(), &mutes, token);
+                comp.create_interaction_response(&ctx.http, |response| {
+                    response
+                        .kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|message| {
+                            message.content(content).set_components(components)
+                        })
+                })
+                .await
+            }
+            [_, "edit", series_id] => {
+                let series_id = series_id.parse::<i64>().unwrap_or_default();
+                let (regs, _) = self.channel_watches(comp.channel_id).await;
+                match regs
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|r| r.series_id == series_id)
+                {
+                    None => {
+                        comp.create_interaction_response(&ctx.http, |response| {
+                            response
+                                .kind(InteractionResponseType::ChannelMessageWithSource)
+                                .interaction_response_data(|message| {
+                                    message
+                                        .flags(MessageFlags::EPHEMERAL)
+                                        .content("This channel isn't watching that series anymore.")
+                                })
+                        })
+                        .await
+                    }
+                    Some(reg)
+                        if !can_change_watch(
+                            &reg,
+                            comp.user.id,
+                            comp.member.as_ref().and_then(|m| m.permissions),
+                        ) =>
+                    {
+                        comp.create_interaction_response(&ctx.http, |response| {
+                            response
+                                .kind(InteractionResponseType::ChannelMessageWithSource)
+                                .interaction_response_data(|message| {
+                                    message
+                                        .flags(MessageFlags::EPHEMERAL)
+                                        .content(RESTRICTED_WATCH)
+                                })
+                        })
+                        .await
+                    }
+                    Some(reg) => {
+                        comp.create_interaction_response(&ctx.http, |response| {
+                            response
+                                .kind(InteractionResponseType::Modal)
+                                .interaction_response_data(|modal| {
+                                    modal
+                                        .custom_id(format!("watching:save:{}", reg.series_id))
+                                        .title(truncate(&reg.series_name, 45))
+                                        .components(|c| {
+                                            watch_form(
+                                                c,
+                                                reg.min_reg,
+                                                reg.max_reg,
+                                                reg.open,
+                                                reg.close,
+                                            )
+                                        })
+                                })
+                        })
+                        .await
+                    }
+                }
+            }
+            _ => return,
+        };
+        if let Err(e) = res {
+            println!("Failed to respond to component {}", e);
+        }
+    }
+
+    async fn modal(&self, ctx: Context, m: ModalSubmitInteraction) {
+        let series_id = match m
+            .data
+            .custom_id
+            .strip_prefix("watching:save:")
+            .and_then(|id| id.parse::<i64>().ok())
+        {
+            Some(id) => id,
+            None => return,
+        };
+        let msg = match parse_watch_edit(&modal_values(&m)) {
+            Err(msg) => msg.to_string(),
+            Ok((min_reg, max_reg, open, close)) => {
+                let ch = m.channel_id;
+                let created_by = m.user.clone();
+                let perms = m.member.as_ref().and_then(|m| m.permissions);
+                let res = self
+                    .state
+                    .db()
+                    .call(move |db| -> rusqlite::Result<Result<Option<Reg>, ()>> {
+                        let reg = db
+                            .channel_regs(ch)?
+                            .into_iter()
+                            .find(|r| r.series_id == series_id);
+                        match reg {
+                            None => Ok(Ok(None)),
+                            Some(reg) if !can_change_watch(&reg, created_by.id, perms) => {
+                                Ok(Err(()))
+                            }
+                            Some(mut reg) => {
+                                reg.min_reg = min_reg;
+                                reg.max_reg = max_reg;
+                                reg.open = open;
+                                reg.close = close;
+                                db.upsert_reg(&reg, &created_by.name, Some(created_by.id))?;
+                                Ok(Ok(Some(reg)))
                             }
                         }
-                        response
+                    })
+                    .await;
+                match res {
+                    Err(e) => {
+                        println!("db failed to update reg {:?}", e);
+                        "Sorry I appear to have lost my notepad, try again later.".to_string()
+                    }
+                    Ok(Err(_)) => RESTRICTED_WATCH.to_string(),
+                    Ok(Ok(None)) => "This channel isn't watching that series anymore.".to_string(),
+                    Ok(Ok(Some(reg))) => format!(
+                        "Okay, I will message this channel about race registrations for {}",
+                        &reg
+                    ),
+                }
+            }
+        };
+        if let Err(e) = m
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| message.content(msg))
+            })
+            .await
+        {
+            println!("Failed to respond to modal {}", e);
+        }
+    }
+}
+
+// PageToken is which page of /watching a button or menu goes to. It has the series the
+// page starts with as well as the page number, so that if watches are added or removed
+// in the meantime it still goes to the page with that series on, rather than moving by
+// whatever changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct PageToken {
+    page: usize,
+    series_id: Option<i64>,
+}
+impl PageToken {
+    fn for_page(regs: &[Reg], page: usize) -> PageToken {
+        PageToken {
+            page,
+            series_id: regs.get(page * WATCHING_PAGE_SIZE).map(|r| r.series_id),
+        }
+    }
+    // parses the page[.series_id] from a custom_id or menu value, older buttons just
+    // have the page.
+    fn parse(s: &str) -> PageToken {
+        let (page, series_id) = s.split_once('.').unwrap_or((s, ""));
+        PageToken {
+            page: page.parse().unwrap_or(0),
+            series_id: series_id.parse().ok(),
+        }
+    }
+    // the page to show from regs, which may have changed since the token was made.
+    fn resolve(&self, regs: &[Reg]) -> usize {
+        self.series_id
+            .and_then(|id| regs.iter().position(|r| r.series_id == id))
+            .map_or(self.page, |i| i / WATCHING_PAGE_SIZE)
+    }
+}
+impl std::fmt::Display for PageToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.series_id {
+            Some(id) => write!(f, "{}.{}", self.page, id),
+            None => write!(f, "{}", self.page),
+        }
+    }
+}
+
+// returns the text and buttons for a page of /watching.
+fn watching_page(regs: &[Reg], mutes: &[String], token: PageToken) -> (String, CreateComponents) {
+    let pages = regs.len().div_ceil(WATCHING_PAGE_SIZE).max(1);
+    let page = token.resolve(regs).min(pages - 1);
+    let on_page: Vec<&Reg> = regs
+        .iter()
+        .skip(page * WATCHING_PAGE_SIZE)
+        .take(WATCHING_PAGE_SIZE)
+        .collect();
+    let mut msgs = Vec::new();
+    msgs.push("Will post about race registrations for:".to_string());
+    for cr in &on_page {
+        match &cr.created_by {
+            Some(by) => msgs.push(format!("\u{2981} {} Added by {}.", cr, by)),
+            None => msgs.push(format!("\u{2981} {}", cr)),
+        }
+    }
+    if pages > 1 {
+        msgs.push(format!("Page {} of {}", page + 1, pages));
+    }
+    if !mutes.is_empty() {
+        msgs.push(format!("Muted categories: {}", mutes.join(", ")));
+    }
+    let mut components = CreateComponents::default();
+    for cr in &on_page {
+        components.create_action_row(|row| {
+            row.create_button(|b| {
+                b.custom_id(format!("watching:edit:{}", cr.series_id))
+                    .label(truncate(&format!("Edit {}", cr.series_name), 80))
+                    .style(ButtonStyle::Secondary)
+            })
+        });
+    }
+    if pages > 1 {
+        components.create_action_row(|row| {
+            row.create_button(|b| {
+                b.custom_id(format!(
+                    "watching:page:{}",
+                    PageToken::for_page(regs, page.saturating_sub(1))
+                ))
+                .label("Previous")
+                .style(ButtonStyle::Primary)
+                .disabled(page == 0)
+            })
+            .create_button(|b| {
+                b.custom_id(format!(
+                    "watching:page:{}",
+                    PageToken::for_page(regs, (page + 1).min(pages - 1))
+                ))
+                .label("Next")
+                .style(ButtonStyle::Primary)
+                .disabled(page + 1 == pages)
+            })
+        });
+        // a menu to go straight to a page, labelled with the series on it. If there's
+        // more pages than fit in the menu, it has the ones around this page.
+        let first = page
+            .saturating_sub(MAX_MENU_OPTIONS / 2)
+            .min(pages.saturating_sub(MAX_MENU_OPTIONS));
+        components.create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id("watching:jump:0")
+                    .placeholder("Go to page...")
+                    .options(|opts| {
+                        for p in (first..pages).take(MAX_MENU_OPTIONS) {
+                            let names: Vec<&str> = regs
+                                .iter()
+                                .skip(p * WATCHING_PAGE_SIZE)
+                                .take(WATCHING_PAGE_SIZE)
+                                .map(|r| r.series_name.as_str())
+                                .collect();
+                            opts.create_option(|o| {
+                                o.label(format!("Page {}", p + 1))
+                                    .description(truncate(&names.join(", "), 100))
+                                    .value(PageToken::for_page(regs, p))
+                                    .default_selection(p == page)
+                            });
+                        }
+                        opts
+                    })
+            })
+        });
+    }
+    let mut content = msgs.join("\n");
+    if content.chars().count() > MAX_MESSAGE_LEN {
+        content = truncate(&content, MAX_MESSAGE_LEN - 1) + "\u{2026}";
+    }
+    (content, components)
+}
+
+// adds the inputs for the form used by /watch setup and the /watching edit buttons.
+fn watch_form(
+    c: &mut CreateComponents,
+    min_reg: i64,
+    max_reg: i64,
+    open: bool,
+    close: bool,
+) -> &mut CreateComponents {
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    let fields = [
+        (
+            "min_reg",
+            "Announce from this many entries",
+            min_reg.to_string(),
+        ),
+        (
+            "max_reg",
+            "Stop announcing after this many entries",
+            max_reg.to_string(),
+        ),
+        (
+            "open",
+            "Announce registration opening (yes/no)",
+            yes_no(open).to_string(),
+        ),
+        (
+            "close",
+            "Announce registration closing (yes/no)",
+            yes_no(close).to_string(),
+        ),
+    ];
+    for (id, label, value) in fields {
+        c.create_action_row(|row| {
+            row.create_input_text(|t| {
+                t.custom_id(id)
+                    .label(label)
+                    .style(InputTextStyle::Short)
+                    .value(value)
+                    .required(true)
+            })
+        });
+    }
+    c
+}
+
+// returns the text inputs from a submitted modal, by custom_id.
+fn modal_values(m: &ModalSubmitInteraction) -> HashMap<&str, &str> {
+    m.data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .filter_map(|c| match c {
+            ActionRowComponent::InputText(t) => Some((t.custom_id.as_str(), t.value.as_str())),
+            _ => None,
+        })
+        .collect()
+}
+
+// the watch options given to /watch, as a preset to apply over the channel's defaults.
+fn given_options(opts: &[CommandDataOption]) -> Preset {
+    Preset {
+        min_reg: resolve_option_i64(opts, "min_reg"),
+        max_reg: resolve_option_i64(opts, "max_reg"),
+        open: resolve_option_bool(opts, "open"),
+        close: resolve_option_bool(opts, "close"),
+        poll: resolve_option_bool(opts, "poll"),
+        hold_mins: resolve_option_i64(opts, "hold_mins"),
+        qualifying: resolve_option_bool(opts, "qualifying"),
+        reopen_mins: resolve_option_i64(opts, "reopen_mins"),
+        last_day: resolve_option_bool(opts, "last_day"),
+        first_open: resolve_option_bool(opts, "first_open"),
+        cooldown_mins: resolve_option_i64(opts, "cooldown_mins"),
+        week_change: resolve_option_bool(opts, "week_change"),
+        predict: resolve_option_bool(opts, "predict"),
+        results: resolve_option_bool(opts, "results"),
+    }
+}
+
+// the after, before & days options from /watch, see Reg::race_after.
+fn parse_race_window(opts: &[CommandDataOption]) -> Result<(i64, i64, i64), &'static str> {
+    let time = |name: &str| resolve_option_str(opts, name).map_or(Ok(0), |t| parse_time_of_day(&t));
+    let days = resolve_option_str(opts, "days").map_or(Ok(0), |d| parse_days(&d))?;
+    Ok((time("after")?, time("before")?, days))
+}
+
+// parses a time of day like 18:30 or 18 into minutes after midnight.
+fn parse_time_of_day(s: &str) -> Result<i64, &'static str> {
+    let s = s.trim();
+    let (h, m) = s.split_once(':').unwrap_or((s, "0"));
+    match (h.parse::<i64>(), m.parse::<i64>()) {
+        (Ok(h), Ok(m)) if (0..24).contains(&h) && (0..60).contains(&m) => Ok(h * 60 + m),
+        _ => Err("Times need to be 24 hour clock times like 18:30."),
+    }
+}
+
+// parses days like sat,sun or weekdays into a Reg::race_days mask.
+fn parse_days(s: &str) -> Result<i64, &'static str> {
+    let mut days = 0;
+    for d in s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|d| !d.is_empty())
+    {
+        let d = d.to_lowercase();
+        days |= match d.as_str() {
+            "weekdays" => 0b0011111,
+            "weekends" => 0b1100000,
+            _ => match DAY_NAMES
+                .iter()
+                .position(|n| d.starts_with(&n.to_lowercase()))
+            {
+                Some(i) => 1 << i,
+                None => return Err("Days need to be like mon,wed,sat or weekdays or weekends."),
+            },
+        };
+    }
+    Ok(days)
+}
+
+// parses the values from the watch form.
+fn parse_watch_edit(values: &HashMap<&str, &str>) -> Result<(i64, i64, bool, bool), &'static str> {
+    let num = |k: &str| {
+        values
+            .get(k)
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .filter(|n| (0..=1000).contains(n))
+    };
+    let yes_no = |k: &str| match values.get(k).map(|v| v.trim().to_lowercase()).as_deref() {
+        Some("yes" | "y" | "true") => Some(true),
+        Some("no" | "n" | "false") => Some(false),
+        _ => None,
+    };
+    let (min_reg, max_reg) = match (num("min_reg"), num("max_reg")) {
+        (Some(min), Some(max)) if max >= 1 => (min, max),
+        _ => return Err("The entry counts should be numbers between 0 and 1000."),
+    };
+    check_reg_range(min_reg, max_reg)?;
+    match (yes_no("open"), yes_no("close")) {
+        (Some(open), Some(close)) => Ok((min_reg, max_reg, open, close)),
+        _ => Err("Open and close should be yes or no."),
+    }
+}
+
+// returns s cut down to at most max chars.
+fn truncate(s: &str, max: usize) -> String {
+    s.chars().take(max).collect()
+}
+
+pub struct ListAllCommand {
+    state: Arc<HandlerState>,
+}
+impl ListAllCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for ListAllCommand {
+    fn name(&self) -> &str {
+        "watching-all"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("List the series being watched in every channel in this server.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if !is_admin(command.member.as_ref().and_then(|m| m.permissions)) {
+            respond_error(&ctx, &command, "Only server admins can do that.").await;
+            return;
+        }
+        let guild_id = match command.guild_id {
+            None => {
+                respond_error(&ctx, &command, "That only works in a server.").await;
+                return;
+            }
+            Some(g) => g,
+        };
+        let regs = match self
+            .state
+            .db()
+            .call(move |db| db.guild_regs(guild_id))
+            .await
+        {
+            Err(e) => {
+                println!("Failed to read guild watches {:?}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, i can't find my notebook right how, try again later.",
+                )
+                .await;
+                return;
+            }
+            Ok(r) => r,
+        };
+        if regs.is_empty() {
+            respond_msg(&ctx, &command, "Nothing is being watched in this server.").await;
+            return;
+        }
+        let mut by_channel: BTreeMap<ChannelId, Vec<Reg>> = BTreeMap::new();
+        for r in regs {
+            by_channel.entry(r.channel).or_default().push(r);
+        }
+        let mut lines = Vec::new();
+        for (ch, mut regs) in by_channel {
+            regs.sort_by(|a, b| a.series_name.cmp(&b.series_name));
+            lines.push(format!("<#{}>", ch));
+            for r in regs {
+                let mut line = format!(
+                    "\u{2981} {} ({}-{} entries)",
+                    r.series_name, r.min_reg, r.max_reg
+                );
+                if r.snoozed {
+                    line.push_str(" snoozed");
+                }
+                if r.muted {
+                    line.push_str(" muted");
+                }
+                lines.push(line);
+            }
+        }
+        let mut chunks = chunk_lines(&lines, 1950).into_iter();
+        if let Some(first) = chunks.next() {
+            respond_msg(&ctx, &command, &first).await;
+        }
+        for c in chunks {
+            if let Err(e) = command
+                .create_followup_message(&ctx.http, |m| m.content(c))
+                .await
+            {
+                println!("Failed to send followup {}", e);
+            }
+        }
+    }
+}
+
+// joins the lines into as few messages as possible that are no more than max_len long.
+fn chunk_lines(lines: &[String], max_len: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut curr = String::new();
+    for l in lines {
+        if !curr.is_empty() && curr.len() + l.len() + 1 > max_len {
+            chunks.push(std::mem::take(&mut curr));
+        }
+        if !curr.is_empty() {
+            curr.push('\n');
+        }
+        curr.push_str(l);
+    }
+    if !curr.is_empty() {
+        chunks.push(curr);
+    }
+    chunks
+}
+
+pub struct RemoveCommand {
+    state: Arc<HandlerState>,
+}
+impl RemoveCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for RemoveCommand {
+    fn name(&self) -> &str {
+        "nomore"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Stop reporting race registrations for a series.")
+                .create_option(
+                    |option| -> &mut serenity::builder::CreateApplicationCommandOption {
+                        option
+                            .name("series")
+                            .description("The series to announce")
+                            .set_autocomplete(true)
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    },
+                )
+        });
+    }
+
+    async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
+        autocomplete_channel_regs(&self.state, &ctx, &autocomp, true).await;
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if resolve_option_str(&command.data.options, "series").as_deref() == Some(NOMORE_ALL) {
+            let mut components = CreateComponents::default();
+            components.create_action_row(|row| {
+                row.create_button(|b| {
+                    b.custom_id("nomore:all")
+                        .label("Remove them all")
+                        .style(ButtonStyle::Danger)
+                })
+                .create_button(|b| {
+                    b.custom_id("nomore:keep")
+                        .label("Keep them")
+                        .style(ButtonStyle::Secondary)
+                })
+            });
+            respond(
+                &ctx,
+                &command,
+                "Are you sure you want me to stop all the announcements in this channel?",
+                true,
+                Some(components),
+            )
+            .await;
+            return;
+        }
+        let series_id = match resolve_series_id(&ctx, &command).await {
+            None => return,
+            Some(i) => i,
+        };
+        defer(&ctx, &command).await;
+        let locale = guild_locale(&self.state, command.guild_id).await;
+        let (ch, user) = (command.channel_id, command.user.id);
+        let perms = command.member.as_ref().and_then(|m| m.permissions);
+        match self
+            .state
+            .db()
+            .call(move |db| -> rusqlite::Result<Option<usize>> {
+                let reg = db
+                    .channel_regs(ch)?
+                    .into_iter()
+                    .find(|r| r.series_id == series_id);
+                match reg {
+                    Some(r) if !can_change_watch(&r, user, perms) => Ok(None),
+                    _ => db.delete_reg(ch, series_id).map(Some),
+                }
+            })
+            .await
+        {
+            Err(e) => {
+                println!("failed to remove registration {}", e);
+                respond_error(&ctx, &command, text(locale, Msg::LostNotepad)).await;
+            }
+            Ok(None) => respond_error(&ctx, &command, RESTRICTED_WATCH).await,
+            Ok(_) => respond_msg(&ctx, &command, text(locale, Msg::Stopped)).await,
+        }
+    }
+
+    // the buttons from /nomore all.
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        let msg = match comp.data.custom_id.as_str() {
+            "nomore:keep" => "Okay, I'll leave them be.".to_string(),
+            "nomore:all" => {
+                let (ch, user) = (comp.channel_id, comp.user.id);
+                let perms = comp.member.as_ref().and_then(|m| m.permissions);
+                match self
+                    .state
+                    .db()
+                    .call(move |db| -> rusqlite::Result<Option<usize>> {
+                        let regs = db.channel_regs(ch)?;
+                        if !regs.iter().all(|r| can_change_watch(r, user, perms)) {
+                            return Ok(None);
+                        }
+                        db.delete_channel_regs(ch).map(Some)
+                    })
+                    .await
+                {
+                    Err(e) => {
+                        println!("failed to remove registrations {}", e);
+                        "Sorry, I seem to have lost my notepad, please try again later."
+                            .to_string()
+                    }
+                    Ok(None) => RESTRICTED_WATCH.to_string(),
+                    Ok(Some(0)) => "This channel isn't watching anything.".to_string(),
+                    Ok(Some(n)) => format!(
+                        "Okay, I've stopped watching {} series in this channel. If that was a mistake, /undo brings them back.",
+                        n
+                    ),
+                }
+            }
+            _ => return,
+        };
+        if let Err(e) = comp
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| message.content(msg).components(|c| c))
+            })
+            .await
+        {
+            println!("Failed to respond to component {}", e);
+        }
+    }
+}
+
+// the series option value for /nomore that removes every watch in the channel.
+const NOMORE_ALL: &str = "all";
+
+pub struct UndoCommand {
+    state: Arc<HandlerState>,
+    limits: WatchLimits,
+}
+impl UndoCommand {
+    pub fn new(state: Arc<HandlerState>, limits: WatchLimits) -> Self {
+        Self { state, limits }
+    }
+}
+#[async_trait]
+impl ACommand for UndoCommand {
+    fn name(&self) -> &str {
+        "undo"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command.name(self.name()).description(
+                "Bring back the watch that was most recently removed from this channel.",
+            )
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        defer(&ctx, &command).await;
+        let (ch, user) = (command.channel_id, command.user.id);
+        let perms = command.member.as_ref().and_then(|m| m.permissions);
+        let limits = self.limits.clone();
+        match self
+            .state
+            .db()
+            .call(move |db| -> rusqlite::Result<Result<Vec<Reg>, String>> {
+                let regs = db.last_deleted_regs(ch)?;
+                if regs.is_empty() {
+                    return Ok(Err(format!(
+                        "There's no removed watch in this channel to bring back, they're only kept for {} days.",
+                        DELETED_REG_DAYS
+                    )));
+                }
+                if !regs.iter().all(|r| can_change_watch(r, user, perms)) {
+                    return Ok(Err(RESTRICTED_WATCH.to_string()));
+                }
+                // the channel may have picked up other watches since these were removed, they
+                // all have to fit for any of them to come back.
+                let (ch_count, guild_count) = db.watch_counts(&regs[0])?;
+                let n = regs.len() as i64 - 1;
+                if let Err(msg) =
+                    limits.check(regs[0].guild.map(|g| g.0), ch_count + n, guild_count + n)
+                {
+                    return Ok(Err(msg));
+                }
+                let series: Vec<i64> = regs.iter().map(|r| r.series_id).collect();
+                db.undelete_regs(ch, &series)?;
+                Ok(Ok(regs))
+            })
+            .await
+        {
+            Err(e) => {
+                println!("failed to undo removing registration {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(Err(msg)) => respond_error(&ctx, &command, &msg).await,
+            Ok(Ok(regs)) => {
+                let msg = match &regs[..] {
+                    [reg] => format!(
+                        "Okay, I'm back to messaging this channel about race registrations for {}",
+                        reg
+                    ),
+                    _ => format!(
+                        "Okay, I'm back to messaging this channel about race registrations for {} series: {}",
+                        regs.len(),
+                        regs.iter()
+                            .map(|r| r.series_name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                };
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+// autocomplete the series option from the series being watched in the channel. With
+// offer_all, every watch in the channel is offered too, see NOMORE_ALL.
+async fn autocomplete_channel_regs(
+    state: &HandlerState,
+    ctx: &Context,
+    autocomp: &AutocompleteInteraction,
+    offer_all: bool,
+) {
+    for opt in &autocomp.data.options {
+        if opt.focused && opt.name == "series" {
+            let ch = autocomp.channel_id;
+            let regs = match state.db().read(move |db| db.channel_regs(ch)).await {
+                Ok(r) => r,
+                Err(e) => {
+                    // no suggestions is better than no bot.
+                    println!("Failed to read channel watches for autocomplete {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = autocomp
+                .create_autocomplete_response(&ctx.http, |response| {
+                    let search_txt = match &autocomp.data.options[0].value {
+                        Some(serde_json::Value::String(s)) => s,
+                        _ => "",
+                    };
+                    let mut count = 0;
+                    let lc_txt = search_txt.to_lowercase();
+                    if offer_all && regs.len() > 1 {
+                        response.add_string_choice(
+                            format!("All {} watches in this channel", regs.len()),
+                            NOMORE_ALL,
+                        );
+                        count += 1;
+                    }
+                    for reg in regs {
+                        if reg.series_name.to_lowercase().contains(&lc_txt) {
+                            response.add_string_choice(&reg.series_name, reg.series_id);
+                            count += 1;
+                            if count == 25 {
+                                break;
+                            }
+                        }
+                    }
+                    response
+                })
+                .await
+            {
+                println!("Failed to send autocomp response {:?}", e);
+            }
+        }
+    }
+}
+
+async fn resolve_series_id(ctx: &Context, command: &ApplicationCommandInteraction) -> Option<i64> {
+    let maybe_series_id = match command.data.options[0].resolved.as_ref().unwrap() {
+        CommandDataOptionValue::String(x) => x.parse(),
+        CommandDataOptionValue::Integer(x) => Ok(*x),
+        _ => Ok(414),
+    };
+    match maybe_series_id {
+        Err(_) => {
+            respond_error(
+                ctx,
+                command,
+                "Please select one of the series from the autocomplete list.",
+            )
+            .await;
+            None
+        }
+        Ok(sid) => Some(sid),
+    }
+}
+
+async fn respond_msg(ctx: &Context, command: &ApplicationCommandInteraction, msg: &str) {
+    respond(ctx, command, msg, false, None).await;
+}
+
+// responds with a message only the user that ran the command can see.
+async fn respond_private(ctx: &Context, command: &ApplicationCommandInteraction, msg: &str) {
+    respond(ctx, command, msg, true, None).await;
+}
+
+async fn respond_error(ctx: &Context, command: &ApplicationCommandInteraction, msg: &str) {
+    if respond(ctx, command, msg, true, None).await {
+        set_outcome("error");
+    }
+}
+
+fn resolve_option_i64(opts: &[CommandDataOption], opt_name: &str) -> Option<i64> {
+    for o in opts {
+        if o.name == opt_name {
+            return match o.resolved {
+                Some(CommandDataOptionValue::Integer(i)) => Some(i),
+                _ => {
+                    println!("unexpected int value for {} of {:?}", opt_name, o.resolved);
+                    None
+                }
+            };
+        }
+    }
+    None
+}
+// how long to wait on the server's /privatereplies setting before giving up and replying
+// publicly, it's read before the command is deferred, which has to happen within 3 seconds.
+const PRIVATE_REPLY_TIMEOUT: Duration = Duration::from_millis(1000);
+
+// true if the reply to the command should only be visible to whoever ran it, from its
+// private option, or the server's /privatereplies setting if it wasn't given.
+async fn private_reply(state: &HandlerState, command: &ApplicationCommandInteraction) -> bool {
+    if let Some(p) = resolve_option_bool(&command.data.options, "private") {
+        return p;
+    }
+    let guild_id = command.guild_id;
+    let db = state.db();
+    let setting = db.read(move |db| db.private_replies(guild_id));
+    match tokio::time::timeout(PRIVATE_REPLY_TIMEOUT, setting).await {
+        Ok(Ok(private)) => private,
+        _ => false,
+    }
+}
+
+// returns the language the server wants responses in, English if it can't tell.
+async fn guild_locale(state: &HandlerState, guild_id: Option<GuildId>) -> Locale {
+    state
+        .db()
+        .read(move |db| db.guild_locale(guild_id))
+        .await
+        .unwrap_or_default()
+}
+
+fn resolve_option_str(opts: &[CommandDataOption], opt_name: &str) -> Option<String> {
+    for o in opts {
+        if o.name == opt_name {
+            return match &o.resolved {
+                Some(CommandDataOptionValue::String(s)) => Some(s.clone()),
+                _ => {
+                    println!("unexpected str value for {} of {:?}", opt_name, o.resolved);
+                    None
+                }
+            };
+        }
+    }
+    None
+}
+fn resolve_option_bool(opts: &[CommandDataOption], opt_name: &str) -> Option<bool> {
+    for o in opts {
+        if o.name == opt_name {
+            return match o.resolved {
+                Some(CommandDataOptionValue::Boolean(i)) => Some(i),
+                _ => {
+                    println!("unexpected bool value for {} of {:?}", opt_name, o.resolved);
+                    None
+                }
+            };
+        }
+    }
+    None
+}
+
+pub struct SnoozeCommand {
+    state: Arc<HandlerState>,
+}
+impl SnoozeCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for SnoozeCommand {
+    fn name(&self) -> &str {
+        "snooze"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Stop announcements for a series until the next race week.")
+                .create_option(
+                    |option| -> &mut serenity::builder::CreateApplicationCommandOption {
+                        option
+                            .name("series")
+                            .description("The series to snooze")
+                            .set_autocomplete(true)
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    },
+                )
+                .create_option(|option| {
+                    option
+                        .name("wake")
+                        .description("Start announcing this series again right away")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        });
+    }
+    async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
+        autocomplete_channel_regs(&self.state, &ctx, &autocomp, false).await;
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let series_id = match resolve_series_id(&ctx, &command).await {
+            None => return,
+            Some(i) => i,
+        };
+        let wake = resolve_option_bool(&command.data.options, "wake").unwrap_or(false);
+        let ch = command.channel_id;
+        match self
+            .state
+            .db()
+            .call(move |db| db.snooze_reg(ch, series_id, !wake))
+            .await
+        {
+            Err(e) => {
+                println!("failed to snooze registration {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(0) => {
+                let locale = guild_locale(&self.state, command.guild_id).await;
+                respond_error(&ctx, &command, text(locale, Msg::NotWatching)).await;
+            }
+            Ok(_) => {
+                let msg = if wake {
+                    "Okay, I'm back on it."
+                } else {
+                    "Okay, I'll keep quiet about it until the next race week."
+                };
+                respond_msg(&ctx, &command, msg).await;
+            }
+        }
+    }
+}
+
+pub struct BoostCommand {
+    state: Arc<HandlerState>,
+}
+impl BoostCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for BoostCommand {
+    fn name(&self) -> &str {
+        "boost"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Announce a series more eagerly for a few days, for a special event.")
+                .create_option(|option| {
+                    option
+                        .name("series")
+                        .description("The series to boost")
+                        .set_autocomplete(true)
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+                .create_option(|option| {
+                    option
+                        .name("days")
+                        .description("How many days to boost it for, 0 ends the boost")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(0)
+                        .max_int_value(14)
+                        .required(true)
+                })
+        });
+    }
+    async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
+        autocomplete_channel_regs(&self.state, &ctx, &autocomp, false).await;
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let series_id = match resolve_series_id(&ctx, &command).await {
+            None => return,
+            Some(i) => i,
+        };
+        let days = resolve_option_i64(&command.data.options, "days").unwrap_or(0);
+        let until = (days > 0).then(|| Utc::now() + chrono::Duration::days(days));
+        let ch = command.channel_id;
+        match self
+            .state
+            .db()
+            .call(move |db| db.boost_reg(ch, series_id, until))
+            .await
+        {
+            Err(e) => {
+                println!("failed to boost registration {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(0) => {
+                let locale = guild_locale(&self.state, command.guild_id).await;
+                respond_error(&ctx, &command, text(locale, Msg::NotWatching)).await;
+            }
+            Ok(_) => {
+                let msg = match until {
+                    Some(t) => format!(
+                        "Okay, I'll be extra chatty about it until <t:{}:f>, then go back to the usual settings.",
+                        t.timestamp()
+                    ),
+                    None => "Okay, back to the usual settings.".to_string(),
+                };
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+// PollCommand handles the button clicks on the "Racing this one?" polls posted when
+// registration opens for watches that asked for a poll.
+pub struct PollCommand {
+    state: Arc<HandlerState>,
+}
+impl PollCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for PollCommand {
+    fn name(&self) -> &str {
+        "poll"
+    }
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        // poll:<racing>:<series_id>:<session_id>
+        let parts: Vec<&str> = comp.data.custom_id.split(':').collect();
+        let (racing, series_id, session_id) = match parts[..] {
+            [_, r, series, session] => match (series.parse(), session.parse()) {
+                (Ok(series), Ok(session)) => (r == "1", series, session),
+                _ => return,
+            },
+            _ => return,
+        };
+        let (ch, user) = (comp.channel_id, comp.user.id);
+        let dbr = self
+            .state
+            .db()
+            .call(move |db| db.upsert_vote(ch, series_id, session_id, user, racing))
+            .await;
+        let msg = match dbr {
+            Err(e) => {
+                println!("Failed to record poll vote {:?}", e);
+                "Sorry, I seem to have lost my notepad, please try again later.".to_string()
+            }
+            Ok((yes, no)) => format!(
+                "Got it. {} racing, {} sitting this one out so far.",
+                yes, no
+            ),
+        };
+        if let Err(e) = comp
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message.flags(MessageFlags::EPHEMERAL).content(msg)
+                    })
+            })
+            .await
+        {
+            println!("Failed to respond to component {}", e);
+        }
+    }
+}
+
+// how long before the session starts the "Remind me" button sends its DM.
+pub const REMIND_BEFORE_MINS: i64 = 10;
+
+// SeriesButtons is the row of buttons attached to announcements for a series.
+#[derive(Debug, Clone)]
+pub struct SeriesButtons {
+    pub series_id: i64,
+    pub short_name: String,
+    // when the session starts, if there's one worth a reminder.
+    pub starts_at: Option<DateTime<Utc>>,
+}
+
+// discord allows at most 5 rows of components on a message, so that's how many series a
+// message with announcement buttons can have.
+pub const MAX_BUTTON_ROWS: usize = 5;
+
+// adds a row of buttons for each series, there should be no more than MAX_BUTTON_ROWS of
+// them. When there's more than one series the rows are labelled with the series name.
+pub fn announcement_buttons<'a>(
+    c: &'a mut CreateComponents,
+    series: &[SeriesButtons],
+    locale: Locale,
+) -> &'a mut CreateComponents {
+    let named = series.len() > 1;
+    for s in series {
+        c.create_action_row(|row| {
+            row.create_button(|b| {
+                let stop = text(locale, Msg::StopWatching);
+                let label = if named {
+                    truncate(&format!("{}: {}", s.short_name, stop), 80)
+                } else {
+                    stop.to_string()
+                };
+                b.custom_id(format!("ann:stop:{}", s.series_id))
+                    .label(label)
+                    .style(ButtonStyle::Secondary)
+            });
+            if let Some(t) = s.starts_at {
+                row.create_button(|b| {
+                    b.custom_id(format!("ann:remind:{}:{}", s.series_id, t.timestamp()))
+                        .label(tr(locale, Msg::RemindMe, &[&REMIND_BEFORE_MINS]))
+                        .style(ButtonStyle::Primary)
+                });
+            }
+            row.create_button(|b| {
+                b.custom_id(format!("ann:stats:{}", s.series_id))
+                    .label(text(locale, Msg::ShowStats))
+                    .style(ButtonStyle::Secondary)
+            })
+        });
+    }
+    c
+}
+
+// saves a reminder to DM the user mins before the session starting at starts_at, see
+// reminder_task. Returns the message for the user.
+async fn add_reminder(
+    state: &HandlerState,
+    user: &User,
+    guild_id: Option<GuildId>,
+    series_id: i64,
+    starts_at: DateTime<Utc>,
+    mins: i64,
+) -> String {
+    let locale = guild_locale(state, guild_id).await;
+    let remind_at = starts_at - chrono::Duration::minutes(mins);
+    if remind_at <= Utc::now() {
+        let starts = format!("<t:{}:R>", starts_at.timestamp());
+        return tr(locale, Msg::ReminderTooSoon, &[&starts]);
+    }
+    let user_id = user.id;
+    match state
+        .db()
+        .call(move |db| db.add_reminder(user_id, guild_id, series_id, starts_at, remind_at))
+        .await
+    {
+        Err(e) => {
+            println!("failed to save reminder {}", e);
+            text(locale, Msg::LostNotepad).to_string()
+        }
+        Ok(_) => {
+            let remind = format!("<t:{}:R>", remind_at.timestamp());
+            let starts = format!("<t:{}:t>", starts_at.timestamp());
+            tr(locale, Msg::ReminderSet, &[&remind, &starts])
+        }
+    }
+}
+
+pub struct WatchEventCommand {
+    state: Arc<HandlerState>,
+}
+impl WatchEventCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for WatchEventCommand {
+    fn name(&self) -> &str {
+        "watchevent"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Announce hosted sessions, e.g. for your league, as people register.")
+                .create_option(|option| {
+                    option
+                        .name("name")
+                        .description("Announce hosted sessions with this in their name")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+                .create_option(|option| {
+                    option
+                        .name("remove")
+                        .description("Stop announcing these sessions")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let name = match resolve_option_str(&command.data.options, "name") {
+            Some(n) if n.trim().chars().count() >= 3 => truncate(n.trim(), 100),
+            _ => {
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Please give me at least 3 letters of the session name to look for.",
+                )
+                .await;
+                return;
+            }
+        };
+        let remove = resolve_option_bool(&command.data.options, "remove").unwrap_or(false);
+        let (guild, ch, user) = (command.guild_id, command.channel_id, command.user.id);
+        let n = name.clone();
+        let res = self
+            .state
+            .db()
+            .call(move |db| {
+                if remove {
+                    db.remove_event_watch(ch, &n)
+                } else {
+                    db.add_event_watch(guild, ch, &n, user)
+                }
+            })
+            .await;
+        let msg = match res {
+            Err(e) => {
+                println!("failed to update event watch {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+                return;
+            }
+            Ok(0) if remove => format!("This channel wasn't watching for {} sessions.", name),
+            Ok(_) if remove => format!("Okay, no more {} sessions.", name),
+            Ok(_) => format!(
+                "Okay, I'll say when a hosted session with {} in its name opens, and how many are registered.",
+                name
+            ),
+        };
+        respond_msg(&ctx, &command, &msg).await;
+    }
+}
+
+pub struct WatchLeagueCommand {
+    state: Arc<HandlerState>,
+}
+impl WatchLeagueCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for WatchLeagueCommand {
+    fn name(&self) -> &str {
+        "watchleague"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Announce a league's upcoming sessions, and how many are registered.")
+                .create_option(|option| {
+                    option
+                        .name("league_id")
+                        .description(
+                            "The league id, it's in the league's page URL on the iRacing site",
+                        )
+                        .kind(CommandOptionType::Integer)
+                        .required(true)
+                        .min_int_value(1)
+                })
+                .create_option(|option| {
+                    option
+                        .name("remove")
+                        .description("Stop announcing this league")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let league_id = match resolve_option_i64(&command.data.options, "league_id") {
+            Some(id) if id > 0 => id,
+            _ => {
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Please give me the id of the league to watch.",
+                )
+                .await;
+                return;
+            }
+        };
+        let remove = resolve_option_bool(&command.data.options, "remove").unwrap_or(false);
+        let (guild, ch, user) = (command.guild_id, command.channel_id, command.user.id);
+        let res = self
+            .state
+            .db()
+            .call(move |db| {
+                if remove {
+                    db.remove_league_watch(ch, league_id)
+                } else {
+                    db.add_league_watch(guild, ch, league_id, user)
+                }
+            })
+            .await;
+        let msg = match res {
+            Err(e) => {
+                println!("failed to update league watch {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+                return;
+            }
+            Ok(0) if remove => format!("This channel wasn't watching league {}.", league_id),
+            Ok(_) if remove => format!("Okay, no more league {} sessions.", league_id),
+            Ok(_) => format!(
+                "Okay, I'll say when league {} has a session in the next week, and how many are registered.",
+                league_id
+            ),
+        };
+        respond_msg(&ctx, &command, &msg).await;
+    }
+}
+
+pub struct RemindMeCommand {
+    state: Arc<HandlerState>,
+}
+impl RemindMeCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for RemindMeCommand {
+    fn name(&self) -> &str {
+        "remindme"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Get a DM before the next session of a series starts.")
+                .create_option(|option| {
+                    option
+                        .name("series")
+                        .description("The series to remind you about")
+                        .set_autocomplete(true)
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+                .create_option(|option| {
+                    option
+                        .name("minutes")
+                        .description("How many minutes before the session starts")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .max_int_value(120)
+                        .required(true)
+                })
+        });
+    }
+    async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
+        autocomplete_series(&self.state, &ctx, &autocomp, false).await;
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let series_id = match resolve_series_id(&ctx, &command).await {
+            None => return,
+            Some(i) => i,
+        };
+        let mins =
+            resolve_option_i64(&command.data.options, "minutes").unwrap_or(REMIND_BEFORE_MINS);
+        let entry = self
+            .state
+            .db()
+            .call(move |db| db.latest_entry(series_id))
+            .await;
+        let locale = guild_locale(&self.state, command.guild_id).await;
+        let msg = match entry {
+            Err(e) => {
+                println!("failed to read race guide entry {}", e);
+                text(locale, Msg::LostNotepad).to_string()
+            }
+            Ok(Some(e)) if e.start_time > Utc::now() => {
+                add_reminder(
+                    &self.state,
+                    &command.user,
+                    command.guild_id,
+                    series_id,
+                    e.start_time,
+                    mins,
+                )
+                .await
+            }
+            Ok(_) => text(locale, Msg::NoNextSession).to_string(),
+        };
+        respond_private(&ctx, &command, &msg).await;
+    }
+}
+
+// AnnouncementCommand handles the buttons on announcements, it has no slash command.
+pub struct AnnouncementCommand {
+    state: Arc<HandlerState>,
+}
+impl AnnouncementCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+    async fn stop(&self, comp: &MessageComponentInteraction, series_id: i64) -> String {
+        let locale = guild_locale(&self.state, comp.guild_id).await;
+        if !is_admin(comp.member.as_ref().and_then(|m| m.permissions)) {
+            return text(locale, Msg::StopAdminOnly).to_string();
+        }
+        let ch = comp.channel_id;
+        let msg = match self
+            .state
+            .db()
+            .call(move |db| db.delete_reg(ch, series_id))
+            .await
+        {
+            Err(e) => {
+                println!("failed to remove registration {}", e);
+                Msg::LostNotepad
+            }
+            Ok(0) => Msg::StoppedAlready,
+            Ok(_) => Msg::Stopped,
+        };
+        text(locale, msg).to_string()
+    }
+    async fn stats(&self, series_id: i64) -> String {
+        let series = match self.state.seasons().get(&series_id) {
+            Some(s) => s.clone(),
+            None => return "I don't know about that series anymore.".to_string(),
+        };
+        let dbr = self
+            .state
+            .db()
+            .call(move |db| -> rusqlite::Result<_> {
+                Ok((
+                    db.latest_entry(series_id)?,
+                    db.series_watch_totals(series_id)?,
+                ))
+            })
+            .await;
+        let (entry, (channels, guilds)) = match dbr {
+            Err(e) => {
+                println!("failed to read series stats {}", e);
+                return "Sorry, I seem to have lost my notepad, please try again later."
+                    .to_string();
+            }
+            Ok(r) => r,
+        };
+        let mut msg = format!(
+            "**{}** week {} at {}",
+            series.name,
+            series.week + 1,
+            series.track_name
+        );
+        if !series.track_config.is_empty() {
+            msg.push_str(&format!(" ({})", series.track_config));
+        }
+        msg.push('\n');
+        if let Some(e) = entry {
+            msg.push_str(&format!(
+                "{} registered for the session <t:{}:R>",
+                e.entry_count,
+                e.start_time.timestamp()
+            ));
+            if e.entry_count >= series.reg_official {
+                msg.push_str(&format!(", {} split(s)", e.num_splits(series.reg_split)));
+            }
+            msg.push_str(".\n");
+        }
+        msg.push_str(&format!(
+            "Official at {}, splits every {}.\nWatched in {} channels across {} servers.",
+            series.reg_official, series.reg_split, channels, guilds
+        ));
+        msg
+    }
+}
+#[async_trait]
+impl ACommand for AnnouncementCommand {
+    fn name(&self) -> &str {
+        "ann"
+    }
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        // ann:stop:<series_id>, ann:remind:<series_id>:<start_time>, ann:stats:<series_id>
+        let parts: Vec<&str> = comp.data.custom_id.split(':').collect();
+        let num = |s: &str| s.parse::<i64>().ok();
+        let msg = match parts[..] {
+            [_, "stop", series] => match num(series) {
+                Some(id) => self.stop(&comp, id).await,
+                None => return,
+            },
+            [_, "remind", series, start] => {
+                match (
+                    num(series),
+                    num(start).and_then(|t| Utc.timestamp_opt(t, 0).single()),
+                ) {
+                    (Some(id), Some(t)) => {
+                        add_reminder(
+                            &self.state,
+                            &comp.user,
+                            comp.guild_id,
+                            id,
+                            t,
+                            REMIND_BEFORE_MINS,
+                        )
+                        .await
+                    }
+                    _ => return,
+                }
+            }
+            [_, "stats", series] => match num(series) {
+                Some(id) => self.stats(id).await,
+                None => return,
+            },
+            _ => return,
+        };
+        if let Err(e) = comp
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message.flags(MessageFlags::EPHEMERAL).content(msg)
+                    })
+            })
+            .await
+        {
+            println!("Failed to respond to component {}", e);
+        }
+    }
+}
+
+// the track categories that a channel can mute, (display name, category)
+const CATEGORIES: [(&str, &str); 4] = [
+    ("Road", "road"),
+    ("Oval", "oval"),
+    ("Dirt Road", "dirt_road"),
+    ("Dirt Oval", "dirt_oval"),
+];
+
+// /now lists sessions starting within this many minutes.
+const NOW_WINDOW_MINS: i64 = 60;
+// and at most this many of them.
+const NOW_MAX_SESSIONS: usize = 15;
+
+pub struct NowCommand {
+    state: Arc<HandlerState>,
+}
+impl NowCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for NowCommand {
+    fn name(&self) -> &str {
+        "now"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("What races are starting soon, busiest first.")
+                .create_option(|option| {
+                    option
+                        .name("category")
+                        .description("Only show series from this category")
+                        .kind(CommandOptionType::String)
+                        .required(false);
+                    for (name, cat) in CATEGORIES {
+                        option.add_string_choice(name, cat);
+                    }
+                    option
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let category = resolve_option_str(&command.data.options, "category");
+        let until = Utc::now() + chrono::Duration::minutes(NOW_WINDOW_MINS);
+        let mut sessions = upcoming_sessions(&self.state, command.guild_id, category).await;
+        sessions.retain(|(_, e)| e.start_time <= until);
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.1.entry_count));
+        if sessions.is_empty() {
+            respond_private(
+                &ctx,
+                &command,
+                &format!(
+                    "Nothing's starting in the next {} minutes, time for another coffee.",
+                    NOW_WINDOW_MINS
+                ),
+            )
+            .await;
+            return;
+        }
+        let mut msg = format!("Starting in the next {} minutes:", NOW_WINDOW_MINS);
+        for (s, e) in sessions.iter().take(NOW_MAX_SESSIONS) {
+            msg.push_str(&session_line(s, e));
+        }
+        if sessions.len() > NOW_MAX_SESSIONS {
+            msg.push_str(&format!(
+                "\nand {} more.",
+                sessions.len() - NOW_MAX_SESSIONS
+            ));
+        }
+        respond_private(&ctx, &command, &truncate(&msg, 2000)).await;
+    }
+}
+
+// Sessions in the cached race guide that haven't started yet, skipping series blocked
+// in the guild, and optionally only those in a track category.
+async fn upcoming_sessions(
+    state: &Arc<HandlerState>,
+    guild: Option<GuildId>,
+    category: Option<String>,
+) -> Vec<(SeasonInfo, RaceGuideEntry)> {
+    let blocked = match guild {
+        Some(g) => state
+            .db()
+            .call(move |db| db.blocked_series(g))
+            .await
+            .unwrap_or_default(),
+        None => HashSet::new(),
+    };
+    let now = Utc::now();
+    let (seasons, guide) = (state.seasons(), state.guide());
+    guide
+        .iter()
+        .filter(|e| e.start_time > now)
+        .filter(|e| !blocked.contains(&e.series_id))
+        .filter_map(|e| seasons.get(&e.series_id).map(|s| (s.clone(), e.clone())))
+        .filter(|(s, _)| category.is_none() || s.track_cat == category)
+        .collect()
+}
+
+fn session_line(s: &SeasonInfo, e: &RaceGuideEntry) -> String {
+    let mut line = format!(
+        "\n\u{2981} {} <t:{}:R> at {}, {} registered",
+        s.name,
+        e.start_time.timestamp(),
+        s.track_name,
+        e.entry_count
+    );
+    if e.entry_count >= s.reg_split {
+        line.push_str(&format!(", {} splits", e.num_splits(s.reg_split)));
+    } else if e.entry_count >= s.reg_official {
+        line.push_str(", official");
+    }
+    line
+}
+
+const POPULAR_DEFAULT: i64 = 10;
+const POPULAR_MAX: i64 = 25;
+
+pub struct PopularCommand {
+    state: Arc<HandlerState>,
+}
+impl PopularCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for PopularCommand {
+    fn name(&self) -> &str {
+        "popular"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("The series with the most registrations for their next race.")
+                .create_option(|option| {
+                    option
+                        .name("category")
+                        .description("Only show series from this category")
+                        .kind(CommandOptionType::String)
+                        .required(false);
+                    for (name, cat) in CATEGORIES {
+                        option.add_string_choice(name, cat);
+                    }
+                    option
+                })
+                .create_option(|option| {
+                    option
+                        .name("license")
+                        .description("Only show series for this license class")
+                        .kind(CommandOptionType::Integer)
+                        .required(false);
+                    for (i, name) in LICENSES.iter().enumerate() {
+                        option.add_int_choice(name, i as i32 + 1);
+                    }
+                    option
+                })
+                .create_option(|option| {
+                    option
+                        .name("count")
+                        .description("How many series to show")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .max_int_value(POPULAR_MAX)
+                        .required(false)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let category = resolve_option_str(&command.data.options, "category");
+        let count = resolve_option_i64(&command.data.options, "count")
+            .unwrap_or(POPULAR_DEFAULT)
+            .clamp(1, POPULAR_MAX) as usize;
+        let license = resolve_option_i64(&command.data.options, "license");
+        // the next session of each series is the one people are registering for.
+        let mut next: HashMap<i64, (SeasonInfo, RaceGuideEntry)> = HashMap::new();
+        for (s, e) in upcoming_sessions(&self.state, command.guild_id, category).await {
+            if license.is_some_and(|l| l != s.license_group) {
+                continue;
+            }
+            match next.get(&s.series_id) {
+                Some((_, n)) if n.start_time <= e.start_time => {}
+                _ => {
+                    next.insert(s.series_id, (s, e));
+                }
+            }
+        }
+        let mut sessions: Vec<_> = next.into_values().collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.1.entry_count));
+        if sessions.is_empty() {
+            respond_private(
+                &ctx,
+                &command,
+                "The race guide is empty, try again shortly.",
+            )
+            .await;
+            return;
+        }
+        let mut msg = String::from("Busiest series right now:");
+        for (s, e) in sessions.iter().take(count) {
+            msg.push_str(&session_line(s, e));
+        }
+        respond_private(&ctx, &command, &truncate(&msg, 2000)).await;
+    }
+}
+
+pub struct MuteCommand {
+    state: Arc<HandlerState>,
+}
+impl MuteCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for MuteCommand {
+    fn name(&self) -> &str {
+        "mute"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Never announce series from a category in this channel, whatever's being watched.")
+                .create_option(|option| {
+                    option
+                        .name("category")
+                        .description("The category to mute")
+                        .kind(CommandOptionType::String)
+                        .required(true);
+                    for (name, cat) in CATEGORIES {
+                        option.add_string_choice(name, cat);
+                    }
+                    option
+                })
+                .create_option(|option| {
+                    option
+                        .name("unmute")
+                        .description("Start announcing this category again")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let category = match resolve_option_str(&command.data.options, "category") {
+            Some(c) if CATEGORIES.iter().any(|(_, cat)| *cat == c) => c,
+            _ => {
+                respond_error(&ctx, &command, "Please pick one of the categories.").await;
+                return;
+            }
+        };
+        let unmute = resolve_option_bool(&command.data.options, "unmute").unwrap_or(false);
+        let (guild, ch) = (command.guild_id, command.channel_id);
+        let cat = category.clone();
+        match self
+            .state
+            .db()
+            .call(move |db| db.mute_category(guild, ch, &cat, !unmute))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update channel mute {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let name = CATEGORIES.iter().find(|(_, c)| *c == category).unwrap().0;
+                let msg = if unmute {
+                    format!("Okay, {} series are back on the menu.", name)
+                } else {
+                    format!("Okay, I won't mention any {} series in this channel.", name)
+                };
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct LicensesCommand {
+    state: Arc<HandlerState>,
+}
+impl LicensesCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for LicensesCommand {
+    fn name(&self) -> &str {
+        "licenses"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Only announce series for some license classes in this channel, whatever's being watched.")
+                .create_option(|option| {
+                    option
+                        .name("classes")
+                        .description("The license classes to announce, e.g. C,B,A or all")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let classes = resolve_option_str(&command.data.options, "classes").unwrap_or_default();
+        let licenses = match parse_licenses(&classes) {
+            Ok(l) => l,
+            Err(msg) => {
+                respond_error(&ctx, &command, msg).await;
+                return;
+            }
+        };
+        let (guild, ch) = (command.guild_id, command.channel_id);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_channel_licenses(guild, ch, licenses))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update channel licenses {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = if licenses == 0 {
+                    "Okay, I'll mention series for any license class in this channel.".to_string()
+                } else {
+                    let names: Vec<&str> = LICENSES
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| licenses & (1 << i) != 0)
+                        .map(|(_, l)| *l)
+                        .collect();
+                    format!(
+                        "Okay, I'll only mention {} class series in this channel.",
+                        names.join(", ")
+                    )
+                };
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+// parses license classes like C,B,A into a ChannelSettings::licenses mask, all is 0.
+fn parse_licenses(s: &str) -> Result<i64, &'static str> {
+    let mut licenses = 0;
+    for l in s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|l| !l.is_empty())
+    {
+        if l.eq_ignore_ascii_case("all") {
+            return Ok(0);
+        }
+        licenses |= match LICENSES.iter().position(|n| n.eq_ignore_ascii_case(l)) {
+            Some(i) => 1 << i,
+            None => return Err("License classes need to be some of R, D, C, B, A, or all."),
+        };
+    }
+    Ok(licenses)
+}
+
+pub struct DefaultsCommand {
+    state: Arc<HandlerState>,
+}
+impl DefaultsCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for DefaultsCommand {
+    fn name(&self) -> &str {
+        "defaults"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Set the /watch settings used in this channel when they're not given.")
+                .create_option(|option| {
+                    option
+                        .name("min_reg")
+                        .description("The minimum number of registered race entries before making an announcement.")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(0)
+                        .max_int_value(1000)
+                        .required(false)
+                })
+                .create_option(|option| {
+                    option
+                        .name("max_reg")
+                        .description("Stop making announcements after this many people are registered.")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .max_int_value(1000)
+                        .required(false)
+                })
+                .create_option(|option| {
+                    option
+                        .name("open")
+                        .description("Always announce when registration opens")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_option(|option| {
+                    option
+                        .name("close")
+                        .description("Always announce when registration closes")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+                .create_option(|option| {
+                    option
+                        .name("clear")
+                        .description("Go back to the usual defaults")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let opts = &command.data.options;
+        let defaults = if resolve_option_bool(opts, "clear").unwrap_or(false) {
+            WatchDefaults::default()
+        } else {
+            WatchDefaults {
+                min_reg: resolve_option_i64(opts, "min_reg"),
+                max_reg: resolve_option_i64(opts, "max_reg"),
+                open: resolve_option_bool(opts, "open"),
+                close: resolve_option_bool(opts, "close"),
+            }
+        };
+        if let (Some(min), Some(max)) = (defaults.min_reg, defaults.max_reg) {
+            if let Err(msg) = check_reg_range(min, max) {
+                respond_error(&ctx, &command, msg).await;
+                return;
+            }
+        }
+        let (guild, ch) = (command.guild_id, command.channel_id);
+        let d = defaults.clone();
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_watch_defaults(guild, ch, &d))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update watch defaults {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = if defaults == WatchDefaults::default() {
+                    "Okay, /watch in this channel will use the usual defaults.".to_string()
+                } else {
+                    let mut msg = String::from("Okay, /watch in this channel will default to");
+                    let mut parts = Vec::new();
+                    if let Some(m) = defaults.min_reg {
+                        parts.push(format!("min_reg {}", m));
+                    }
+                    if let Some(m) = defaults.max_reg {
+                        parts.push(format!("max_reg {}", m));
+                    }
+                    if let Some(o) = defaults.open {
+                        parts.push(format!("open {}", o));
+                    }
+                    if let Some(c) = defaults.close {
+                        parts.push(format!("close {}", c));
+                    }
+                    msg.push_str(&format!(" {}.", parts.join(", ")));
+                    msg
+                };
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct StyleCommand {
+    state: Arc<HandlerState>,
+}
+impl StyleCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for StyleCommand {
+    fn name(&self) -> &str {
+        "style"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Choose how announcements look in this channel.")
+                .create_option(|option| {
+                    option
+                        .name("style")
+                        .description("standard is a sentence per announcement, ticker is a short line for busy channels")
+                        .kind(CommandOptionType::String)
+                        .required(true);
+                    for st in Style::ALL {
+                        option.add_string_choice(st.name(), st.name());
+                    }
+                    option
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let style: Style =
+            match resolve_option_str(&command.data.options, "style").and_then(|s| s.parse().ok()) {
+                Some(s) => s,
+                None => {
+                    respond_error(&ctx, &command, "Please pick one of the styles.").await;
+                    return;
+                }
+            };
+        let (guild, ch) = (command.guild_id, command.channel_id);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_channel_style(guild, ch, style))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update channel style {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = format!("Okay, I'll use the {} style in this channel.", style.name());
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct BlockCommand {
+    state: Arc<HandlerState>,
+}
+impl BlockCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for BlockCommand {
+    fn name(&self) -> &str {
+        "block"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description(
+                    "Stop a series from being watched or announced anywhere in this server.",
+                )
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+                .create_option(|option| {
+                    option
+                        .name("series")
+                        .description("The series to block")
+                        .set_autocomplete(true)
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+                .create_option(|option| {
+                    option
+                        .name("unblock")
+                        .description("Allow the series again")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        });
+    }
+    async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
+        // blocked series are still offered, so that they can be unblocked.
+        autocomplete_series_except(&self.state, &ctx, &autocomp, &HashSet::new(), false).await;
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let series_id = match resolve_series_id(&ctx, &command).await {
+            None => return,
+            Some(i) => i,
+        };
+        let guild = match command.guild_id {
+            Some(g) => g,
+            None => return,
+        };
+        let series_name = match self.state.seasons().get(&series_id) {
+            Some(s) => s.name.clone(),
+            None => format!("Series {}", series_id),
+        };
+        let block = !resolve_option_bool(&command.data.options, "unblock").unwrap_or(false);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_series_blocked(guild, series_id, block))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update series block {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = if block {
+                    format!(
+                        "Okay, {} can't be watched in this server, and I won't announce it for any existing watches.",
+                        series_name
+                    )
+                } else {
+                    format!("Okay, {} can be watched again.", series_name)
+                };
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct TimezoneCommand {
+    state: Arc<HandlerState>,
+}
+impl TimezoneCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for TimezoneCommand {
+    fn name(&self) -> &str {
+        "timezone"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description(
+                    "Set the server's timezone, used by first_open to work out what day it is.",
+                )
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+                .create_option(|option| {
+                    option
+                        .name("utc_offset")
+                        .description("Hours from UTC, e.g. -5 or +10 or +5:30")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let guild = match command.guild_id {
+            Some(g) => g,
+            None => {
+                respond_error(&ctx, &command, "The timezone can only be set for a server.").await;
+                return;
+            }
+        };
+        let mins = match resolve_option_str(&command.data.options, "utc_offset")
+            .and_then(|s| parse_utc_offset(&s))
+        {
+            Some(m) => m,
+            None => {
+                respond_error(
+                    &ctx,
+                    &command,
+                    "I didn't understand that offset, try something like -5 or +10 or +5:30.",
+                )
+                .await;
+                return;
+            }
+        };
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_guild_offset(guild, mins))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update guild timezone {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = format!(
+                    "Okay, this server is at UTC{}{}:{:02}.",
+                    if mins < 0 { '-' } else { '+' },
+                    mins.abs() / 60,
+                    mins.abs() % 60
+                );
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct LanguageCommand {
+    state: Arc<HandlerState>,
+}
+impl LanguageCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for LanguageCommand {
+    fn name(&self) -> &str {
+        "language"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Set the language I use for announcements and help in this server.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+                .create_option(|option| {
+                    option
+                        .name("language")
+                        .description("The language to use")
+                        .kind(CommandOptionType::String)
+                        .required(true);
+                    for l in Locale::ALL {
+                        option.add_string_choice(l.name(), l.code());
+                    }
+                    option
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let guild = match command.guild_id {
+            Some(g) => g,
+            None => {
+                respond_error(&ctx, &command, "The language can only be set for a server.").await;
+                return;
+            }
+        };
+        let locale: Locale = match resolve_option_str(&command.data.options, "language")
+            .and_then(|s| s.parse().ok())
+        {
+            Some(l) => l,
+            None => {
+                respond_error(&ctx, &command, "Please pick one of the listed languages.").await;
+                return;
+            }
+        };
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_guild_locale(guild, locale))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update guild language {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = tr(locale, Msg::LanguageSet, &[&locale.name()]);
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct RestrictWatchesCommand {
+    state: Arc<HandlerState>,
+}
+impl RestrictWatchesCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for RestrictWatchesCommand {
+    fn name(&self) -> &str {
+        "restrictwatches"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Only let whoever added a watch, or people that can manage channels, remove or edit it.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+                .create_option(|option| {
+                    option
+                        .name("on")
+                        .description("Turn the restriction on or off")
+                        .kind(CommandOptionType::Boolean)
+                        .required(true)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let guild = match command.guild_id {
+            Some(g) => g,
+            None => {
+                respond_error(&ctx, &command, "This can only be set for a server.").await;
+                return;
+            }
+        };
+        if !is_admin(command.member.as_ref().and_then(|m| m.permissions)) {
+            respond_error(&ctx, &command, "Only server admins can do that.").await;
+            return;
+        }
+        let on = resolve_option_bool(&command.data.options, "on").unwrap_or(false);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_restrict_watches(guild, on))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update restrict watches {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = if on {
+                    "Okay, only whoever added a watch, or someone that can manage channels, can /nomore or edit it now."
+                } else {
+                    "Okay, anyone can /nomore or edit watches again."
+                };
+                respond_msg(&ctx, &command, msg).await;
+            }
+        }
+    }
+}
+
+pub struct PrivateRepliesCommand {
+    state: Arc<HandlerState>,
+}
+impl PrivateRepliesCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for PrivateRepliesCommand {
+    fn name(&self) -> &str {
+        "privatereplies"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Show the replies to /watching and /help only to whoever asked, unless they say otherwise.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+                .create_option(|option| {
+                    option
+                        .name("on")
+                        .description("Turn private replies on or off")
+                        .kind(CommandOptionType::Boolean)
+                        .required(true)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let guild = match command.guild_id {
+            Some(g) => g,
+            None => {
+                respond_error(&ctx, &command, "This can only be set for a server.").await;
+                return;
+            }
+        };
+        if !is_admin(command.member.as_ref().and_then(|m| m.permissions)) {
+            respond_error(&ctx, &command, "Only server admins can do that.").await;
+            return;
+        }
+        let on = resolve_option_bool(&command.data.options, "on").unwrap_or(false);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_private_replies(guild, on))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update private replies {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = if on {
+                    "Okay, /watching and /help will only be shown to whoever asked, unless they pick private:False."
+                } else {
+                    "Okay, /watching and /help will be shown to the whole channel, unless they pick private:True."
+                };
+                respond_msg(&ctx, &command, msg).await;
+            }
+        }
+    }
+}
+
+// parses a timezone offset like -5, +10, +5:30 or UTC+2 into minutes from UTC.
+fn parse_utc_offset(s: &str) -> Option<i64> {
+    let s = s.trim().to_ascii_uppercase();
+    let s = s
+        .strip_prefix("UTC")
+        .or_else(|| s.strip_prefix("GMT"))
+        .unwrap_or(&s)
+        .trim();
+    if s.is_empty() {
+        return Some(0);
+    }
+    let (sign, s) = match s.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (h, m) = s.split_once(':').unwrap_or((s, "0"));
+    let (h, m) = (h.parse::<i64>().ok()?, m.parse::<i64>().ok()?);
+    if !(0..60).contains(&m) {
+        return None;
+    }
+    let mins = sign * (h * 60 + m);
+    (-12 * 60..=14 * 60).contains(&mins).then_some(mins)
+}
+
+pub struct PreviewStylesCommand {
+    state: Arc<HandlerState>,
+}
+impl PreviewStylesCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for PreviewStylesCommand {
+    fn name(&self) -> &str {
+        "previewstyles"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("See what announcements for a series look like in each style.")
+                .create_option(|option| {
+                    option
+                        .name("series")
+                        .description("The series to show announcements for")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                })
+        });
+    }
+    async fn autocomplete(&self, ctx: Context, autocomp: AutocompleteInteraction) {
+        autocomplete_series(&self.state, &ctx, &autocomp, false).await;
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let series_id = match resolve_series_id(&ctx, &command).await {
+            None => return,
+            Some(i) => i,
+        };
+        let series = self.state.seasons().get(&series_id).cloned();
+        let series = match series {
+            None => {
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Please select one of the series from the autocomplete list.",
+                )
+                .await;
+                return;
+            }
+            Some(s) => s,
+        };
+        let samples = Announcement::samples(&series);
+        let locale = guild_locale(&self.state, command.guild_id).await;
+        let mut msg = String::new();
+        for style in Style::ALL {
+            msg.push_str(&format!("**{}**\n", style.name()));
+            for ann in &samples {
+                msg.push_str(&style.render(&ann.escaped(), locale));
+                msg.push('\n');
+            }
+        }
+        msg.push_str("Use /style to pick one for this channel.");
+        respond_private(&ctx, &command, &msg).await;
+    }
+}
+
+pub struct StatusBoardCommand {
+    state: Arc<HandlerState>,
+}
+impl StatusBoardCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for StatusBoardCommand {
+    fn name(&self) -> &str {
+        "statusboard"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Keep a pinned message showing the registrations for everything watched in this channel.")
+                .create_option(|option| {
+                    option
+                        .name("on")
+                        .description("Turn the status message on or off")
+                        .kind(CommandOptionType::Boolean)
+                        .required(true)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let on = resolve_option_bool(&command.data.options, "on").unwrap_or(false);
+        let (guild, ch) = (command.guild_id, command.channel_id);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_channel_status(guild, ch, on))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update channel status {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(prev) => {
+                if let (false, Some(msg)) = (on, prev) {
+                    if let Err(e) = ch.delete_message(&ctx.http, msg).await {
+                        println!("Failed to delete status message {:?}", e);
+                    }
+                }
+                let msg = if on {
+                    "Okay, I'll keep a status message pinned in this channel, it'll show up within a minute or so."
+                } else {
+                    "Okay, no more status message."
+                };
+                respond_msg(&ctx, &command, msg).await;
+            }
+        }
+    }
+}
+
+pub struct DigestCommand {
+    state: Arc<HandlerState>,
+}
+impl DigestCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for DigestCommand {
+    fn name(&self) -> &str {
+        "digest"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Get a single digest message each time there's news, instead of a line per announcement.")
+                .create_option(|option| {
+                    option
+                        .name("on")
+                        .description("Turn digests on or off")
+                        .kind(CommandOptionType::Boolean)
+                        .required(true)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let on = resolve_option_bool(&command.data.options, "on").unwrap_or(false);
+        let layout = if on { Layout::Digest } else { Layout::Lines };
+        let (guild, ch) = (command.guild_id, command.channel_id);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_channel_layout(guild, ch, layout))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update channel layout {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = if on {
+                    "Okay, I'll roll up each round of announcements into a single digest message."
+                } else {
+                    "Okay, back to a line per announcement."
+                };
+                respond_msg(&ctx, &command, msg).await;
+            }
+        }
+    }
+}
+
+pub struct WeeklyPostCommand {
+    state: Arc<HandlerState>,
+}
+impl WeeklyPostCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for WeeklyPostCommand {
+    fn name(&self) -> &str {
+        "weeklypost"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description(
+                    "Post this week's track for every series the channel watches, once a week.",
+                )
+                .create_option(|option| {
+                    option
+                        .name("on")
+                        .description("Turn the weekly post on or off")
+                        .kind(CommandOptionType::Boolean)
+                        .required(true)
+                })
+                .create_option(|option| {
+                    option
+                        .name("day")
+                        .description(
+                            "The day to post on, Tuesday is just after the race week changes",
+                        )
+                        .kind(CommandOptionType::Integer)
+                        .required(false);
+                    for (i, name) in DAY_NAMES.iter().enumerate() {
+                        option.add_int_choice(name, i as i32);
+                    }
+                    option
+                })
+                .create_option(|option| {
+                    option
+                        .name("hour")
+                        .description("The hour to post at, in the server's /timezone")
+                        .kind(CommandOptionType::Integer)
+                        .required(false)
+                        .min_int_value(0)
+                        .max_int_value(23)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let opts = &command.data.options;
+        let on = resolve_option_bool(opts, "on").unwrap_or(false);
+        let day = resolve_option_i64(opts, "day").unwrap_or(1).clamp(0, 6);
+        let hour = resolve_option_i64(opts, "hour").unwrap_or(12).clamp(0, 23);
+        let when = if on { Some((day, hour)) } else { None };
+        let (guild, ch) = (command.guild_id, command.channel_id);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_weekly_post(guild, ch, when))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update weekly post {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = if on {
+                    format!(
+                        "Okay, every {} at {:02}:00 I'll post this week's track for each series this channel watches.",
+                        DAY_NAMES[day as usize], hour
+                    )
+                } else {
+                    "Okay, no more weekly posts.".to_string()
+                };
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct DailyDigestCommand {
+    state: Arc<HandlerState>,
+}
+impl DailyDigestCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for DailyDigestCommand {
+    fn name(&self) -> &str {
+        "dailydigest"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Get a summary of yesterday's racing for the watched series each day.")
+                .create_option(|option| {
+                    option
+                        .name("on")
+                        .description("Turn the daily digest on or off")
+                        .kind(CommandOptionType::Boolean)
+                        .required(true)
+                })
+                .create_option(|option| {
+                    option
+                        .name("hour")
+                        .description("The hour to post at, in the server's /timezone")
+                        .kind(CommandOptionType::Integer)
+                        .required(false)
+                        .min_int_value(0)
+                        .max_int_value(23)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let opts = &command.data.options;
+        let on = resolve_option_bool(opts, "on").unwrap_or(false);
+        let hour = resolve_option_i64(opts, "hour").unwrap_or(9).clamp(0, 23);
+        let (guild, ch) = (command.guild_id, command.channel_id);
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_daily_digest(guild, ch, if on { Some(hour) } else { None }))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update daily digest {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(_) => {
+                let msg = if on {
+                    format!(
+                        "Okay, every day at {:02}:00 I'll sum up yesterday's racing for the series this channel watches.",
+                        hour
+                    )
+                } else {
+                    "Okay, no more daily digests.".to_string()
+                };
+                respond_msg(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct WebhookCommand {
+    state: Arc<HandlerState>,
+    json_hosts: Vec<String>,
+}
+impl WebhookCommand {
+    pub fn new(state: Arc<HandlerState>, json_hosts: Vec<String>) -> Self {
+        Self { state, json_hosts }
+    }
+}
+#[async_trait]
+impl ACommand for WebhookCommand {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Send this channel's announcements to a webhook instead of posting them here.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+                .create_option(|option| {
+                    option
+                        .name("url")
+                        .description("The https webhook URL, a discord webhook or a host the bot allows JSON POSTs to")
+                        .kind(CommandOptionType::String)
+                        .required(false)
+                })
+                .create_option(|option| {
+                    option
+                        .name("remove")
+                        .description("Go back to posting announcements in this channel")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if !is_admin(command.member.as_ref().and_then(|m| m.permissions)) {
+            respond_error(&ctx, &command, "Only server admins can do that.").await;
+            return;
+        }
+        let opts = &command.data.options;
+        let remove = resolve_option_bool(opts, "remove").unwrap_or(false);
+        let target = if remove {
+            None
+        } else {
+            let url = resolve_option_str(opts, "url")
+                .map(|u| u.trim().to_string())
+                .unwrap_or_default();
+            let format = match WebhookFormat::for_url(&url, &self.json_hosts) {
+                Some(f) => f,
+                None => {
+                    let mut msg = "Please give me the URL of a discord webhook (https://discord.com/api/webhooks/...)".to_string();
+                    if !self.json_hosts.is_empty() {
+                        msg.push_str(&format!(
+                            ", or an https URL on {} for JSON",
+                            self.json_hosts.join(", ")
+                        ));
+                    }
+                    msg.push_str(", or use remove to stop using one.");
+                    respond_error(&ctx, &command, &msg).await;
+                    return;
+                }
+            };
+            Some(WebhookTarget { url, format })
+        };
+        let (guild, ch, user) = (command.guild_id, command.channel_id, command.user.id);
+        let t = target.clone();
+        match self
+            .state
+            .db()
+            .call(move |db| db.set_webhook(guild, ch, t.as_ref(), user))
+            .await
+        {
+            Err(e) => {
+                println!("failed to update webhook {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(n) => {
+                let msg = match target {
+                    None if n == 0 => {
+                        "This channel's announcements weren't going to a webhook.".to_string()
+                    }
+                    None => "Okay, back to posting announcements in this channel.".to_string(),
+                    Some(t) => format!(
+                        "Okay, this channel's announcements will go to the webhook as {} instead, there's no buttons or polls there.",
+                        t.format.name()
+                    ),
+                };
+                respond_private(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+pub struct HistoryCommand {
+    state: Arc<HandlerState>,
+}
+impl HistoryCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for HistoryCommand {
+    fn name(&self) -> &str {
+        "history"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("List the last few announcements sent for this channel.")
+                .create_option(|option| {
+                    option
+                        .name("count")
+                        .description("How many announcements to list")
+                        .kind(CommandOptionType::Integer)
+                        .required(false)
+                        .min_int_value(1)
+                        .max_int_value(25)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let count = resolve_option_i64(&command.data.options, "count")
+            .unwrap_or(10)
+            .clamp(1, 25);
+        let ch = command.channel_id;
+        match self
+            .state
+            .db()
+            .call(move |db| db.announce_log(ch, count))
+            .await
+        {
+            Err(e) => {
+                println!("failed to read announce log {}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, I seem to have lost my notepad, please try again later.",
+                )
+                .await;
+            }
+            Ok(log) if log.is_empty() => {
+                respond_private(
+                    &ctx,
+                    &command,
+                    "I haven't sent any announcements for this channel recently.",
+                )
+                .await;
+            }
+            Ok(log) => {
+                let mut msg = String::from("The most recent announcements for this channel:\n");
+                for a in log {
+                    msg.push_str(&format!(
+                        "<t:{}:f> {} {}, {} registered",
+                        a.at.timestamp(),
+                        a.series_name,
+                        a.ann_type,
+                        a.entry_count
+                    ));
+                    match a.message_id {
+                        Some(id) => msg.push_str(&format!(" {}", id.link(a.channel, a.guild))),
+                        None => msg.push_str(" (webhook)"),
+                    }
+                    msg.push('\n');
+                }
+                respond_private(&ctx, &command, &msg).await;
+            }
+        }
+    }
+}
+
+// CopyWatchesCommand copies another channel's watches into this one, for servers
+// rearranging their channels.
+pub struct CopyWatchesCommand {
+    state: Arc<HandlerState>,
+    limits: WatchLimits,
+}
+impl CopyWatchesCommand {
+    pub fn new(state: Arc<HandlerState>, limits: WatchLimits) -> Self {
+        Self { state, limits }
+    }
+}
+#[async_trait]
+impl ACommand for CopyWatchesCommand {
+    fn name(&self) -> &str {
+        "copywatches"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Copy the watches from another channel into this one.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+                .create_option(|option| {
+                    option
+                        .name("from_channel")
+                        .description("The channel to copy the watches from")
+                        .kind(CommandOptionType::Channel)
+                        .required(true)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if !is_admin(command.member.as_ref().and_then(|m| m.permissions)) {
+            respond_error(&ctx, &command, "Only server admins can do that.").await;
+            return;
+        }
+        let guild_id = match command.guild_id {
+            None => {
+                respond_error(&ctx, &command, "I only copy watches within a server.").await;
+                return;
+            }
+            Some(g) => g,
+        };
+        let from = command.data.options.iter().find_map(|o| match &o.resolved {
+            Some(CommandDataOptionValue::Channel(c)) if o.name == "from_channel" => Some(c.id),
+            _ => None,
+        });
+        let (from, to) = match from {
+            Some(f) if f != command.channel_id => (f, command.channel_id),
+            _ => {
+                respond_error(&ctx, &command, "Pick a different channel to copy from.").await;
+                return;
+            }
+        };
+        defer(&ctx, &command).await;
+        let created_by = command.user.clone();
+        let limits = self.limits.clone();
+        let dbr = self
+            .state
+            .db()
+            .call(move |db| -> rusqlite::Result<_> {
+                let watching: HashSet<i64> =
+                    db.channel_regs(to)?.iter().map(|r| r.series_id).collect();
+                let from_regs: Vec<Reg> = db
+                    .channel_regs(from)?
+                    .into_iter()
+                    // regs from another server's channel are none of our business.
+                    .filter(|r| r.guild == Some(guild_id) && !r.blocked)
+                    .collect();
+                let already = from_regs
+                    .iter()
+                    .filter(|r| watching.contains(&r.series_id))
+                    .count();
+                let mut regs: Vec<Reg> = from_regs
+                    .into_iter()
+                    .filter(|r| !watching.contains(&r.series_id))
+                    .map(|r| Reg { channel: to, ..r })
+                    .collect();
+                // stop at whichever watch would take the channel or server over its limit.
+                let (ch_count, guild_count) =
+                    (watching.len() as i64, db.guild_regs(guild_id)?.len() as i64);
+                let mut over = None;
+                for i in 0..regs.len() {
+                    let n = i as i64;
+                    if let Err(msg) = limits.check(Some(guild_id.0), ch_count + n, guild_count + n)
+                    {
+                        over = Some((regs.len() - i, msg));
+                        regs.truncate(i);
+                        break;
+                    }
+                }
+                let added = db.insert_regs(&regs, &created_by.name, Some(created_by.id))?;
+                Ok((added, already, over))
+            })
+            .await;
+        match dbr {
+            Err(e) => {
+                println!("db failed to copy regs {:?}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry I appear to have lost my notepad, try again later.",
+                )
+                .await;
+            }
+            Ok((0, 0, None)) => {
+                respond_error(
+                    &ctx,
+                    &command,
+                    &format!("<#{}> isn't watching anything I can copy.", from),
+                )
+                .await
+            }
+            Ok((added, already, over)) => {
+                let mut msg = format!("Okay, I've copied {} watches from <#{}>.", added, from);
+                if already > 0 {
+                    msg.push_str(&format!(
+                        " {} were already being watched here, so I left them as they are.",
+                        already
+                    ));
+                }
+                if let Some((n, limit)) = over {
+                    msg.push_str(&format!(" I couldn't copy the other {}. {}", n, limit));
+                }
+                respond_msg(&ctx, &command, &msg).await;
+                if let Some(warning) = missing_permissions(&ctx, to) {
+                    respond_private(&ctx, &command, &warning).await;
+                }
+            }
+        }
+    }
+}
+
+pub struct ExportCommand {
+    state: Arc<HandlerState>,
+}
+impl ExportCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for ExportCommand {
+    fn name(&self) -> &str {
+        "export"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description(
+                    "Get all the watches and settings for this server as a JSON file, for use with /import.",
+                )
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if !is_admin(command.member.as_ref().and_then(|m| m.permissions)) {
+            respond_error(&ctx, &command, "Only server admins can do that.").await;
+            return;
+        }
+        let guild_id = match command.guild_id {
+            None => {
+                respond_error(&ctx, &command, "I only export watches for servers.").await;
+                return;
+            }
+            Some(g) => g,
+        };
+        let export = match self
+            .state
+            .db()
+            .call(move |db| db.export_guild(guild_id))
+            .await
+        {
+            Err(e) => {
+                println!("Failed to read guild watches {:?}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, i can't find my notebook right how, try again later.",
+                )
+                .await;
+                return;
+            }
+            Ok(r) => r,
+        };
+        let json = serde_json::to_vec_pretty(&export).unwrap_or_default();
+        let content = format!(
+            "Here's the {} watches and {} channel settings for this server.",
+            export.watches.len(),
+            export.channels.len()
+        );
+        if let Err(e) = command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message
+                            .flags(MessageFlags::EPHEMERAL)
+                            .content(&content)
+                            .add_file(AttachmentType::Bytes {
+                                data: Cow::Owned(json),
+                                filename: format!("watches-{}.json", guild_id),
+                            })
+                    })
+            })
+            .await
+        {
+            println!("Failed to respond to command {}", e);
+        }
+    }
+}
+
+pub struct ImportCommand {
+    state: Arc<HandlerState>,
+    limits: WatchLimits,
+    // imports waiting for the admin to say where each channel should go, by interaction id.
+    pending: Mutex<HashMap<u64, PendingImport>>,
+}
+impl ImportCommand {
+    pub fn new(state: Arc<HandlerState>, limits: WatchLimits) -> Self {
+        Self {
+            state,
+            limits,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+// PendingImport is an import from another server, the channels it mentions need
+// matching up with channels in this server before it can go ahead.
+struct PendingImport {
+    guild: GuildId,
+    export: GuildExport,
+    // the channels from the export that still need a home, in the order they're asked about.
+    unmapped: Vec<u64>,
+    // where each channel from the export goes, channels not in here are skipped.
+    mapping: HashMap<u64, ChannelId>,
+    // which page of this server's channels the select menus are showing.
+    page: usize,
+    created: Instant,
+}
+
+// how many channels to ask about at once, a message can only have 5 rows of components.
+const IMPORT_CHANNELS_PER_STEP: usize = 4;
+// a select menu can only have 25 options, servers with more channels get pages of them.
+const IMPORT_CHANNELS_PER_PAGE: usize = 25;
+// how long an import waits for channels to be picked.
+const IMPORT_PENDING_SECS: u64 = 15 * 60;
+
+#[async_trait]
+impl ACommand for ImportCommand {
+    fn name(&self) -> &str {
+        "import"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Add the watches and settings from a file made by /export.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+                .create_option(|option| {
+                    option
+                        .name("file")
+                        .description("The JSON file from /export")
+                        .kind(CommandOptionType::Attachment)
+                        .required(true)
+                })
+                .create_option(|option| {
+                    option
+                        .name("channel")
+                        .description("Put all the watches in this channel, rather than the channels they came from")
+                        .kind(CommandOptionType::Channel)
+                        .required(false)
+                })
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if !is_admin(command.member.as_ref().and_then(|m| m.permissions)) {
+            respond_error(&ctx, &command, "Only server admins can do that.").await;
+            return;
+        }
+        let guild_id = match command.guild_id {
+            None => {
+                respond_error(&ctx, &command, "I only import watches for servers.").await;
+                return;
+            }
+            Some(g) => g,
+        };
+        let mut file = None;
+        let mut target = None;
+        for o in &command.data.options {
+            match &o.resolved {
+                Some(CommandDataOptionValue::Attachment(a)) if o.name == "file" => {
+                    file = Some(a.clone())
+                }
+                Some(CommandDataOptionValue::Channel(c)) if o.name == "channel" => {
+                    target = Some(c.id)
+                }
+                _ => {}
+            }
+        }
+        let export: GuildExport = match file {
+            None => None,
+            Some(f) => match f.download().await {
+                Err(e) => {
+                    println!("Failed to download import file {:?}", e);
+                    None
+                }
+                Ok(data) => serde_json::from_slice::<ExportFile>(&data).ok(),
+            },
+        }
+        .map(GuildExport::from)
+        .unwrap_or_default();
+        if export.watches.is_empty() && export.channels.is_empty() {
+            respond_error(
+                &ctx,
+                &command,
+                "I couldn't find any watches in that, it should be a file from /export.",
+            )
+            .await;
+            return;
+        }
+        // channels that are in this server stay where they are, the rest need a new home.
+        let mut mapping = HashMap::new();
+        let mut unmapped = Vec::new();
+        let from_channels = export
+            .watches
+            .iter()
+            .map(|w| w.channel_id)
+            .chain(export.channels.iter().map(|c| c.channel_id));
+        for from in from_channels {
+            if mapping.contains_key(&from) || unmapped.contains(&from) {
+                continue;
+            }
+            match target {
+                Some(t) => {
+                    mapping.insert(from, t);
+                }
+                None if in_guild(&ctx, ChannelId(from), guild_id) => {
+                    mapping.insert(from, ChannelId(from));
+                }
+                None => unmapped.push(from),
+            }
+        }
+        if unmapped.is_empty() {
+            mapping.retain(|_, to| in_guild(&ctx, *to, guild_id));
+            let msg = self
+                .import(guild_id, &command.user, &export, &mapping)
+                .await;
+            respond_msg(&ctx, &command, &msg).await;
+            return;
+        }
+        let pending = PendingImport {
+            guild: guild_id,
+            export,
+            unmapped,
+            mapping,
+            page: 0,
+            created: Instant::now(),
+        };
+        let (content, components) = import_step(&ctx, command.id.0, &pending);
+        {
+            let mut p = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+            p.retain(|_, i| i.created.elapsed().as_secs() < IMPORT_PENDING_SECS);
+            p.insert(command.id.0, pending);
+        }
+        respond(&ctx, &command, &content, true, Some(components)).await;
+    }
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        if !is_admin(comp.member.as_ref().and_then(|m| m.permissions)) {
+            return;
+        }
+        let parts: Vec<&str> = comp.data.custom_id.split(':').collect();
+        let (content, components) = match parts[..] {
+            [_, "map", id, from] => {
+                let (id, from) = (id.parse().unwrap_or(0), from.parse().unwrap_or(0));
+                let to = comp
+                    .data
+                    .values
+                    .first()
+                    .and_then(|v| v.parse().ok())
+                    .map(ChannelId);
+                if let Some(p) = self
+                    .pending
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .get_mut(&id)
+                {
+                    match to {
+                        Some(to) if in_guild(&ctx, to, p.guild) => p.mapping.insert(from, to),
+                        _ => p.mapping.remove(&from),
+                    };
+                }
+                // nothing to change on screen until they hit next.
+                if let Err(e) = comp
+                    .create_interaction_response(&ctx.http, |r| {
+                        r.kind(InteractionResponseType::DeferredUpdateMessage)
                     })
                     .await
                 {
-                    println!("Failed to send autocomp response {:?}", e);
+                    println!("Failed to respond to component {}", e);
+                }
+                return;
+            }
+            [_, "next", id] => {
+                let id = id.parse().unwrap_or(0);
+                // either the import that's ready to go, or the next step to show.
+                let next = {
+                    let mut all = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+                    match all.get_mut(&id) {
+                        None => None,
+                        Some(p) => {
+                            let n = p.unmapped.len().min(IMPORT_CHANNELS_PER_STEP);
+                            p.unmapped.drain(..n);
+                            p.page = 0;
+                            if p.unmapped.is_empty() {
+                                all.remove(&id).map(Ok)
+                            } else {
+                                Some(Err(import_step(&ctx, id, p)))
+                            }
+                        }
+                    }
+                };
+                match next {
+                    None => (
+                        "That import has expired, please run /import again.".to_string(),
+                        None,
+                    ),
+                    Some(Ok(mut p)) => {
+                        // the channels could have moved or gone since they were picked.
+                        let guild = p.guild;
+                        p.mapping.retain(|_, to| in_guild(&ctx, *to, guild));
+                        let msg = self
+                            .import(p.guild, &comp.user, &p.export, &p.mapping)
+                            .await;
+                        (msg, None)
+                    }
+                    Some(Err((content, components))) => (content, Some(components)),
                 }
             }
+            [_, "page", id] => {
+                let id = id.parse().unwrap_or(0);
+                let step = self
+                    .pending
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .get_mut(&id)
+                    .map(|p| {
+                        p.page += 1;
+                        import_step(&ctx, id, p)
+                    });
+                match step {
+                    None => (
+                        "That import has expired, please run /import again.".to_string(),
+                        None,
+                    ),
+                    Some((content, components)) => (content, Some(components)),
+                }
+            }
+            [_, "cancel", id] => {
+                let id: u64 = id.parse().unwrap_or(0);
+                self.pending
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .remove(&id);
+                ("Okay, I won't import anything.".to_string(), None)
+            }
+            _ => return,
+        };
+        if let Err(e) = comp
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| {
+                        message.content(content);
+                        match components {
+                            Some(c) => message.set_components(c),
+                            None => message.components(|c| c),
+                        }
+                    })
+            })
+            .await
+        {
+            println!("Failed to respond to component {}", e);
         }
     }
-    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
-        let series_id = match resolve_series_id(&ctx, &command).await {
-            None => return,
-            Some(i) => i,
-        };
-        let dbr;
+}
+
+impl ImportCommand {
+    // adds the watches & settings from the export, with the channels moved according to
+    // mapping. Returns the message for the user saying how it went.
+    async fn import(
+        &self,
+        guild_id: GuildId,
+        user: &User,
+        export: &GuildExport,
+        mapping: &HashMap<u64, ChannelId>,
+    ) -> String {
+        let mut regs = Vec::with_capacity(export.watches.len());
+        let (mut skipped, mut unmapped) = (0, 0);
         {
-            let mut st = self.state.lock().expect("Unable to lock state");
-            dbr = st.db.delete_reg(command.channel_id, series_id);
+            let seasons = self.state.seasons();
+            for e in &export.watches {
+                let ch = match mapping.get(&e.channel_id) {
+                    Some(ch) => *ch,
+                    None => {
+                        unmapped += 1;
+                        continue;
+                    }
+                };
+                match seasons.get(&e.series_id) {
+                    Some(s) => regs.push(e.to_reg(Some(guild_id), ch, s)),
+                    None => skipped += 1,
+                }
+            }
         }
+        let created_by = user.clone();
+        let limits = self.limits.clone();
+        let export = export.clone();
+        let mapping = mapping.clone();
+        let dbr = self
+            .state
+            .db()
+            .call(move |db| -> rusqlite::Result<(usize, usize, usize)> {
+                db.import_guild_settings(guild_id, &export, &mapping)?;
+                let (mut added, mut over, mut blocked) = (0, 0, 0);
+                for r in &regs {
+                    if db.is_series_blocked(r.guild, r.series_id)? {
+                        blocked += 1;
+                        continue;
+                    }
+                    // only admins can import, so they can replace any watch.
+                    let perms = Some(Permissions::ADMINISTRATOR);
+                    match upsert_reg_within(db, r, &created_by, perms, &limits)? {
+                        Ok(_) => added += 1,
+                        Err(_) => over += 1,
+                    }
+                }
+                Ok((added, over, blocked))
+            })
+            .await;
         match dbr {
             Err(e) => {
-                println!("failed to remove registration {}", e);
+                println!("db failed to import regs {:?}", e);
+                set_outcome("error");
+                "Sorry I appear to have lost my notepad, try again later.".to_string()
+            }
+            Ok((count, over, blocked)) => {
+                let mut msg = format!("Okay, I've added {} watches.", count);
+                if skipped > 0 {
+                    msg.push_str(&format!(
+                        " {} were for series that aren't running right now, so I skipped them.",
+                        skipped
+                    ));
+                }
+                if unmapped > 0 {
+                    msg.push_str(&format!(" {} were for channels you skipped.", unmapped));
+                }
+                if blocked > 0 {
+                    msg.push_str(&format!(
+                        " {} were for series this server has blocked, so I skipped them.",
+                        blocked
+                    ));
+                }
+                if over > 0 {
+                    msg.push_str(&format!(
+                        " {} would have gone over the number of watches I can keep track of, so I skipped them.",
+                        over
+                    ));
+                }
+                msg
+            }
+        }
+    }
+}
+
+// the message & components asking where the next few channels from the pending import should go.
+fn import_step(ctx: &Context, id: u64, p: &PendingImport) -> (String, CreateComponents) {
+    let mut channels: Vec<GuildChannel> = ctx
+        .cache
+        .guild_channels(p.guild)
+        .map(|chs| {
+            chs.iter()
+                .map(|c| c.value().clone())
+                .filter(|c| c.kind == ChannelType::Text)
+                .collect()
+        })
+        .unwrap_or_default();
+    channels.sort_by_key(|c| c.position);
+    let pages = channels.len().div_ceil(IMPORT_CHANNELS_PER_PAGE);
+    let page = p.page % pages.max(1);
+    let page_channels: Vec<&GuildChannel> = channels
+        .iter()
+        .skip(page * IMPORT_CHANNELS_PER_PAGE)
+        .take(IMPORT_CHANNELS_PER_PAGE)
+        .collect();
+    let step: Vec<u64> = p
+        .unmapped
+        .iter()
+        .take(IMPORT_CHANNELS_PER_STEP)
+        .copied()
+        .collect();
+    let mut content = format!(
+        "These channels aren't in this server, pick where their watches and settings should go. Anything left unpicked is skipped. ({} to go)",
+        p.unmapped.len()
+    );
+    let mut components = CreateComponents::default();
+    for from in &step {
+        let watches = p
+            .export
+            .watches
+            .iter()
+            .filter(|w| w.channel_id == *from)
+            .map(|w| w.series_name.as_str())
+            .collect::<Vec<_>>();
+        content.push_str(&format!(
+            "\n<#{}>: {}",
+            from,
+            truncate(&watches.join(", "), 300)
+        ));
+        // the menu only shows the pick when it's on this page, so say where it's going.
+        if let Some(to) = p.mapping.get(from) {
+            content.push_str(&format!(" \u{2192} <#{}>", to));
+        }
+        components.create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id(format!("import:map:{}:{}", id, from))
+                    .placeholder(truncate(&format!("Channel {} goes to...", from), 100))
+                    .min_values(0)
+                    .max_values(1)
+                    .options(|opts| {
+                        for c in &page_channels {
+                            opts.create_option(|o| {
+                                o.label(truncate(&format!("#{}", c.name), 100))
+                                    .value(c.id.0)
+                            });
+                        }
+                        opts
+                    })
+            })
+        });
+    }
+    components.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id(format!("import:next:{}", id))
+                .label(if p.unmapped.len() > IMPORT_CHANNELS_PER_STEP {
+                    "Next"
+                } else {
+                    "Import"
+                })
+                .style(ButtonStyle::Primary)
+        })
+        .create_button(|b| {
+            b.custom_id(format!("import:cancel:{}", id))
+                .label("Cancel")
+                .style(ButtonStyle::Secondary)
+        });
+        if pages > 1 {
+            row.create_button(|b| {
+                b.custom_id(format!("import:page:{}", id))
+                    .label(format!("More channels ({}/{})", page + 1, pages))
+                    .style(ButtonStyle::Secondary)
+            });
+        }
+        row
+    });
+    (content, components)
+}
+
+// true if the channel is one of the guild's, so that an import only writes to its own server.
+fn in_guild(ctx: &Context, ch: ChannelId, guild: GuildId) -> bool {
+    ctx.cache.guild_channel(ch).map(|c| c.guild_id) == Some(guild)
+}
+
+pub struct BotStatsCommand {
+    state: Arc<HandlerState>,
+    health: Arc<Health>,
+    owner_id: Option<u64>,
+}
+impl BotStatsCommand {
+    pub fn new(state: Arc<HandlerState>, health: Arc<Health>, owner_id: Option<u64>) -> Self {
+        Self {
+            state,
+            health,
+            owner_id,
+        }
+    }
+}
+#[async_trait]
+impl ACommand for BotStatsCommand {
+    fn name(&self) -> &str {
+        "botstats"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("How the bot is doing, for the bot operator.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if self.owner_id != Some(command.user.id.0) {
+            respond_error(&ctx, &command, "Only the bot operator can do that.").await;
+            return;
+        }
+        let (watches, channels) = match self.state.db().call(|db| db.watch_totals()).await {
+            Err(e) => {
+                println!("Failed to count watches {:?}", e);
                 respond_error(
                     &ctx,
                     &command,
-                    "Sorry, I seem to have lost my notepad, please try again later.",
+                    "Sorry, i can't find my notebook right how, try again later.",
                 )
                 .await;
+                return;
             }
-            Ok(_) => {
-                respond_msg(&ctx, &command, "Okay, I wont mention it again.").await;
-            }
+            Ok(t) => t,
+        };
+        let series = self.state.seasons().len();
+        let health = self.health.status();
+        let when = |t: Option<DateTime<Utc>>| match t {
+            Some(t) => format!("<t:{}:R>", t.timestamp()),
+            None => "never".to_string(),
+        };
+        let mut msg = format!(
+            "Servers: {}\nWatches: {} in {} channels\nActive series: {}\nAnnouncements in the last 24h: {}\nLast iRacing poll: {}\n",
+            ctx.cache.guild_count(),
+            watches,
+            channels,
+            series,
+            self.health.announced_last_day(),
+            when(health.last_poll_ok),
+        );
+        if let Some(e) = &health.last_poll_error {
+            msg.push_str(&format!("Last iRacing error: {}\n", e));
         }
+        let down: Vec<String> = (0..health.shard_count)
+            .filter(|id| !health.shards.get(id).copied().unwrap_or(false))
+            .map(|id| id.to_string())
+            .collect();
+        let gateway = if health.shard_count == 0 {
+            "not connected yet".to_string()
+        } else if down.is_empty() {
+            format!(
+                "{} of {} shards connected",
+                health.shard_count, health.shard_count
+            )
+        } else {
+            format!(
+                "{} of {} shards connected, down: {}",
+                health.shard_count - down.len() as u64,
+                health.shard_count,
+                down.join(", ")
+            )
+        };
+        // iRacing doesn't say when the login expires, we find out when a request is refused.
+        msg.push_str(&format!(
+            "iRacing login: {}, good until iRacing says otherwise\nGateway: {}\nSchema changes: {}",
+            when(health.ir_login),
+            gateway,
+            health.schema_drift
+        ));
+        respond_private(&ctx, &command, &msg).await;
     }
 }
 
-async fn resolve_series_id(ctx: &Context, command: &ApplicationCommandInteraction) -> Option<i64> {
-    let maybe_series_id = match command.data.options[0].resolved.as_ref().unwrap() {
-        CommandDataOptionValue::String(x) => x.parse(),
-        CommandDataOptionValue::Integer(x) => Ok(*x),
-        _ => Ok(414),
-    };
-    match maybe_series_id {
-        Err(_) => {
-            respond_error(
-                ctx,
-                command,
-                "Please select one of the series from the autocomplete list.",
-            )
-            .await;
-            None
+pub struct ForgetMeCommand {
+    state: Arc<HandlerState>,
+}
+impl ForgetMeCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
+#[async_trait]
+impl ACommand for ForgetMeCommand {
+    fn name(&self) -> &str {
+        "forgetme"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Delete everything Reg has stored for this server.")
+                .default_member_permissions(Permissions::ADMINISTRATOR)
+                .dm_permission(false)
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        if !is_admin(command.member.as_ref().and_then(|m| m.permissions)) {
+            respond_error(&ctx, &command, "Only server admins can do that.").await;
+            return;
+        }
+        if let Err(e) = command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message
+                            .flags(MessageFlags::EPHEMERAL)
+                            .content("This will delete all the watches and settings for every channel in this server, there's no undo. Are you sure?")
+                            .components(|c| {
+                                c.create_action_row(|row| {
+                                    row.create_button(|b| {
+                                        b.custom_id("forgetme:confirm")
+                                            .label("Yes, forget everything")
+                                            .style(ButtonStyle::Danger)
+                                    })
+                                })
+                            })
+                    })
+            })
+            .await
+        {
+            println!("Failed to respond to command {}", e);
+        }
+    }
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        let msg = match comp.guild_id {
+            _ if !is_admin(comp.member.as_ref().and_then(|m| m.permissions)) => {
+                "Only server admins can do that.".to_string()
+            }
+            None => "I only keep things for servers.".to_string(),
+            Some(guild_id) => {
+                match self
+                    .state
+                    .db()
+                    .call(move |db| db.purge_guild(guild_id))
+                    .await
+                {
+                    Err(e) => {
+                        println!("Failed to purge guild {} {:?}", guild_id, e);
+                        "Sorry, I seem to have lost my notepad, please try again later.".to_string()
+                    }
+                    Ok(counts) => {
+                        println!("purged guild {} {:?}", guild_id, counts);
+                        let details: Vec<String> = counts
+                            .iter()
+                            .map(|(table, n)| format!("{} {}", n, table))
+                            .collect();
+                        format!(
+                            "Done, I've forgotten everything about this server. Removed {}.",
+                            details.join(", ")
+                        )
+                    }
+                }
+            }
+        };
+        if let Err(e) = comp
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|message| message.content(msg).components(|c| c))
+            })
+            .await
+        {
+            println!("Failed to respond to component {}", e);
         }
-        Ok(sid) => Some(sid),
     }
 }
 
-async fn respond_msg(ctx: &Context, command: &ApplicationCommandInteraction, msg: &str) {
-    if let Err(e) = command
-        .create_interaction_response(&ctx.http, |response| {
-            response
-                .kind(InteractionResponseType::ChannelMessageWithSource)
-                .interaction_response_data(|message| message.content(msg))
-        })
-        .await
-    {
-        println!("Failed to respond to command {}", e);
-    }
+// returns true if the member permissions from an interaction include admin rights.
+fn is_admin(perms: Option<Permissions>) -> bool {
+    perms
+        .map(|p| p.administrator() || p.manage_guild())
+        .unwrap_or(false)
 }
 
-async fn respond_error(ctx: &Context, command: &ApplicationCommandInteraction, msg: &str) {
-    if let Err(e) = command
-        .create_interaction_response(&ctx.http, |response| {
-            response
-                .kind(InteractionResponseType::ChannelMessageWithSource)
-                .interaction_response_data(|message| {
-                    message.flags(MessageFlags::EPHEMERAL);
-                    message.content(msg)
-                })
-        })
-        .await
-    {
-        println!("Failed to respond to command {}", e);
+const RESTRICTED_WATCH: &str =
+    "Only whoever added this watch, or someone that can manage channels, can change it.";
+
+// returns true if the user can remove or edit the watch, anyone can unless the server
+// has turned on /restrictwatches.
+fn can_change_watch(reg: &Reg, user: UserId, perms: Option<Permissions>) -> bool {
+    !reg.restricted
+        || reg.created_by_id == Some(user)
+        || is_admin(perms)
+        || perms.is_some_and(|p| p.manage_channels())
+}
+
+pub struct SetupStatusCommand {
+    state: Arc<HandlerState>,
+}
+impl SetupStatusCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
     }
 }
+#[async_trait]
+impl ACommand for SetupStatusCommand {
+    fn name(&self) -> &str {
+        "setupstatus"
+    }
+    fn create(&self, commands: &mut CreateApplicationCommands) {
+        commands.create_application_command(|command| {
+            command
+                .name(self.name())
+                .description("Check what's been setup for Reg in this server.")
+        });
+    }
+    async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
+        let guild_id = match command.guild_id {
+            None => {
+                respond_error(&ctx, &command, "I only do setup for servers.").await;
+                return;
+            }
+            Some(g) => g,
+        };
+        let regs = match self
+            .state
+            .db()
+            .call(move |db| db.guild_regs(guild_id))
+            .await
+        {
+            Err(e) => {
+                println!("Failed to read guild watches {:?}", e);
+                respond_error(
+                    &ctx,
+                    &command,
+                    "Sorry, i can't find my notebook right how, try again later.",
+                )
+                .await;
+                return;
+            }
+            Ok(r) => r,
+        };
+        let channels: HashSet<ChannelId> = regs.iter().map(|r| r.channel).collect();
+        let here = regs
+            .iter()
+            .filter(|r| r.channel == command.channel_id)
+            .count();
+        let can_post = bot_permissions(&ctx, command.channel_id)
+            .map(|p| p.contains(Permissions::SEND_MESSAGES));
+        let installed = guild_id
+            .get_application_commands(&ctx.http)
+            .await
+            .unwrap_or_default();
+        let perms = guild_id
+            .get_application_commands_permissions(&ctx.http)
+            .await
+            .unwrap_or_default();
+        // with the command_group feature on, they're both under /reg.
+        let names: &[&str] = if installed.iter().any(|c| c.name == COMMAND_GROUP) {
+            &[COMMAND_GROUP]
+        } else {
+            &["watch", "nomore"]
+        };
+        let restricted = names.iter().all(|name| {
+            installed.iter().filter(|c| c.name == *name).any(|c| {
+                perms
+                    .iter()
+                    .any(|p| p.id == c.id && !p.permissions.is_empty())
+            })
+        });
 
-fn resolve_option_i64(opts: &[CommandDataOption], opt_name: &str) -> Option<i64> {
-    for o in opts {
-        if o.name == opt_name {
-            return match o.resolved {
-                Some(CommandDataOptionValue::Integer(i)) => Some(i),
-                _ => {
-                    println!("unexpected int value for {} of {:?}", opt_name, o.resolved);
-                    None
-                }
-            };
+        let mut msgs = vec!["Here's how things are setup in this server:".to_string()];
+        msgs.push(format!(
+            "{} Watching {} series across {} channel{}.",
+            tick(!regs.is_empty()),
+            regs.len(),
+            channels.len(),
+            if channels.len() == 1 { "" } else { "s" }
+        ));
+        msgs.push(format!(
+            "{} {} watches in this channel.",
+            tick(here > 0),
+            here
+        ));
+        msgs.push(match can_post {
+            Some(true) => format!("{} I can post messages in this channel.", tick(true)),
+            Some(false) => format!("{} I can't post messages in this channel.", tick(false)),
+            None => "\u{2754} I couldn't check my permissions in this channel.".to_string(),
+        });
+        msgs.push(format!(
+            "{} /watch and /nomore are {}restricted to particular roles or members.",
+            if restricted {
+                tick(true)
+            } else {
+                "\u{26a0}\u{fe0f}"
+            },
+            if restricted { "" } else { "not " }
+        ));
+        let content = msgs.join("\n");
+        if let Err(e) = command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message
+                            .flags(MessageFlags::EPHEMERAL)
+                            .content(&content)
+                            .components(|c| {
+                                c.create_action_row(|row| {
+                                    row.create_button(|b| {
+                                        b.custom_id("setupstatus:watch")
+                                            .label("Watch a series")
+                                            .style(ButtonStyle::Primary)
+                                    })
+                                    .create_button(|b| {
+                                        b.custom_id("setupstatus:perms")
+                                            .label("Restrict commands")
+                                            .style(ButtonStyle::Secondary)
+                                    })
+                                    .create_button(|b| {
+                                        b.custom_id("setupstatus:help")
+                                            .label("Help")
+                                            .style(ButtonStyle::Secondary)
+                                    })
+                                })
+                            })
+                    })
+            })
+            .await
+        {
+            println!("Failed to respond to command {}", e);
         }
     }
-    None
-}
-fn resolve_option_bool(opts: &[CommandDataOption], opt_name: &str) -> Option<bool> {
-    for o in opts {
-        if o.name == opt_name {
-            return match o.resolved {
-                Some(CommandDataOptionValue::Boolean(i)) => Some(i),
-                _ => {
-                    println!("unexpected bool value for {} of {:?}", opt_name, o.resolved);
-                    None
-                }
-            };
+    async fn component(&self, ctx: Context, comp: MessageComponentInteraction) {
+        let msg = match comp.data.custom_id.as_str() {
+            "setupstatus:watch" => "Go to the channel you want announcements in and use /watch, pick the series from the list. Use /watching to check what's setup.",
+            "setupstatus:perms" => "Open Server Settings, Integrations, then Regbot. From there you can limit who can use /watch and /nomore, for example to an admin role.",
+            _ => "Use /help to see what I can do.",
+        };
+        if let Err(e) = comp
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message.flags(MessageFlags::EPHEMERAL).content(msg)
+                    })
+            })
+            .await
+        {
+            println!("Failed to respond to component {}", e);
         }
     }
-    None
 }
 
-pub struct HelpCommand;
-
-const HELP_MSG:&str = "Hey there, I'm Reginald. While I sip my coffee I'll keep an eye on race registrations for you. Let me know what series you're interested in and I'll message a channel when I see some activity for that series. Use the /watch command to select a series.
+fn tick(ok: bool) -> &'static str {
+    if ok {
+        "\u{2705}"
+    } else {
+        "\u{274c}"
+    }
+}
 
-You can control how many race entries are needed before i say anything with the min_reg option. I can also stop yammering on about it once there's a critical mass registered, use the max_reg option. If you want to always know when race registration opens or closes, you can use the open and close options to turn that on.
+// the permissions the bot needs in a channel to make announcements.
+const ANNOUNCE_PERMISSIONS: [(Permissions, &str); 3] = [
+    (Permissions::VIEW_CHANNEL, "View Channel"),
+    (Permissions::SEND_MESSAGES, "Send Messages"),
+    (Permissions::EMBED_LINKS, "Embed Links"),
+];
 
-By default I'll start reporting registrations at 50% of official and stop if it reaches halfway between official and splitting.
+// returns a warning for the user if the bot is missing permissions it needs to
+// announce in the channel.
+fn missing_permissions(ctx: &Context, ch: ChannelId) -> Option<String> {
+    let perms = bot_permissions(ctx, ch)?;
+    let missing: Vec<&str> = ANNOUNCE_PERMISSIONS
+        .iter()
+        .filter(|(p, _)| !perms.contains(*p))
+        .map(|(_, name)| *name)
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "\u{26a0} Heads up, I don't have the {} permission{} in this channel, so I won't be able to announce anything here until someone fixes that.",
+        missing.join(" and "),
+        if missing.len() == 1 { "" } else { "s" }
+    ))
+}
 
-The entry/split numbers reported at registration closed might not match exactly the race session(s) as you can't get the numbers until the end of the race.
+// returns the bot's permissions in the channel, if the channel is in the cache.
+fn bot_permissions(ctx: &Context, ch: ChannelId) -> Option<Permissions> {
+    let channel = ctx.cache.guild_channel(ch)?;
+    channel
+        .permissions_for_user(&ctx.cache, ctx.cache.current_user_id())
+        .ok()
+}
 
-If you forget what you asked for, you can /watching to find out. You can also /nomore if you don't care about a series anymore.";
+pub struct HelpCommand {
+    state: Arc<HandlerState>,
+}
+impl HelpCommand {
+    pub fn new(state: Arc<HandlerState>) -> Self {
+        Self { state }
+    }
+}
 
 #[async_trait]
 impl ACommand for HelpCommand {
@@ -404,9 +5504,83 @@ impl ACommand for HelpCommand {
             command
                 .name(self.name())
                 .description("Ask Reg what his deal is.")
+                .create_option(|option| {
+                    option
+                        .name("private")
+                        .description("Only show the help to you")
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
         });
     }
     async fn execute(&self, ctx: Context, command: ApplicationCommandInteraction) {
-        respond_msg(&ctx, &command, HELP_MSG).await;
+        let private = private_reply(&self.state, &command).await;
+        // the help is more than fits in one message, so it goes a few paragraphs at a time.
+        if private {
+            defer_private(&ctx, &command).await;
+        } else {
+            defer(&ctx, &command).await;
+        }
+        let locale = guild_locale(&self.state, command.guild_id).await;
+        for chunk in paragraphs(text(locale, Msg::Help), 2000) {
+            respond(&ctx, &command, &chunk, private, None).await;
+        }
+    }
+}
+
+// splits msg into pieces of no more than max bytes, breaking between paragraphs. A
+// paragraph that's too long by itself is broken between its lines, or words for a line
+// that's too long too.
+fn paragraphs(msg: &str, max: usize) -> Vec<String> {
+    let mut res: Vec<String> = Vec::new();
+    for p in msg.split("\n\n") {
+        if p.len() <= max {
+            pack(&mut res, p, "\n\n", max);
+            continue;
+        }
+        let mut sep = "\n\n";
+        for line in p.lines() {
+            for piece in split_line(line, max) {
+                pack(&mut res, piece, sep, max);
+                sep = "\n";
+            }
+        }
+    }
+    res
+}
+
+// adds piece to the last of res, after sep, if it fits, or as a new one if not.
+fn pack(res: &mut Vec<String>, piece: &str, sep: &str, max: usize) {
+    match res.last_mut() {
+        Some(last) if last.len() + sep.len() + piece.len() <= max => {
+            last.push_str(sep);
+            last.push_str(piece);
+        }
+        _ => res.push(piece.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paragraphs() {
+        // short paragraphs share a message.
+        assert_eq!(paragraphs("one\n\ntwo", 20), vec!["one\n\ntwo"]);
+        assert_eq!(
+            paragraphs("one two three\n\nfour five six", 20),
+            vec!["one two three", "four five six"]
+        );
+        // a long paragraph is split between its lines.
+        assert_eq!(
+            paragraphs("intro\n\n- line one\n- line two\n- line three", 24),
+            vec!["intro\n\n- line one", "- line two\n- line three"]
+        );
+        // and a long line between its words, nothing is lost.
+        let long = "word ".repeat(30);
+        let res = paragraphs(long.trim(), 20);
+        assert!(res.iter().all(|m| m.len() <= 20), "{:?}", res);
+        assert_eq!(res.join(" ").split_whitespace().count(), 30);
     }
 }
@@ -0,0 +1,482 @@
+use crate::db::Reg;
+use crate::i18n::Locale;
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::Path;
+use std::str::FromStr;
+
+// Config is loaded from regbot.toml (or the file named by REGBOT_CONFIG), any
+// settings can then be overridden by environment variables.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    // DISCORD_TOKEN
+    pub discord_token: String,
+    // IRUSER
+    pub ir_user: String,
+    // IRPWD
+    pub ir_password: String,
+    // REGBOT_DB
+    pub db_path: String,
+    // REGBOT_POLL_INTERVAL, seconds between race guide polls.
+    pub poll_interval_secs: u64,
+    // REGBOT_POLL_FAST, seconds between polls while a watched series has a session
+    // starting soon, to catch the last minute registrations.
+    pub poll_fast_secs: u64,
+    // REGBOT_POLL_QUIET, seconds between polls during the quiet hours.
+    pub poll_quiet_secs: u64,
+    // REGBOT_QUIET_START & REGBOT_QUIET_END, the UTC hours when not much is going on,
+    // equal means there's no quiet hours.
+    pub quiet_start_hour: u32,
+    pub quiet_end_hour: u32,
+    // REGBOT_MAX_MESSAGE_LEN, announcements to a channel are batched into messages upto this size.
+    pub max_message_len: usize,
+    // REGBOT_MAX_CHANNEL_MESSAGES, the most messages to send a channel at once, any more
+    // announcements wait for the outbox.
+    pub max_channel_messages: usize,
+    // REGBOT_SHARDS, how many gateway shards to run, 0 to use the number discord recommends.
+    pub shards: u64,
+    // HEALTH_ADDR
+    pub health_addr: Option<String>,
+    // API_ADDR, where to serve the REST API for managing watches, no API if not set.
+    pub api_addr: Option<String>,
+    // API_TOKEN, the bearer token the API wants on every request.
+    pub api_token: Option<String>,
+    // DASHBOARD_TOKEN, what to log in to the read only dashboard with, it's served
+    // alongside the API if this is set. Kept apart from api_token as it's typed into
+    // a browser.
+    pub dashboard_token: Option<String>,
+    // REGBOT_IR_FIXTURES, a directory of json files to use instead of talking to iRacing, see MockIrApi.
+    pub ir_fixtures: Option<String>,
+    // REGBOT_GLOBAL_COMMANDS, register the slash commands globally once, rather than per guild.
+    pub global_commands: bool,
+    // OWNER_ID, the discord user id of the bot operator, who can use /botstats.
+    pub owner_id: Option<u64>,
+    // OPS_CHANNEL, a channel id to send operational warnings to, e.g. iRacing API changes.
+    pub ops_channel: Option<u64>,
+    // REGBOT_BACKUP_DIR, where to keep scheduled db backups, no backups if not set.
+    pub backup_dir: Option<String>,
+    // REGBOT_RECORD_DIR, where to keep a copy of every iRacing API response, off if not set.
+    pub record_dir: Option<String>,
+    // REGBOT_RECORD_MAX_MB, the oldest recordings are deleted once they take up more than this.
+    pub record_max_mb: u64,
+    // REGBOT_BACKUP_INTERVAL, hours between backups.
+    pub backup_interval_hours: u64,
+    // REGBOT_BACKUP_KEEP, how many backups to keep.
+    pub backup_keep: usize,
+    // REGBOT_MAX_CHANNEL_WATCHES, the most watches a channel can have.
+    pub max_channel_watches: i64,
+    // REGBOT_MAX_GUILD_WATCHES, the most watches a server can have.
+    pub max_guild_watches: i64,
+    // REGBOT_MAX_SEND_FAILURES, a channel's watches are disabled after this many
+    // announcements in a row are refused for lack of permissions.
+    pub max_send_failures: i64,
+    // REGBOT_GUILD_GRACE_DAYS, how long to keep everything for a server the bot was
+    // removed from, in case it's invited back.
+    pub guild_grace_days: i64,
+    // servers that the watch limits don't apply to.
+    pub unlimited_guilds: Vec<u64>,
+    // series_ids that can't be watched and are never announced, e.g. test series.
+    pub blocked_series: Vec<i64>,
+    // hosts that /webhook can POST JSON announcements to, discord's own webhooks are
+    // always allowed.
+    pub webhook_hosts: Vec<String>,
+    // on/off switches for optional features, e.g. watchdog = false
+    pub features: HashMap<String, bool>,
+    // named combinations of /watch settings, offered as the preset option.
+    pub presets: BTreeMap<String, Preset>,
+    // TELEGRAM_TOKEN, the telegram bot token, needed to send to the telegram chats.
+    pub telegram_token: Option<String>,
+    // telegram chats to send announcements to, for communities not on discord.
+    pub telegram: Vec<TelegramChat>,
+}
+
+// TelegramChat is a telegram chat and the series it wants announcements for, there's
+// no slash commands on telegram so its watches are set up here.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TelegramChat {
+    pub chat_id: String,
+    pub series: Vec<i64>,
+    // one of the presets, for the watch settings.
+    pub preset: Option<String>,
+    // the language to announce in, one of the /language codes, e.g. de.
+    pub locale: Option<String>,
+}
+
+impl TelegramChat {
+    pub fn locale(&self) -> Locale {
+        self.locale
+            .as_deref()
+            .and_then(|l| l.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+// Preset is a named set of /watch options, anything not set by the preset (or
+// given explicitly to /watch) gets the usual default.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Preset {
+    pub min_reg: Option<i64>,
+    pub max_reg: Option<i64>,
+    pub open: Option<bool>,
+    pub close: Option<bool>,
+    pub poll: Option<bool>,
+    pub hold_mins: Option<i64>,
+    pub qualifying: Option<bool>,
+    pub reopen_mins: Option<i64>,
+    pub last_day: Option<bool>,
+    pub first_open: Option<bool>,
+    pub cooldown_mins: Option<i64>,
+    pub week_change: Option<bool>,
+    pub predict: Option<bool>,
+    pub results: Option<bool>,
+}
+
+impl Preset {
+    // sets the options the preset has on the watch, anything it doesn't have is left as
+    // it is. If the preset doesn't have a max the watch's max is raised to at least its
+    // min, as a min can be over a quiet series' usual max.
+    pub fn apply(&self, reg: &mut Reg) {
+        fn set<T: Copy>(field: &mut T, value: Option<T>) {
+            if let Some(v) = value {
+                *field = v;
+            }
+        }
+        set(&mut reg.min_reg, self.min_reg);
+        set(&mut reg.max_reg, self.max_reg);
+        set(&mut reg.open, self.open);
+        set(&mut reg.close, self.close);
+        set(&mut reg.poll, self.poll);
+        set(&mut reg.hold_mins, self.hold_mins);
+        set(&mut reg.qualifying, self.qualifying);
+        set(&mut reg.reopen_mins, self.reopen_mins);
+        set(&mut reg.last_day, self.last_day);
+        set(&mut reg.first_open, self.first_open);
+        set(&mut reg.cooldown_mins, self.cooldown_mins);
+        set(&mut reg.week_change, self.week_change);
+        set(&mut reg.predict, self.predict);
+        set(&mut reg.results, self.results);
+        if self.max_reg.is_none() {
+            reg.max_reg = reg.max_reg.max(reg.min_reg);
+        }
+    }
+}
+
+// WatchLimits caps how many watches a channel and a server can have, each watch
+// is checked against every announcement so a server can't have an unlimited number.
+#[derive(Debug, Clone)]
+pub struct WatchLimits {
+    pub per_channel: i64,
+    pub per_guild: i64,
+    pub unlimited_guilds: Vec<u64>,
+}
+
+impl WatchLimits {
+    // checks if there's room for another watch given how many the channel and
+    // server already have, returns the reason why not if there isn't.
+    pub fn check(
+        &self,
+        guild: Option<u64>,
+        channel_count: i64,
+        guild_count: i64,
+    ) -> Result<(), String> {
+        if guild.is_some_and(|g| self.unlimited_guilds.contains(&g)) {
+            return Ok(());
+        }
+        if channel_count >= self.per_channel {
+            return Err(format!(
+                "This channel is already watching {} series, which is as many as I can keep track of. Use /nomore to remove one first.",
+                channel_count
+            ));
+        }
+        if guild.is_some() && guild_count >= self.per_guild {
+            return Err(format!(
+                "This server is already watching {} series, which is as many as I can keep track of. Use /nomore to remove one first.",
+                guild_count
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn default_presets() -> BTreeMap<String, Preset> {
+    let mut p = BTreeMap::new();
+    p.insert("default".to_string(), Preset::default());
+    p.insert(
+        "quiet".to_string(),
+        Preset {
+            close: Some(true),
+            hold_mins: Some(15),
+            ..Preset::default()
+        },
+    );
+    p.insert(
+        "everything".to_string(),
+        Preset {
+            min_reg: Some(0),
+            max_reg: Some(1000),
+            open: Some(true),
+            close: Some(true),
+            qualifying: Some(true),
+            last_day: Some(true),
+            week_change: Some(true),
+            ..Preset::default()
+        },
+    );
+    p
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            discord_token: String::new(),
+            ir_user: String::new(),
+            ir_password: String::new(),
+            db_path: "regbot.db".to_string(),
+            poll_interval_secs: 61,
+            poll_fast_secs: 20,
+            poll_quiet_secs: 180,
+            quiet_start_hour: 4,
+            quiet_end_hour: 9,
+            max_message_len: 1950,
+            max_channel_messages: 5,
+            shards: 1,
+            health_addr: None,
+            api_addr: None,
+            api_token: None,
+            dashboard_token: None,
+            ir_fixtures: None,
+            global_commands: false,
+            owner_id: None,
+            ops_channel: None,
+            backup_dir: None,
+            record_dir: None,
+            record_max_mb: 1024,
+            backup_interval_hours: 24,
+            backup_keep: 7,
+            max_channel_watches: 25,
+            max_guild_watches: 100,
+            max_send_failures: 5,
+            guild_grace_days: 7,
+            unlimited_guilds: Vec::new(),
+            blocked_series: Vec::new(),
+            webhook_hosts: Vec::new(),
+            features: HashMap::new(),
+            presets: default_presets(),
+            telegram_token: None,
+            telegram: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    // load reads the config file, if there is one, applies any environment variable
+    // overrides and checks that everything needed is set.
+    pub fn load() -> anyhow::Result<Config> {
+        let file = env::var("REGBOT_CONFIG").unwrap_or_else(|_| "regbot.toml".to_string());
+        let mut cfg = if Path::new(&file).exists() {
+            let txt = std::fs::read_to_string(&file)?;
+            toml::from_str(&txt).map_err(|e| anyhow!("unable to parse {}: {}", file, e))?
+        } else {
+            Config::default()
+        };
+        override_from_env(&mut cfg.discord_token, "DISCORD_TOKEN")?;
+        override_from_env(&mut cfg.ir_user, "IRUSER")?;
+        override_from_env(&mut cfg.ir_password, "IRPWD")?;
+        override_from_env(&mut cfg.db_path, "REGBOT_DB")?;
+        override_from_env(&mut cfg.poll_interval_secs, "REGBOT_POLL_INTERVAL")?;
+        override_from_env(&mut cfg.poll_fast_secs, "REGBOT_POLL_FAST")?;
+        override_from_env(&mut cfg.poll_quiet_secs, "REGBOT_POLL_QUIET")?;
+        override_from_env(&mut cfg.quiet_start_hour, "REGBOT_QUIET_START")?;
+        override_from_env(&mut cfg.quiet_end_hour, "REGBOT_QUIET_END")?;
+        override_from_env(&mut cfg.max_message_len, "REGBOT_MAX_MESSAGE_LEN")?;
+        override_from_env(&mut cfg.max_channel_messages, "REGBOT_MAX_CHANNEL_MESSAGES")?;
+        override_from_env(&mut cfg.shards, "REGBOT_SHARDS")?;
+        override_from_env(&mut cfg.global_commands, "REGBOT_GLOBAL_COMMANDS")?;
+        override_from_env(&mut cfg.backup_interval_hours, "REGBOT_BACKUP_INTERVAL")?;
+        override_from_env(&mut cfg.backup_keep, "REGBOT_BACKUP_KEEP")?;
+        override_from_env(&mut cfg.record_max_mb, "REGBOT_RECORD_MAX_MB")?;
+        override_from_env(&mut cfg.max_channel_watches, "REGBOT_MAX_CHANNEL_WATCHES")?;
+        override_from_env(&mut cfg.max_guild_watches, "REGBOT_MAX_GUILD_WATCHES")?;
+        override_from_env(&mut cfg.max_send_failures, "REGBOT_MAX_SEND_FAILURES")?;
+        override_from_env(&mut cfg.guild_grace_days, "REGBOT_GUILD_GRACE_DAYS")?;
+        if let Ok(dir) = env::var("REGBOT_BACKUP_DIR") {
+            cfg.backup_dir = Some(dir);
+        }
+        if let Ok(dir) = env::var("REGBOT_RECORD_DIR") {
+            cfg.record_dir = Some(dir);
+        }
+        if let Ok(dir) = env::var("REGBOT_IR_FIXTURES") {
+            cfg.ir_fixtures = Some(dir);
+        }
+        if let Ok(id) = env::var("OWNER_ID") {
+            cfg.owner_id = Some(
+                id.parse()
+                    .map_err(|e| anyhow!("invalid value for OWNER_ID: {}", e))?,
+            );
+        }
+        if let Ok(ch) = env::var("OPS_CHANNEL") {
+            cfg.ops_channel = Some(
+                ch.parse()
+                    .map_err(|e| anyhow!("invalid value for OPS_CHANNEL: {}", e))?,
+            );
+        }
+        if let Ok(addr) = env::var("HEALTH_ADDR") {
+            cfg.health_addr = Some(addr);
+        }
+        if let Ok(addr) = env::var("API_ADDR") {
+            cfg.api_addr = Some(addr);
+        }
+        if let Ok(token) = env::var("API_TOKEN") {
+            cfg.api_token = Some(token);
+        }
+        if let Ok(token) = env::var("DASHBOARD_TOKEN") {
+            cfg.dashboard_token = Some(token);
+        }
+        if let Ok(token) = env::var("TELEGRAM_TOKEN") {
+            cfg.telegram_token = Some(token);
+        }
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.discord_token.is_empty() {
+            return Err(anyhow!(
+                "Expected a discord token in the config or environment"
+            ));
+        }
+        if self.ir_fixtures.is_none() && (self.ir_user.is_empty() || self.ir_password.is_empty()) {
+            return Err(anyhow!(
+                "Expected an iRacing username & password in the config or environment"
+            ));
+        }
+        if self.poll_interval_secs == 0 || self.poll_fast_secs == 0 || self.poll_quiet_secs == 0 {
+            return Err(anyhow!(
+                "poll_interval_secs, poll_fast_secs and poll_quiet_secs should be more than 0"
+            ));
+        }
+        if self.quiet_start_hour > 23 || self.quiet_end_hour > 23 {
+            return Err(anyhow!(
+                "quiet_start_hour and quiet_end_hour should be 0 to 23"
+            ));
+        }
+        // discord won't take messages over 2000 chars.
+        if !(100..=2000).contains(&self.max_message_len) {
+            return Err(anyhow!("max_message_len should be between 100 and 2000"));
+        }
+        if self.max_channel_messages < 1 {
+            return Err(anyhow!("max_channel_messages should be more than 0"));
+        }
+        if self.max_channel_watches < 1 || self.max_guild_watches < 1 {
+            return Err(anyhow!(
+                "max_channel_watches and max_guild_watches should be more than 0"
+            ));
+        }
+        if self.record_dir.is_some() && self.record_max_mb == 0 {
+            return Err(anyhow!("record_max_mb should be more than 0"));
+        }
+        if self.guild_grace_days < 0 {
+            return Err(anyhow!("guild_grace_days can't be negative"));
+        }
+        if self.max_send_failures < 1 {
+            return Err(anyhow!("max_send_failures should be more than 0"));
+        }
+        if self.backup_interval_hours == 0 {
+            return Err(anyhow!("backup_interval_hours should be more than 0"));
+        }
+        // discord allows upto 25 choices for an option.
+        if self.presets.len() > 25 {
+            return Err(anyhow!("there can be at most 25 presets"));
+        }
+        for (name, p) in &self.presets {
+            if name.is_empty() || name.len() > 100 {
+                return Err(anyhow!("preset name '{}' should be 1-100 characters", name));
+            }
+            if p.cooldown_mins.is_some_and(|c| !(0..=120).contains(&c)) {
+                return Err(anyhow!(
+                    "preset {} cooldown_mins should be between 0 and 120",
+                    name
+                ));
+            }
+            if p.reopen_mins.is_some_and(|r| !(0..=120).contains(&r)) {
+                return Err(anyhow!(
+                    "preset {} reopen_mins should be between 0 and 120",
+                    name
+                ));
+            }
+            if p.hold_mins.is_some_and(|h| !(0..=120).contains(&h)) {
+                return Err(anyhow!(
+                    "preset {} hold_mins should be between 0 and 120",
+                    name
+                ));
+            }
+        }
+        if self.api_addr.is_some() && self.api_token.as_ref().is_none_or(|t| t.len() < 16) {
+            return Err(anyhow!(
+                "Expected an api_token of at least 16 characters for the API"
+            ));
+        }
+        if let Some(t) = &self.dashboard_token {
+            if t.len() < 16 || self.api_token.as_ref() == Some(t) {
+                return Err(anyhow!(
+                    "Expected a dashboard_token of at least 16 characters that's not the api_token"
+                ));
+            }
+        }
+        if !self.telegram.is_empty() && self.telegram_token.is_none() {
+            return Err(anyhow!(
+                "Expected a telegram token in the config or environment for the telegram chats"
+            ));
+        }
+        for chat in &self.telegram {
+            if chat.chat_id.is_empty() {
+                return Err(anyhow!("telegram chats need a chat_id"));
+            }
+            if let Some(p) = &chat.preset {
+                if !self.presets.contains_key(p) {
+                    return Err(anyhow!(
+                        "telegram chat {} has an unknown preset {}",
+                        chat.chat_id,
+                        p
+                    ));
+                }
+            }
+            if let Some(l) = &chat.locale {
+                Locale::from_str(l)
+                    .map_err(|e| anyhow!("telegram chat {} has {}", chat.chat_id, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn watch_limits(&self) -> WatchLimits {
+        WatchLimits {
+            per_channel: self.max_channel_watches,
+            per_guild: self.max_guild_watches,
+            unlimited_guilds: self.unlimited_guilds.clone(),
+        }
+    }
+
+    // returns true if the named feature is enabled, features not mentioned in the config
+    // get the supplied default.
+    pub fn feature(&self, name: &str, default: bool) -> bool {
+        *self.features.get(name).unwrap_or(&default)
+    }
+}
+
+fn override_from_env<T: FromStr>(v: &mut T, name: &str) -> anyhow::Result<()>
+where
+    T::Err: std::fmt::Display,
+{
+    if let Ok(val) = env::var(name) {
+        *v = val
+            .parse()
+            .map_err(|e| anyhow!("invalid value for {}: {}", name, e))?;
+    }
+    Ok(())
+}
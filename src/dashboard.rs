@@ -0,0 +1,230 @@
+use axum::extract::{Form, Path};
+use axum::http::header::{COOKIE, SET_COOKIE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::{Extension, Router};
+use serde::Deserialize;
+use serenity::model::prelude::GuildId;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::api::{db_error, ApiError, ApiState};
+
+const COOKIE_NAME: &str = "regbot_dashboard";
+
+// the read only dashboard, you log in with the dashboard token from a form, which sets
+// a cookie for the rest of the pages. It's never in the URL, so it doesn't end up in
+// browser history or proxy logs.
+pub fn routes() -> Router {
+    Router::new()
+        .route("/dashboard", get(overview))
+        .route("/dashboard/guilds/:guild_id", get(guild))
+        .route("/dashboard/login", get(login_form).post(login))
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    #[serde(default)]
+    token: String,
+}
+
+fn logged_in(api: &ApiState, headers: &HeaderMap) -> bool {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .any(|(name, value)| name == COOKIE_NAME && api.check_dashboard_cookie(value).is_ok())
+}
+
+fn to_login() -> Response {
+    Redirect::to("/dashboard/login").into_response()
+}
+
+// GET /dashboard/login
+async fn login_form() -> Html<String> {
+    login_page("")
+}
+
+// POST /dashboard/login, sets the cookie if the token is right.
+async fn login(Extension(api): Extension<ApiState>, Form(f): Form<LoginForm>) -> Response {
+    if api.check_dashboard_token(&f.token).is_err() {
+        return (StatusCode::UNAUTHORIZED, login_page("<p>Wrong token.</p>")).into_response();
+    }
+    let cookie = format!(
+        "{}={}; Path=/dashboard; HttpOnly; SameSite=Strict; Max-Age=2592000",
+        COOKIE_NAME,
+        api.dashboard_cookie()
+    );
+    ([(SET_COOKIE, cookie)], Redirect::to("/dashboard")).into_response()
+}
+
+fn login_page(msg: &str) -> Html<String> {
+    page(
+        "regbot",
+        &format!(
+            "{}<form method=\"post\" action=\"/dashboard/login\">\
+             <input type=\"password\" name=\"token\" placeholder=\"Dashboard token\">\
+             <button type=\"submit\">Log in</button></form>",
+            msg
+        ),
+    )
+}
+
+// GET /dashboard, each current series with its next session, and the servers that have watches.
+async fn overview(
+    Extension(api): Extension<ApiState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    if !logged_in(&api, &headers) {
+        return Ok(to_login());
+    }
+    let mut rows = Vec::new();
+    {
+        let guide = api.state.guide();
+        for s in api.state.seasons().values() {
+            let next = guide
+                .iter()
+                .filter(|e| e.series_id == s.series_id)
+                .min_by_key(|e| e.start_time);
+            let (when, count, status) = match next {
+                None => ("-".to_string(), "-".to_string(), "-".to_string()),
+                Some(e) => {
+                    let status = if e.entry_count < s.reg_official {
+                        "not official".to_string()
+                    } else if e.entry_count <= s.reg_split {
+                        "official".to_string()
+                    } else {
+                        format!("{} splits", 1 + (e.entry_count - 1) / s.reg_split.max(1))
+                    };
+                    (
+                        e.start_time.format("%a %H:%M UTC").to_string(),
+                        e.entry_count.to_string(),
+                        status,
+                    )
+                }
+            };
+            rows.push((s.name.clone(), s.track_name.clone(), when, count, status));
+        }
+    }
+    rows.sort();
+    let regs = api.db().call(|db| db.regs()).await.map_err(db_error)?;
+    let mut guilds: HashMap<u64, usize> = HashMap::new();
+    for r in regs.values().flatten() {
+        if let Some(g) = r.guild {
+            *guilds.entry(g.0).or_default() += 1;
+        }
+    }
+    let mut guilds: Vec<_> = guilds.into_iter().collect();
+    guilds.sort();
+
+    let mut body = String::from(
+        "<h2>Series</h2><table><tr><th>Series</th><th>Track</th><th>Next session</th>\
+         <th>Registered</th><th>Splits</th></tr>",
+    );
+    for (name, track, when, count, status) in rows {
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            esc(&name),
+            esc(&track),
+            when,
+            count,
+            status
+        );
+    }
+    body.push_str("</table><h2>Servers</h2><table><tr><th>Server</th><th>Watches</th></tr>");
+    for (g, n) in guilds {
+        let _ = write!(
+            body,
+            "<tr><td><a href=\"/dashboard/guilds/{}\">{}</a></td><td>{}</td></tr>",
+            g, g, n
+        );
+    }
+    body.push_str("</table>");
+    Ok(page("regbot", &body).into_response())
+}
+
+// GET /dashboard/guilds/:guild_id, the server's watches.
+async fn guild(
+    Extension(api): Extension<ApiState>,
+    Path(guild_id): Path<u64>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    if !logged_in(&api, &headers) {
+        return Ok(to_login());
+    }
+    let mut regs = api
+        .db()
+        .call(move |db| db.guild_regs(GuildId(guild_id)))
+        .await
+        .map_err(db_error)?;
+    regs.sort_by(|a, b| (a.channel, &a.series_name).cmp(&(b.channel, &b.series_name)));
+    let mut body = String::from(
+        "<p><a href=\"/dashboard\">All series</a></p><table><tr><th>Channel</th>\
+         <th>Series</th><th>Min</th><th>Max</th><th>Open</th><th>Close</th><th>Notes</th></tr>",
+    );
+    for r in regs {
+        let mut notes = Vec::new();
+        for (flag, note) in [
+            (r.muted, "muted"),
+            (r.snoozed, "snoozed"),
+            (r.blocked, "blocked"),
+            (r.inactive, "inactive"),
+            (r.disabled, "disabled"),
+        ] {
+            if flag {
+                notes.push(note);
+            }
+        }
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            r.channel.0,
+            esc(&r.series_name),
+            r.min_reg,
+            r.max_reg,
+            yes_no(r.open),
+            yes_no(r.close),
+            notes.join(", ")
+        );
+    }
+    body.push_str("</table>");
+    Ok(page(&format!("regbot server {}", guild_id), &body).into_response())
+}
+
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{t}</title>\
+         <style>body{{font-family:sans-serif}} td,th{{padding:2px 8px;text-align:left}}</style>\
+         </head><body><h1>{t}</h1>{b}</body></html>",
+        t = esc(title),
+        b = body
+    ))
+}
+
+fn yes_no(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+// series & track names come from iRacing, so escape them before they go in the page.
+fn esc(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
@@ -1,9 +1,22 @@
-use crate::ir::{Season, Series};
-use crate::ir_watcher::{Announcement, AnnouncementType};
-use rusqlite::{params, Connection, Row, Transaction};
-use serenity::model::prelude::{ChannelId, GuildId};
-use std::collections::HashMap;
+use crate::announce_rules;
+use crate::config::Preset;
+use crate::error::BotError;
+use crate::forecast::{sample_mins, Sample};
+use crate::i18n::Locale;
+use crate::ir::{RaceGuideEntry, Season, SeasonBasic, Series};
+use crate::ir_watcher::Announcement;
+use crate::style::{escape_markdown, Layout, Style};
+use crate::webhook::{WebhookFormat, WebhookTarget};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, params_from_iter, Connection, Params, Row, ToSql, Transaction};
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::{ChannelId, GuildId, MessageId, UserId};
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{mpsc, oneshot};
 
 #[derive(Debug, Clone)]
 pub struct SeasonInfo {
@@ -15,11 +28,29 @@ pub struct SeasonInfo {
     pub track_name: String,
     pub track_config: String,
     pub track_cat: Option<String>,
+    pub short_name: String,
+    // when the current race week ends.
+    pub week_end: Option<DateTime<Utc>>,
+    // the iRacing season the series is running, 0 if we don't know.
+    pub season_id: i64,
+    pub season_year: i64,
+    pub season_quarter: i64,
+    // the license class needed to race, 0 if we don't know, see LICENSES.
+    pub license_group: i64,
+    // true if everyone races the same car setup.
+    pub fixed_setup: bool,
+    // the car class & car names, lowercase for searching.
+    pub cars: String,
 
     pub lc_name: String,
 }
 impl SeasonInfo {
-    pub fn new(series: &Series, _season: &Season) -> Self {
+    pub fn new(
+        series: &Series,
+        _season: &Season,
+        basic: Option<&SeasonBasic>,
+        cars: String,
+    ) -> Self {
         let n = &series.series_name;
         println!("{} race week {}", series.series_name, _season.race_week);
         let sc = &_season.schedules[_season.race_week as usize];
@@ -30,16 +61,35 @@ impl SeasonInfo {
             reg_split: series.max_starters,
             week: _season.race_week,
             track_name: sc.track.track_name.clone(),
-            track_config: sc
-                .track
-                .config_name
-                .as_ref()
-                .map(|c| c.clone())
-                .unwrap_or_default(),
+            track_config: sc.track.config_name.clone().unwrap_or_default(),
             track_cat: sc.track.category.clone(),
+            short_name: if series.series_short_name.is_empty() {
+                n.to_string()
+            } else {
+                series.series_short_name.clone()
+            },
+            // race weeks are a week long from the start of the season.
+            week_end: Some(_season.start_date + Duration::weeks(_season.race_week + 1)),
+            season_id: _season.season_id,
+            season_year: _season.season_year,
+            season_quarter: _season.season_quarter,
+            license_group: basic.map_or(0, |b| b.license_group),
+            fixed_setup: basic.is_some_and(|b| b.fixed_setup),
+            cars,
             lc_name: n.to_lowercase(),
         }
     }
+    // e.g. 2026 S3
+    pub fn season_label(&self) -> String {
+        format!("{} S{}", self.season_year, self.season_quarter)
+    }
+    // true if now is in the last day of the race week.
+    pub fn is_last_day(&self, now: DateTime<Utc>) -> bool {
+        match self.week_end {
+            Some(end) => now < end && end - now <= Duration::days(1),
+            None => false,
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -53,21 +103,126 @@ pub struct Reg {
     pub max_reg: i64,
     pub open: bool,
     pub close: bool,
+    // true while snoozed until the series moves to the next race week.
+    pub snoozed: bool,
+    // true if the channel has muted the series track category, or only wants other license classes.
+    pub muted: bool,
+    // true if the series isn't in the current season data, so won't get any announcements.
+    pub inactive: bool,
+    // true if the bot kept being refused when posting to the channel, so stopped trying.
+    pub disabled: bool,
+    // true if the server has blocked the series.
+    pub blocked: bool,
+    // ask the channel who's racing when registration opens.
+    pub poll: bool,
+    // don't announce counts until this many minutes after registration opens.
+    pub hold_mins: i64,
+    // announce when a new race week opens up time trials & open qualifying.
+    pub qualifying: bool,
+    // skip the open announcement if registration closed less than this many minutes ago.
+    pub reopen_mins: i64,
+    // announce when the last day of the race week starts.
+    pub last_day: bool,
+    // only announce registration opening for the first race of each day.
+    pub first_open: bool,
+    // announce the new track when the series moves to a new race week.
+    pub week_change: bool,
+    // announce when the session looks like it'll go official, from how past sessions filled up.
+    pub predict: bool,
+    // post the winners & SOF once an official race has finished.
+    pub results: bool,
+    // don't announce counts within this many minutes of the last count announcement.
+    pub cooldown_mins: i64,
+    // only announce sessions starting at or after this many minutes past midnight,
+    // and before race_before, in the server's timezone. Equal means any time of day.
+    pub race_after: i64,
+    pub race_before: i64,
+    // only announce sessions on these days, bit 0 is Monday, 0 means every day.
+    pub race_days: i64,
+    // when a count was last announced for this watch.
+    pub last_count_at: Option<DateTime<Utc>>,
+    // announce more eagerly until this time, see boosted.
+    pub boost_until: Option<DateTime<Utc>>,
+    // the server's timezone as minutes from UTC, for working out what day it is.
+    pub utc_offset_mins: i64,
+    // the language the server wants announcements in.
+    pub locale: Locale,
+    // the name and id of who added the watch, if we know.
+    pub created_by: Option<String>,
+    pub created_by_id: Option<UserId>,
+    // true if the server only lets the creator, or members that can manage channels,
+    // remove or edit the watch.
+    pub restricted: bool,
 }
 impl Reg {
+    // a watch on the series in the channel with the usual entry range and nothing else
+    // turned on, use a Preset to set the rest.
+    pub fn new(guild: Option<GuildId>, channel: ChannelId, series: &SeasonInfo) -> Reg {
+        let (min_reg, max_reg) = announce_rules::default_reg_range(series);
+        Reg {
+            guild,
+            channel,
+            series_id: series.series_id,
+            series_name: series.name.clone(),
+            min_reg,
+            max_reg,
+            open: false,
+            close: false,
+            snoozed: false,
+            muted: false,
+            inactive: false,
+            disabled: false,
+            blocked: false,
+            poll: false,
+            hold_mins: 0,
+            qualifying: false,
+            reopen_mins: 0,
+            last_day: false,
+            first_open: false,
+            week_change: false,
+            predict: false,
+            results: false,
+            cooldown_mins: 0,
+            race_after: 0,
+            race_before: 0,
+            race_days: 0,
+            last_count_at: None,
+            boost_until: None,
+            utc_offset_mins: 0,
+            locale: Locale::default(),
+            created_by: None,
+            created_by_id: None,
+            restricted: false,
+        }
+    }
+    // true if other is for the same series with the same announcement settings.
+    pub fn same_settings(&self, other: &Reg) -> bool {
+        self.series_id == other.series_id
+            && self.min_reg == other.min_reg
+            && self.max_reg == other.max_reg
+            && self.open == other.open
+            && self.close == other.close
+            && self.poll == other.poll
+            && self.hold_mins == other.hold_mins
+            && self.qualifying == other.qualifying
+            && self.reopen_mins == other.reopen_mins
+            && self.last_day == other.last_day
+            && self.first_open == other.first_open
+            && self.week_change == other.week_change
+            && self.predict == other.predict
+            && self.results == other.results
+            && self.cooldown_mins == other.cooldown_mins
+            && self.race_after == other.race_after
+            && self.race_before == other.race_before
+            && self.race_days == other.race_days
+    }
+    pub fn is_boosted(&self) -> bool {
+        self.boost_until.is_some_and(|b| Utc::now() < b)
+    }
+    // true if the watch wants the announcement, see announce_rules::wants.
     pub fn wants(&self, ann: &Announcement) -> bool {
         assert_eq!(self.series_id, ann.curr.series_id);
-        match ann.ann_type {
-            AnnouncementType::Open => self.open,
-            AnnouncementType::Closed => self.close && ann.prev.entry_count >= self.min_reg,
-            // Also deal with the situation where the watch is configured for
-            // 3-5 entries and the reg count goes from 2 to 10
-            AnnouncementType::Count => {
-                (ann.curr.entry_count >= self.min_reg && ann.curr.entry_count <= self.max_reg)
-                    || (ann.prev.entry_count < self.min_reg && ann.curr.entry_count > self.max_reg)
-                    || ann.splits_changed()
-            }
-        }
+        announce_rules::wants(self, ann, Utc::now())
     }
 }
 impl Display for Reg {
@@ -75,14 +230,294 @@ impl Display for Reg {
         write!(
             f,
             "{} between {} and {} entries.",
-            self.series_name, self.min_reg, self.max_reg
+            escape_markdown(&self.series_name),
+            self.min_reg,
+            self.max_reg
         )?;
         f.write_str(match (self.open, self.close) {
             (true, true) => " I'll also say when registration opens and closes.",
             (true, false) => " I'll also say when registration opens.",
             (false, true) => " I'll also say when registration closes.",
             (false, false) => "",
-        })
+        })?;
+        if self.hold_mins > 0 {
+            write!(
+                f,
+                " I'll wait {} minutes after registration opens before mentioning any counts.",
+                self.hold_mins
+            )?;
+        }
+        if self.cooldown_mins > 0 {
+            write!(
+                f,
+                " I'll leave at least {} minutes between count announcements.",
+                self.cooldown_mins
+            )?;
+        }
+        if self.poll {
+            f.write_str(" I'll ask who's racing when registration opens.")?;
+        }
+        if self.qualifying {
+            f.write_str(" I'll say when time trials and open qualifying open for each race week.")?;
+        }
+        if self.week_change {
+            f.write_str(" I'll say which track it's at when each new race week starts.")?;
+        }
+        if self.predict {
+            f.write_str(" I'll say when it looks like a session will go official.")?;
+        }
+        if self.results {
+            f.write_str(" I'll post who won once an official race is over.")?;
+        }
+        if self.open && self.reopen_mins > 0 {
+            write!(
+                f,
+                " I won't mention registration opening if it's within {} minutes of the last one closing.",
+                self.reopen_mins
+            )?;
+        }
+        if self.open && self.first_open {
+            f.write_str(" I'll only mention registration opening for the first race of each day.")?;
+        }
+        if self.last_day {
+            f.write_str(" I'll say when it's the last day of each race week.")?;
+        }
+        if self.race_after != self.race_before || self.race_days != 0 {
+            f.write_str(" Only for races starting")?;
+            match (self.race_after, self.race_before) {
+                (a, b) if a == b => {}
+                (a, 0) => write!(f, " after {}", hhmm(a))?,
+                (0, b) => write!(f, " before {}", hhmm(b))?,
+                (a, b) => write!(f, " between {} and {}", hhmm(a), hhmm(b))?,
+            }
+            if self.race_days != 0 {
+                let days: Vec<&str> = DAY_NAMES
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| self.race_days & (1 << i) != 0)
+                    .map(|(_, d)| *d)
+                    .collect();
+                write!(f, " on {}", days.join(", "))?;
+            }
+            f.write_str(" (server time).")?;
+        }
+        if let (true, Some(until)) = (self.is_boosted(), self.boost_until) {
+            write!(f, " Boosted until <t:{}:f>.", until.timestamp())?;
+        }
+        if self.snoozed {
+            f.write_str(" Snoozed until the next race week.")?;
+        }
+        if self.muted {
+            f.write_str(" Muted, this channel has muted the series category or license class.")?;
+        }
+        if self.blocked {
+            f.write_str(" Blocked, this server has blocked the series.")?;
+        }
+        if self.disabled {
+            f.write_str(
+                " \u{26a0} Disabled, I wasn't allowed to post in this channel. Fix my permissions and use /watch again to turn it back on.",
+            )?;
+        }
+        if self.inactive {
+            f.write_str(
+                " \u{26a0} This series isn't running this season, so there's nothing to announce.",
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// the license classes, in license_group order starting from 1.
+pub const LICENSES: [&str; 5] = ["R", "D", "C", "B", "A"];
+
+// the days of the week for Reg::race_days, starting with Monday.
+pub const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+// formats minutes after midnight as e.g. 18:30
+fn hhmm(mins: i64) -> String {
+    format!("{:02}:{:02}", mins / 60, mins % 60)
+}
+
+// WatchDefaults are a channel's values for the /watch options that aren't given,
+// anything not set here falls back to the usual defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchDefaults {
+    pub min_reg: Option<i64>,
+    pub max_reg: Option<i64>,
+    pub open: Option<bool>,
+    pub close: Option<bool>,
+}
+impl WatchDefaults {
+    // the defaults as a preset, for applying to a watch before the /watch options.
+    pub fn preset(&self) -> Preset {
+        Preset {
+            min_reg: self.min_reg,
+            max_reg: self.max_reg,
+            open: self.open,
+            close: self.close,
+            ..Preset::default()
+        }
+    }
+}
+
+// ChannelSettings are the per channel options that apply to all the watches in the channel.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelSettings {
+    pub style: Style,
+    // how a poll's announcements are grouped into messages.
+    pub layout: Layout,
+    // keep a status message listing the state of every watch in the channel.
+    pub status: bool,
+    // the current status message, if there is one.
+    pub status_msg: Option<MessageId>,
+    // only announce series for these license classes, bit 0 is rookie, 0 means all of them.
+    pub licenses: i64,
+}
+
+// RegExport is the JSON form of a Reg, for exporting & importing watches.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegExport {
+    pub guild_id: Option<u64>,
+    pub channel_id: u64,
+    pub series_id: i64,
+    pub series_name: String,
+    pub min_reg: i64,
+    pub max_reg: i64,
+    pub open: bool,
+    pub close: bool,
+    #[serde(default)]
+    pub poll: bool,
+    #[serde(default)]
+    pub hold_mins: i64,
+    #[serde(default)]
+    pub qualifying: bool,
+    #[serde(default)]
+    pub reopen_mins: i64,
+    #[serde(default)]
+    pub last_day: bool,
+    #[serde(default)]
+    pub first_open: bool,
+    #[serde(default)]
+    pub cooldown_mins: i64,
+    #[serde(default)]
+    pub week_change: bool,
+    #[serde(default)]
+    pub predict: bool,
+    #[serde(default)]
+    pub results: bool,
+    #[serde(default)]
+    pub race_after: i64,
+    #[serde(default)]
+    pub race_before: i64,
+    #[serde(default)]
+    pub race_days: i64,
+}
+impl From<&Reg> for RegExport {
+    fn from(r: &Reg) -> Self {
+        RegExport {
+            guild_id: r.guild.map(|g| g.0),
+            channel_id: r.channel.0,
+            series_id: r.series_id,
+            series_name: r.series_name.clone(),
+            min_reg: r.min_reg,
+            max_reg: r.max_reg,
+            open: r.open,
+            close: r.close,
+            poll: r.poll,
+            hold_mins: r.hold_mins,
+            qualifying: r.qualifying,
+            reopen_mins: r.reopen_mins,
+            last_day: r.last_day,
+            first_open: r.first_open,
+            cooldown_mins: r.cooldown_mins,
+            week_change: r.week_change,
+            predict: r.predict,
+            results: r.results,
+            race_after: r.race_after,
+            race_before: r.race_before,
+            race_days: r.race_days,
+        }
+    }
+}
+
+impl RegExport {
+    // the Reg for this export in the supplied channel.
+    pub fn to_reg(&self, guild: Option<GuildId>, channel: ChannelId, series: &SeasonInfo) -> Reg {
+        let mut reg = Reg::new(guild, channel, series);
+        self.preset().apply(&mut reg);
+        reg.race_after = self.race_after;
+        reg.race_before = self.race_before;
+        reg.race_days = self.race_days;
+        reg
+    }
+    // the watch options in the export.
+    fn preset(&self) -> Preset {
+        Preset {
+            min_reg: Some(self.min_reg),
+            max_reg: Some(self.max_reg),
+            open: Some(self.open),
+            close: Some(self.close),
+            poll: Some(self.poll),
+            hold_mins: Some(self.hold_mins),
+            qualifying: Some(self.qualifying),
+            reopen_mins: Some(self.reopen_mins),
+            last_day: Some(self.last_day),
+            first_open: Some(self.first_open),
+            cooldown_mins: Some(self.cooldown_mins),
+            week_change: Some(self.week_change),
+            predict: Some(self.predict),
+            results: Some(self.results),
+        }
+    }
+}
+
+// ChannelExport is the JSON form of a channel's settings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChannelExport {
+    pub channel_id: u64,
+    pub style: String,
+    #[serde(default)]
+    pub layout: String,
+    #[serde(default)]
+    pub status: bool,
+    #[serde(default)]
+    pub mutes: Vec<String>,
+    #[serde(default)]
+    pub licenses: i64,
+    #[serde(default)]
+    pub defaults: Option<WatchDefaults>,
+}
+
+// GuildExport is everything configured for a server, for moving it to another server.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GuildExport {
+    pub watches: Vec<RegExport>,
+    #[serde(default)]
+    pub channels: Vec<ChannelExport>,
+    #[serde(default)]
+    pub utc_offset_mins: i64,
+    #[serde(default)]
+    pub blocked_series: Vec<i64>,
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+// ExportFile is what /import accepts, older exports were just the list of watches.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ExportFile {
+    Guild(GuildExport),
+    Watches(Vec<RegExport>),
+}
+impl From<ExportFile> for GuildExport {
+    fn from(f: ExportFile) -> Self {
+        match f {
+            ExportFile::Guild(g) => g,
+            ExportFile::Watches(watches) => GuildExport {
+                watches,
+                ..GuildExport::default()
+            },
+        }
     }
 }
 
@@ -91,8 +526,8 @@ pub struct SeriesUpdater<'a> {
 }
 impl<'a> SeriesUpdater<'a> {
     pub fn upsert(&mut self, s: &SeasonInfo) -> rusqlite::Result<usize> {
-        self.tx.execute("INSERT INTO series(series_id,active,name,reg_official,reg_split,week,track_name,track_config,track_cat)
-                VALUES (?,1,?,?,?,?,?,?,?) ON CONFLICT DO UPDATE SET
+        self.tx.execute("INSERT INTO series(series_id,active,name,reg_official,reg_split,week,track_name,track_config,track_cat,short_name,week_end,season_id,season_year,season_quarter,license_group,fixed_setup,cars)
+                VALUES (?,1,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?) ON CONFLICT DO UPDATE SET
                     name         = excluded.name,
                     active       = excluded.active,
                     reg_official = excluded.reg_official,
@@ -100,20 +535,315 @@ impl<'a> SeriesUpdater<'a> {
                     week         = excluded.week,
                     track_name   = excluded.track_name,
                     track_config = excluded.tracK_config,
-                    track_cat    = excluded.track_cat", 
-                params![s.series_id,s.name,s.reg_official,s.reg_split,s.week,s.track_name,s.track_config,s.track_cat])
+                    track_cat    = excluded.track_cat,
+                    short_name   = excluded.short_name,
+                    week_end     = excluded.week_end,
+                    season_id    = excluded.season_id,
+                    season_year  = excluded.season_year,
+                    season_quarter = excluded.season_quarter,
+                    license_group  = excluded.license_group,
+                    fixed_setup    = excluded.fixed_setup,
+                    cars           = excluded.cars", 
+                params![s.series_id,s.name,s.reg_official,s.reg_split,s.week,s.track_name,s.track_config,s.track_cat,s.short_name,s.week_end.map(|t| t.to_rfc3339()),s.season_id,s.season_year,s.season_quarter,s.license_group,s.fixed_setup,s.cars])
     }
     pub fn commit(self) -> rusqlite::Result<()> {
+        // any snoozes for series that have moved onto a new week are over.
+        self.tx.execute(
+            "UPDATE reg SET snooze_week=NULL WHERE snooze_week IS NOT NULL AND
+                snooze_week != (SELECT s.week FROM series s WHERE s.series_id=reg.series_id)",
+            [],
+        )?;
         self.tx.commit()
     }
 }
+// per command summary of the command_audit table.
+#[derive(Debug, Clone)]
+pub struct CommandStats {
+    pub command: String,
+    pub count: usize,
+    pub failed: usize,
+    pub p50_ms: i64,
+    pub p95_ms: i64,
+    pub max_ms: i64,
+}
+
+// the last race guide entry seen for a series, and when its registration opened.
+pub type WatchState = (RaceGuideEntry, Option<DateTime<Utc>>);
+
+const WATCH_STATE_SAVED: &str = "watch_state_saved";
+
+// tables that can be exported for offline analysis, and their timestamp column.
+pub const EXPORT_TABLES: &[(&str, &str)] = &[
+    ("command_audit", "at"),
+    ("series_request", "at"),
+    ("announce_log", "at"),
+];
+
+// inserts a new reg, with the values from insert_reg_params. upsert_reg & insert_regs
+// add their own ON CONFLICT to the end.
+const INSERT_REG: &str = "INSERT INTO reg(guild_id, channel_id, series_id, min_reg, max_reg, open, close, poll, hold_mins, qualifying, reopen_mins, last_day, first_open, cooldown_mins, week_change, predict, results, race_after, race_before, race_days, created_by, created_by_id, created_date)
+                VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,datetime('now'))";
+
+fn insert_reg_params(
+    reg: &Reg,
+    created_by: &str,
+    created_by_id: Option<UserId>,
+) -> Vec<Box<dyn ToSql>> {
+    vec![
+        Box::new(reg.guild.map(|g| g.0)),
+        Box::new(reg.channel.0),
+        Box::new(reg.series_id),
+        Box::new(reg.min_reg),
+        Box::new(reg.max_reg),
+        Box::new(reg.open),
+        Box::new(reg.close),
+        Box::new(reg.poll),
+        Box::new(reg.hold_mins),
+        Box::new(reg.qualifying),
+        Box::new(reg.reopen_mins),
+        Box::new(reg.last_day),
+        Box::new(reg.first_open),
+        Box::new(reg.cooldown_mins),
+        Box::new(reg.week_change),
+        Box::new(reg.predict),
+        Box::new(reg.results),
+        Box::new(reg.race_after),
+        Box::new(reg.race_before),
+        Box::new(reg.race_days),
+        Box::new(created_by.to_string()),
+        Box::new(created_by_id.map(|u| u.0)),
+    ]
+}
+
+// how long to keep command_audit rows for.
+const COMMAND_AUDIT_DAYS: i64 = 30;
+
+// how many times to try sending a message from the outbox before giving up.
+const OUTBOX_MAX_ATTEMPTS: i64 = 5;
+
+// a message that failed to send and is waiting in the outbox to be tried again.
+#[derive(Debug, Clone)]
+pub struct Outbound {
+    pub id: i64,
+    pub channel: ChannelId,
+    pub content: String,
+    pub attempts: i64,
+}
+
+// Reminder is a DM someone asked for before a session starts.
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    pub id: i64,
+    pub user: UserId,
+    pub series_name: String,
+    pub starts_at: DateTime<Utc>,
+    // the language of the server the reminder was asked for in.
+    pub locale: Locale,
+}
+
+// WeeklyPost is a channel's weekly schedule post, sent on weekday (0 is Monday) at
+// hour, in the server's timezone.
+#[derive(Debug, Clone)]
+pub struct WeeklyPost {
+    pub channel: ChannelId,
+    pub weekday: i64,
+    pub hour: i64,
+    pub last_sent: Option<DateTime<Utc>>,
+    pub utc_offset_mins: i64,
+}
+
+// DailyDigest is a channel's daily summary of the previous day's racing, sent at hour
+// in the server's timezone.
+#[derive(Debug, Clone)]
+pub struct DailyDigest {
+    pub channel: ChannelId,
+    pub hour: i64,
+    pub last_sent: Option<DateTime<Utc>>,
+    pub utc_offset_mins: i64,
+}
+
+// how long the entry counts for each session are kept.
+const SESSION_HISTORY_DAYS: i64 = 8;
+
+// AnnounceLog is an announcement that was delivered to a channel.
+#[derive(Debug, Clone)]
+pub struct AnnounceLog {
+    pub at: DateTime<Utc>,
+    pub guild: Option<GuildId>,
+    pub channel: ChannelId,
+    pub series_id: i64,
+    pub series_name: String,
+    pub ann_type: String,
+    pub entry_count: i64,
+    // the message it went out in, None if it went to a webhook.
+    pub message_id: Option<MessageId>,
+}
+
+// how long to keep announce_log rows for.
+const ANNOUNCE_LOG_DAYS: i64 = 14;
+// how long a removed watch hangs around for /undo before it's gone for good.
+pub const DELETED_REG_DAYS: i64 = 7;
+
+// outbox times are compared as text, so they need to always be in the same format.
+fn outbox_time(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
 pub struct Db {
     con: Connection,
 }
 
+// db jobs taking longer than this get logged, everything else waiting on the db is
+// stuck behind them.
+const SLOW_DB_JOB_MS: u128 = 500;
+
+type DbJob = Box<dyn FnOnce(&mut Db) + Send>;
+
+// the file name sqlite takes to mean a db that's only in memory.
+const MEMORY_DB: &str = ":memory:";
+// for naming each in memory DbHandle's db.
+static MEMORY_DBS: AtomicUsize = AtomicUsize::new(0);
+type DbReadJob = Box<dyn FnOnce(&Db) + Send>;
+
+// DbHandle runs calls to the Db on a dedicated thread, so that async code can await them
+// rather than blocking the runtime while sqlite does its thing. Reads that don't need
+// to see a write that's in progress can use read instead, which has its own thread and
+// connection, so they don't queue up behind the watcher's bigger transactions.
+#[derive(Clone)]
+pub struct DbHandle {
+    tx: mpsc::UnboundedSender<DbJob>,
+    read_tx: mpsc::UnboundedSender<DbReadJob>,
+}
+
+impl DbHandle {
+    pub fn open(file: &str) -> rusqlite::Result<DbHandle> {
+        // every ":memory:" connection gets its own empty db, so the writer & reader share
+        // a named one instead.
+        let shared_cache = file == MEMORY_DB;
+        let file = if shared_cache {
+            let n = MEMORY_DBS.fetch_add(1, Ordering::SeqCst);
+            format!("file:regbot-{}?mode=memory&cache=shared", n)
+        } else {
+            file.to_string()
+        };
+        let mut db = Db::new(&file)?;
+        let reader = Db::open_reader(&file, shared_cache)?;
+        let (tx, mut rx) = mpsc::unbounded_channel::<DbJob>();
+        std::thread::Builder::new()
+            .name("db".to_string())
+            .spawn(move || {
+                while let Some(job) = rx.blocking_recv() {
+                    let start = std::time::Instant::now();
+                    // a panic in one job shouldn't take the db away from everyone else.
+                    if catch_unwind(AssertUnwindSafe(|| job(&mut db))).is_err() {
+                        println!("db job panicked");
+                    }
+                    log_slow_job("db", start);
+                }
+            })
+            .expect("Unable to start db thread");
+        let (read_tx, mut read_rx) = mpsc::unbounded_channel::<DbReadJob>();
+        std::thread::Builder::new()
+            .name("db-read".to_string())
+            .spawn(move || {
+                while let Some(job) = read_rx.blocking_recv() {
+                    let start = std::time::Instant::now();
+                    if catch_unwind(AssertUnwindSafe(|| job(&reader))).is_err() {
+                        println!("db read job panicked");
+                    }
+                    log_slow_job("db read", start);
+                }
+            })
+            .expect("Unable to start db read thread");
+        Ok(DbHandle { tx, read_tx })
+    }
+    // like call, but on the read connection, which sees the last committed data.
+    pub async fn read<F, T, E>(&self, f: F) -> Result<T, BotError>
+    where
+        F: FnOnce(&Db) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: Into<BotError> + Send + 'static,
+    {
+        self.try_read(f).await?.map_err(Into::into)
+    }
+    // like read, for jobs that can't fail themselves, the error is only for when the db
+    // read thread can't run the job.
+    pub async fn try_read<F, R>(&self, f: F) -> Result<R, BotError>
+    where
+        F: FnOnce(&Db) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (res_tx, res_rx) = oneshot::channel();
+        self.read_tx
+            .send(Box::new(move |db| {
+                let _ = res_tx.send(f(db));
+            }))
+            .map_err(|_| BotError::DbUnavailable)?;
+        res_rx.await.map_err(|_| BotError::DbUnavailable)
+    }
+    // runs f with the db on the db thread and returns its result. A db error, or the
+    // job not running at all, comes back as a BotError for the caller to log and move on.
+    pub async fn call<F, T, E>(&self, f: F) -> Result<T, BotError>
+    where
+        F: FnOnce(&mut Db) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: Into<BotError> + Send + 'static,
+    {
+        self.try_call(f).await?.map_err(Into::into)
+    }
+    // like call, for jobs that can't fail themselves, the error is only for when the db
+    // thread can't run the job.
+    pub async fn try_call<F, R>(&self, f: F) -> Result<R, BotError>
+    where
+        F: FnOnce(&mut Db) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (res_tx, res_rx) = oneshot::channel();
+        self.tx
+            .send(Box::new(move |db| {
+                let _ = res_tx.send(f(db));
+            }))
+            .map_err(|_| BotError::DbUnavailable)?;
+        res_rx.await.map_err(|_| BotError::DbUnavailable)
+    }
+}
+
+fn log_slow_job(thread: &str, start: std::time::Instant) {
+    let ms = start.elapsed().as_millis();
+    if ms > SLOW_DB_JOB_MS {
+        println!("slow {} job took {}ms", thread, ms);
+    }
+}
+
 impl Db {
+    // a connection that can only read, for DbHandle::read. The schema's already been
+    // set up by the main connection.
+    fn open_reader(file: &str, shared_cache: bool) -> rusqlite::Result<Self> {
+        let con = Connection::open(file)?;
+        con.busy_timeout(std::time::Duration::from_secs(5))?;
+        con.pragma_update(None, "query_only", true)?;
+        // a shared cache db locks whole tables rather than using WAL, and a reader that
+        // runs into a write gets SQLITE_LOCKED straight away, which the busy timeout
+        // doesn't cover. Reading uncommitted data skips those locks, the in memory dbs
+        // are only for tests & trying things out, so seeing a write in progress is fine.
+        if shared_cache {
+            con.pragma_update(None, "read_uncommitted", true)?;
+        }
+        con.set_prepared_statement_cache_capacity(32);
+        Ok(Db { con })
+    }
     pub fn new(file: &str) -> rusqlite::Result<Self> {
         let con = Connection::open(file)?;
+        // WAL lets the command handlers read while the watcher is writing, and the
+        // busy timeout covers the remaining writer vs writer contention.
+        let mode: String = con.query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))?;
+        // in memory dbs don't have a journal file to be WAL.
+        if !mode.eq_ignore_ascii_case("wal") && !mode.eq_ignore_ascii_case("memory") {
+            println!("Unable to switch db to WAL mode, using {}", mode);
+        }
+        con.busy_timeout(std::time::Duration::from_secs(5))?;
+        con.pragma_update(None, "foreign_keys", true)?;
+        con.set_prepared_statement_cache_capacity(32);
         con.execute(
             "CREATE TABLE IF NOT EXISTS reg(
                                 guild_id    integer, 
@@ -147,25 +877,398 @@ impl Db {
                                 track_cat   text)",
             [],
         )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS channel_settings(
+                                channel_id  integer primary key,
+                                guild_id    integer,
+                                style       text not null default 'standard'
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS meta(
+                                key     text primary key,
+                                value   text not null
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS channel_mute(
+                                channel_id  integer not null,
+                                guild_id    integer,
+                                category    text not null,
+                                PRIMARY KEY(channel_id,category)
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS watch_defaults(
+                                channel_id  integer primary key,
+                                guild_id    integer,
+                                min_reg     integer,
+                                max_reg     integer,
+                                open        integer,
+                                close       integer
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS poll_vote(
+                                channel_id  integer not null,
+                                series_id   integer not null,
+                                session_id  integer not null,
+                                user_id     integer not null,
+                                racing      integer not null,
+                                PRIMARY KEY(channel_id,session_id,user_id)
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS command_audit(
+                                at          text not null,
+                                command     text not null,
+                                guild_id    integer,
+                                user_id     integer not null,
+                                duration_ms integer not null,
+                                outcome     text not null
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE INDEX IF NOT EXISTS idx_command_audit_at ON command_audit(at)",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS watch_state(
+                                series_id   integer primary key,
+                                entry       text not null,
+                                opened_at   text
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS outbox(
+                                id          integer primary key,
+                                channel_id  integer not null,
+                                content     text not null,
+                                attempts    integer not null default 0,
+                                next_at     text not null,
+                                expires_at  text not null
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS reminder(
+                                id          integer primary key,
+                                user_id     integer not null,
+                                guild_id    integer,
+                                series_id   integer not null,
+                                starts_at   text not null,
+                                remind_at   text not null,
+                                UNIQUE(user_id,series_id,starts_at)
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS event_watch(
+                                channel_id      integer not null,
+                                guild_id        integer,
+                                name            text not null,
+                                created_by_id   integer,
+                                PRIMARY KEY(channel_id,name)
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS league_watch(
+                                channel_id      integer not null,
+                                guild_id        integer,
+                                league_id       integer not null,
+                                created_by_id   integer,
+                                PRIMARY KEY(channel_id,league_id)
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS weekly_post(
+                                channel_id  integer primary key,
+                                guild_id    integer,
+                                weekday     integer not null,
+                                hour        integer not null,
+                                last_sent   text
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS daily_digest(
+                                channel_id  integer primary key,
+                                guild_id    integer,
+                                hour        integer not null,
+                                last_sent   text
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS session_history(
+                                series_id   integer not null,
+                                start_time  text not null,
+                                entry_count integer not null,
+                                PRIMARY KEY(series_id,start_time)
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS entry_sample(
+                                series_id   integer not null,
+                                start_time  text not null,
+                                mins_before integer not null,
+                                entry_count integer not null,
+                                PRIMARY KEY(series_id,start_time,mins_before)
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS announce_log(
+                                at          text not null,
+                                guild_id    integer,
+                                channel_id  integer not null,
+                                series_id   integer not null,
+                                series_name text not null,
+                                ann_type    text not null,
+                                entry_count integer not null,
+                                message_id  integer
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE INDEX IF NOT EXISTS idx_announce_log_channel ON announce_log(channel_id, at)",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS webhook_target(
+                                channel_id      integer primary key,
+                                guild_id        integer,
+                                url             text not null,
+                                format          text not null,
+                                created_by_id   integer
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS series_request(
+                                at          text not null,
+                                guild_id    integer,
+                                user_id     integer not null,
+                                query       text not null
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS series_block(
+                                guild_id    integer not null,
+                                series_id   integer not null,
+                                PRIMARY KEY(guild_id,series_id)
+                            )",
+            [],
+        )?;
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS guild_settings(
+                                guild_id        integer primary key,
+                                utc_offset_mins integer not null default 0
+                            )",
+            [],
+        )?;
+        // columns added since the tables were first created.
+        add_column(&con, "reg", "snooze_week", "integer")?;
+        add_column(&con, "reg", "poll", "integer not null default 0")?;
+        add_column(&con, "reg", "hold_mins", "integer not null default 0")?;
+        add_column(&con, "reg", "qualifying", "integer not null default 0")?;
+        add_column(&con, "reg", "reopen_mins", "integer not null default 0")?;
+        add_column(&con, "reg", "last_day", "integer not null default 0")?;
+        add_column(&con, "reg", "created_by_id", "integer")?;
+        add_column(&con, "reg", "disabled", "integer not null default 0")?;
+        add_column(&con, "reg", "first_open", "integer not null default 0")?;
+        add_column(&con, "reg", "cooldown_mins", "integer not null default 0")?;
+        add_column(&con, "reg", "last_count_at", "text")?;
+        add_column(&con, "reg", "boost_until", "text")?;
+        add_column(&con, "reg", "week_change", "integer not null default 0")?;
+        add_column(&con, "reg", "predict", "integer not null default 0")?;
+        add_column(&con, "reg", "results", "integer not null default 0")?;
+        add_column(&con, "reg", "race_after", "integer not null default 0")?;
+        add_column(&con, "reg", "race_before", "integer not null default 0")?;
+        add_column(&con, "reg", "race_days", "integer not null default 0")?;
+        add_column(&con, "guild_settings", "locale", "text")?;
+        add_column(&con, "guild_settings", "removed_at", "text")?;
+        add_column(&con, "reg", "deleted_at", "text")?;
+        add_column(
+            &con,
+            "guild_settings",
+            "restrict_watches",
+            "integer not null default 0",
+        )?;
+        add_column(
+            &con,
+            "guild_settings",
+            "private_replies",
+            "integer not null default 0",
+        )?;
+        add_column(&con, "series", "short_name", "text")?;
+        add_column(&con, "series", "week_end", "text")?;
+        add_column(&con, "series", "season_id", "integer not null default 0")?;
+        add_column(&con, "series", "season_year", "integer not null default 0")?;
+        add_column(
+            &con,
+            "series",
+            "season_quarter",
+            "integer not null default 0",
+        )?;
+        add_column(
+            &con,
+            "series",
+            "license_group",
+            "integer not null default 0",
+        )?;
+        add_column(&con, "series", "fixed_setup", "integer not null default 0")?;
+        add_column(&con, "series", "cars", "text")?;
+        add_column(
+            &con,
+            "channel_settings",
+            "licenses",
+            "integer not null default 0",
+        )?;
+        add_column(
+            &con,
+            "channel_settings",
+            "status",
+            "integer not null default 0",
+        )?;
+        add_column(&con, "channel_settings", "status_msg", "integer")?;
+        add_column(
+            &con,
+            "channel_settings",
+            "layout",
+            "text not null default 'lines'",
+        )?;
+        add_column(
+            &con,
+            "channel_settings",
+            "send_failures",
+            "integer not null default 0",
+        )?;
         Ok(Db { con })
     }
-    pub fn start_series_update(&mut self) -> rusqlite::Result<SeriesUpdater> {
+    pub fn get_meta(&self, key: &str) -> rusqlite::Result<Option<String>> {
+        let mut stmt = self.con.prepare("SELECT value FROM meta WHERE key=?")?;
+        let mut rows = stmt.query([key])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+    pub fn set_meta(&self, key: &str, value: &str) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO meta(key,value) VALUES(?,?) ON CONFLICT DO UPDATE SET value=excluded.value",
+            [key, value],
+        )
+    }
+    // saves the watcher state for the series that have changed, and notes when the
+    // state was last brought up to date.
+    pub fn save_watch_state(&mut self, changed: &[(i64, WatchState)]) -> rusqlite::Result<()> {
         let tx = self.con.transaction()?;
-        tx.execute("UPDATE series SET active=0", [])?;
-        Ok(SeriesUpdater { tx })
+        for (series_id, (entry, opened_at)) in changed {
+            let entry = serde_json::to_string(entry).expect("RaceGuideEntry is serializable");
+            tx.execute(
+                "INSERT INTO watch_state(series_id, entry, opened_at) VALUES(?,?,?)
+                    ON CONFLICT DO UPDATE SET entry=excluded.entry, opened_at=excluded.opened_at",
+                params![series_id, entry, opened_at.map(|t| t.to_rfc3339())],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO meta(key,value) VALUES(?,?) ON CONFLICT DO UPDATE SET value=excluded.value",
+            params![WATCH_STATE_SAVED, Utc::now().to_rfc3339()],
+        )?;
+        tx.commit()
     }
-    pub fn get_series(&self) -> rusqlite::Result<HashMap<i64, SeasonInfo>> {
-        let mut stmt = self.con.prepare("SELECT * FROM series WHERE active=1;")?;
+    // returns the saved watcher state, or nothing if it wasn't saved within max_age, as
+    // comparing against old state would generate bogus announcements.
+    pub fn watch_state(&self, max_age: Duration) -> rusqlite::Result<HashMap<i64, WatchState>> {
+        let mut res = HashMap::new();
+        let saved = self
+            .get_meta(WATCH_STATE_SAVED)?
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok());
+        match saved {
+            Some(t) if Utc::now() - t.with_timezone(&Utc) < max_age => {}
+            _ => return Ok(res),
+        }
+        let mut stmt = self
+            .con
+            .prepare("SELECT series_id, entry, opened_at FROM watch_state")?;
         let rows = stmt.query_map([], |row| {
-            Ok(SeasonInfo {
-                series_id: row.get("series_id")?,
-                name: row.get("name")?,
-                reg_official: row.get("reg_official")?,
-                reg_split: row.get("reg_split")?,
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (series_id, entry, opened_at) = row?;
+            match serde_json::from_str(&entry) {
+                Ok(e) => {
+                    let opened_at = opened_at
+                        .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                        .map(|t| t.with_timezone(&Utc));
+                    res.insert(series_id, (e, opened_at));
+                }
+                Err(e) => println!("Ignoring saved state for series {} {:?}", series_id, e),
+            }
+        }
+        Ok(res)
+    }
+    // returns the most recently saved race guide entry for the series.
+    pub fn latest_entry(&self, series_id: i64) -> rusqlite::Result<Option<RaceGuideEntry>> {
+        let entry: Option<String> = self
+            .con
+            .query_row(
+                "SELECT entry FROM watch_state WHERE series_id=?",
+                [series_id],
+                |row| row.get(0),
+            )
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+        Ok(entry.and_then(|e| serde_json::from_str(&e).ok()))
+    }
+    pub fn start_series_update(&mut self) -> rusqlite::Result<SeriesUpdater<'_>> {
+        let tx = self.con.transaction()?;
+        tx.execute("UPDATE series SET active=0", [])?;
+        Ok(SeriesUpdater { tx })
+    }
+    pub fn get_series(&self) -> rusqlite::Result<HashMap<i64, SeasonInfo>> {
+        let mut stmt = self.con.prepare("SELECT * FROM series WHERE active=1;")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(SeasonInfo {
+                series_id: row.get("series_id")?,
+                name: row.get("name")?,
+                reg_official: row.get("reg_official")?,
+                reg_split: row.get("reg_split")?,
                 week: row.get("week")?,
                 track_name: row.get("track_name")?,
                 track_config: row.get("track_config")?,
                 track_cat: row.get("track_cat")?,
+                short_name: row
+                    .get::<_, Option<String>>("short_name")?
+                    .unwrap_or(row.get("name")?),
+                week_end: row
+                    .get::<_, Option<String>>("week_end")?
+                    .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                    .map(|t| t.with_timezone(&Utc)),
+                season_id: row.get("season_id")?,
+                season_year: row.get("season_year")?,
+                season_quarter: row.get("season_quarter")?,
+                license_group: row.get("license_group")?,
+                fixed_setup: row.get("fixed_setup")?,
+                cars: row.get::<_, Option<String>>("cars")?.unwrap_or_default(),
                 lc_name: row.get::<_, String>("name")?.to_lowercase(),
             })
         })?;
@@ -176,53 +1279,1483 @@ impl Db {
         }
         Ok(res)
     }
-    pub fn upsert_reg(&mut self, reg: &Reg, created_by: &str) -> rusqlite::Result<usize> {
-        self.con.execute("INSERT INTO reg(guild_id, channel_id, series_id, min_reg, max_reg, open, close, created_by, created_date)
-                VALUES (?,?,?,?,?,?,?,?,datetime('now')) ON CONFLICT DO UPDATE SET
+    // adds or updates the reg, this also turns it back on if it was disabled.
+    pub fn upsert_reg(
+        &mut self,
+        reg: &Reg,
+        created_by: &str,
+        created_by_id: Option<UserId>,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(&format!("{} ON CONFLICT DO UPDATE SET
                     min_reg = excluded.min_reg,
                     max_reg = excluded.max_reg,
                     open    = excluded.open,
                     close   = excluded.close,
-                    modified_date = excluded.created_date", 
-                params![reg.guild.map(|g|g.0), reg.channel.0, reg.series_id,reg.min_reg, reg.max_reg, reg.open, reg.close, created_by])
+                    poll    = excluded.poll,
+                    hold_mins = excluded.hold_mins,
+                    qualifying = excluded.qualifying,
+                    reopen_mins = excluded.reopen_mins,
+                    last_day = excluded.last_day,
+                    first_open = excluded.first_open,
+                    cooldown_mins = excluded.cooldown_mins,
+                    week_change = excluded.week_change,
+                    predict = excluded.predict,
+                    results = excluded.results,
+                    race_after = excluded.race_after,
+                    race_before = excluded.race_before,
+                    race_days = excluded.race_days,
+                    created_by_id = CASE WHEN deleted_at IS NULL THEN ifnull(created_by_id, excluded.created_by_id) ELSE excluded.created_by_id END,
+                    snooze_week = CASE WHEN deleted_at IS NULL THEN snooze_week END,
+                    boost_until = CASE WHEN deleted_at IS NULL THEN boost_until END,
+                    deleted_at = NULL,
+                    disabled = 0,
+                    modified_date = excluded.created_date", INSERT_REG),
+                params_from_iter(insert_reg_params(reg, created_by, created_by_id)))
+    }
+    // adds all the regs in one transaction, leaving any the channel is already watching
+    // alone. A removed reg that's still waiting to be purged is replaced. Returns how many
+    // were added.
+    pub fn insert_regs(
+        &mut self,
+        regs: &[Reg],
+        created_by: &str,
+        created_by_id: Option<UserId>,
+    ) -> rusqlite::Result<usize> {
+        let tx = self.con.transaction()?;
+        let mut added = 0;
+        {
+            let mut purge = tx.prepare(
+                "DELETE FROM reg WHERE channel_id=? AND series_id=? AND deleted_at IS NOT NULL",
+            )?;
+            let mut insert = tx.prepare(&format!("{} ON CONFLICT DO NOTHING", INSERT_REG))?;
+            for reg in regs {
+                purge.execute(params![reg.channel.0, reg.series_id])?;
+                added += insert.execute(params_from_iter(insert_reg_params(
+                    reg,
+                    created_by,
+                    created_by_id,
+                )))?;
+            }
+        }
+        tx.commit()?;
+        Ok(added)
     }
+    // returns the total number of watches, and the number of channels they're in.
+    pub fn watch_totals(&self) -> rusqlite::Result<(i64, i64)> {
+        self.con.query_row(
+            "SELECT count(*), count(DISTINCT channel_id) FROM reg WHERE deleted_at IS NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+    // returns the number of channels and servers watching the series.
+    pub fn series_watch_totals(&self, series_id: i64) -> rusqlite::Result<(i64, i64)> {
+        self.con.query_row(
+            "SELECT count(*), count(DISTINCT guild_id) FROM reg WHERE series_id=? AND deleted_at IS NULL",
+            [series_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+    // returns the number of watches in the channel and guild of reg, not counting
+    // reg itself if it already exists.
+    pub fn watch_counts(&self, reg: &Reg) -> rusqlite::Result<(i64, i64)> {
+        let ch = self.con.query_row(
+            "SELECT count(*) FROM reg WHERE channel_id=? AND series_id<>? AND deleted_at IS NULL",
+            params![reg.channel.0, reg.series_id],
+            |row| row.get(0),
+        )?;
+        let guild = match reg.guild {
+            None => 0,
+            Some(g) => self.con.query_row(
+                "SELECT count(*) FROM reg WHERE guild_id=? AND NOT (channel_id=? AND series_id=?) AND deleted_at IS NULL",
+                params![g.0, reg.channel.0, reg.series_id],
+                |row| row.get(0),
+            )?,
+        };
+        Ok((ch, guild))
+    }
+    // marks the reg deleted, it can be brought back with undelete_regs until
+    // purge_deleted_regs gets to it.
     pub fn delete_reg(&mut self, channel_id: ChannelId, series_id: i64) -> rusqlite::Result<usize> {
         self.con.execute(
-            "DELETE FROM reg WHERE series_id=? AND channel_id=?",
-            params![series_id, channel_id.0],
+            "UPDATE reg SET deleted_at=? WHERE series_id=? AND channel_id=? AND deleted_at IS NULL",
+            params![outbox_time(Utc::now()), series_id, channel_id.0],
+        )
+    }
+    // marks every reg in the channel deleted, they all get the same deleted_at so that
+    // undelete can bring them back together.
+    pub fn delete_channel_regs(&mut self, channel_id: ChannelId) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "UPDATE reg SET deleted_at=? WHERE channel_id=? AND deleted_at IS NULL",
+            params![outbox_time(Utc::now()), channel_id.0],
+        )
+    }
+    // the regs in the channel that were most recently deleted, more than one if they
+    // went together, e.g. from /nomore all.
+    pub fn last_deleted_regs(&self, ch: ChannelId) -> rusqlite::Result<Vec<Reg>> {
+        let mut res = Vec::new();
+        self.query_regs(
+            "WHERE r.channel_id=? AND r.deleted_at=(SELECT max(deleted_at) FROM reg WHERE channel_id=?)",
+            [ch.0, ch.0],
+            |r| res.push(r),
+        )?;
+        Ok(res)
+    }
+    // brings back the deleted regs for the series in the channel, all or none of them.
+    pub fn undelete_regs(
+        &mut self,
+        channel_id: ChannelId,
+        series_ids: &[i64],
+    ) -> rusqlite::Result<usize> {
+        let tx = self.con.transaction()?;
+        let mut count = 0;
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE reg SET deleted_at=NULL, modified_date=datetime('now') WHERE series_id=? AND channel_id=?",
+            )?;
+            for series_id in series_ids {
+                count += stmt.execute(params![series_id, channel_id.0])?;
+            }
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+    // removes the regs that were deleted before before for good.
+    pub fn purge_deleted_regs(&mut self, before: DateTime<Utc>) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "DELETE FROM reg WHERE deleted_at<?",
+            params![outbox_time(before)],
+        )
+    }
+    // snooze the reg until the series race week changes, or wake it up again.
+    pub fn snooze_reg(
+        &mut self,
+        channel_id: ChannelId,
+        series_id: i64,
+        snooze: bool,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "UPDATE reg SET snooze_week=CASE WHEN ? THEN (SELECT s.week FROM series s WHERE s.series_id=reg.series_id) ELSE NULL END
+                WHERE series_id=? AND channel_id=? AND deleted_at IS NULL",
+            params![snooze, series_id, channel_id.0],
+        )
+    }
+    // boosts the watch until the supplied time, or ends the boost.
+    pub fn boost_reg(
+        &mut self,
+        channel_id: ChannelId,
+        series_id: i64,
+        until: Option<DateTime<Utc>>,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "UPDATE reg SET boost_until=? WHERE series_id=? AND channel_id=? AND deleted_at IS NULL",
+            params![until.map(|t| t.to_rfc3339()), series_id, channel_id.0],
         )
     }
     pub fn delete_channel(&mut self, channel_id: ChannelId) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "DELETE FROM poll_vote WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM channel_mute WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM channel_settings WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM watch_defaults WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM event_watch WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM league_watch WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM weekly_post WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM daily_digest WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM webhook_target WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM announce_log WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
+        self.con.execute(
+            "DELETE FROM outbox WHERE channel_id=?",
+            params![channel_id.0],
+        )?;
         self.con
             .execute("DELETE FROM reg WHERE channel_id=?", params![channel_id.0])
     }
-    pub fn delete_guild(&mut self, guild_id: GuildId) -> rusqlite::Result<usize> {
+    // adds messages that failed to send to the outbox, they're dropped if they can't
+    // be sent before expires.
+    pub fn queue_outbound(
+        &mut self,
+        msgs: &[(ChannelId, String)],
+        expires: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        let tx = self.con.transaction()?;
+        for (ch, content) in msgs {
+            tx.execute(
+                "INSERT INTO outbox(channel_id, content, next_at, expires_at) VALUES(?,?,?,?)",
+                params![ch.0, content, outbox_time(Utc::now()), outbox_time(expires)],
+            )?;
+        }
+        tx.commit()
+    }
+    // returns the outbox messages that are due to be tried again, after throwing
+    // away any that have expired or run out of attempts.
+    pub fn due_outbound(&mut self) -> rusqlite::Result<Vec<Outbound>> {
+        let now = outbox_time(Utc::now());
+        let dropped = self.con.execute(
+            "DELETE FROM outbox WHERE expires_at<=? OR attempts>=?",
+            params![now, OUTBOX_MAX_ATTEMPTS],
+        )?;
+        if dropped > 0 {
+            println!(
+                "Dropped {} outbox messages that couldn't be sent in time",
+                dropped
+            );
+        }
+        let mut stmt = self.con.prepare(
+            "SELECT id, channel_id, content, attempts FROM outbox WHERE next_at<=? ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![now], |row| {
+            Ok(Outbound {
+                id: row.get(0)?,
+                channel: ChannelId(row.get(1)?),
+                content: row.get(2)?,
+                attempts: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+    pub fn remove_outbound(&mut self, id: i64) -> rusqlite::Result<usize> {
         self.con
-            .execute("DELETE FROM reg WHERE guild_id=?", params![guild_id.0])
+            .execute("DELETE FROM outbox WHERE id=?", params![id])
     }
-    pub fn regs(&self) -> rusqlite::Result<HashMap<ChannelId, Vec<Reg>>> {
+    pub fn retry_outbound(&mut self, id: i64, next_at: DateTime<Utc>) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "UPDATE outbox SET attempts=attempts+1, next_at=? WHERE id=?",
+            params![outbox_time(next_at), id],
+        )
+    }
+    // watches hosted sessions with name in their name, see hosted::hosted_loop_task.
+    pub fn add_event_watch(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        name: &str,
+        created_by: UserId,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT OR IGNORE INTO event_watch(channel_id, guild_id, name, created_by_id) VALUES(?,?,?,?)",
+            params![channel_id.0, guild_id.map(|g| g.0), name.to_lowercase(), created_by.0],
+        )
+    }
+    pub fn remove_event_watch(
+        &mut self,
+        channel_id: ChannelId,
+        name: &str,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "DELETE FROM event_watch WHERE channel_id=? AND name=?",
+            params![channel_id.0, name.to_lowercase()],
+        )
+    }
+    // the (channel, lowercase name) of every event watch.
+    pub fn event_watches(&self) -> rusqlite::Result<Vec<(ChannelId, String)>> {
+        let mut stmt = self
+            .con
+            .prepare_cached("SELECT channel_id, name FROM event_watch")?;
+        let rows = stmt.query_map([], |row| Ok((ChannelId(row.get(0)?), row.get(1)?)))?;
+        rows.collect()
+    }
+    pub fn add_league_watch(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        league_id: i64,
+        created_by: UserId,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT OR IGNORE INTO league_watch(channel_id, guild_id, league_id, created_by_id) VALUES(?,?,?,?)",
+            params![channel_id.0, guild_id.map(|g| g.0), league_id, created_by.0],
+        )
+    }
+    pub fn remove_league_watch(
+        &mut self,
+        channel_id: ChannelId,
+        league_id: i64,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "DELETE FROM league_watch WHERE channel_id=? AND league_id=?",
+            params![channel_id.0, league_id],
+        )
+    }
+    // the (channel, league_id) of every league watch.
+    pub fn league_watches(&self) -> rusqlite::Result<Vec<(ChannelId, i64)>> {
+        let mut stmt = self
+            .con
+            .prepare_cached("SELECT channel_id, league_id FROM league_watch")?;
+        let rows = stmt.query_map([], |row| Ok((ChannelId(row.get(0)?), row.get(1)?)))?;
+        rows.collect()
+    }
+    // sets when the channel gets its weekly schedule post, None turns it off. It counts
+    // as sent now, so that turning it on doesn't post straight away for a time that's
+    // already passed this week.
+    pub fn set_weekly_post(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        when: Option<(i64, i64)>,
+    ) -> rusqlite::Result<usize> {
+        match when {
+            None => self.con.execute(
+                "DELETE FROM weekly_post WHERE channel_id=?",
+                [channel_id.0],
+            ),
+            Some((weekday, hour)) => self.con.execute(
+                "INSERT INTO weekly_post(channel_id, guild_id, weekday, hour, last_sent) VALUES(?,?,?,?,?)
+                    ON CONFLICT DO UPDATE SET weekday=excluded.weekday, hour=excluded.hour, last_sent=excluded.last_sent",
+                params![
+                    channel_id.0,
+                    guild_id.map(|g| g.0),
+                    weekday,
+                    hour,
+                    outbox_time(Utc::now())
+                ],
+            ),
+        }
+    }
+    pub fn weekly_posts(&self) -> rusqlite::Result<Vec<WeeklyPost>> {
+        let mut stmt = self.con.prepare_cached(
+            "SELECT w.channel_id, w.weekday, w.hour, w.last_sent, ifnull(g.utc_offset_mins,0)
+                FROM weekly_post w LEFT JOIN guild_settings g ON w.guild_id=g.guild_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(WeeklyPost {
+                channel: ChannelId(row.get(0)?),
+                weekday: row.get(1)?,
+                hour: row.get(2)?,
+                last_sent: row
+                    .get::<_, Option<String>>(3)?
+                    .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                    .map(|t| t.with_timezone(&Utc)),
+                utc_offset_mins: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+    pub fn weekly_post_sent(
+        &mut self,
+        channel_id: ChannelId,
+        at: DateTime<Utc>,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "UPDATE weekly_post SET last_sent=? WHERE channel_id=?",
+            params![outbox_time(at), channel_id.0],
+        )
+    }
+    // sends the channel's announcements to the webhook instead, None goes back to the channel.
+    pub fn set_webhook(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        target: Option<&WebhookTarget>,
+        created_by: UserId,
+    ) -> rusqlite::Result<usize> {
+        match target {
+            None => self.con.execute(
+                "DELETE FROM webhook_target WHERE channel_id=?",
+                [channel_id.0],
+            ),
+            Some(t) => self.con.execute(
+                "INSERT INTO webhook_target(channel_id, guild_id, url, format, created_by_id) VALUES(?,?,?,?,?)
+                    ON CONFLICT DO UPDATE SET url=excluded.url, format=excluded.format, created_by_id=excluded.created_by_id",
+                params![
+                    channel_id.0,
+                    guild_id.map(|g| g.0),
+                    t.url,
+                    t.format.name(),
+                    created_by.0
+                ],
+            ),
+        }
+    }
+    pub fn webhooks(&self) -> rusqlite::Result<HashMap<ChannelId, WebhookTarget>> {
+        let mut stmt = self
+            .con
+            .prepare_cached("SELECT channel_id, url, format FROM webhook_target")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                ChannelId(row.get(0)?),
+                WebhookTarget {
+                    url: row.get(1)?,
+                    format: row
+                        .get::<_, String>(2)?
+                        .parse()
+                        .unwrap_or(WebhookFormat::Json),
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+    // sets when the channel gets its daily digest, None turns it off. Like the weekly
+    // post, it counts as sent now.
+    pub fn set_daily_digest(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        hour: Option<i64>,
+    ) -> rusqlite::Result<usize> {
+        match hour {
+            None => self.con.execute(
+                "DELETE FROM daily_digest WHERE channel_id=?",
+                [channel_id.0],
+            ),
+            Some(hour) => self.con.execute(
+                "INSERT INTO daily_digest(channel_id, guild_id, hour, last_sent) VALUES(?,?,?,?)
+                    ON CONFLICT DO UPDATE SET hour=excluded.hour, last_sent=excluded.last_sent",
+                params![
+                    channel_id.0,
+                    guild_id.map(|g| g.0),
+                    hour,
+                    outbox_time(Utc::now())
+                ],
+            ),
+        }
+    }
+    pub fn daily_digests(&self) -> rusqlite::Result<Vec<DailyDigest>> {
+        let mut stmt = self.con.prepare_cached(
+            "SELECT d.channel_id, d.hour, d.last_sent, ifnull(g.utc_offset_mins,0)
+                FROM daily_digest d LEFT JOIN guild_settings g ON d.guild_id=g.guild_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(DailyDigest {
+                channel: ChannelId(row.get(0)?),
+                hour: row.get(1)?,
+                last_sent: row
+                    .get::<_, Option<String>>(2)?
+                    .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                    .map(|t| t.with_timezone(&Utc)),
+                utc_offset_mins: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+    pub fn daily_digest_sent(
+        &mut self,
+        channel_id: ChannelId,
+        at: DateTime<Utc>,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "UPDATE daily_digest SET last_sent=? WHERE channel_id=?",
+            params![outbox_time(at), channel_id.0],
+        )
+    }
+    // keeps the highest entry count seen for each session in the race guide, and how it
+    // filled up while registration was open, and throws away the ones that are too old
+    // to be interesting.
+    pub fn record_sessions(&mut self, sessions: &[RaceGuideEntry]) -> rusqlite::Result<()> {
+        let now = Utc::now();
+        let tx = self.con.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO session_history(series_id, start_time, entry_count) VALUES(?,?,?)
+                    ON CONFLICT DO UPDATE SET entry_count=max(entry_count, excluded.entry_count)",
+            )?;
+            let mut sample = tx.prepare_cached(
+                "INSERT OR IGNORE INTO entry_sample(series_id, start_time, mins_before, entry_count) VALUES(?,?,?,?)",
+            )?;
+            for s in sessions {
+                stmt.execute(params![
+                    s.series_id,
+                    outbox_time(s.start_time),
+                    s.entry_count
+                ])?;
+                if s.session_id.is_some() && s.start_time > now {
+                    sample.execute(params![
+                        s.series_id,
+                        outbox_time(s.start_time),
+                        sample_mins(s.start_time, now),
+                        s.entry_count
+                    ])?;
+                }
+            }
+        }
+        let cutoff = outbox_time(now - Duration::days(SESSION_HISTORY_DAYS));
+        tx.execute(
+            "DELETE FROM session_history WHERE start_time<?",
+            params![cutoff],
+        )?;
+        tx.execute(
+            "DELETE FROM entry_sample WHERE start_time<?",
+            params![cutoff],
+        )?;
+        tx.commit()
+    }
+    // the series' sessions that started before before, with their entry count at
+    // mins_before minutes to go and the most they got to.
+    pub fn entry_samples(
+        &self,
+        series_id: i64,
+        mins_before: i64,
+        before: DateTime<Utc>,
+    ) -> rusqlite::Result<Vec<Sample>> {
+        let mut stmt = self.con.prepare_cached(
+            "SELECT e.start_time, e.entry_count, h.entry_count FROM entry_sample e
+                JOIN session_history h ON e.series_id=h.series_id AND e.start_time=h.start_time
+                WHERE e.series_id=? AND e.mins_before=? AND e.start_time<?",
+        )?;
+        let rows = stmt.query_map(
+            params![series_id, mins_before, outbox_time(before)],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            },
+        )?;
+        let mut res = Vec::new();
+        for row in rows {
+            let (start, count, final_count) = row?;
+            if let Ok(t) = DateTime::parse_from_rfc3339(&start) {
+                res.push(Sample {
+                    start_time: t.with_timezone(&Utc),
+                    count,
+                    final_count,
+                });
+            }
+        }
+        Ok(res)
+    }
+    // the series that at least one watch wants predictions for.
+    pub fn predict_series(&self) -> rusqlite::Result<HashSet<i64>> {
+        let mut stmt = self.con.prepare_cached(
+            "SELECT DISTINCT series_id FROM reg WHERE predict=1 AND deleted_at IS NULL",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+    // the series that have at least one watch that's still posting.
+    pub fn watched_series(&self) -> rusqlite::Result<HashSet<i64>> {
+        let mut stmt = self.con.prepare_cached(
+            "SELECT DISTINCT series_id FROM reg WHERE disabled=0 AND deleted_at IS NULL
+                    AND NOT EXISTS (SELECT 1 FROM guild_settings g
+                        WHERE g.guild_id=reg.guild_id AND g.removed_at IS NOT NULL)",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+    // the series that at least one watch wants race results for.
+    pub fn results_series(&self) -> rusqlite::Result<HashSet<i64>> {
+        let mut stmt = self.con.prepare_cached(
+            "SELECT DISTINCT series_id FROM reg WHERE results=1 AND deleted_at IS NULL",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+    // the (series_id, entry count) of each session that started from from up to to.
+    pub fn session_history(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> rusqlite::Result<Vec<(i64, i64)>> {
+        let mut stmt = self.con.prepare_cached(
+            "SELECT series_id, entry_count FROM session_history WHERE start_time>=? AND start_time<?",
+        )?;
+        let rows = stmt.query_map(params![outbox_time(from), outbox_time(to)], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect()
+    }
+    // records the announcements that were delivered, and prunes old records.
+    pub fn log_announcements(&mut self, log: &[AnnounceLog]) -> rusqlite::Result<()> {
+        let tx = self.con.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO announce_log(at, guild_id, channel_id, series_id, series_name, ann_type, entry_count, message_id)
+                    VALUES(?,?,?,?,?,?,?,?)",
+            )?;
+            for a in log {
+                stmt.execute(params![
+                    outbox_time(a.at),
+                    a.guild.map(|g| g.0),
+                    a.channel.0,
+                    a.series_id,
+                    a.series_name,
+                    a.ann_type,
+                    a.entry_count,
+                    a.message_id.map(|m| m.0),
+                ])?;
+            }
+        }
+        tx.execute(
+            "DELETE FROM announce_log WHERE at<?",
+            params![outbox_time(Utc::now() - Duration::days(ANNOUNCE_LOG_DAYS))],
+        )?;
+        tx.commit()
+    }
+    // the most recent announcements delivered to the channel, newest first.
+    pub fn announce_log(
+        &self,
+        channel_id: ChannelId,
+        limit: i64,
+    ) -> rusqlite::Result<Vec<AnnounceLog>> {
+        let mut stmt = self.con.prepare_cached(
+            "SELECT at, guild_id, channel_id, series_id, series_name, ann_type, entry_count, message_id
+                FROM announce_log WHERE channel_id=? ORDER BY at DESC, rowid DESC LIMIT ?",
+        )?;
+        let rows = stmt.query_map(params![channel_id.0, limit], |row| {
+            Ok(AnnounceLog {
+                at: DateTime::parse_from_rfc3339(&row.get::<_, String>(0)?)
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+                guild: row.get::<_, Option<u64>>(1)?.map(GuildId),
+                channel: ChannelId(row.get(2)?),
+                series_id: row.get(3)?,
+                series_name: row.get(4)?,
+                ann_type: row.get(5)?,
+                entry_count: row.get(6)?,
+                message_id: row.get::<_, Option<u64>>(7)?.map(MessageId),
+            })
+        })?;
+        rows.collect()
+    }
+    // adds a reminder for the user, asking again for the same session just moves it.
+    pub fn add_reminder(
+        &mut self,
+        user: UserId,
+        guild_id: Option<GuildId>,
+        series_id: i64,
+        starts_at: DateTime<Utc>,
+        remind_at: DateTime<Utc>,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO reminder(user_id, guild_id, series_id, starts_at, remind_at) VALUES(?,?,?,?,?)
+                ON CONFLICT DO UPDATE SET remind_at=excluded.remind_at",
+            params![
+                user.0,
+                guild_id.map(|g| g.0),
+                series_id,
+                outbox_time(starts_at),
+                outbox_time(remind_at)
+            ],
+        )
+    }
+    // returns the reminders that are due, after throwing away any for sessions that
+    // have already started, which can happen if the bot was down.
+    pub fn due_reminders(&mut self) -> rusqlite::Result<Vec<Reminder>> {
+        let now = outbox_time(Utc::now());
+        let dropped = self
+            .con
+            .execute("DELETE FROM reminder WHERE starts_at<=?", params![now])?;
+        if dropped > 0 {
+            println!(
+                "Dropped {} reminders for sessions that already started",
+                dropped
+            );
+        }
+        let mut stmt = self.con.prepare(
+            "SELECT r.id, r.user_id, ifnull(s.name,'Series '||r.series_id), r.starts_at, g.locale
+                FROM reminder r LEFT JOIN series s ON r.series_id=s.series_id
+                LEFT JOIN guild_settings g ON r.guild_id=g.guild_id
+                WHERE r.remind_at<=? ORDER BY r.id",
+        )?;
+        let rows = stmt.query_map(params![now], |row| {
+            Ok(Reminder {
+                id: row.get(0)?,
+                user: UserId(row.get(1)?),
+                series_name: row.get(2)?,
+                starts_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(3)?)
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+                locale: row
+                    .get::<_, Option<String>>(4)?
+                    .and_then(|l| l.parse().ok())
+                    .unwrap_or_default(),
+            })
+        })?;
+        rows.collect()
+    }
+    pub fn remove_reminder(&mut self, id: i64) -> rusqlite::Result<usize> {
+        self.con
+            .execute("DELETE FROM reminder WHERE id=?", params![id])
+    }
+    // records when counts were announced for the (channel, series_id) watches.
+    pub fn record_counts(
+        &mut self,
+        counted: &[(ChannelId, i64)],
+        at: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        let tx = self.con.transaction()?;
+        for (ch, series_id) in counted {
+            tx.execute(
+                "UPDATE reg SET last_count_at=? WHERE channel_id=? AND series_id=?",
+                params![at.to_rfc3339(), ch.0, series_id],
+            )?;
+        }
+        tx.commit()
+    }
+    // records the outcome of announcing to channels. Channels that refused us max_failures
+    // times in a row have their watches disabled, returns the watches that were just
+    // disabled along with who created them.
+    pub fn record_sends(
+        &mut self,
+        delivered: &[ChannelId],
+        refused: &[ChannelId],
+        max_failures: i64,
+    ) -> rusqlite::Result<Vec<(Option<UserId>, Reg)>> {
+        let tx = self.con.transaction()?;
+        for ch in delivered {
+            tx.execute(
+                "UPDATE channel_settings SET send_failures=0 WHERE channel_id=? AND send_failures>0",
+                params![ch.0],
+            )?;
+        }
+        let mut disable = Vec::new();
+        for ch in refused {
+            let failures: i64 = tx.query_row(
+                "INSERT INTO channel_settings(channel_id, guild_id, send_failures)
+                    VALUES(?,(SELECT guild_id FROM reg WHERE channel_id=? LIMIT 1),1)
+                    ON CONFLICT DO UPDATE SET send_failures=send_failures+1
+                    RETURNING send_failures",
+                params![ch.0, ch.0],
+                |row| row.get(0),
+            )?;
+            if failures >= max_failures {
+                disable.push(*ch);
+            }
+        }
+        let mut res = Vec::new();
+        for ch in disable {
+            {
+                let mut stmt = tx.prepare(
+                    "SELECT r.created_by_id, r.*, ifnull(s.name,'Series '||r.series_id) as series_name,
+                            0 as snoozed, 0 as muted, 0 as inactive, 0 as utc_offset_mins, 0 as blocked, NULL as locale
+                        FROM reg r LEFT JOIN series s ON r.series_id=s.series_id
+                        WHERE r.channel_id=? AND r.disabled=0 AND r.deleted_at IS NULL",
+                )?;
+                let rows = stmt.query_map(params![ch.0], |row| {
+                    let by: Option<u64> = row.get(0)?;
+                    let mut reg = to_reg(row)?;
+                    reg.disabled = true;
+                    Ok((by.map(UserId), reg))
+                })?;
+                for r in rows {
+                    res.push(r?);
+                }
+            }
+            tx.execute(
+                "UPDATE reg SET disabled=1 WHERE channel_id=?",
+                params![ch.0],
+            )?;
+            tx.execute(
+                "UPDATE channel_settings SET send_failures=0 WHERE channel_id=?",
+                params![ch.0],
+            )?;
+        }
+        tx.commit()?;
+        Ok(res)
+    }
+    // blocks or unblocks the series in the server.
+    pub fn set_series_blocked(
+        &mut self,
+        guild_id: GuildId,
+        series_id: i64,
+        blocked: bool,
+    ) -> rusqlite::Result<usize> {
+        if blocked {
+            self.con.execute(
+                "INSERT OR IGNORE INTO series_block(guild_id, series_id) VALUES(?,?)",
+                params![guild_id.0, series_id],
+            )
+        } else {
+            self.con.execute(
+                "DELETE FROM series_block WHERE guild_id=? AND series_id=?",
+                params![guild_id.0, series_id],
+            )
+        }
+    }
+    // returns the series_ids of the series the server has blocked.
+    pub fn blocked_series(&self, guild_id: GuildId) -> rusqlite::Result<HashSet<i64>> {
+        let mut stmt = self
+            .con
+            .prepare_cached("SELECT series_id FROM series_block WHERE guild_id=?")?;
+        let rows = stmt.query_map([guild_id.0], |row| row.get(0))?;
+        rows.collect()
+    }
+    pub fn is_series_blocked(
+        &self,
+        guild_id: Option<GuildId>,
+        series_id: i64,
+    ) -> rusqlite::Result<bool> {
+        match guild_id {
+            None => Ok(false),
+            Some(g) => self.con.query_row(
+                "SELECT EXISTS(SELECT 1 FROM series_block WHERE guild_id=? AND series_id=?)",
+                params![g.0, series_id],
+                |row| row.get(0),
+            ),
+        }
+    }
+    // sets the server's timezone, as minutes from UTC.
+    pub fn set_guild_offset(&mut self, guild_id: GuildId, mins: i64) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO guild_settings(guild_id, utc_offset_mins) VALUES(?,?)
+                ON CONFLICT DO UPDATE SET utc_offset_mins=excluded.utc_offset_mins",
+            params![guild_id.0, mins],
+        )
+    }
+    // notes that the bot was removed from the server. Everything stored for it is kept
+    // until purge_removed_guilds, in case it's back soon, but it's not announced to.
+    pub fn mark_guild_removed(
+        &mut self,
+        guild_id: GuildId,
+        at: DateTime<Utc>,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO guild_settings(guild_id, removed_at) VALUES(?,?)
+                ON CONFLICT DO UPDATE SET removed_at=excluded.removed_at",
+            params![guild_id.0, outbox_time(at)],
+        )
+    }
+    // clears the removed mark for a server the bot is in, returns true if it had been removed.
+    pub fn guild_returned(&mut self, guild_id: GuildId) -> rusqlite::Result<bool> {
+        let n = self.con.execute(
+            "UPDATE guild_settings SET removed_at=NULL WHERE guild_id=? AND removed_at IS NOT NULL",
+            params![guild_id.0],
+        )?;
+        Ok(n > 0)
+    }
+    // deletes everything for the servers that were removed before before, returns their ids.
+    pub fn purge_removed_guilds(
+        &mut self,
+        before: DateTime<Utc>,
+    ) -> rusqlite::Result<Vec<GuildId>> {
+        let guilds: Vec<GuildId> = {
+            let mut stmt = self
+                .con
+                .prepare("SELECT guild_id FROM guild_settings WHERE removed_at<?")?;
+            let rows =
+                stmt.query_map(params![outbox_time(before)], |row| Ok(GuildId(row.get(0)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        for g in &guilds {
+            self.purge_guild(*g)?;
+        }
+        Ok(guilds)
+    }
+    // sets the language the server wants regbot to use.
+    pub fn set_guild_locale(
+        &mut self,
+        guild_id: GuildId,
+        locale: Locale,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO guild_settings(guild_id, locale) VALUES(?,?)
+                ON CONFLICT DO UPDATE SET locale=excluded.locale",
+            params![guild_id.0, locale.code()],
+        )
+    }
+    // sets whether only the creator of a watch, or members that can manage channels, can
+    // remove or edit it.
+    pub fn set_restrict_watches(&mut self, guild_id: GuildId, on: bool) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO guild_settings(guild_id, restrict_watches) VALUES(?,?)
+                ON CONFLICT DO UPDATE SET restrict_watches=excluded.restrict_watches",
+            params![guild_id.0, on],
+        )
+    }
+    // sets whether /watching and /help reply so only the person that asked sees it, unless
+    // they say otherwise.
+    pub fn set_private_replies(&mut self, guild_id: GuildId, on: bool) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO guild_settings(guild_id, private_replies) VALUES(?,?)
+                ON CONFLICT DO UPDATE SET private_replies=excluded.private_replies",
+            params![guild_id.0, on],
+        )
+    }
+    // returns true if the server wants private replies, DMs are private anyway.
+    pub fn private_replies(&self, guild_id: Option<GuildId>) -> rusqlite::Result<bool> {
+        let g = match guild_id {
+            None => return Ok(false),
+            Some(g) => g,
+        };
+        self.con
+            .query_row(
+                "SELECT private_replies FROM guild_settings WHERE guild_id=?",
+                [g.0],
+                |row| row.get(0),
+            )
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(false),
+                e => Err(e),
+            })
+    }
+    // returns the language the server wants regbot to use, English for DMs or servers that haven't picked one.
+    pub fn guild_locale(&self, guild_id: Option<GuildId>) -> rusqlite::Result<Locale> {
+        let g = match guild_id {
+            None => return Ok(Locale::default()),
+            Some(g) => g,
+        };
+        self.con
+            .query_row(
+                "SELECT locale FROM guild_settings WHERE guild_id=?",
+                [g.0],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })
+            .map(|l| l.and_then(|l| l.parse().ok()).unwrap_or_default())
+    }
+    pub fn set_channel_style(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        style: Style,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO channel_settings(channel_id, guild_id, style) VALUES(?,?,?)
+                ON CONFLICT DO UPDATE SET style=excluded.style",
+            params![channel_id.0, guild_id.map(|g| g.0), style.name()],
+        )
+    }
+    pub fn set_channel_layout(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        layout: Layout,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO channel_settings(channel_id, guild_id, layout) VALUES(?,?,?)
+                ON CONFLICT DO UPDATE SET layout=excluded.layout",
+            params![channel_id.0, guild_id.map(|g| g.0), layout.name()],
+        )
+    }
+    // sets the license classes announced in the channel, 0 for all of them.
+    pub fn set_channel_licenses(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        licenses: i64,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO channel_settings(channel_id, guild_id, licenses) VALUES(?,?,?)
+                ON CONFLICT DO UPDATE SET licenses=excluded.licenses",
+            params![channel_id.0, guild_id.map(|g| g.0), licenses],
+        )
+    }
+    // turns the status message for the channel on or off, returns the id of the
+    // existing status message, which is forgotten about when turning it off.
+    pub fn set_channel_status(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        on: bool,
+    ) -> rusqlite::Result<Option<MessageId>> {
+        let tx = self.con.transaction()?;
+        let prev: Option<u64> = tx
+            .query_row(
+                "SELECT status_msg FROM channel_settings WHERE channel_id=?",
+                [channel_id.0],
+                |row| row.get(0),
+            )
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+        tx.execute(
+            "INSERT INTO channel_settings(channel_id, guild_id, status) VALUES(?,?,?)
+                ON CONFLICT DO UPDATE SET status=excluded.status,
+                    status_msg=CASE WHEN excluded.status THEN status_msg ELSE NULL END",
+            params![channel_id.0, guild_id.map(|g| g.0), on],
+        )?;
+        tx.commit()?;
+        Ok(prev.map(MessageId))
+    }
+    pub fn set_status_msg(
+        &mut self,
+        channel_id: ChannelId,
+        msg: Option<MessageId>,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "UPDATE channel_settings SET status_msg=? WHERE channel_id=?",
+            params![msg.map(|m| m.0), channel_id.0],
+        )
+    }
+    // returns the settings for every channel that has changed any of them.
+    pub fn channel_settings(&self) -> rusqlite::Result<HashMap<ChannelId, ChannelSettings>> {
+        let mut stmt = self.con.prepare("SELECT * FROM channel_settings")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((ChannelId(row.get("channel_id")?), to_channel_settings(row)?))
+        })?;
+        rows.collect()
+    }
+    // sets the channel's /watch defaults, all None removes them.
+    pub fn set_watch_defaults(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        defaults: &WatchDefaults,
+    ) -> rusqlite::Result<usize> {
+        upsert_watch_defaults(&self.con, guild_id, channel_id, defaults)
+    }
+    pub fn watch_defaults(&self, channel_id: ChannelId) -> rusqlite::Result<WatchDefaults> {
+        self.con
+            .query_row(
+                "SELECT min_reg, max_reg, open, close FROM watch_defaults WHERE channel_id=?",
+                [channel_id.0],
+                |row| {
+                    Ok(WatchDefaults {
+                        min_reg: row.get(0)?,
+                        max_reg: row.get(1)?,
+                        open: row.get(2)?,
+                        close: row.get(3)?,
+                    })
+                },
+            )
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(WatchDefaults::default()),
+                e => Err(e),
+            })
+    }
+    pub fn mute_category(
+        &mut self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        category: &str,
+        mute: bool,
+    ) -> rusqlite::Result<usize> {
+        if mute {
+            self.con.execute(
+                "INSERT OR IGNORE INTO channel_mute(channel_id, guild_id, category) VALUES(?,?,?)",
+                params![channel_id.0, guild_id.map(|g| g.0), category],
+            )
+        } else {
+            self.con.execute(
+                "DELETE FROM channel_mute WHERE channel_id=? AND category=?",
+                params![channel_id.0, category],
+            )
+        }
+    }
+    pub fn channel_mutes(&self, channel_id: ChannelId) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = self
+            .con
+            .prepare("SELECT category FROM channel_mute WHERE channel_id=? ORDER BY category")?;
+        let rows = stmt.query_map([channel_id.0], |row| row.get(0))?;
+        rows.collect()
+    }
+    // returns everything configured for the server.
+    pub fn export_guild(&self, guild_id: GuildId) -> rusqlite::Result<GuildExport> {
+        let watches = self
+            .guild_regs(guild_id)?
+            .iter()
+            .map(RegExport::from)
+            .collect();
+        let mut channels = Vec::new();
+        {
+            let mut stmt = self
+                .con
+                .prepare("SELECT * FROM channel_settings WHERE guild_id=?")?;
+            let rows = stmt.query_map([guild_id.0], |row| {
+                Ok((row.get::<_, u64>("channel_id")?, to_channel_settings(row)?))
+            })?;
+            for row in rows {
+                let (ch, cs) = row?;
+                channels.push(ChannelExport {
+                    channel_id: ch,
+                    style: cs.style.name().to_string(),
+                    layout: cs.layout.name().to_string(),
+                    status: cs.status,
+                    mutes: Vec::new(),
+                    licenses: cs.licenses,
+                    defaults: None,
+                });
+            }
+        }
+        // channels can have mutes without having changed any other settings.
+        let mut stmt = self
+            .con
+            .prepare("SELECT DISTINCT channel_id FROM channel_mute WHERE guild_id=?")?;
+        let muted: Vec<u64> = stmt
+            .query_map([guild_id.0], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for ch in muted {
+            let mutes = self.channel_mutes(ChannelId(ch))?;
+            match channels.iter_mut().find(|c| c.channel_id == ch) {
+                Some(c) => c.mutes = mutes,
+                None => channels.push(ChannelExport {
+                    channel_id: ch,
+                    style: Style::default().name().to_string(),
+                    layout: Layout::default().name().to_string(),
+                    status: false,
+                    mutes,
+                    licenses: 0,
+                    defaults: None,
+                }),
+            }
+        }
+        // and defaults.
+        let mut stmt = self
+            .con
+            .prepare("SELECT channel_id FROM watch_defaults WHERE guild_id=?")?;
+        let with_defaults: Vec<u64> = stmt
+            .query_map([guild_id.0], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for ch in with_defaults {
+            let defaults = Some(self.watch_defaults(ChannelId(ch))?);
+            match channels.iter_mut().find(|c| c.channel_id == ch) {
+                Some(c) => c.defaults = defaults,
+                None => channels.push(ChannelExport {
+                    channel_id: ch,
+                    style: Style::default().name().to_string(),
+                    layout: Layout::default().name().to_string(),
+                    status: false,
+                    mutes: Vec::new(),
+                    licenses: 0,
+                    defaults,
+                }),
+            }
+        }
+        let utc_offset_mins = self
+            .con
+            .query_row(
+                "SELECT utc_offset_mins FROM guild_settings WHERE guild_id=?",
+                [guild_id.0],
+                |row| row.get(0),
+            )
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(0),
+                e => Err(e),
+            })?;
+        let mut blocked_series: Vec<i64> = self.blocked_series(guild_id)?.into_iter().collect();
+        blocked_series.sort_unstable();
+        let locale = self.guild_locale(Some(guild_id))?;
+        Ok(GuildExport {
+            watches,
+            channels,
+            utc_offset_mins,
+            blocked_series,
+            locale: (locale != Locale::default()).then(|| locale.code().to_string()),
+        })
+    }
+    // applies the server & channel settings from an export to the server, channels
+    // are moved to their entry in channels, those without one are skipped.
+    pub fn import_guild_settings(
+        &mut self,
+        guild_id: GuildId,
+        export: &GuildExport,
+        channels: &HashMap<u64, ChannelId>,
+    ) -> rusqlite::Result<()> {
+        let tx = self.con.transaction()?;
+        for c in &export.channels {
+            let ch = match channels.get(&c.channel_id) {
+                Some(ch) => ch,
+                None => continue,
+            };
+            let style: Style = c.style.parse().unwrap_or_default();
+            let layout: Layout = c.layout.parse().unwrap_or_default();
+            tx.execute(
+                "INSERT INTO channel_settings(channel_id, guild_id, style, layout, status, licenses) VALUES(?,?,?,?,?,?)
+                    ON CONFLICT DO UPDATE SET style=excluded.style, layout=excluded.layout, status=excluded.status, licenses=excluded.licenses",
+                params![ch.0, guild_id.0, style.name(), layout.name(), c.status, c.licenses],
+            )?;
+            for m in &c.mutes {
+                tx.execute(
+                    "INSERT OR IGNORE INTO channel_mute(channel_id, guild_id, category) VALUES(?,?,?)",
+                    params![ch.0, guild_id.0, m],
+                )?;
+            }
+            if let Some(d) = &c.defaults {
+                upsert_watch_defaults(&tx, Some(guild_id), *ch, d)?;
+            }
+        }
+        if export.utc_offset_mins != 0 {
+            tx.execute(
+                "INSERT INTO guild_settings(guild_id, utc_offset_mins) VALUES(?,?)
+                    ON CONFLICT DO UPDATE SET utc_offset_mins=excluded.utc_offset_mins",
+                params![guild_id.0, export.utc_offset_mins],
+            )?;
+        }
+        if let Some(l) = export
+            .locale
+            .as_ref()
+            .and_then(|l| l.parse::<Locale>().ok())
+        {
+            tx.execute(
+                "INSERT INTO guild_settings(guild_id, locale) VALUES(?,?)
+                    ON CONFLICT DO UPDATE SET locale=excluded.locale",
+                params![guild_id.0, l.code()],
+            )?;
+        }
+        for s in &export.blocked_series {
+            tx.execute(
+                "INSERT OR IGNORE INTO series_block(guild_id, series_id) VALUES(?,?)",
+                params![guild_id.0, s],
+            )?;
+        }
+        tx.commit()
+    }
+    // records a vote from a poll, returns the updated number of (racing, not racing) votes.
+    pub fn upsert_vote(
+        &mut self,
+        channel_id: ChannelId,
+        series_id: i64,
+        session_id: i64,
+        user_id: UserId,
+        racing: bool,
+    ) -> rusqlite::Result<(i64, i64)> {
+        self.con.execute(
+            "INSERT INTO poll_vote(channel_id, series_id, session_id, user_id, racing) VALUES(?,?,?,?,?)
+                ON CONFLICT DO UPDATE SET racing=excluded.racing",
+            params![channel_id.0, series_id, session_id, user_id.0, racing],
+        )?;
+        self.con.query_row(
+            "SELECT ifnull(sum(racing),0), ifnull(sum(1-racing),0) FROM poll_vote WHERE channel_id=? AND session_id=?",
+            params![channel_id.0, session_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+    // returns the (racing, not racing) counts for every channel that had a poll for
+    // the session, and clears them out.
+    pub fn take_poll_results(
+        &mut self,
+        session_id: i64,
+    ) -> rusqlite::Result<HashMap<ChannelId, (i64, i64)>> {
         let mut res = HashMap::new();
-        self.query_regs("", |r| {
-            res.entry(r.channel).or_insert_with(Vec::new).push(r)
+        {
+            let mut stmt = self.con.prepare(
+                "SELECT channel_id, sum(racing), sum(1-racing) FROM poll_vote WHERE session_id=? GROUP BY channel_id",
+            )?;
+            let rows = stmt.query_map([session_id], |row| {
+                Ok((ChannelId(row.get(0)?), row.get(1)?, row.get(2)?))
+            })?;
+            for row in rows {
+                let (ch, yes, no) = row?;
+                res.insert(ch, (yes, no));
+            }
+        }
+        self.con
+            .execute("DELETE FROM poll_vote WHERE session_id=?", [session_id])?;
+        Ok(res)
+    }
+    // records the execution of a slash command, and prunes old records.
+    pub fn record_command(
+        &mut self,
+        command: &str,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+        duration_ms: i64,
+        outcome: &str,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "DELETE FROM command_audit WHERE at < datetime('now', ?)",
+            [format!("-{} days", COMMAND_AUDIT_DAYS)],
+        )?;
+        self.con.execute(
+            "INSERT INTO command_audit(at, command, guild_id, user_id, duration_ms, outcome) VALUES(datetime('now'),?,?,?,?,?)",
+            params![command, guild_id.map(|g| g.0), user_id.0, duration_ms, outcome],
+        )
+    }
+    // records someone asking to watch a series we don't know about.
+    pub fn record_series_request(
+        &mut self,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+        query: &str,
+    ) -> rusqlite::Result<usize> {
+        self.con.execute(
+            "INSERT INTO series_request(at, guild_id, user_id, query) VALUES(datetime('now'),?,?,?)",
+            params![guild_id.map(|g| g.0), user_id.0, query],
+        )
+    }
+    // returns stats on command executions over the last few days, slowest first.
+    pub fn command_stats(&self, days: i64) -> rusqlite::Result<Vec<CommandStats>> {
+        let mut stmt = self.con.prepare(
+            "SELECT command, duration_ms, outcome FROM command_audit WHERE at >= datetime('now', ?) ORDER BY command, duration_ms",
+        )?;
+        let rows = stmt.query_map([format!("-{} days", days)], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
         })?;
+        let mut by_cmd: HashMap<String, (Vec<i64>, usize)> = HashMap::new();
+        for row in rows {
+            let (cmd, ms, outcome) = row?;
+            let e = by_cmd.entry(cmd).or_default();
+            e.0.push(ms);
+            if outcome != "ok" {
+                e.1 += 1;
+            }
+        }
+        let pct = |d: &[i64], p: usize| d[(d.len() - 1) * p / 100];
+        let mut res: Vec<CommandStats> = by_cmd
+            .into_iter()
+            .map(|(command, (d, failed))| CommandStats {
+                command,
+                count: d.len(),
+                failed,
+                p50_ms: pct(&d, 50),
+                p95_ms: pct(&d, 95),
+                max_ms: d[d.len() - 1],
+            })
+            .collect();
+        res.sort_by_key(|r| std::cmp::Reverse(r.p95_ms));
+        Ok(res)
+    }
+    // returns the column names and rows (as text) of one of the EXPORT_TABLES, optionally
+    // limited to rows between the from and to dates (YYYY-MM-DD, inclusive).
+    pub fn export_rows(
+        &self,
+        table: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> rusqlite::Result<(Vec<String>, Vec<Vec<String>>)> {
+        let (table, at) = EXPORT_TABLES
+            .iter()
+            .find(|(t, _)| *t == table)
+            .ok_or(rusqlite::Error::InvalidParameterName(table.to_string()))?;
+        let mut stmt = self.con.prepare(&format!(
+            "SELECT * FROM {table} WHERE (?1 IS NULL OR {at} >= date(?1)) AND (?2 IS NULL OR {at} < date(?2, '+1 day')) ORDER BY {at}"
+        ))?;
+        let cols: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let rows = stmt.query_map(params![from, to], |row| {
+            (0..cols.len())
+                .map(|i| -> rusqlite::Result<String> {
+                    Ok(match row.get_ref(i)? {
+                        ValueRef::Null => String::new(),
+                        ValueRef::Integer(i) => i.to_string(),
+                        ValueRef::Real(f) => f.to_string(),
+                        ValueRef::Text(t) => String::from_utf8_lossy(t).to_string(),
+                        ValueRef::Blob(b) => base64::encode(b),
+                    })
+                })
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })?;
+        let rows = rows.collect::<rusqlite::Result<_>>()?;
+        Ok((cols, rows))
+    }
+    // deletes everything we have stored for the guild, returns the number of rows
+    // deleted from each table.
+    pub fn purge_guild(
+        &mut self,
+        guild_id: GuildId,
+    ) -> rusqlite::Result<Vec<(&'static str, usize)>> {
+        let tx = self.con.transaction()?;
+        let mut res = Vec::new();
+        // these are keyed by channel, so need to go before the reg rows they hang off.
+        for table in ["poll_vote", "outbox"] {
+            let n = tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE channel_id IN (SELECT channel_id FROM reg WHERE guild_id=?)",
+                    table
+                ),
+                params![guild_id.0],
+            )?;
+            res.push((table, n));
+        }
+        for table in [
+            "channel_mute",
+            "channel_settings",
+            "watch_defaults",
+            "event_watch",
+            "league_watch",
+            "weekly_post",
+            "daily_digest",
+            "webhook_target",
+            "guild_settings",
+            "series_block",
+            "series_request",
+            "reminder",
+            "command_audit",
+            "announce_log",
+            "reg",
+        ] {
+            let n = tx.execute(
+                &format!("DELETE FROM {} WHERE guild_id=?", table),
+                params![guild_id.0],
+            )?;
+            res.push((table, n));
+        }
+        tx.commit()?;
+        Ok(res)
+    }
+    // every watch, apart from those in servers the bot has been removed from.
+    pub fn regs(&self) -> rusqlite::Result<HashMap<ChannelId, Vec<Reg>>> {
+        let mut res = HashMap::new();
+        self.query_regs(
+            "WHERE g.removed_at IS NULL AND r.deleted_at IS NULL",
+            [],
+            |r| res.entry(r.channel).or_insert_with(Vec::new).push(r),
+        )?;
         Ok(res)
     }
     pub fn channel_regs(&self, ch: ChannelId) -> rusqlite::Result<Vec<Reg>> {
         let mut res = Vec::new();
-        let filter = format!("WHERE r.channel_id={}", ch.0);
-        self.query_regs(&filter, |r| res.push(r))?;
+        self.query_regs(
+            "WHERE r.channel_id=? AND r.deleted_at IS NULL",
+            [ch.0],
+            |r| res.push(r),
+        )?;
+        Ok(res)
+    }
+    pub fn guild_regs(&self, g: GuildId) -> rusqlite::Result<Vec<Reg>> {
+        let mut res = Vec::new();
+        self.query_regs("WHERE r.guild_id=? AND r.deleted_at IS NULL", [g.0], |r| {
+            res.push(r)
+        })?;
         Ok(res)
     }
-    fn query_regs<F>(&self, filter: &str, mut f: F) -> rusqlite::Result<()>
+    // returns the regs for series that are unknown or not active this season.
+    pub fn inactive_regs(&self) -> rusqlite::Result<Vec<Reg>> {
+        let mut res = Vec::new();
+        self.query_regs(
+            "WHERE ifnull(s.active,0)=0 AND r.deleted_at IS NULL",
+            [],
+            |r| res.push(r),
+        )?;
+        Ok(res)
+    }
+    fn query_regs<P, F>(&self, filter: &str, params: P, mut f: F) -> rusqlite::Result<()>
     where
+        P: Params,
         F: FnMut(Reg),
     {
         let sql = format!(
-            "SELECT r.*, s.name as series_name FROM reg r INNER JOIN series s ON r.series_id=s.series_id {}",
+            "SELECT r.*, ifnull(s.name,'Series '||r.series_id) as series_name, ifnull(r.snooze_week=s.week,0) as snoozed,
+                    (EXISTS(SELECT 1 FROM channel_mute m WHERE m.channel_id=r.channel_id AND m.category=s.track_cat)
+                        OR EXISTS(SELECT 1 FROM channel_settings c WHERE c.channel_id=r.channel_id AND c.licenses!=0
+                            AND ifnull(s.license_group,0)>0 AND (c.licenses>>(s.license_group-1))&1=0)) as muted,
+                    ifnull(s.active,0)=0 as inactive, ifnull(g.utc_offset_mins,0) as utc_offset_mins, g.locale,
+                    ifnull(g.restrict_watches,0) as restricted,
+                    EXISTS(SELECT 1 FROM series_block b WHERE b.guild_id=r.guild_id AND b.series_id=r.series_id) as blocked
+                FROM reg r LEFT JOIN series s ON r.series_id=s.series_id
+                    LEFT JOIN guild_settings g ON r.guild_id=g.guild_id {}",
             filter
         );
-        let mut stmt = self.con.prepare(&sql)?;
-        for row in stmt.query_map([], to_reg)? {
+        // these are hot, so keep the prepared statements around.
+        let mut stmt = self.con.prepare_cached(&sql)?;
+        for row in stmt.query_map(params, to_reg)? {
             f(row?);
         }
         Ok(())
@@ -241,5 +2774,345 @@ fn to_reg(row: &Row) -> rusqlite::Result<Reg> {
         max_reg: row.get("max_reg")?,
         open: row.get("open")?,
         close: row.get("close")?,
+        snoozed: row.get("snoozed")?,
+        muted: row.get("muted")?,
+        inactive: row.get("inactive")?,
+        disabled: row.get("disabled")?,
+        blocked: row.get("blocked")?,
+        poll: row.get("poll")?,
+        hold_mins: row.get("hold_mins")?,
+        qualifying: row.get("qualifying")?,
+        reopen_mins: row.get("reopen_mins")?,
+        last_day: row.get("last_day")?,
+        first_open: row.get("first_open")?,
+        week_change: row.get("week_change")?,
+        predict: row.get("predict")?,
+        results: row.get("results")?,
+        cooldown_mins: row.get("cooldown_mins")?,
+        race_after: row.get("race_after")?,
+        race_before: row.get("race_before")?,
+        race_days: row.get("race_days")?,
+        last_count_at: row
+            .get::<_, Option<String>>("last_count_at")?
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+            .map(|t| t.with_timezone(&Utc)),
+        boost_until: row
+            .get::<_, Option<String>>("boost_until")?
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+            .map(|t| t.with_timezone(&Utc)),
+        utc_offset_mins: row.get("utc_offset_mins")?,
+        locale: row
+            .get::<_, Option<String>>("locale")?
+            .and_then(|l| l.parse().ok())
+            .unwrap_or_default(),
+        created_by: row.get("created_by")?,
+        created_by_id: row.get::<_, Option<u64>>("created_by_id")?.map(UserId),
+        restricted: row.get("restricted")?,
+    })
+}
+
+fn to_channel_settings(row: &Row) -> rusqlite::Result<ChannelSettings> {
+    let style: String = row.get("style")?;
+    Ok(ChannelSettings {
+        style: style.parse().unwrap_or_default(),
+        layout: row.get::<_, String>("layout")?.parse().unwrap_or_default(),
+        status: row.get("status")?,
+        status_msg: row.get::<_, Option<u64>>("status_msg")?.map(MessageId),
+        licenses: row.get("licenses")?,
     })
 }
+
+fn upsert_watch_defaults(
+    con: &Connection,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    d: &WatchDefaults,
+) -> rusqlite::Result<usize> {
+    if *d == WatchDefaults::default() {
+        return con.execute(
+            "DELETE FROM watch_defaults WHERE channel_id=?",
+            [channel_id.0],
+        );
+    }
+    con.execute(
+        "INSERT INTO watch_defaults(channel_id, guild_id, min_reg, max_reg, open, close) VALUES(?,?,?,?,?,?)
+            ON CONFLICT DO UPDATE SET min_reg=excluded.min_reg, max_reg=excluded.max_reg, open=excluded.open, close=excluded.close",
+        params![channel_id.0, guild_id.map(|g| g.0), d.min_reg, d.max_reg, d.open, d.close],
+    )
+}
+
+// adds a column to an existing table, for db files created before the column existed.
+fn add_column(con: &Connection, table: &str, column: &str, def: &str) -> rusqlite::Result<()> {
+    let exists: bool = con.query_row(
+        "SELECT count(*) FROM pragma_table_info(?) WHERE name=?",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        con.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, def),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // a made up series, for tests that need one.
+    pub fn series(series_id: i64, name: &str) -> SeasonInfo {
+        SeasonInfo {
+            series_id,
+            name: name.to_string(),
+            reg_official: 8,
+            reg_split: 20,
+            week: 2,
+            track_name: "Spa".to_string(),
+            track_config: "Endurance".to_string(),
+            track_cat: Some("road".to_string()),
+            short_name: name.to_string(),
+            week_end: None,
+            season_id: 0,
+            season_year: 2026,
+            season_quarter: 3,
+            license_group: 0,
+            fixed_setup: false,
+            cars: String::new(),
+            lc_name: name.to_lowercase(),
+        }
+    }
+
+    fn reg(guild: u64, channel: u64, series_id: i64) -> Reg {
+        Reg::new(
+            Some(GuildId(guild)),
+            ChannelId(channel),
+            &series(series_id, "Test"),
+        )
+    }
+
+    fn count(db: &Db, sql: &str) -> i64 {
+        db.con.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_upsert_reg_updates_existing() {
+        let mut db = Db::new(MEMORY_DB).unwrap();
+        let mut r = reg(1, 10, 100);
+        db.upsert_reg(&r, "alice", Some(UserId(5))).unwrap();
+        r.min_reg = 2;
+        r.max_reg = 30;
+        r.open = true;
+        db.upsert_reg(&r, "bob", Some(UserId(6))).unwrap();
+        let regs = db.channel_regs(ChannelId(10)).unwrap();
+        assert_eq!(regs.len(), 1);
+        assert_eq!(
+            (regs[0].min_reg, regs[0].max_reg, regs[0].open),
+            (2, 30, true)
+        );
+        // the watch still belongs to whoever added it.
+        assert_eq!(regs[0].created_by_id, Some(UserId(5)));
+
+        // a removed watch comes back, and belongs to whoever brought it back.
+        db.delete_reg(ChannelId(10), 100).unwrap();
+        assert!(db.channel_regs(ChannelId(10)).unwrap().is_empty());
+        db.upsert_reg(&r, "bob", Some(UserId(6))).unwrap();
+        let regs = db.channel_regs(ChannelId(10)).unwrap();
+        assert_eq!(regs.len(), 1);
+        assert_eq!(regs[0].created_by_id, Some(UserId(6)));
+        assert_eq!(count(&db, "SELECT count(*) FROM reg"), 1);
+    }
+
+    #[test]
+    fn test_insert_regs_skips_existing() {
+        let mut db = Db::new(MEMORY_DB).unwrap();
+        let mut existing = reg(1, 10, 100);
+        existing.min_reg = 3;
+        db.upsert_reg(&existing, "alice", None).unwrap();
+        let added = db
+            .insert_regs(&[reg(1, 10, 100), reg(1, 10, 101)], "bob", None)
+            .unwrap();
+        assert_eq!(added, 1);
+        let regs = db.channel_regs(ChannelId(10)).unwrap();
+        assert_eq!(regs.len(), 2);
+        let r = regs.iter().find(|r| r.series_id == 100).unwrap();
+        assert_eq!(r.min_reg, 3);
+    }
+
+    // puts something in each of the tables keyed by channel for the guild & channel.
+    fn fill_channel(db: &mut Db, guild: u64, channel: u64) {
+        let (g, ch) = (Some(GuildId(guild)), ChannelId(channel));
+        db.upsert_reg(&reg(guild, channel, 100), "alice", None)
+            .unwrap();
+        db.mute_category(g, ch, "oval", true).unwrap();
+        db.set_channel_style(g, ch, Style::Ticker).unwrap();
+        db.set_watch_defaults(
+            g,
+            ch,
+            &WatchDefaults {
+                min_reg: Some(5),
+                ..WatchDefaults::default()
+            },
+        )
+        .unwrap();
+        db.add_event_watch(g, ch, "24h", UserId(5)).unwrap();
+        db.queue_outbound(
+            &[(ch, "hello".to_string())],
+            Utc::now() + Duration::hours(1),
+        )
+        .unwrap();
+    }
+
+    const CHANNEL_TABLES: &[&str] = &[
+        "reg",
+        "channel_mute",
+        "channel_settings",
+        "watch_defaults",
+        "event_watch",
+        "outbox",
+    ];
+
+    fn channel_rows(db: &Db, channel: u64) -> i64 {
+        CHANNEL_TABLES
+            .iter()
+            .map(|t| {
+                count(
+                    db,
+                    &format!("SELECT count(*) FROM {} WHERE channel_id={}", t, channel),
+                )
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_purge_guild() {
+        let mut db = Db::new(MEMORY_DB).unwrap();
+        fill_channel(&mut db, 1, 10);
+        fill_channel(&mut db, 1, 11);
+        fill_channel(&mut db, 2, 20);
+        db.set_series_blocked(GuildId(1), 100, true).unwrap();
+        db.set_guild_offset(GuildId(1), 60).unwrap();
+        let purged = db.purge_guild(GuildId(1)).unwrap();
+        assert!(purged.iter().any(|(t, n)| *t == "reg" && *n == 2));
+        assert_eq!(channel_rows(&db, 10), 0);
+        assert_eq!(channel_rows(&db, 11), 0);
+        assert!(db.blocked_series(GuildId(1)).unwrap().is_empty());
+        assert_eq!(
+            count(&db, "SELECT count(*) FROM guild_settings WHERE guild_id=1"),
+            0
+        );
+        // other servers are left alone.
+        assert_eq!(channel_rows(&db, 20), CHANNEL_TABLES.len() as i64);
+    }
+
+    #[test]
+    fn test_delete_channel() {
+        let mut db = Db::new(MEMORY_DB).unwrap();
+        fill_channel(&mut db, 1, 10);
+        fill_channel(&mut db, 1, 11);
+        db.delete_channel(ChannelId(10)).unwrap();
+        assert_eq!(channel_rows(&db, 10), 0);
+        assert_eq!(channel_rows(&db, 11), CHANNEL_TABLES.len() as i64);
+        assert_eq!(
+            db.watch_defaults(ChannelId(10)).unwrap(),
+            WatchDefaults::default()
+        );
+    }
+
+    #[test]
+    fn test_watched_series() {
+        let mut db = Db::new(MEMORY_DB).unwrap();
+        db.upsert_reg(&reg(1, 10, 100), "alice", None).unwrap();
+        db.upsert_reg(&reg(2, 20, 101), "alice", None).unwrap();
+        // a watch that's not in a server, e.g. from the API.
+        db.upsert_reg(
+            &Reg::new(None, ChannelId(30), &series(102, "Test")),
+            "alice",
+            None,
+        )
+        .unwrap();
+        db.mark_guild_removed(GuildId(2), Utc::now()).unwrap();
+        let want: HashSet<i64> = [100, 102].into_iter().collect();
+        assert_eq!(db.watched_series().unwrap(), want);
+    }
+
+    fn update_series(db: &mut Db, series: &[SeasonInfo]) {
+        let mut u = db.start_series_update().unwrap();
+        for s in series {
+            u.upsert(s).unwrap();
+        }
+        u.commit().unwrap();
+    }
+
+    #[test]
+    fn test_series_update() {
+        let mut db = Db::new(MEMORY_DB).unwrap();
+        update_series(&mut db, &[series(100, "Old Name"), series(101, "Other")]);
+        let mut renamed = series(100, "New Name");
+        renamed.week = 3;
+        renamed.reg_split = 30;
+        update_series(&mut db, &[renamed]);
+        // the existing row is updated in place, and a series missing from the update
+        // is no longer active.
+        assert_eq!(count(&db, "SELECT count(*) FROM series"), 2);
+        let all = db.get_series().unwrap();
+        assert_eq!(all.len(), 1);
+        let s = &all[&100];
+        assert_eq!((s.name.as_str(), s.week, s.reg_split), ("New Name", 3, 30));
+    }
+
+    #[test]
+    fn test_regs_join_series() {
+        let mut db = Db::new(MEMORY_DB).unwrap();
+        update_series(&mut db, &[series(100, "Old Name"), series(101, "Other")]);
+        for series_id in [100, 101, 102] {
+            db.upsert_reg(&reg(1, 10, series_id), "alice", None)
+                .unwrap();
+        }
+        update_series(&mut db, &[series(100, "New Name")]);
+        let regs = db.regs().unwrap();
+        let mut got: Vec<(i64, &str, bool)> = regs[&ChannelId(10)]
+            .iter()
+            .map(|r| (r.series_id, r.series_name.as_str(), r.inactive))
+            .collect();
+        got.sort();
+        assert_eq!(
+            got,
+            vec![
+                // the rename shows through.
+                (100, "New Name", false),
+                // not running this season.
+                (101, "Other", true),
+                // never heard of, it gets a made up name and isn't active.
+                (102, "Series 102", true),
+            ]
+        );
+        // and only the unknown & inactive ones are left for the inactive sweep.
+        let mut inactive: Vec<i64> = db
+            .inactive_regs()
+            .unwrap()
+            .iter()
+            .map(|r| r.series_id)
+            .collect();
+        inactive.sort();
+        assert_eq!(inactive, vec![101, 102]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_handle() {
+        let db = DbHandle::open(MEMORY_DB).unwrap();
+        db.call(|db| db.upsert_reg(&reg(1, 10, 100), "alice", None))
+            .await
+            .unwrap();
+        // the read connection sees the same db, and each handle gets its own.
+        let regs = db.read(|db| db.channel_regs(ChannelId(10))).await.unwrap();
+        assert_eq!(regs.len(), 1);
+        let other = DbHandle::open(MEMORY_DB).unwrap();
+        let regs = other
+            .read(|db| db.channel_regs(ChannelId(10)))
+            .await
+            .unwrap();
+        assert!(regs.is_empty());
+    }
+}
@@ -0,0 +1,26 @@
+use std::fmt::Display;
+
+// BotError is what can go wrong in the paths that run all the time, the race guide
+// listener and the commands. None of these should take the bot down, the caller logs
+// it and skips whatever it was doing.
+#[derive(Debug)]
+pub enum BotError {
+    // a db query failed.
+    Db(rusqlite::Error),
+    // the db thread has stopped, or the job panicked before it could answer.
+    DbUnavailable,
+}
+impl Display for BotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BotError::Db(e) => write!(f, "db error {}", e),
+            BotError::DbUnavailable => f.write_str("db thread unavailable"),
+        }
+    }
+}
+impl std::error::Error for BotError {}
+impl From<rusqlite::Error> for BotError {
+    fn from(e: rusqlite::Error) -> Self {
+        BotError::Db(e)
+    }
+}
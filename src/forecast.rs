@@ -0,0 +1,160 @@
+use chrono::{DateTime, Timelike, Utc};
+
+// how far apart the entry count samples for a session are, in minutes before it starts.
+pub const SAMPLE_MINS: i64 = 5;
+
+// the fewest past sessions a prediction can be based on.
+const MIN_SAMPLES: usize = 3;
+
+// Sample is a past session of a series, with its entry count at some point before it
+// started and the most it got to.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub start_time: DateTime<Utc>,
+    pub count: i64,
+    pub final_count: i64,
+}
+
+// the sample point for a session starting at start_time, the minutes to go rounded
+// down to SAMPLE_MINS, so sessions are compared at the same point before their start.
+pub fn sample_mins(start_time: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    (start_time - now).num_minutes().max(0) / SAMPLE_MINS * SAMPLE_MINS
+}
+
+// predicts how many entries a session starting at start_time that has count entries
+// now will end up with, from how much the series' past sessions grew from the same
+// point. Sessions at the same time of day are used if there's enough of them, as a
+// prime time slot fills up differently to one at 4am. It's the median growth so one
+// odd session doesn't throw it off. None if there's not enough history to go on.
+pub fn predict(start_time: DateTime<Utc>, count: i64, samples: &[Sample]) -> Option<i64> {
+    if count <= 0 {
+        return None;
+    }
+    let slot: Vec<&Sample> = samples
+        .iter()
+        .filter(|s| same_slot(s.start_time, start_time))
+        .collect();
+    let growth =
+        median_growth(&slot).or_else(|| median_growth(&samples.iter().collect::<Vec<_>>()))?;
+    Some((count as f64 * growth).round() as i64)
+}
+
+fn same_slot(a: DateTime<Utc>, b: DateTime<Utc>) -> bool {
+    a.hour() == b.hour() && a.minute() == b.minute()
+}
+
+fn median_growth(samples: &[&Sample]) -> Option<f64> {
+    let mut growth: Vec<f64> = samples
+        .iter()
+        .filter(|s| s.count > 0)
+        .map(|s| s.final_count as f64 / s.count as f64)
+        .collect();
+    if growth.len() < MIN_SAMPLES {
+        return None;
+    }
+    growth.sort_by(|a, b| a.total_cmp(b));
+    Some(growth[growth.len() / 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    // a session at 18:00 on the day, and one at 04:00.
+    fn evening(day: i64) -> DateTime<Utc> {
+        "2026-07-01T18:00:00Z".parse::<DateTime<Utc>>().unwrap() - Duration::days(day)
+    }
+    fn early(day: i64) -> DateTime<Utc> {
+        "2026-07-01T04:00:00Z".parse::<DateTime<Utc>>().unwrap() - Duration::days(day)
+    }
+
+    fn sample(start_time: DateTime<Utc>, count: i64, final_count: i64) -> Sample {
+        Sample {
+            start_time,
+            count,
+            final_count,
+        }
+    }
+
+    #[test]
+    fn test_sample_mins() {
+        let start = evening(0);
+        assert_eq!(sample_mins(start, start - Duration::minutes(23)), 20);
+        assert_eq!(sample_mins(start, start - Duration::minutes(25)), 25);
+        assert_eq!(sample_mins(start, start - Duration::minutes(4)), 0);
+        // it's started.
+        assert_eq!(sample_mins(start, start + Duration::minutes(3)), 0);
+    }
+
+    #[test]
+    fn test_too_few_samples() {
+        let samples = [sample(evening(1), 10, 20), sample(evening(2), 10, 20)];
+        assert_eq!(predict(evening(0), 10, &samples), None);
+        assert_eq!(predict(evening(0), 10, &[]), None);
+        // there's nothing to grow from.
+        let samples = [
+            sample(evening(1), 10, 20),
+            sample(evening(2), 10, 20),
+            sample(evening(3), 10, 20),
+        ];
+        assert_eq!(predict(evening(0), 0, &samples), None);
+    }
+
+    #[test]
+    fn test_prefers_same_slot() {
+        let samples = [
+            sample(evening(1), 10, 30),
+            sample(evening(2), 10, 30),
+            sample(evening(3), 10, 30),
+            sample(early(1), 10, 10),
+            sample(early(2), 10, 10),
+            sample(early(3), 10, 10),
+        ];
+        assert_eq!(predict(evening(0), 5, &samples), Some(15));
+        assert_eq!(predict(early(0), 5, &samples), Some(5));
+    }
+
+    #[test]
+    fn test_falls_back_to_all_samples() {
+        // not enough at 04:00, so they're all used.
+        let samples = [
+            sample(evening(1), 10, 20),
+            sample(evening(2), 10, 20),
+            sample(evening(3), 10, 20),
+            sample(early(1), 10, 40),
+        ];
+        assert_eq!(predict(early(0), 5, &samples), Some(10));
+    }
+
+    #[test]
+    fn test_ignores_zero_counts() {
+        let samples = [
+            sample(evening(1), 0, 20),
+            sample(evening(2), 10, 20),
+            sample(evening(3), 10, 20),
+        ];
+        assert_eq!(predict(evening(0), 5, &samples), None);
+        let samples = [
+            sample(evening(1), 0, 20),
+            sample(evening(2), 10, 20),
+            sample(evening(3), 10, 20),
+            sample(evening(4), 10, 20),
+        ];
+        assert_eq!(predict(evening(0), 5, &samples), Some(10));
+    }
+
+    #[test]
+    fn test_median_ignores_outlier() {
+        let samples = [
+            sample(evening(1), 10, 15),
+            sample(evening(2), 10, 15),
+            sample(evening(3), 10, 16),
+            sample(evening(4), 1, 60),
+        ];
+        // the growth is 1.5, 1.5, 1.6 & 60, the median picks 1.6.
+        assert_eq!(predict(evening(0), 10, &samples), Some(16));
+        assert!(same_slot(evening(1), evening(2)));
+        assert!(!same_slot(evening(1), early(1)));
+    }
+}
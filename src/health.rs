@@ -0,0 +1,194 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::os::unix::net::UnixDatagram;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::spawn;
+
+// if we haven't had a good race guide poll in this long, something is stuck.
+pub const MAX_POLL_AGE_SECS: i64 = 5 * 60;
+
+// Health tracks the liveness of the iRacing poller and the discord gateway.
+#[derive(Default)]
+pub struct Health {
+    state: Mutex<HealthStatus>,
+    // when announcements were sent and how many, for the last day.
+    sent: Mutex<VecDeque<(DateTime<Utc>, usize)>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HealthStatus {
+    pub healthy: bool,
+    // true when every shard is connected.
+    pub gateway_connected: bool,
+    // shard_id -> connected, for the shards we've heard from.
+    pub shards: BTreeMap<u64, bool>,
+    // how many shards there should be, 0 until the first one is ready.
+    pub shard_count: u64,
+    pub last_poll_ok: Option<DateTime<Utc>>,
+    pub last_poll_error: Option<String>,
+    // number of changes seen in the shape of the iRacing data.
+    pub schema_drift: u64,
+    // when we last logged into iRacing.
+    pub ir_login: Option<DateTime<Utc>>,
+    // the supervised background tasks, by name.
+    pub tasks: BTreeMap<String, TaskStatus>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TaskStatus {
+    // when the task last said it was still going.
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    pub restarts: u64,
+}
+
+impl Health {
+    pub fn poll_succeeded(&self) {
+        let mut st = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        st.last_poll_ok = Some(Utc::now());
+        st.last_poll_error = None;
+    }
+    pub fn poll_failed(&self, err: &anyhow::Error) {
+        let mut st = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        st.last_poll_error = Some(format!("{}", err));
+    }
+    pub fn schema_drift(&self, count: u64) {
+        let mut st = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        st.schema_drift = count;
+    }
+    pub fn ir_logged_in(&self) {
+        let mut st = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        st.ir_login = Some(Utc::now());
+    }
+    pub fn announced(&self, count: usize) {
+        let mut sent = self.sent.lock().unwrap_or_else(PoisonError::into_inner);
+        let cutoff = Utc::now() - Duration::days(1);
+        while sent.front().is_some_and(|(at, _)| *at < cutoff) {
+            sent.pop_front();
+        }
+        sent.push_back((Utc::now(), count));
+    }
+    // returns the number of announcements sent in the last day.
+    pub fn announced_last_day(&self) -> usize {
+        let cutoff = Utc::now() - Duration::days(1);
+        self.sent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|(at, _)| *at >= cutoff)
+            .map(|(_, c)| c)
+            .sum()
+    }
+    pub fn heartbeat(&self, task: &str) {
+        let mut st = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        st.tasks.entry(task.to_string()).or_default().last_heartbeat = Some(Utc::now());
+    }
+    pub fn restarted(&self, task: &str) {
+        let mut st = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        st.tasks.entry(task.to_string()).or_default().restarts += 1;
+    }
+    pub fn shard_connected(&self, shard_id: u64, connected: bool) {
+        let mut st = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        st.shards.insert(shard_id, connected);
+    }
+    pub fn shard_count(&self, count: u64) {
+        let mut st = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        st.shard_count = count;
+    }
+    pub fn status(&self) -> HealthStatus {
+        let mut st = self
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let poll_ok = match st.last_poll_ok {
+            Some(t) => Utc::now() - t < Duration::seconds(MAX_POLL_AGE_SECS),
+            None => false,
+        };
+        // a shard that has never connected isn't in shards, so count them too.
+        st.gateway_connected = st.shard_count > 0
+            && st.shards.len() as u64 >= st.shard_count
+            && st.shards.values().all(|c| *c);
+        st.healthy = poll_ok && st.gateway_connected;
+        st
+    }
+}
+
+// serve answers every http request on addr with the current health status as json,
+// 200 if healthy, 503 if not.
+pub async fn serve(addr: String, health: Arc<Health>) {
+    let listener = match TcpListener::bind(&addr).await {
+        Err(e) => {
+            println!("Failed to start health endpoint on {} {:?}", addr, e);
+            return;
+        }
+        Ok(l) => l,
+    };
+    println!("Health endpoint listening on {}", addr);
+    loop {
+        let mut sock = match listener.accept().await {
+            Err(e) => {
+                println!("Health endpoint failed to accept connection {:?}", e);
+                continue;
+            }
+            Ok((s, _)) => s,
+        };
+        let status = health.status();
+        spawn(async move {
+            // we don't care what was asked for, there's only one thing to say
+            let mut buf = [0u8; 1024];
+            let _ = sock.read(&mut buf).await;
+            let body = serde_json::to_string(&status).unwrap_or_default();
+            let resp = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                if status.healthy {
+                    "200 OK"
+                } else {
+                    "503 Service Unavailable"
+                },
+                body.len(),
+                body
+            );
+            if let Err(e) = sock.write_all(resp.as_bytes()).await {
+                println!("Failed to write health response {:?}", e);
+            }
+        });
+    }
+}
+
+// watchdog_task sends sd_notify READY & WATCHDOG pings to systemd while we're healthy.
+// Does nothing if systemd didn't give us a NOTIFY_SOCKET.
+pub async fn watchdog_task(health: Arc<Health>) {
+    let path = match env::var("NOTIFY_SOCKET") {
+        Err(_) => return,
+        Ok(p) => p,
+    };
+    // systemd wants a ping at least every WATCHDOG_USEC, so go at twice that rate.
+    let interval = env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|u| u.parse::<u64>().ok())
+        .map(|u| tokio::time::Duration::from_micros(u / 2))
+        .unwrap_or_else(|| tokio::time::Duration::from_secs(30));
+    let mut ready = false;
+    loop {
+        if health.status().healthy {
+            if !ready {
+                ready = sd_notify(&path, "READY=1");
+            }
+            sd_notify(&path, "WATCHDOG=1");
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+fn sd_notify(path: &str, msg: &str) -> bool {
+    let res = UnixDatagram::unbound().and_then(|s| s.send_to(msg.as_bytes(), path));
+    if let Err(e) = res {
+        println!("Failed to notify systemd {:?}", e);
+        return false;
+    }
+    true
+}
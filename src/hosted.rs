@@ -0,0 +1,211 @@
+use chrono::Utc;
+use serenity::http::Http;
+use serenity::model::prelude::ChannelId;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::time::Duration;
+
+use crate::config::Config;
+use crate::db::DbHandle;
+use crate::ir::{HostedSession, IrApi, IrClient, LeagueSession};
+use crate::ir_mock::MockIrApi;
+use crate::recorder::Recorder;
+use crate::sink::send_lines;
+use crate::style::escape_markdown;
+use crate::Messenger;
+
+// how often to look at the hosted sessions, they don't fill up as quickly as official races.
+const HOSTED_POLL_SECS: u64 = 300;
+// league sessions are announced once they're this close.
+const LEAGUE_AHEAD_DAYS: i64 = 7;
+
+// hosted_loop_task watches the hosted sessions for the ones channels asked about with
+// /watchevent, and the sessions of leagues asked about with /watchleague, and tells them
+// when one shows up and as people register for it. The race guide only has official
+// series, so this runs separately to the main poller.
+pub async fn hosted_loop_task(config: Arc<Config>, db: DbHandle, recorder: Option<Arc<Recorder>>) {
+    loop {
+        let res = match &config.ir_fixtures {
+            Some(dir) => match MockIrApi::load(Path::new(dir)) {
+                Ok(api) => hosted_loop(&api, &config, &db).await,
+                Err(e) => Err(e),
+            },
+            None => match IrClient::new(&config.ir_user, &config.ir_password).await {
+                Ok(client) => {
+                    let client = client.with_recorder(recorder.clone());
+                    hosted_loop(&client, &config, &db).await
+                }
+                Err(e) => Err(e.into()),
+            },
+        };
+        if let Err(e) = res {
+            println!("Error polling hosted sessions {:?}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(HOSTED_POLL_SECS)).await;
+    }
+}
+
+async fn hosted_loop<A: IrApi>(client: &A, config: &Config, db: &DbHandle) -> anyhow::Result<()> {
+    let http = Http::new(&config.discord_token);
+    // session_id -> entry count when we last looked, None until the first look so
+    // that starting up doesn't announce every session again.
+    let mut seen: Option<HashMap<i64, i64>> = None;
+    // the same for league sessions, keyed by (league_id, session_key).
+    let mut league_seen: Option<HashMap<(i64, i64), i64>> = None;
+    let mut league_names: HashMap<i64, String> = HashMap::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(HOSTED_POLL_SECS));
+    loop {
+        interval.tick().await;
+        let sessions = client.hosted_sessions().await?.sessions;
+        let watches = db.call(|db| db.event_watches()).await?;
+        let mut by_channel: HashMap<ChannelId, Vec<String>> = HashMap::new();
+        if let Some(seen) = &seen {
+            for (ch, msg) in hosted_messages(&sessions, &watches, seen) {
+                by_channel.entry(ch).or_default().push(msg);
+            }
+        }
+        seen = Some(
+            sessions
+                .iter()
+                .map(|s| (s.session_id, s.num_drivers))
+                .collect(),
+        );
+
+        let league_watches = db.call(|db| db.league_watches()).await?;
+        let leagues: HashSet<i64> = league_watches.iter().map(|(_, l)| *l).collect();
+        let mut counts = HashMap::new();
+        for league_id in leagues {
+            let name = match league_names.get(&league_id) {
+                Some(n) => n.clone(),
+                None => {
+                    let n = client
+                        .league(league_id)
+                        .await
+                        .map(|l| l.league_name)
+                        .unwrap_or_else(|_| format!("League {}", league_id));
+                    league_names.insert(league_id, n.clone());
+                    n
+                }
+            };
+            let sessions = match league_sessions(client, league_id).await {
+                Ok(s) => s,
+                Err(e) => {
+                    println!("Error getting sessions for league {} {:?}", league_id, e);
+                    // keep what we knew, otherwise every session would look new next time.
+                    if let Some(seen) = &league_seen {
+                        counts.extend(seen.iter().filter(|((l, _), _)| *l == league_id));
+                    }
+                    continue;
+                }
+            };
+            for s in sessions {
+                let key = (league_id, session_key(&s));
+                if let Some(seen) = &league_seen {
+                    if let Some(msg) = league_message(&name, &s, seen.get(&key).copied()) {
+                        for (ch, _) in league_watches.iter().filter(|(_, l)| *l == league_id) {
+                            by_channel.entry(*ch).or_default().push(msg.clone());
+                        }
+                    }
+                }
+                counts.insert(key, s.entry_count);
+            }
+        }
+        league_seen = Some(counts);
+
+        for (ch, lines) in by_channel {
+            let mut msger = Messenger::new(ch, &http, config.max_message_len);
+            send_lines(&mut msger, &lines).await;
+        }
+    }
+}
+
+// the league's upcoming sessions, from all its active seasons.
+async fn league_sessions<A: IrApi>(
+    client: &A,
+    league_id: i64,
+) -> anyhow::Result<Vec<LeagueSession>> {
+    let until = Utc::now() + chrono::Duration::days(LEAGUE_AHEAD_DAYS);
+    let mut res = Vec::new();
+    for season in client.league_seasons(league_id).await?.seasons {
+        if !season.active {
+            continue;
+        }
+        let sessions = client
+            .league_season_sessions(league_id, season.season_id)
+            .await?
+            .sessions;
+        res.extend(sessions.into_iter().filter(|s| {
+            !s.has_results && s.launch_at.is_some_and(|at| at > Utc::now() && at <= until)
+        }));
+    }
+    Ok(res)
+}
+
+// league sessions don't always have a session_id until they're close to starting.
+fn session_key(s: &LeagueSession) -> i64 {
+    s.session_id
+        .unwrap_or_else(|| s.launch_at.map_or(0, |t| t.timestamp()))
+}
+
+// what to say about a league session that's new, or has a different number of entries
+// to prev.
+fn league_message(league: &str, s: &LeagueSession, prev: Option<i64>) -> Option<String> {
+    let start = s.launch_at.map_or(0, |t| t.timestamp());
+    let league = escape_markdown(league);
+    match prev {
+        None => {
+            let mut msg = format!("\u{1F3C6} {} race <t:{}:f>", league, start);
+            if let Some(t) = &s.track {
+                msg.push_str(&format!(" at {}", escape_markdown(&t.track_name)));
+            }
+            msg.push_str(&format!(", {} registered.", s.entry_count));
+            Some(msg)
+        }
+        Some(prev) if prev != s.entry_count => Some(format!(
+            "{} race <t:{}:R> has {} registered.",
+            league, start, s.entry_count
+        )),
+        _ => None,
+    }
+}
+
+// the (channel, message) for each watched session that's new, or has a different number
+// of entries to last time.
+fn hosted_messages(
+    sessions: &[HostedSession],
+    watches: &[(ChannelId, String)],
+    seen: &HashMap<i64, i64>,
+) -> Vec<(ChannelId, String)> {
+    let mut res = Vec::new();
+    for s in sessions {
+        // anyone can host a session, and call it anything.
+        let session_name = escape_markdown(&s.session_name);
+        let msg = match seen.get(&s.session_id) {
+            None => {
+                let mut msg = format!(
+                    "\u{1F3C1} {} is open for registration, {} of {} registered",
+                    session_name, s.num_drivers, s.max_drivers
+                );
+                if let Some(t) = &s.track {
+                    msg.push_str(&format!(" at {}", escape_markdown(&t.track_name)));
+                }
+                if let Some(at) = s.launch_at {
+                    msg.push_str(&format!(", starts <t:{}:R>", at.timestamp()));
+                }
+                msg.push('.');
+                msg
+            }
+            Some(prev) if *prev != s.num_drivers => format!(
+                "{} has {} of {} registered.",
+                session_name, s.num_drivers, s.max_drivers
+            ),
+            _ => continue,
+        };
+        let name = s.session_name.to_lowercase();
+        for (ch, _) in watches.iter().filter(|(_, w)| name.contains(w.as_str())) {
+            res.push((*ch, msg.clone()));
+        }
+    }
+    res
+}
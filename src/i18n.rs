@@ -0,0 +1,420 @@
+use anyhow::anyhow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+// Locale is a language regbot can talk in, servers pick one with /language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Es,
+    Fr,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 4] = [Locale::En, Locale::De, Locale::Es, Locale::Fr];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+            Locale::Es => "es",
+            Locale::Fr => "fr",
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::De => "Deutsch",
+            Locale::Es => "Español",
+            Locale::Fr => "Français",
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Locale::ALL
+            .into_iter()
+            .find(|l| l.code() == s)
+            .ok_or_else(|| anyhow!("unknown locale {}", s))
+    }
+}
+
+// Msg identifies a string in the message catalogs. Strings can have numbered
+// placeholders, {0}, {1} etc, which tr fills in.
+#[derive(Debug, Clone, Copy)]
+pub enum Msg {
+    // series, minutes
+    RegOpen,
+    // series, count, splits, starts in
+    RegCount,
+    Official,
+    // splits
+    Splits,
+    StartsVerySoon,
+    // minutes
+    Minute,
+    // minutes
+    Minutes,
+    // series, count, splits
+    RegClosed,
+    // series, week, track
+    WeekOpen,
+    WeekOpenTail,
+    // series, week, track
+    NewWeek,
+    // series, season, week, track
+    NewSeason,
+    // series, week, track
+    LastDay,
+    // series, count, predicted count, minutes
+    Predicted,
+    // sof
+    EstSof,
+    // series, winners, sof, drivers
+    Results,
+    // series, winners, splits, sof, drivers
+    ResultsSplits,
+    // series
+    PollQuestion,
+    PollYes,
+    PollNo,
+    // series, yes, no
+    PollResults,
+    DigestTitle,
+    WeekScheduleTitle,
+    DailyDigestTitle,
+    // peak count, splits, official sessions, sessions
+    DailyDigestLine,
+    TickerOpen,
+    TickerClosed,
+    TickerWeek,
+    TickerLastDay,
+    NotWatching,
+    // language
+    LanguageSet,
+    Help,
+    StopWatching,
+    // minutes
+    RemindMe,
+    ShowStats,
+    StopAdminOnly,
+    Stopped,
+    StoppedAlready,
+    LostNotepad,
+    // starts at
+    ReminderTooSoon,
+    // remind at, starts at
+    ReminderSet,
+    // series, starts at
+    ReminderDue,
+    NoNextSession,
+}
+
+// returns the string for msg in the locale, every locale must have every string.
+pub fn text(locale: Locale, msg: Msg) -> &'static str {
+    use Locale::*;
+    use Msg::*;
+    match (locale, msg) {
+        (En, RegOpen) => "{0}: Registration open!, {1} minutes til race time",
+        (De, RegOpen) => "{0}: Anmeldung offen!, noch {1} Minuten bis zum Rennen",
+        (Es, RegOpen) => "{0}: ¡Inscripción abierta!, faltan {1} minutos para la carrera",
+        (Fr, RegOpen) => "{0} : Inscriptions ouvertes !, course dans {1} minutes",
+
+        (En, RegCount) => "{0}: {1} registered. {2}Session starts in {3}",
+        (De, RegCount) => "{0}: {1} angemeldet. {2}Session startet in {3}",
+        (Es, RegCount) => "{0}: {1} inscritos. {2}La sesión empieza en {3}",
+        (Fr, RegCount) => "{0} : {1} inscrits. {2}La session commence dans {3}",
+
+        (En, Official) => "Official! ",
+        (De, Official) => "Offiziell! ",
+        (Es, Official) => "¡Oficial! ",
+        (Fr, Official) => "Officielle ! ",
+
+        (En, Splits) => "{0} splits! ",
+        (De, Splits) => "{0} Splits! ",
+        (Es, Splits) => "¡{0} splits! ",
+        (Fr, Splits) => "{0} splits ! ",
+
+        (En, StartsVerySoon) => "less than a minute! \u{1f3ce}",
+        (De, StartsVerySoon) => "weniger als einer Minute! \u{1f3ce}",
+        (Es, StartsVerySoon) => "¡menos de un minuto! \u{1f3ce}",
+        (Fr, StartsVerySoon) => "moins d'une minute ! \u{1f3ce}",
+
+        (En, Minute) => "{0} minute",
+        (De, Minute) => "{0} Minute",
+        (Es, Minute) => "{0} minuto",
+        (Fr, Minute) => "{0} minute",
+
+        (En, Minutes) => "{0} minutes",
+        (De, Minutes) => "{0} Minuten",
+        (Es, Minutes) => "{0} minutos",
+        (Fr, Minutes) => "{0} minutes",
+
+        (En, RegClosed) => "{0}: registration closed \u{26d4} {1} registered {2}.",
+        (De, RegClosed) => "{0}: Anmeldung geschlossen \u{26d4} {1} angemeldet {2}.",
+        (Es, RegClosed) => "{0}: inscripción cerrada \u{26d4} {1} inscritos {2}.",
+        (Fr, RegClosed) => "{0} : inscriptions fermées \u{26d4} {1} inscrits {2}.",
+
+        (En, WeekOpen) => "{0}: week {1} is at {2}",
+        (De, WeekOpen) => "{0}: Woche {1} ist auf {2}",
+        (Es, WeekOpen) => "{0}: la semana {1} es en {2}",
+        (Fr, WeekOpen) => "{0} : la semaine {1} est à {2}",
+
+        (En, WeekOpenTail) => ", time trials and open qualifying are open \u{23f1}",
+        (De, WeekOpenTail) => ", Zeitfahren und offenes Qualifying sind offen \u{23f1}",
+        (Es, WeekOpenTail) => {
+            ", las contrarreloj y la clasificación abierta están abiertas \u{23f1}"
+        }
+        (Fr, WeekOpenTail) => {
+            ", les contre-la-montre et les qualifications ouvertes sont ouverts \u{23f1}"
+        }
+
+        (En, NewWeek) => "{0}: week {1} at {2} this week! \u{1f5d3}",
+        (De, NewWeek) => "{0}: Woche {1} diese Woche auf {2}! \u{1f5d3}",
+        (Es, NewWeek) => "{0}: ¡semana {1} en {2} esta semana! \u{1f5d3}",
+        (Fr, NewWeek) => "{0} : semaine {1} à {2} cette semaine ! \u{1f5d3}",
+
+        (En, NewSeason) => "{0}: {1} has started \u{1f389} week {2} is at {3}",
+        (De, NewSeason) => "{0}: {1} hat begonnen \u{1f389} Woche {2} ist auf {3}",
+        (Es, NewSeason) => "{0}: ¡ha empezado {1}! \u{1f389} la semana {2} es en {3}",
+        (Fr, NewSeason) => "{0} : {1} a commencé \u{1f389} la semaine {2} est à {3}",
+
+        (En, LastDay) => "{0}: last day for week {1} at {2}",
+        (De, LastDay) => "{0}: letzter Tag für Woche {1} auf {2}",
+        (Es, LastDay) => "{0}: último día de la semana {1} en {2}",
+        (Fr, LastDay) => "{0} : dernier jour de la semaine {1} à {2}",
+
+        (En, Predicted) => "{0}: {1} registered and filling up, on course to go official with about {2} in {3} minutes \u{1f52e}",
+        (De, Predicted) => "{0}: {1} angemeldet und es werden mehr, auf Kurs zu offiziell mit etwa {2} in {3} Minuten \u{1f52e}",
+        (Es, Predicted) => "{0}: {1} inscritos y subiendo, en camino de ser oficial con unos {2} en {3} minutos \u{1f52e}",
+        (Fr, Predicted) => "{0} : {1} inscrits et ça se remplit, en route pour être officielle avec environ {2} dans {3} minutes \u{1f52e}",
+
+        (En, EstSof) => "est. SOF {0}",
+        (De, EstSof) => "geschätzte SoF {0}",
+        (Es, EstSof) => "SOF estimado {0}",
+        (Fr, EstSof) => "SOF estimé {0}",
+
+        (En, Results) => "{0}: results are in \u{1f3c6} {1} won, SOF {2} with {3} drivers.",
+        (De, Results) => "{0}: Ergebnisse sind da \u{1f3c6} {1} hat gewonnen, SoF {2} mit {3} Fahrern.",
+        (Es, Results) => "{0}: ya hay resultados \u{1f3c6} ganó {1}, SOF {2} con {3} pilotos.",
+        (Fr, Results) => "{0} : les résultats sont là \u{1f3c6} {1} a gagné, SOF {2} avec {3} pilotes.",
+
+        (En, ResultsSplits) => "{0}: results are in \u{1f3c6} {1} won the top split of {2}, SOF {3}, {4} drivers in all.",
+        (De, ResultsSplits) => "{0}: Ergebnisse sind da \u{1f3c6} {1} hat den Top-Split von {2} gewonnen, SoF {3}, insgesamt {4} Fahrer.",
+        (Es, ResultsSplits) => "{0}: ya hay resultados \u{1f3c6} {1} ganó el split principal de {2}, SOF {3}, {4} pilotos en total.",
+        (Fr, ResultsSplits) => "{0} : les résultats sont là \u{1f3c6} {1} a gagné le meilleur split sur {2}, SOF {3}, {4} pilotes au total.",
+
+        (En, PollQuestion) => "{0}: Racing this one?",
+        (De, PollQuestion) => "{0}: Fährst du mit?",
+        (Es, PollQuestion) => "{0}: ¿Corres esta?",
+        (Fr, PollQuestion) => "{0} : Tu cours celle-ci ?",
+
+        (En, PollYes) => "I'm in",
+        (De, PollYes) => "Bin dabei",
+        (Es, PollYes) => "Me apunto",
+        (Fr, PollYes) => "J'en suis",
+
+        (En, PollNo) => "Not this time",
+        (De, PollNo) => "Diesmal nicht",
+        (Es, PollNo) => "Esta vez no",
+        (Fr, PollNo) => "Pas cette fois",
+
+        (En, PollResults) => "{0}: {1} said they're racing, {2} are sitting this one out.",
+        (De, PollResults) => "{0}: {1} fahren mit, {2} setzen diesmal aus.",
+        (Es, PollResults) => "{0}: {1} dijeron que corren, {2} se quedan fuera esta vez.",
+        (Fr, PollResults) => "{0} : {1} ont dit qu'ils courent, {2} passent leur tour.",
+
+        (En, DigestTitle) => "Race registration",
+        (De, DigestTitle) => "Rennanmeldung",
+        (Es, DigestTitle) => "Inscripción de carreras",
+        (Fr, DigestTitle) => "Inscriptions aux courses",
+
+        (En, WeekScheduleTitle) => "This week's tracks",
+        (De, WeekScheduleTitle) => "Die Strecken dieser Woche",
+        (Es, WeekScheduleTitle) => "Los circuitos de esta semana",
+        (Fr, WeekScheduleTitle) => "Les circuits de la semaine",
+
+        (En, DailyDigestTitle) => "Yesterday's racing",
+        (De, DailyDigestTitle) => "Die Rennen von gestern",
+        (Es, DailyDigestTitle) => "Las carreras de ayer",
+        (Fr, DailyDigestTitle) => "Les courses d'hier",
+
+        (En, DailyDigestLine) => {
+            "Peaked at {0} registered in {1} split(s), {2} of {3} sessions went official."
+        }
+        (De, DailyDigestLine) => {
+            "Bis zu {0} angemeldet in {1} Split(s), {2} von {3} Rennen waren offiziell."
+        }
+        (Es, DailyDigestLine) => {
+            "Máximo de {0} inscritos en {1} split(s), {2} de {3} sesiones fueron oficiales."
+        }
+        (Fr, DailyDigestLine) => {
+            "Jusqu'à {0} inscrits en {1} split(s), {2} sessions officielles sur {3}."
+        }
+
+        (En, TickerOpen) => "open",
+        (De, TickerOpen) => "offen",
+        (Es, TickerOpen) => "abierta",
+        (Fr, TickerOpen) => "ouverte",
+
+        (En, TickerClosed) => "closed",
+        (De, TickerClosed) => "geschlossen",
+        (Es, TickerClosed) => "cerrada",
+        (Fr, TickerClosed) => "fermée",
+
+        (En, TickerWeek) => "wk",
+        (De, TickerWeek) => "Wo",
+        (Es, TickerWeek) => "sem",
+        (Fr, TickerWeek) => "sem",
+
+        (En, TickerLastDay) => "last day",
+        (De, TickerLastDay) => "letzter Tag",
+        (Es, TickerLastDay) => "último día",
+        (Fr, TickerLastDay) => "dernier jour",
+
+        (En, NotWatching) => "This channel isn't watching that series.",
+        (De, NotWatching) => "Dieser Kanal beobachtet diese Serie nicht.",
+        (Es, NotWatching) => "Este canal no está siguiendo esa serie.",
+        (Fr, NotWatching) => "Ce salon ne suit pas cette série.",
+
+        (En, LanguageSet) => "Okay, I'll talk in {0} in this server.",
+        (De, LanguageSet) => "Alles klar, ich spreche auf diesem Server {0}.",
+        (Es, LanguageSet) => "Vale, hablaré en {0} en este servidor.",
+        (Fr, LanguageSet) => "D'accord, je parlerai en {0} sur ce serveur.",
+
+        (En, Help) => HELP_EN,
+        (De, Help) => HELP_DE,
+        (Es, Help) => HELP_ES,
+        (Fr, Help) => HELP_FR,
+
+        (En, StopWatching) => "Stop watching",
+        (De, StopWatching) => "Nicht mehr beobachten",
+        (Es, StopWatching) => "Dejar de seguir",
+        (Fr, StopWatching) => "Ne plus suivre",
+
+        (En, RemindMe) => "Remind me {0} min before start",
+        (De, RemindMe) => "{0} Min. vor dem Start erinnern",
+        (Es, RemindMe) => "Recordármelo {0} min antes",
+        (Fr, RemindMe) => "Me le rappeler {0} min avant",
+
+        (En, ShowStats) => "Show stats",
+        (De, ShowStats) => "Statistiken zeigen",
+        (Es, ShowStats) => "Ver estadísticas",
+        (Fr, ShowStats) => "Voir les stats",
+
+        (En, StopAdminOnly) => "Only server admins can stop a watch from here, or use /nomore.",
+        (De, StopAdminOnly) => {
+            "Nur Server-Admins können eine Beobachtung hier beenden, sonst nutzt /nomore."
+        }
+        (Es, StopAdminOnly) => {
+            "Solo los administradores del servidor pueden dejar de seguir desde aquí, si no usad /nomore."
+        }
+        (Fr, StopAdminOnly) => {
+            "Seuls les admins du serveur peuvent arrêter un suivi d'ici, sinon utilisez /nomore."
+        }
+
+        (En, Stopped) => {
+            "Okay, I wont mention it again. If that was a mistake, /undo brings it back."
+        }
+        (De, Stopped) => {
+            "Alles klar, ich erwähne sie nicht mehr. Falls das ein Versehen war, holt /undo sie zurück."
+        }
+        (Es, Stopped) => "Vale, no la mencionaré más. Si ha sido un error, /undo la recupera.",
+        (Fr, Stopped) => {
+            "D'accord, je n'en parlerai plus. Si c'était une erreur, /undo la rétablit."
+        }
+
+        (En, StoppedAlready) => "This channel isn't watching that series anymore.",
+        (De, StoppedAlready) => "Dieser Kanal beobachtet diese Serie nicht mehr.",
+        (Es, StoppedAlready) => "Este canal ya no sigue esa serie.",
+        (Fr, StoppedAlready) => "Ce salon ne suit plus cette série.",
+
+        (En, LostNotepad) => "Sorry, I seem to have lost my notepad, please try again later.",
+        (De, LostNotepad) => {
+            "Entschuldigung, ich habe wohl meinen Notizblock verlegt, bitte versucht es später noch einmal."
+        }
+        (Es, LostNotepad) => "Lo siento, parece que he perdido mi libreta, inténtalo más tarde.",
+        (Fr, LostNotepad) => "Désolé, j'ai l'air d'avoir perdu mon carnet, réessayez plus tard.",
+
+        (En, ReminderTooSoon) => "It starts {0}, that's too soon for a reminder.",
+        (De, ReminderTooSoon) => "Es startet {0}, das ist zu bald für eine Erinnerung.",
+        (Es, ReminderTooSoon) => "Empieza {0}, es demasiado pronto para un recordatorio.",
+        (Fr, ReminderTooSoon) => "Ça commence {0}, c'est trop tôt pour un rappel.",
+
+        (En, ReminderSet) => "Okay, I'll DM you {0}, the session starts at {1}.",
+        (De, ReminderSet) => "Alles klar, ich schreibe dir {0}, die Session startet um {1}.",
+        (Es, ReminderSet) => "Vale, te escribiré {0}, la sesión empieza a las {1}.",
+        (Fr, ReminderSet) => "D'accord, je t'enverrai un message {0}, la session commence à {1}.",
+
+        (En, ReminderDue) => "{0}: the session starts {1}, time to get in the car.",
+        (De, ReminderDue) => "{0}: die Session startet {1}, Zeit einzusteigen.",
+        (Es, ReminderDue) => "{0}: la sesión empieza {1}, hora de subirse al coche.",
+        (Fr, ReminderDue) => "{0} : la session commence {1}, il est temps de monter en voiture.",
+
+        (En, NoNextSession) => {
+            "I don't know when the next session for that series is yet, try again a bit later."
+        }
+        (De, NoNextSession) => {
+            "Ich weiß noch nicht, wann die nächste Session dieser Serie ist, versuch es etwas später noch einmal."
+        }
+        (Es, NoNextSession) => {
+            "Aún no sé cuándo es la próxima sesión de esa serie, inténtalo un poco más tarde."
+        }
+        (Fr, NoNextSession) => {
+            "Je ne sais pas encore quand aura lieu la prochaine session de cette série, réessayez un peu plus tard."
+        }
+    }
+}
+
+// returns the string for msg in the locale with the placeholders filled in from args.
+pub fn tr(locale: Locale, msg: Msg, args: &[&dyn Display]) -> String {
+    let mut s = text(locale, msg).to_string();
+    for (i, a) in args.iter().enumerate() {
+        s = s.replace(&format!("{{{}}}", i), &a.to_string());
+    }
+    s
+}
+
+const HELP_EN:&str = "Hey there, I'm Reginald. While I sip my coffee I'll keep an eye on race registrations for you. Let me know what series you're interested in and I'll message a channel when I see some activity for that series. Use the /watch command to select a series.
+
+You can control how many race entries are needed before i say anything with the min_reg option. I can also stop yammering on about it once there's a critical mass registered, use the max_reg option. If you want to always know when race registration opens or closes, you can use the open and close options to turn that on. If the counts bounce around too much right after registration opens, use hold_mins to have me wait a bit before mentioning them, and cooldown_mins leaves a gap between count announcements for busy series. Use the poll option and I'll ask who's racing when registration opens, and let you know how that went when it closes. If a series opens the next slot as soon as the last one closes, reopen_mins stops me announcing that. Turn on qualifying and I'll tell you when each new race week opens up time trials and open qualifying, or week_change just tells you the new track, and last_day reminds you when it's the last day to race a week's track. Turn on predict and I'll say when a session is filling up fast enough to go official, going by how the last week's sessions filled up, and results posts who won once an official race is over. For series that race every hour, first_open only tells you about registration opening for the first race of the day. If you can only race at certain times, after, before and days (like sat,sun or weekdays) skip sessions outside those times (server admins can set the server's /timezone).
+
+By default I'll start reporting registrations at 50% of official and stop if it reaches halfway between official and splitting. If you don't want to think about all that, pick a preset like quiet or everything and I'll fill in the rest.
+
+The entry/split numbers reported at registration closed might not match exactly the race session(s) as you can't get the numbers until the end of the race.
+
+If you forget what you asked for, you can /watching to find out, the Edit buttons there let you change the counts and open/close settings, server admins can use /watching-all to see every channel. /watchmany adds several series at once. You can also /nomore if you don't care about a series anymore (or /nomore all to clear the channel), and /undo if you change your mind. If it gets busy, /style ticker will keep my announcements short (/previewstyles shows what each style looks like), or use /statusboard to have me keep one pinned message up to date instead. /digest rolls each round of announcements up into one message. Use /mute to stop me mentioning a whole category (say dirt oval) in a channel. If you don't fancy this week's track use /snooze and I'll keep quiet about it until the next race week, or for a special event /boost has me announce a series more eagerly for a few days. Server admins can /block a series they never want to hear about, and /language picks the language I talk in.";
+
+const HELP_DE:&str = "Hallo, ich bin Reginald. Während ich meinen Kaffee trinke, behalte ich die Rennanmeldungen für euch im Auge. Sagt mir, welche Serien euch interessieren, und ich schreibe in einen Kanal, sobald sich bei dieser Serie etwas tut. Wählt eine Serie mit dem Befehl /watch aus.
+
+Mit min_reg legt ihr fest, wie viele Anmeldungen es braucht, bevor ich etwas sage, und mit max_reg höre ich auf, sobald genug Leute angemeldet sind. Mit open und close sage ich immer Bescheid, wenn die Anmeldung öffnet oder schließt. Wenn die Zahlen direkt nach dem Öffnen stark schwanken, lässt mich hold_mins etwas warten, und cooldown_mins sorgt für Pausen zwischen den Meldungen bei vollen Serien. Mit poll frage ich beim Öffnen, wer mitfährt, und melde das Ergebnis beim Schließen. reopen_mins verhindert eine Meldung, wenn der nächste Slot direkt nach dem letzten öffnet. qualifying meldet, wenn Zeitfahren und offenes Qualifying einer neuen Rennwoche öffnen, week_change nennt nur die neue Strecke, last_day erinnert an den letzten Tag einer Strecke, und first_open meldet nur die erste Öffnung des Tages. Mit predict sage ich Bescheid, wenn sich eine Session schnell genug füllt, um offiziell zu werden, gemessen an den Sessions der letzten Woche, und mit results melde ich nach einem offiziellen Rennen, wer gewonnen hat. Wer nur zu bestimmten Zeiten fahren kann, nutzt after, before und days (z. B. sat,sun oder weekdays), dann überspringe ich Rennen außerhalb dieser Zeiten (Admins können mit /timezone die Zeitzone des Servers einstellen).
+
+Standardmäßig melde ich ab 50% der Offiziell-Grenze und höre auf halbem Weg zwischen Offiziell und Splitting auf. Wer sich darum nicht kümmern will, wählt ein preset wie quiet oder everything.
+
+Die Anmelde- und Splitzahlen, die ich beim Schließen der Anmeldung melde, passen nicht unbedingt genau zu den Rennen, da die echten Zahlen erst nach dem Rennen feststehen.
+
+Mit /watching seht ihr, was beobachtet wird (die Edit-Knöpfe ändern die Einstellungen), Admins sehen mit /watching-all alle Kanäle. /watchmany fügt mehrere Serien auf einmal hinzu. /nomore entfernt eine Serie (oder /nomore all alle im Kanal), und /undo holt sie zurück, falls ihr es euch anders überlegt. Wird es zu voll, hält /style ticker die Meldungen kurz (/previewstyles zeigt, wie jeder Stil aussieht), /statusboard pflegt eine angepinnte Nachricht und /digest fasst jede Runde in einer Nachricht zusammen. /mute blendet eine ganze Kategorie aus, /snooze pausiert eine Serie bis zur nächsten Rennwoche und /boost meldet eine Serie ein paar Tage lang eifriger. Admins können mit /block Serien sperren und mit /language die Sprache wählen.";
+
+const HELP_ES:&str = "Hola, soy Reginald. Mientras tomo mi café vigilo las inscripciones a las carreras por vosotros. Decidme qué series os interesan y escribiré en un canal cuando vea actividad en esa serie. Usad el comando /watch para elegir una serie.
+
+Con min_reg decidís cuántas inscripciones hacen falta antes de que diga nada, y con max_reg dejo de insistir cuando ya hay suficientes inscritos. Con open y close aviso siempre que la inscripción abre o cierra. Si los números bailan mucho justo después de abrir, hold_mins me hace esperar un poco, y cooldown_mins deja un hueco entre avisos en las series con mucho movimiento. Con poll pregunto quién corre al abrir la inscripción y os cuento el resultado al cerrar. reopen_mins evita el aviso cuando el siguiente turno abre justo después de cerrar el anterior. qualifying avisa cuando abren las contrarreloj y la clasificación abierta de cada semana, week_change solo dice el nuevo circuito, last_day recuerda el último día para correr un circuito, y first_open solo avisa de la primera apertura del día. Con predict aviso cuando una sesión se llena lo bastante rápido para ser oficial, según cómo se llenaron las de la última semana, y con results os cuento quién ganó al terminar una carrera oficial. Si solo podéis correr a ciertas horas, after, before y days (como sat,sun o weekdays) omiten las carreras fuera de ese horario (los administradores pueden fijar la zona horaria del servidor con /timezone).
+
+Por defecto empiezo a avisar al 50% del mínimo oficial y paro a mitad de camino entre oficial y splits. Si no queréis pensar en eso, elegid un preset como quiet o everything.
+
+Los números de inscritos y splits que doy al cerrar la inscripción pueden no coincidir exactamente con la(s) carrera(s), ya que no se conocen hasta que termina la carrera.
+
+Con /watching veis qué se sigue (los botones Edit cambian los ajustes) y los administradores ven todos los canales con /watching-all. /watchmany añade varias series a la vez. /nomore quita una serie (o /nomore all todas las del canal), y /undo la recupera si cambiáis de opinión. Si hay mucho ruido, /style ticker acorta los avisos (/previewstyles muestra cómo queda cada estilo), /statusboard mantiene un mensaje fijado y /digest junta cada ronda en un solo mensaje. /mute silencia una categoría entera, /snooze pausa una serie hasta la próxima semana y /boost avisa con más ganas durante unos días. Los administradores pueden bloquear series con /block y elegir el idioma con /language.";
+
+const HELP_FR:&str = "Salut, je suis Reginald. Pendant que je sirote mon café, je surveille les inscriptions aux courses pour vous. Dites-moi quelles séries vous intéressent et j'écrirai dans un salon dès que je vois de l'activité sur cette série. Utilisez la commande /watch pour choisir une série.
+
+Avec min_reg vous choisissez combien d'inscrits il faut avant que je dise quoi que ce soit, et avec max_reg j'arrête une fois qu'il y a assez de monde. Avec open et close je préviens toujours à l'ouverture ou à la fermeture des inscriptions. Si les chiffres bougent beaucoup juste après l'ouverture, hold_mins me fait patienter un peu, et cooldown_mins laisse un délai entre les annonces pour les séries chargées. Avec poll je demande qui court à l'ouverture et je donne le résultat à la fermeture. reopen_mins évite l'annonce quand le créneau suivant ouvre juste après la fermeture du précédent. qualifying annonce l'ouverture des contre-la-montre et des qualifications ouvertes de chaque semaine, week_change donne juste le nouveau circuit, last_day rappelle le dernier jour pour rouler sur un circuit, et first_open n'annonce que la première ouverture de la journée. Avec predict je préviens quand une session se remplit assez vite pour devenir officielle, d'après les sessions de la semaine passée, et avec results je donne le vainqueur une fois une course officielle terminée. Si vous ne pouvez courir qu'à certaines heures, after, before et days (comme sat,sun ou weekdays) ignorent les courses en dehors de ces horaires (les admins peuvent régler le fuseau du serveur avec /timezone).
+
+Par défaut j'annonce à partir de 50% du seuil officiel et j'arrête à mi-chemin entre officiel et splits. Si vous ne voulez pas y penser, choisissez un preset comme quiet ou everything.
+
+Les nombres d'inscrits et de splits annoncés à la fermeture des inscriptions peuvent ne pas correspondre exactement à la ou aux courses, car on ne les connaît qu'à la fin de la course.
+
+/watching montre ce qui est suivi (les boutons Edit changent les réglages) et les admins voient tous les salons avec /watching-all. /watchmany ajoute plusieurs séries d'un coup. /nomore retire une série (ou /nomore all toutes celles du salon), et /undo la rétablit si vous changez d'avis. Si ça devient trop bavard, /style ticker raccourcit les annonces (/previewstyles montre à quoi ressemble chaque style), /statusboard tient un message épinglé à jour et /digest regroupe chaque tournée en un seul message. /mute coupe une catégorie entière, /snooze met une série en pause jusqu'à la semaine suivante et /boost l'annonce plus activement pendant quelques jours. Les admins peuvent bloquer des séries avec /block et choisir la langue avec /language.";
@@ -1,17 +1,151 @@
-use anyhow::anyhow;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::{
+    HeaderMap, HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serenity::async_trait;
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+use tokio::time::Duration;
+
+use crate::ir_schema::{self, SchemaWatch};
+use crate::recorder::Recorder;
 
 const IR_API: &str = "https://members-ng.iracing.com/data";
 
+// how many times to try a request before giving up.
+const MAX_ATTEMPTS: u32 = 4;
+// once the rate limit remaining drops to this, wait for the reset before making more requests.
+const RATE_LIMIT_LOW_WATER: i64 = 5;
+// never wait longer than this for a rate limit reset.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(120);
+
+#[derive(Debug)]
+pub enum IrError {
+    // the credentials were rejected, retrying won't help.
+    Auth(String),
+    // our login session has expired, need to login again.
+    SessionExpired,
+    // still rate limited after retrying.
+    RateLimited {
+        reset: Option<DateTime<Utc>>,
+    },
+    // some other unsuccessful http response.
+    Http {
+        status: StatusCode,
+        url: String,
+        body: String,
+    },
+    // couldn't talk to iRacing at all.
+    Network(reqwest::Error),
+    // the response wasn't what we expected.
+    Decode {
+        url: String,
+        err: serde_json::Error,
+    },
+    // we were asked for something that can't be requested, no request was made.
+    BadRequest(String),
+}
+impl Display for IrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IrError::Auth(body) => write!(f, "failed to authenticate: {}", body),
+            IrError::SessionExpired => f.write_str("session expired"),
+            IrError::RateLimited { reset } => write!(f, "rate limited until {:?}", reset),
+            IrError::Http { status, url, body } => {
+                write!(f, "http error {} for {}\n{}", status, url, body)
+            }
+            IrError::Network(e) => write!(f, "network error {}", e),
+            IrError::Decode { url, err } => {
+                write!(f, "unable to decode response from {}: {}", url, err)
+            }
+            IrError::BadRequest(msg) => write!(f, "bad request: {}", msg),
+        }
+    }
+}
+impl std::error::Error for IrError {}
+impl From<reqwest::Error> for IrError {
+    fn from(e: reqwest::Error) -> Self {
+        IrError::Network(e)
+    }
+}
+
+#[derive(Debug, Default)]
+struct RateLimit {
+    remaining: Option<i64>,
+    reset: Option<DateTime<Utc>>,
+}
+
+// the validators for conditional requests
+#[derive(Debug, Default, Clone)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    validators: Validators,
+    hash: Vec<u8>,
+    body: String,
+}
+
+// IrApi is the subset of the iRacing data API the watcher needs.
+#[async_trait]
+pub trait IrApi: Send + Sync {
+    // returns the seasons, and false if they're unchanged since the last call.
+    async fn seasons(&self) -> Result<(Vec<Season>, bool), IrError>;
+    // returns the series, and false if they're unchanged since the last call.
+    async fn series(&self) -> Result<(Vec<Series>, bool), IrError>;
+    async fn race_guide(&self) -> Result<RaceGuide, IrError>;
+    // the seasons running in a quarter, this has the bits that series/seasons doesn't.
+    async fn season_list(&self, year: i64, quarter: i64) -> Result<SeasonList, IrError>;
+    // returns the car classes, and false if they're unchanged since the last call.
+    async fn car_classes(&self) -> Result<(Vec<CarClass>, bool), IrError>;
+    // returns the cars, and false if they're unchanged since the last call.
+    async fn cars(&self) -> Result<(Vec<Car>, bool), IrError>;
+    // the hosted sessions that are open for registration.
+    async fn hosted_sessions(&self) -> Result<HostedSessions, IrError>;
+    async fn league(&self, league_id: i64) -> Result<League, IrError>;
+    async fn league_seasons(&self, league_id: i64) -> Result<LeagueSeasons, IrError>;
+    async fn league_season_sessions(
+        &self,
+        league_id: i64,
+        season_id: i64,
+    ) -> Result<LeagueSessions, IrError>;
+    // the official race results for the series since since, a row per split.
+    async fn search_series(
+        &self,
+        series_id: i64,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SeriesResult>, IrError>;
+    // the full results of one split of a race.
+    async fn results(&self, subsession_id: i64) -> Result<SubsessionResult, IrError>;
+    // how many requests we can make before being rate limited, None if we don't know.
+    fn rate_limit_remaining(&self) -> Option<i64>;
+    // when the rate limit next resets, None if we don't know.
+    fn rate_limit_reset(&self) -> Option<DateTime<Utc>>;
+    // tracks changes to the shape of the data returned.
+    fn schema(&self) -> &SchemaWatch;
+}
+
 pub struct IrClient {
     client: reqwest::Client,
+    rate_limit: Mutex<RateLimit>,
+    // most recent response for paths fetched with fetch_cached.
+    cache: Mutex<HashMap<String, CacheEntry>>,
+    schema: SchemaWatch,
+    // keeps a copy of the responses, if recording is on.
+    recorder: Option<Arc<Recorder>>,
 }
 
 impl IrClient {
-    pub async fn new(username: &str, password: &str) -> Result<IrClient, anyhow::Error> {
+    pub async fn new(username: &str, password: &str) -> Result<IrClient, IrError> {
         let c = reqwest::Client::builder().cookie_store(true).build()?;
 
         let mut hasher = Sha256::new();
@@ -29,76 +163,394 @@ impl IrClient {
             println!("auth error: status {}", res.status());
             let body = res.text().await?;
             println!("{}", body);
-            return Err(anyhow!("failed to authenticate: {}", body));
+            return Err(IrError::Auth(body));
         }
         let _body = res.text().await?;
-        Ok(IrClient { client: c })
+        Ok(IrClient {
+            client: c,
+            rate_limit: Mutex::new(RateLimit::default()),
+            cache: Mutex::new(HashMap::new()),
+            schema: SchemaWatch::default(),
+            recorder: None,
+        })
+    }
+
+    pub fn with_recorder(mut self, recorder: Option<Arc<Recorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    fn record(&self, path: &str, body: &str) {
+        if let Some(r) = &self.recorder {
+            r.record(path, body);
+        }
     }
 
     // returns the parsed result of the supplied url, dealing with the additional
     // "link" extra resolution needed by the iracing API.
-    pub async fn fetch<T: serde::de::DeserializeOwned>(
+    pub async fn fetch<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, IrError> {
+        let u = format!("{}/{}", IR_API, path);
+        let lnk: Link = self.get_json(&u).await?;
+        match self.get(&lnk.link, &Validators::default()).await? {
+            Some((body, _)) => {
+                self.record(path, &body);
+                self.decode_payload(path, &lnk.link, &body)
+            }
+            None => Err(not_modified(&lnk.link)),
+        }
+    }
+
+    // like fetch, but also returns false if the data is the same as the last time it was fetched.
+    // Uses conditional requests where the server supports them, and otherwise compares
+    // a hash of the response.
+    pub async fn fetch_cached<T: serde::de::DeserializeOwned>(
         &self,
         path: &str,
-    ) -> Result<T, anyhow::Error> {
+    ) -> Result<(T, bool), IrError> {
         let u = format!("{}/{}", IR_API, path);
-        let req = self.client.get(u.clone());
-        let res = req.send().await?;
-        if !res.status().is_success() {
-            if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-                let limit = res.headers().get("x-ratelimit-limit");
-                let remaining = res.headers().get("x-ratelimit-remaining");
-                let reset = res.headers().get("x-ratelimit-reset");
+        let lnk: Link = self.get_json(&u).await?;
+        let cached = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(path)
+            .cloned();
+        let validators = cached
+            .as_ref()
+            .map(|c| c.validators.clone())
+            .unwrap_or_default();
+        let (body, changed) = match (self.get(&lnk.link, &validators).await?, cached) {
+            (None, Some(c)) => (c.body, false),
+            (None, None) => return Err(not_modified(&lnk.link)),
+            (Some((body, validators)), cached) => {
+                self.record(path, &body);
+                let hash = Sha256::digest(&body).to_vec();
+                let changed = cached.map(|c| c.hash != hash).unwrap_or(true);
+                self.cache
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(
+                        path.to_string(),
+                        CacheEntry {
+                            validators,
+                            hash,
+                            body: body.clone(),
+                        },
+                    );
+                (body, changed)
+            }
+        };
+        Ok((self.decode_payload(path, &lnk.link, &body)?, changed))
+    }
+
+    // decodes the payload for path, checking it for schema drift along the way.
+    fn decode_payload<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        url: &str,
+        body: &str,
+    ) -> Result<T, IrError> {
+        let v: Value = decode(url, body)?;
+        let payload = path.split('?').next().unwrap_or(path);
+        if let Some(shapes) = ir_schema::shapes_for(payload) {
+            self.schema.check(payload, &v, shapes);
+        }
+        serde_json::from_value(v).map_err(|err| {
+            println!("error {:?} decoding {}", err, payload);
+            IrError::Decode {
+                url: url.to_string(),
+                err,
+            }
+        })
+    }
+
+    // like fetch, for the payloads that are too big for one response and come as a list
+    // of chunk files instead.
+    pub async fn fetch_chunked<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> Result<Vec<T>, IrError> {
+        let chunked: Chunked = self.fetch(path).await?;
+        let info = match chunked.data.chunk_info {
+            Some(i) => i,
+            None => return Ok(Vec::new()),
+        };
+        // the chunk files have their own shape, checked as "<path> chunk".
+        let payload = format!("{} chunk", path.split('?').next().unwrap_or(path));
+        let mut res = Vec::new();
+        for (i, name) in info.chunk_file_names.iter().enumerate() {
+            let url = format!("{}{}", info.base_download_url, name);
+            let rows: Value = match self.get(&url, &Validators::default()).await? {
+                Some((body, _)) => {
+                    // numbered, so the chunks from one fetch don't overwrite each other.
+                    self.record(&format!("{} {}", payload, i), &body);
+                    decode(&url, &body)?
+                }
+                None => return Err(not_modified(&url)),
+            };
+            if let Some(shapes) = ir_schema::shapes_for(&payload) {
+                self.schema.check(&payload, &rows, shapes);
+            }
+            let rows: Vec<T> = serde_json::from_value(rows).map_err(|err| {
+                println!("error {:?} decoding {}", err, payload);
+                IrError::Decode { url, err }
+            })?;
+            res.extend(rows);
+        }
+        Ok(res)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, IrError> {
+        match self.get(url, &Validators::default()).await? {
+            Some((body, _)) => decode(url, &body),
+            None => Err(not_modified(url)),
+        }
+    }
+
+    // makes a GET request to the url returning the response body, retrying network errors,
+    // server errors and rate limiting, and pacing requests when the rate limit is running low.
+    // Returns None if validators were supplied and the server says its not modified.
+    async fn get(
+        &self,
+        url: &str,
+        validators: &Validators,
+    ) -> Result<Option<(String, Validators)>, IrError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            self.wait_for_rate_limit().await;
+            let mut req = self.client.get(url);
+            if let Some(etag) = &validators.etag {
+                req = req.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(lm) = &validators.last_modified {
+                req = req.header(IF_MODIFIED_SINCE, lm);
+            }
+            let res = match req.send().await {
+                Err(e) if attempt < MAX_ATTEMPTS && (e.is_connect() || e.is_timeout()) => {
+                    println!("network error {:?} for {}, retrying", e, url);
+                    tokio::time::sleep(backoff(attempt)).await;
+                    continue;
+                }
+                Err(e) => return Err(IrError::Network(e)),
+                Ok(r) => r,
+            };
+            self.update_rate_limit(res.headers());
+            let status = res.status();
+            if status == StatusCode::NOT_MODIFIED {
+                return Ok(None);
+            }
+            if status.is_success() {
+                let header = |name: HeaderName| {
+                    res.headers()
+                        .get(name)
+                        .and_then(|v| v.to_str().ok())
+                        .map(|v| v.to_string())
+                };
+                let v = Validators {
+                    etag: header(ETAG),
+                    last_modified: header(LAST_MODIFIED),
+                };
+                return Ok(Some((res.text().await?, v)));
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                let reset = self
+                    .rate_limit
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .reset;
                 println!(
                     "got rated limited\nlimit:{:?} remaining:{:?} reset:{:?}",
-                    limit, remaining, reset
+                    res.headers().get("x-ratelimit-limit"),
+                    res.headers().get("x-ratelimit-remaining"),
+                    res.headers().get("x-ratelimit-reset")
                 );
+                if attempt >= MAX_ATTEMPTS {
+                    return Err(IrError::RateLimited { reset });
+                }
+                // wait_for_rate_limit will wait for the reset, if we know when that is
+                if reset.is_none() {
+                    tokio::time::sleep(backoff(attempt)).await;
+                }
+                continue;
+            }
+            if status == StatusCode::UNAUTHORIZED {
+                return Err(IrError::SessionExpired);
             }
+            let body = res.text().await.unwrap_or_default();
+            if status.is_server_error() && attempt < MAX_ATTEMPTS {
+                println!("http error {} for {}, retrying", status, url);
+                tokio::time::sleep(backoff(attempt)).await;
+                continue;
+            }
+            return Err(IrError::Http {
+                status,
+                url: url.to_string(),
+                body,
+            });
+        }
+    }
 
-            return Err(anyhow!(
-                "http error {} for {}\n{}",
-                res.status(),
-                u,
-                res.text().await?
-            ));
+    fn update_rate_limit(&self, headers: &HeaderMap) {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<i64>().ok())
+        };
+        // the S3 link responses don't have rate limit headers.
+        if let Some(remaining) = header("x-ratelimit-remaining") {
+            let mut rl = self
+                .rate_limit
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            rl.remaining = Some(remaining);
+            rl.reset = header("x-ratelimit-reset").and_then(|r| Utc.timestamp_opt(r, 0).single());
         }
-        let lnk: Link = res.json().await?;
-        let req = self.client.get(&lnk.link);
-        match req.send().await?.json().await {
-            Ok(r) => Ok(r),
-            Err(e) => {
-                // provide better error
-                let req = self.client.get(&lnk.link);
-                let txt = req.send().await?.text().await;
-                if let Ok(rb) = txt {
-                    println!("error {:?} response body\n{}", e, rb);
+    }
+
+    async fn wait_for_rate_limit(&self) {
+        let wait = {
+            let rl = self
+                .rate_limit
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            match (rl.remaining, rl.reset) {
+                (Some(rem), Some(reset)) if rem <= RATE_LIMIT_LOW_WATER => {
+                    (reset - Utc::now()).to_std().ok()
                 }
-                Err(anyhow!("request failed {:?}", e))
+                _ => None,
             }
+        };
+        if let Some(w) = wait {
+            let w = w.min(MAX_RATE_LIMIT_WAIT);
+            println!("rate limit running low, waiting {:?}", w);
+            tokio::time::sleep(w).await;
+            // we'll find out the new limits from the next response.
+            self.rate_limit
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remaining = None;
         }
     }
+}
 
-    #[allow(dead_code)]
-    pub async fn season_list(&self, year: i64, quarter: i64) -> Result<SeasonList, anyhow::Error> {
-        assert!((1..=4).contains(&quarter));
+#[async_trait]
+impl IrApi for IrClient {
+    async fn race_guide(&self) -> Result<RaceGuide, IrError> {
+        self.fetch("season/race_guide").await
+    }
+    async fn season_list(&self, year: i64, quarter: i64) -> Result<SeasonList, IrError> {
+        if !(1..=4).contains(&quarter) {
+            return Err(IrError::BadRequest(format!(
+                "season quarter {} should be 1-4",
+                quarter
+            )));
+        }
         self.fetch(&format!(
             "season/list?season_year={}&season_quarter={}",
             year, quarter
         ))
         .await
     }
-    pub async fn race_guide(&self) -> Result<RaceGuide, anyhow::Error> {
-        self.fetch("season/race_guide").await
+    async fn seasons(&self) -> Result<(Vec<Season>, bool), IrError> {
+        self.fetch_cached("series/seasons?include_series=false")
+            .await
+    }
+    async fn series(&self) -> Result<(Vec<Series>, bool), IrError> {
+        self.fetch_cached("series/get").await
+    }
+    async fn car_classes(&self) -> Result<(Vec<CarClass>, bool), IrError> {
+        self.fetch_cached("carclass/get").await
+    }
+    async fn cars(&self) -> Result<(Vec<Car>, bool), IrError> {
+        self.fetch_cached("car/get").await
     }
-    pub async fn seasons(&self) -> Result<Vec<Season>, anyhow::Error> {
-        self.fetch("series/seasons?include_series=false").await
+    async fn hosted_sessions(&self) -> Result<HostedSessions, IrError> {
+        self.fetch("hosted/combined_sessions").await
     }
-    pub async fn series(&self) -> Result<Vec<Series>, anyhow::Error> {
-        self.fetch("series/get").await
+    async fn league(&self, league_id: i64) -> Result<League, IrError> {
+        self.fetch(&format!("league/get?league_id={}", league_id))
+            .await
+    }
+    async fn league_seasons(&self, league_id: i64) -> Result<LeagueSeasons, IrError> {
+        self.fetch(&format!("league/seasons?league_id={}", league_id))
+            .await
+    }
+    async fn league_season_sessions(
+        &self,
+        league_id: i64,
+        season_id: i64,
+    ) -> Result<LeagueSessions, IrError> {
+        self.fetch(&format!(
+            "league/season_sessions?league_id={}&season_id={}",
+            league_id, season_id
+        ))
+        .await
+    }
+    async fn search_series(
+        &self,
+        series_id: i64,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SeriesResult>, IrError> {
+        self.fetch_chunked(&format!(
+            "results/search_series?series_id={}&start_range_begin={}&official_only=true&event_types=5",
+            series_id,
+            since.format("%Y-%m-%dT%H:%MZ")
+        ))
+        .await
+    }
+    async fn results(&self, subsession_id: i64) -> Result<SubsessionResult, IrError> {
+        self.fetch(&format!("results/get?subsession_id={}", subsession_id))
+            .await
+    }
+    fn rate_limit_remaining(&self) -> Option<i64> {
+        self.rate_limit
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remaining
+    }
+    fn rate_limit_reset(&self) -> Option<DateTime<Utc>> {
+        self.rate_limit
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .reset
+    }
+    fn schema(&self) -> &SchemaWatch {
+        &self.schema
     }
 }
 
+fn decode<T: serde::de::DeserializeOwned>(url: &str, body: &str) -> Result<T, IrError> {
+    serde_json::from_str(body).map_err(|err| {
+        println!("error {:?} response body\n{}", err, body);
+        IrError::Decode {
+            url: url.to_string(),
+            err,
+        }
+    })
+}
+
+fn not_modified(url: &str) -> IrError {
+    IrError::Http {
+        status: StatusCode::NOT_MODIFIED,
+        url: url.to_string(),
+        body: String::new(),
+    }
+}
+
+// exponential backoff with some jitter, so we're not in lockstep with anyone else.
+fn backoff(attempt: u32) -> Duration {
+    let jitter = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.subsec_millis() as u64 % 500)
+        .unwrap_or_default();
+    Duration::from_millis(500 * 2u64.pow(attempt) + jitter)
+}
+
 /// JSON types
+// Fields we don't strictly need are defaulted, so that iRacing renaming one of them
+// doesn't stop the poller. See ir_schema for how such changes are reported.
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Link {
@@ -106,43 +558,210 @@ struct Link {
     pub expires: Option<DateTime<Utc>>,
 }
 
+#[derive(Deserialize, Debug)]
+struct Chunked {
+    data: ChunkedData,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChunkedData {
+    chunk_info: Option<ChunkInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChunkInfo {
+    #[serde(default)]
+    base_download_url: String,
+    #[serde(default)]
+    chunk_file_names: Vec<String>,
+}
+
+// SeriesResult is one split of a finished race, from results/search_series.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SeriesResult {
+    pub series_id: i64,
+    pub session_id: i64,
+    pub subsession_id: i64,
+    pub start_time: DateTime<Utc>,
+    #[serde(default)]
+    pub event_strength_of_field: i64,
+    #[serde(default)]
+    pub num_drivers: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubsessionResult {
+    pub subsession_id: i64,
+    #[serde(default)]
+    pub session_results: Vec<SimSessionResult>,
+}
+impl SubsessionResult {
+    // the winner of each class in the race, in class order.
+    pub fn class_winners(&self) -> Vec<&DriverResult> {
+        let race = self
+            .session_results
+            .iter()
+            .find(|s| s.simsession_name.eq_ignore_ascii_case("race"));
+        let mut winners: Vec<&DriverResult> = race
+            .map(|r| {
+                r.results
+                    .iter()
+                    .filter(|d| d.finish_position_in_class == 0)
+                    .collect()
+            })
+            .unwrap_or_default();
+        winners.sort_by_key(|d| d.finish_position);
+        winners
+    }
+}
+
+// SimSessionResult is the results of one part of the event, practice, qualifying or the race.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SimSessionResult {
+    #[serde(default)]
+    pub simsession_name: String,
+    #[serde(default)]
+    pub results: Vec<DriverResult>,
+}
+
+// DriverResult is where a driver (or team) finished, positions start at 0.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DriverResult {
+    pub display_name: String,
+    pub finish_position: i64,
+    pub finish_position_in_class: i64,
+    #[serde(default)]
+    pub car_class_short_name: String,
+}
+
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct SeasonList {
-    season_quarter: i64,
-    season_year: i64,
-    seasons: Vec<SeasonBasic>,
+    pub season_quarter: i64,
+    pub season_year: i64,
+    pub seasons: Vec<SeasonBasic>,
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct SeasonBasic {
-    season_id: i64,
-    series_id: i64,
-    season_name: String,
-    series_name: String,
-    official: bool,
-    season_year: i64,
-    season_quarter: i64,
-    license_group: i64,
-    fixed_setup: bool,
-    driver_changes: bool,
+    pub season_id: i64,
+    pub series_id: i64,
+    #[serde(default)]
+    pub season_name: String,
+    #[serde(default)]
+    pub series_name: String,
+    #[serde(default)]
+    pub official: bool,
+    #[serde(default)]
+    pub season_year: i64,
+    #[serde(default)]
+    pub season_quarter: i64,
+    // 1 is rookie through to 5 for A, see db::LICENSES
+    #[serde(default)]
+    pub license_group: i64,
+    #[serde(default)]
+    pub fixed_setup: bool,
+    #[serde(default)]
+    pub driver_changes: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CarClass {
+    pub car_class_id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub short_name: String,
+    #[serde(default)]
+    pub cars_in_class: Vec<CarInClass>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CarInClass {
+    pub car_id: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Car {
+    pub car_id: i64,
+    pub car_name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HostedSessions {
+    #[serde(default)]
+    pub sessions: Vec<HostedSession>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HostedSession {
+    pub session_id: i64,
+    #[serde(default)]
+    pub session_name: String,
+    pub launch_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub num_drivers: i64,
+    #[serde(default)]
+    pub max_drivers: i64,
+    pub track: Option<Track>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct League {
+    pub league_name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct LeagueSeasons {
+    #[serde(default)]
+    pub seasons: Vec<LeagueSeason>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LeagueSeason {
+    pub season_id: i64,
+    #[serde(default)]
+    pub active: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct LeagueSessions {
+    #[serde(default)]
+    pub sessions: Vec<LeagueSession>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LeagueSession {
+    pub session_id: Option<i64>,
+    pub launch_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub entry_count: i64,
+    #[serde(default)]
+    pub has_results: bool,
+    pub track: Option<Track>,
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct RaceGuide {
+    #[serde(default)]
     pub subscribed: bool,
     pub sessions: Vec<RaceGuideEntry>,
+    #[serde(default)]
     pub block_begin_time: String,
+    #[serde(default)]
     pub block_end_time: String,
+    #[serde(default)]
     pub success: bool,
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct RaceGuideEntry {
     pub season_id: i64,
     pub start_time: DateTime<Utc>,
+    #[serde(default)]
     pub super_session: bool,
     pub series_id: i64,
+    #[serde(default)]
     pub race_week_num: i64,
+    #[serde(default)]
     pub end_time: String,
     pub session_id: Option<i64>,
     pub entry_count: i64,
@@ -154,16 +773,24 @@ impl RaceGuideEntry {
 }
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Season {
+    #[serde(default)]
     pub active: bool,
+    #[serde(default)]
     pub official: bool,
     pub start_date: DateTime<Utc>,
     pub race_week: i64,
+    #[serde(default)]
     pub max_weeks: i64,
     pub season_id: i64,
+    #[serde(default)]
     pub season_quarter: i64,
+    #[serde(default)]
     pub season_year: i64,
     pub series_id: i64,
+    #[serde(default)]
     pub season_name: String,
+    #[serde(default)]
+    pub car_class_ids: Vec<i64>,
     pub schedules: Vec<Schedule>,
 }
 
@@ -171,8 +798,11 @@ pub struct Season {
 pub struct Schedule {
     pub series_id: i64,
     pub season_id: i64,
+    #[serde(default)]
     pub race_week_num: i64,
+    #[serde(default)]
     pub series_name: String,
+    #[serde(default)]
     pub season_name: String,
     pub track: Track,
 }
@@ -188,15 +818,21 @@ pub struct Track {
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct Series {
+    #[serde(default)]
     pub category: String,
+    #[serde(default)]
     pub category_id: i64,
+    #[serde(default)]
     pub eligible: bool,
     pub max_starters: i64,
     pub min_starters: i64,
+    #[serde(default)]
     pub oval_caution_type: i64,
+    #[serde(default)]
     pub road_caution_type: i64,
     pub search_filters: Option<String>,
     pub series_id: i64,
     pub series_name: String,
+    #[serde(default)]
     pub series_short_name: String,
 }
@@ -0,0 +1,215 @@
+use crate::ir::{
+    Car, CarClass, HostedSessions, IrApi, IrError, League, LeagueSeasons, LeagueSessions,
+    RaceGuide, Season, SeasonList, Series, SeriesResult, SubsessionResult,
+};
+use crate::ir_schema::{self, SchemaWatch};
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serenity::async_trait;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// MockIrApi serves iRacing data from a directory of fixture json files, so the watcher
+// can be run without iRacing credentials. The directory should contain seasons.json,
+// series.json and one or more race_guide*.json files. Each call to race_guide returns
+// the next race guide file (in filename order), sticking on the last one. An optional
+// season_list.json supplies the license & setup details for the seasons, and optional
+// carclass.json & car.json the cars, hosted.json the hosted sessions,
+// search_series.json the race results (the rows from the chunk files), and
+// results_*.json the full results for a split.
+pub struct MockIrApi {
+    seasons: Vec<Season>,
+    season_list: Option<SeasonList>,
+    car_classes: Vec<CarClass>,
+    cars: Vec<Car>,
+    hosted: Option<HostedSessions>,
+    results: Vec<SeriesResult>,
+    subsessions: HashMap<i64, SubsessionResult>,
+    series: Vec<Series>,
+    race_guides: Vec<RaceGuide>,
+    next_guide: AtomicUsize,
+    seasons_sent: AtomicBool,
+    series_sent: AtomicBool,
+    schema: SchemaWatch,
+}
+
+impl MockIrApi {
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let read = |f: &Path| -> anyhow::Result<String> {
+            std::fs::read_to_string(f).map_err(|e| anyhow!("unable to read {}: {}", f.display(), e))
+        };
+        // fixtures are checked against the expected shapes too, which makes them handy
+        // for trying out a payload that iRacing has changed.
+        let schema = SchemaWatch::default();
+        let load = |f: &Path, path: &str| -> anyhow::Result<_> {
+            let v: serde_json::Value = serde_json::from_str(&read(f)?)?;
+            if let Some(shapes) = ir_schema::shapes_for(path) {
+                schema.check(path, &v, shapes);
+            }
+            Ok(v)
+        };
+        let seasons = from_value(load(&dir.join("seasons.json"), "series/seasons")?)?;
+        let series = from_value(load(&dir.join("series.json"), "series/get")?)?;
+        let list_file = dir.join("season_list.json");
+        let season_list = if list_file.exists() {
+            Some(from_value(load(&list_file, "season/list")?)?)
+        } else {
+            None
+        };
+        let class_file = dir.join("carclass.json");
+        let car_classes = if class_file.exists() {
+            from_value(load(&class_file, "carclass/get")?)?
+        } else {
+            Vec::new()
+        };
+        let hosted_file = dir.join("hosted.json");
+        let hosted = if hosted_file.exists() {
+            Some(from_value(load(&hosted_file, "hosted/combined_sessions")?)?)
+        } else {
+            None
+        };
+        let results_file = dir.join("search_series.json");
+        let results = if results_file.exists() {
+            from_value(load(&results_file, "results/search_series chunk")?)?
+        } else {
+            Vec::new()
+        };
+        let car_file = dir.join("car.json");
+        let cars = if car_file.exists() {
+            from_value(load(&car_file, "car/get")?)?
+        } else {
+            Vec::new()
+        };
+        let mut guide_files = Vec::new();
+        let mut subsessions = HashMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            if name.starts_with("race_guide") && name.ends_with(".json") {
+                guide_files.push(path);
+            } else if name.starts_with("results_") && name.ends_with(".json") {
+                let r: SubsessionResult = from_value(load(&path, "results/get")?)?;
+                subsessions.insert(r.subsession_id, r);
+            }
+        }
+        guide_files.sort();
+        let mut race_guides = Vec::with_capacity(guide_files.len());
+        for f in &guide_files {
+            race_guides.push(from_value(load(f, "season/race_guide")?)?);
+        }
+        if race_guides.is_empty() {
+            return Err(anyhow!("no race_guide*.json files in {}", dir.display()));
+        }
+        Ok(MockIrApi {
+            seasons,
+            season_list,
+            car_classes,
+            cars,
+            hosted,
+            results,
+            subsessions,
+            series,
+            race_guides,
+            next_guide: AtomicUsize::new(0),
+            seasons_sent: AtomicBool::new(false),
+            series_sent: AtomicBool::new(false),
+            schema,
+        })
+    }
+    // how many race guide files there are, race_guide sticks on the last after this many calls.
+    pub fn race_guide_count(&self) -> usize {
+        self.race_guides.len()
+    }
+}
+
+fn from_value<T: DeserializeOwned>(v: serde_json::Value) -> anyhow::Result<T> {
+    Ok(serde_json::from_value(v)?)
+}
+
+#[async_trait]
+impl IrApi for MockIrApi {
+    async fn seasons(&self) -> Result<(Vec<Season>, bool), IrError> {
+        let changed = !self.seasons_sent.swap(true, Ordering::SeqCst);
+        Ok((self.seasons.clone(), changed))
+    }
+    async fn series(&self) -> Result<(Vec<Series>, bool), IrError> {
+        let changed = !self.series_sent.swap(true, Ordering::SeqCst);
+        Ok((self.series.clone(), changed))
+    }
+    async fn race_guide(&self) -> Result<RaceGuide, IrError> {
+        let idx = self.next_guide.fetch_add(1, Ordering::SeqCst);
+        Ok(self.race_guides[idx.min(self.race_guides.len() - 1)].clone())
+    }
+    async fn season_list(&self, year: i64, quarter: i64) -> Result<SeasonList, IrError> {
+        Ok(self.season_list.clone().unwrap_or(SeasonList {
+            season_quarter: quarter,
+            season_year: year,
+            seasons: Vec::new(),
+        }))
+    }
+    async fn car_classes(&self) -> Result<(Vec<CarClass>, bool), IrError> {
+        Ok((self.car_classes.clone(), false))
+    }
+    async fn cars(&self) -> Result<(Vec<Car>, bool), IrError> {
+        Ok((self.cars.clone(), false))
+    }
+    async fn hosted_sessions(&self) -> Result<HostedSessions, IrError> {
+        Ok(self.hosted.clone().unwrap_or(HostedSessions {
+            sessions: Vec::new(),
+        }))
+    }
+    // there's no league fixtures, every league exists and has no seasons.
+    async fn league(&self, league_id: i64) -> Result<League, IrError> {
+        Ok(League {
+            league_name: format!("League {}", league_id),
+        })
+    }
+    async fn league_seasons(&self, _league_id: i64) -> Result<LeagueSeasons, IrError> {
+        Ok(LeagueSeasons::default())
+    }
+    async fn league_season_sessions(
+        &self,
+        _league_id: i64,
+        _season_id: i64,
+    ) -> Result<LeagueSessions, IrError> {
+        Ok(LeagueSessions::default())
+    }
+    async fn search_series(
+        &self,
+        series_id: i64,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SeriesResult>, IrError> {
+        Ok(self
+            .results
+            .iter()
+            .filter(|r| r.start_time >= since)
+            .filter(|r| r.series_id == series_id)
+            .cloned()
+            .collect())
+    }
+    async fn results(&self, subsession_id: i64) -> Result<SubsessionResult, IrError> {
+        self.subsessions
+            .get(&subsession_id)
+            .cloned()
+            .ok_or_else(|| IrError::Http {
+                status: StatusCode::NOT_FOUND,
+                url: format!("results/get?subsession_id={}", subsession_id),
+                body: String::new(),
+            })
+    }
+    fn rate_limit_remaining(&self) -> Option<i64> {
+        None
+    }
+    fn rate_limit_reset(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+    fn schema(&self) -> &SchemaWatch {
+        &self.schema
+    }
+}
@@ -0,0 +1,341 @@
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+// Shape is the fields we deserialize from the objects found at path in a payload.
+// The path is a list of field names separated by '.', "[]" steps into every item of an array.
+pub struct Shape {
+    pub path: &'static str,
+    pub fields: &'static [&'static str],
+}
+
+pub const RACE_GUIDE: &[Shape] = &[
+    Shape {
+        path: "",
+        fields: &[
+            "subscribed",
+            "sessions",
+            "block_begin_time",
+            "block_end_time",
+            "success",
+        ],
+    },
+    Shape {
+        path: "sessions.[]",
+        fields: &[
+            "season_id",
+            "start_time",
+            "super_session",
+            "series_id",
+            "race_week_num",
+            "end_time",
+            "session_id",
+            "entry_count",
+        ],
+    },
+];
+
+pub const SEASONS: &[Shape] = &[
+    Shape {
+        path: "[]",
+        fields: &[
+            "active",
+            "official",
+            "start_date",
+            "race_week",
+            "max_weeks",
+            "season_id",
+            "season_quarter",
+            "season_year",
+            "series_id",
+            "season_name",
+            "car_class_ids",
+            "schedules",
+        ],
+    },
+    Shape {
+        path: "[].schedules.[]",
+        fields: &[
+            "series_id",
+            "season_id",
+            "race_week_num",
+            "series_name",
+            "season_name",
+            "track",
+        ],
+    },
+    Shape {
+        path: "[].schedules.[].track",
+        fields: &["track_id", "track_name", "config_name", "category"],
+    },
+];
+
+pub const SERIES: &[Shape] = &[Shape {
+    path: "[]",
+    fields: &[
+        "category",
+        "category_id",
+        "eligible",
+        "max_starters",
+        "min_starters",
+        "oval_caution_type",
+        "road_caution_type",
+        "search_filters",
+        "series_id",
+        "series_name",
+        "series_short_name",
+    ],
+}];
+
+pub const CAR_CLASSES: &[Shape] = &[
+    Shape {
+        path: "[]",
+        fields: &["car_class_id", "name", "short_name", "cars_in_class"],
+    },
+    Shape {
+        path: "[].cars_in_class.[]",
+        fields: &["car_id"],
+    },
+];
+
+pub const CARS: &[Shape] = &[Shape {
+    path: "[]",
+    fields: &["car_id", "car_name"],
+}];
+
+pub const HOSTED: &[Shape] = &[
+    Shape {
+        path: "",
+        fields: &["sessions"],
+    },
+    Shape {
+        path: "sessions.[]",
+        fields: &[
+            "session_id",
+            "session_name",
+            "launch_at",
+            "num_drivers",
+            "max_drivers",
+            "track",
+        ],
+    },
+];
+
+pub const LEAGUE: &[Shape] = &[Shape {
+    path: "",
+    fields: &["league_name"],
+}];
+
+pub const LEAGUE_SEASONS: &[Shape] = &[
+    Shape {
+        path: "",
+        fields: &["seasons"],
+    },
+    Shape {
+        path: "seasons.[]",
+        fields: &["season_id", "active"],
+    },
+];
+
+pub const LEAGUE_SESSIONS: &[Shape] = &[
+    Shape {
+        path: "",
+        fields: &["sessions"],
+    },
+    Shape {
+        path: "sessions.[]",
+        fields: &[
+            "session_id",
+            "launch_at",
+            "entry_count",
+            "has_results",
+            "track",
+        ],
+    },
+];
+
+pub const SEARCH_SERIES: &[Shape] = &[
+    Shape {
+        path: "",
+        fields: &["type", "data"],
+    },
+    Shape {
+        path: "data.chunk_info",
+        fields: &["base_download_url", "chunk_file_names"],
+    },
+];
+
+pub const SEARCH_SERIES_CHUNK: &[Shape] = &[Shape {
+    path: "[]",
+    fields: &[
+        "series_id",
+        "session_id",
+        "subsession_id",
+        "start_time",
+        "event_strength_of_field",
+        "num_drivers",
+    ],
+}];
+
+pub const RESULTS: &[Shape] = &[
+    Shape {
+        path: "",
+        fields: &[
+            "subsession_id",
+            "event_strength_of_field",
+            "session_results",
+        ],
+    },
+    Shape {
+        path: "session_results.[]",
+        fields: &["simsession_name", "results"],
+    },
+    Shape {
+        path: "session_results.[].results.[]",
+        fields: &[
+            "display_name",
+            "finish_position",
+            "finish_position_in_class",
+            "car_class_short_name",
+        ],
+    },
+];
+
+pub const SEASON_LIST: &[Shape] = &[
+    Shape {
+        path: "",
+        fields: &["season_quarter", "season_year", "seasons"],
+    },
+    Shape {
+        path: "seasons.[]",
+        fields: &[
+            "season_id",
+            "series_id",
+            "season_name",
+            "series_name",
+            "official",
+            "season_year",
+            "season_quarter",
+            "license_group",
+            "fixed_setup",
+            "driver_changes",
+        ],
+    },
+];
+
+// returns the expected shape of the payload from the iRacing API path.
+pub fn shapes_for(path: &str) -> Option<&'static [Shape]> {
+    match path {
+        "season/race_guide" => Some(RACE_GUIDE),
+        "series/seasons" => Some(SEASONS),
+        "series/get" => Some(SERIES),
+        "season/list" => Some(SEASON_LIST),
+        "carclass/get" => Some(CAR_CLASSES),
+        "car/get" => Some(CARS),
+        "hosted/combined_sessions" => Some(HOSTED),
+        "league/get" => Some(LEAGUE),
+        "league/seasons" => Some(LEAGUE_SEASONS),
+        "league/season_sessions" => Some(LEAGUE_SESSIONS),
+        "results/search_series" => Some(SEARCH_SERIES),
+        "results/search_series chunk" => Some(SEARCH_SERIES_CHUNK),
+        "results/get" => Some(RESULTS),
+        _ => None,
+    }
+}
+
+// SchemaWatch notices when iRacing changes the shape of a payload. The first time a
+// payload is seen its field names become the baseline, after that any new fields, or
+// fields that have gone away are reported, as are any fields we deserialize that are
+// missing. Each change is only reported once.
+#[derive(Default)]
+pub struct SchemaWatch {
+    // payload/path -> field names seen
+    baseline: Mutex<HashMap<String, BTreeSet<String>>>,
+    // payload/path/field of expected fields we've already reported missing
+    missing: Mutex<HashSet<String>>,
+    warnings: Mutex<Vec<String>>,
+    drift_count: AtomicU64,
+}
+
+impl SchemaWatch {
+    pub fn check(&self, payload: &str, v: &Value, shapes: &[Shape]) {
+        for shape in shapes {
+            let objects = objects_at(v, shape.path);
+            if objects.is_empty() {
+                // e.g. no sessions in the race guide, nothing to compare.
+                continue;
+            }
+            let key = format!("{} {}", payload, shape.path);
+            let mut seen = BTreeSet::new();
+            for o in &objects {
+                seen.extend(o.keys().cloned());
+            }
+            for f in shape.fields {
+                if !seen.contains(*f)
+                    && self
+                        .missing
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(format!("{} {}", key, f))
+                {
+                    self.drift(format!(
+                        "iRacing {} is missing expected field '{}' at '{}'",
+                        payload, f, shape.path
+                    ));
+                }
+            }
+            let mut baseline = self.baseline.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(prev) = baseline.get(&key) {
+                let added: Vec<_> = seen.difference(prev).cloned().collect();
+                let removed: Vec<_> = prev.difference(&seen).cloned().collect();
+                if !added.is_empty() || !removed.is_empty() {
+                    self.drift(format!(
+                        "iRacing {} fields changed at '{}', new: [{}] gone: [{}]",
+                        payload,
+                        shape.path,
+                        added.join(", "),
+                        removed.join(", ")
+                    ));
+                }
+            }
+            baseline.insert(key, seen);
+        }
+    }
+
+    // returns the drift warnings since the last call.
+    pub fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    // total number of changes detected.
+    pub fn drift_count(&self) -> u64 {
+        self.drift_count.load(Ordering::SeqCst)
+    }
+
+    fn drift(&self, msg: String) {
+        println!("{}", msg);
+        self.drift_count.fetch_add(1, Ordering::SeqCst);
+        self.warnings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(msg);
+    }
+}
+
+fn objects_at<'a>(v: &'a Value, path: &str) -> Vec<&'a serde_json::Map<String, Value>> {
+    let mut curr = vec![v];
+    for step in path.split('.').filter(|s| !s.is_empty()) {
+        curr = curr
+            .into_iter()
+            .flat_map(|v: &'a Value| -> Vec<&'a Value> {
+                match (step, v) {
+                    ("[]", Value::Array(items)) => items.iter().collect(),
+                    (f, Value::Object(o)) => o.get(f).into_iter().collect(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+    curr.into_iter().filter_map(|v| v.as_object()).collect()
+}
@@ -1,34 +1,225 @@
-use chrono::{Duration, Utc};
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, Timelike, Utc};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Display,
-    sync::{Arc, Mutex},
+    path::Path,
+    sync::Arc,
 };
-use tokio::{sync::mpsc::Sender, time::Instant};
+use tokio::{sync::mpsc::UnboundedSender, time::Instant};
 
-use crate::ir::{IrClient, RaceGuideEntry};
-use crate::{db::SeasonInfo, HandlerState};
+use crate::announce_rules::{self, Transition};
+use crate::config::Config;
+use crate::db::{Reg, SeasonInfo, WatchState};
+use crate::forecast;
+use crate::health::{Health, MAX_POLL_AGE_SECS};
+use crate::i18n::{text, tr, Locale, Msg};
+use crate::ir::{CarClass, IrApi, IrClient, IrError, RaceGuideEntry, Season, Series, SeriesResult};
+use crate::ir_mock::MockIrApi;
+use crate::jobs::DelayedJobs;
+use crate::recorder::Recorder;
+use crate::style::escape_markdown;
+use crate::HandlerState;
+
+// saved watcher state older than this is ignored at startup.
+const MAX_WATCH_STATE_AGE_MINS: i64 = 10;
+
+// don't bother predicting a session that's about to start, the counts will say it all.
+const PREDICT_MIN_MINS: i64 = 10;
+
+// the estimated SOF for a series is based on its official races over this many hours.
+const SOF_HISTORY_HOURS: i64 = 24;
+// how long an estimated SOF is used for before its fetched again.
+const SOF_CACHE_MINS: i64 = 60;
+// the most results searches to make in one poll, the rest wait for the next one.
+const SOF_MAX_FETCHES: usize = 5;
+// leave this many requests in the rate limit for the race guide, SOF & results are
+// just nice to haves.
+const SPARE_RATE_LIMIT: i64 = 50;
+
+// results take a few minutes to show up after a race ends.
+const RESULTS_DELAY_MINS: i64 = 10;
+// how long to wait before looking again for results that weren't in yet.
+const RESULTS_RETRY_MINS: i64 = 15;
+// give up on a race's results after this many tries.
+const RESULTS_MAX_ATTEMPTS: u32 = 4;
+// how long a race is assumed to last when the race guide doesn't say when it ends.
+const DEFAULT_SESSION_MINS: i64 = 60;
+
+// poll faster when a watched series has a session starting within this many minutes.
+const FAST_POLL_MINS: i64 = 10;
+// a poll is at least the race guide link and its payload, see PollTiming::per_poll.
+const REQUESTS_PER_POLL: i64 = 2;
+
+// PollTiming works out how long to wait before the next race guide poll, quicker when
+// a watched session is about to start and there's a rush of registrations, slower in
+// the quiet hours, and never so quick that we'd run out of rate limit before it resets.
+struct PollTiming {
+    normal: Duration,
+    fast: Duration,
+    quiet: Duration,
+    quiet_hours: (u32, u32),
+    // the telegram chats' series, which don't have watches in the db.
+    telegram_series: HashSet<i64>,
+    // how many requests the last poll used, including the SOF, results & hosted fetches.
+    per_poll: i64,
+    // the rate limit (remaining, reset) at the end of the last poll.
+    last_limit: Option<(i64, DateTime<Utc>)>,
+}
+impl PollTiming {
+    fn new(config: &Config) -> Self {
+        PollTiming {
+            normal: Duration::seconds(config.poll_interval_secs as i64),
+            fast: Duration::seconds(config.poll_fast_secs as i64),
+            quiet: Duration::seconds(config.poll_quiet_secs as i64),
+            quiet_hours: (config.quiet_start_hour, config.quiet_end_hour),
+            telegram_series: config
+                .telegram
+                .iter()
+                .flat_map(|c| c.series.iter().cloned())
+                .collect(),
+            per_poll: REQUESTS_PER_POLL,
+            last_limit: None,
+        }
+    }
+    fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        let h = now.hour();
+        match self.quiet_hours {
+            (a, b) if a == b => false,
+            (a, b) if a < b => h >= a && h < b,
+            (a, b) => h >= a || h < b,
+        }
+    }
+    fn next_poll<A: IrApi>(
+        &mut self,
+        client: &A,
+        now: DateTime<Utc>,
+        starting_soon: bool,
+    ) -> Duration {
+        let wanted = if starting_soon {
+            self.fast
+        } else if self.is_quiet(now) {
+            self.quiet
+        } else {
+            self.normal
+        };
+        let limit = client.rate_limit_remaining().zip(client.rate_limit_reset());
+        // what the rate limit went down by since the last poll is what a poll costs, as
+        // long as it hasn't been reset in between.
+        if let (Some((remaining, reset)), Some((last, last_reset))) = (limit, self.last_limit) {
+            if reset == last_reset && last >= remaining {
+                self.per_poll = (last - remaining).max(REQUESTS_PER_POLL);
+            }
+        }
+        self.last_limit = limit;
+        // spread what's left of the rate limit over the time until it resets.
+        let spread = match limit {
+            Some((remaining, reset)) if reset > now => {
+                let polls = ((remaining - SPARE_RATE_LIMIT) / self.per_poll).max(1);
+                (reset - now) / polls as i32
+            }
+            _ => Duration::zero(),
+        };
+        // but don't go so long that the health check thinks the poller is stuck, if that
+        // runs the limit down the client paces its requests until it resets.
+        wanted
+            .max(spread)
+            .min(Duration::seconds(MAX_POLL_AGE_SECS - 60))
+    }
+}
 
 #[derive(Debug)]
 pub enum RaceGuideEvent {
     Seasons(HashMap<i64, SeasonInfo>),
     Announcements(HashMap<i64, Announcement>),
+    // something the bot operator should know about.
+    OpsWarning(String),
+    // the latest race guide entry for every series, for the status messages.
+    Status(HashMap<i64, RaceGuideEntry>),
+}
+
+// coalesce combines a backlog of events so the listener can catch up. Only the latest
+// Seasons & Status matter, and a Count announcement is replaced by a newer Count for the
+// same series. Other announcements for a series that already has one pending go in a
+// later batch so they're not lost.
+pub fn coalesce(events: Vec<RaceGuideEvent>) -> Vec<RaceGuideEvent> {
+    let mut seasons = None;
+    let mut status = None;
+    let mut batches: Vec<HashMap<i64, Announcement>> = Vec::new();
+    let mut warnings = Vec::new();
+    for e in events {
+        match e {
+            RaceGuideEvent::Seasons(s) => seasons = Some(s),
+            RaceGuideEvent::Status(s) => status = Some(s),
+            RaceGuideEvent::OpsWarning(w) => warnings.push(w),
+            RaceGuideEvent::Announcements(anns) => {
+                for (series_id, mut ann) in anns {
+                    let is_count = |a: &Announcement| matches!(a.ann_type, AnnouncementType::Count);
+                    // is there a pending announcement for the series, and is it a Count.
+                    let pending = batches.last().map(|b| b.get(&series_id).map(is_count));
+                    match (pending, batches.last_mut()) {
+                        (Some(None), Some(b)) => {
+                            b.insert(series_id, ann);
+                        }
+                        (Some(Some(true)), Some(b)) if is_count(&ann) => {
+                            // compare against what was last announced, not the superseded count.
+                            ann.prev = b[&series_id].prev.clone();
+                            b.insert(series_id, ann);
+                        }
+                        _ => batches.push(HashMap::from([(series_id, ann)])),
+                    }
+                }
+            }
+        }
+    }
+    let mut res = Vec::new();
+    if let Some(s) = seasons {
+        res.push(RaceGuideEvent::Seasons(s));
+    }
+    res.extend(batches.into_iter().map(RaceGuideEvent::Announcements));
+    res.extend(warnings.into_iter().map(RaceGuideEvent::OpsWarning));
+    if let Some(s) = status {
+        res.push(RaceGuideEvent::Status(s));
+    }
+    res
 }
 
+// the name of the poller in the health status.
+pub const POLLER_TASK: &str = "iRacing poller";
+
 pub async fn iracing_loop_task(
-    user: String,
-    password: String,
-    mut tx: Sender<RaceGuideEvent>,
-    state: Arc<Mutex<HandlerState>>,
+    config: Arc<Config>,
+    mut tx: UnboundedSender<RaceGuideEvent>,
+    state: Arc<HandlerState>,
+    health: Arc<Health>,
+    recorder: Option<Arc<Recorder>>,
 ) {
     let def_backoff = tokio::time::Duration::from_secs(1);
     let max_backoff = tokio::time::Duration::from_secs(120);
     let mut backoff = def_backoff;
     let mut series_state = HashMap::new();
     loop {
-        match iracing_loop(&mut series_state, &user, &password, &mut tx, state.clone()).await {
+        match iracing_loop(
+            &mut series_state,
+            &config,
+            &mut tx,
+            state.clone(),
+            &health,
+            recorder.clone(),
+        )
+        .await
+        {
+            Err(_) if tx.is_closed() => {
+                println!("race guide listener has gone, iRacing poller stopping");
+                return;
+            }
             Err(e) => {
                 println!("Error polling iRacing {:?}", e);
+                health.poll_failed(&e);
+                // no point hammering away with bad credentials
+                if let Some(IrError::Auth(_)) = e.downcast_ref::<IrError>() {
+                    backoff = max_backoff;
+                }
                 tokio::time::sleep(backoff).await;
                 backoff = (backoff * 2).min(max_backoff);
             }
@@ -38,91 +229,722 @@ pub async fn iracing_loop_task(
         }
     }
 }
-async fn update_series_info(
-    client: &IrClient,
-    series_state: &mut HashMap<i64, SeriesReg>,
-    tx: &mut Sender<RaceGuideEvent>,
-    state: Arc<Mutex<HandlerState>>,
-) -> anyhow::Result<()> {
-    println!("checking for updated series/season info");
-    let seasons = client.seasons().await?;
-    let series = client.series().await?;
+
+// replay runs the race guides from a fixtures directory (see MockIrApi) through the
+// watcher, returning a line for each open, count & closed announcement, marked with a *
+// if reg_for's watch for the series wants it. The clock is each race guide's
+// block_begin_time, or if that's missing it moves on step from the last one, so hours of
+// polls replay in a moment.
+pub async fn replay(
+    dir: &Path,
+    step: Duration,
+    reg_for: impl Fn(&SeasonInfo) -> Reg,
+) -> anyhow::Result<Vec<String>> {
+    let client = MockIrApi::load(dir)?;
+    let (seasons, _) = client.seasons().await?;
+    let (series, _) = client.series().await?;
+    let mut series_state: HashMap<i64, SeriesReg> = season_infos(&client, seasons, series)
+        .await
+        .iter()
+        .map(|si| (si.series_id, SeriesReg::new(si)))
+        .collect();
+    let mut now: Option<DateTime<Utc>> = None;
+    let mut lines = Vec::new();
+    for _ in 0..client.race_guide_count() {
+        let guide = client.race_guide().await?;
+        let at = DateTime::parse_from_rfc3339(&guide.block_begin_time)
+            .map(|t| t.with_timezone(&Utc))
+            .ok()
+            .or_else(|| now.map(|n| n + step))
+            .unwrap_or_else(Utc::now);
+        now = Some(at);
+        for (series_id, sessions) in by_series(guide.sessions) {
+            let sr = match series_state.get_mut(&series_id) {
+                Some(sr) => sr,
+                None => continue,
+            };
+            for ann in sr.update(sessions, at) {
+                let wanted = announce_rules::wants(&reg_for(&ann.series), &ann, at);
+                lines.push(format!(
+                    "{} {} {}: {} {} -> {}",
+                    at.format("%Y-%m-%d %H:%M"),
+                    if wanted { "*" } else { " " },
+                    ann.series.name,
+                    ann.ann_type.name(),
+                    ann.prev.entry_count,
+                    ann.curr.entry_count
+                ));
+            }
+        }
+    }
+    Ok(lines)
+}
+
+// the names of the car classes & cars raced in the season, lowercase.
+fn car_names(season: &Season, classes: &[CarClass], cars: &HashMap<i64, String>) -> String {
+    let mut names = Vec::new();
+    for class in classes
+        .iter()
+        .filter(|c| season.car_class_ids.contains(&c.car_class_id))
+    {
+        names.push(class.name.clone());
+        if !class.short_name.is_empty() && class.short_name != class.name {
+            names.push(class.short_name.clone());
+        }
+        for car in &class.cars_in_class {
+            if let Some(n) = cars.get(&car.car_id) {
+                if !names.contains(n) {
+                    names.push(n.clone());
+                }
+            }
+        }
+    }
+    names.join(", ").to_lowercase()
+}
+
+// builds the SeasonInfo for each season from the seasons & series, along with the
+// season list & cars if they can be had.
+async fn season_infos<A: IrApi>(
+    client: &A,
+    seasons: Vec<Season>,
+    series: Vec<Series>,
+) -> Vec<SeasonInfo> {
     let mut series_by_id = HashMap::with_capacity(series.len());
     for s in series {
         series_by_id.insert(s.series_id, s);
     }
-    let season_infos: HashMap<i64, SeasonInfo>;
-    {
-        let mut st = state.lock().expect("Unable to lock state");
-        let mut updater = st.db.start_series_update()?;
-        for season in seasons {
-            let series = series_by_id.remove(&season.series_id).unwrap();
-            let si = SeasonInfo::new(&series, &season);
-            updater.upsert(&si)?;
+    // license classes are only in the season list, we can live without them if that fails.
+    let quarters: HashSet<(i64, i64)> = seasons
+        .iter()
+        .map(|s| (s.season_year, s.season_quarter))
+        .filter(|(_, q)| (1..=4).contains(q))
+        .collect();
+    let mut basics = HashMap::new();
+    for (year, quarter) in quarters {
+        match client.season_list(year, quarter).await {
+            Ok(list) => basics.extend(list.seasons.into_iter().map(|b| (b.season_id, b))),
+            Err(e) => println!(
+                "Error getting season list for {} S{} {:?}",
+                year, quarter, e
+            ),
+        }
+    }
+    // cars are just for searching, so same again.
+    let classes = match client.car_classes().await {
+        Ok((classes, _)) => classes,
+        Err(e) => {
+            println!("Error getting car classes {:?}", e);
+            Vec::new()
+        }
+    };
+    let cars: HashMap<i64, String> = match client.cars().await {
+        Ok((cars, _)) => cars.into_iter().map(|c| (c.car_id, c.car_name)).collect(),
+        Err(e) => {
+            println!("Error getting cars {:?}", e);
+            HashMap::new()
         }
-        updater.commit()?;
+    };
+    let mut infos = Vec::with_capacity(seasons.len());
+    for season in seasons {
+        // iRacing can list a season before its series shows up, skip it until it does.
+        let series = match series_by_id.remove(&season.series_id) {
+            Some(s) => s,
+            None => {
+                println!(
+                    "No series {} for season {}, skipping it",
+                    season.series_id, season.season_id
+                );
+                continue;
+            }
+        };
+        let car_names = car_names(&season, &classes, &cars);
+        infos.push(SeasonInfo::new(
+            &series,
+            &season,
+            basics.get(&season.season_id),
+            car_names,
+        ));
+    }
+    infos
+}
 
-        season_infos = st.db.get_series()?;
-        for si in season_infos.values() {
-            series_state
-                .entry(si.series_id)
-                .or_insert_with(|| SeriesReg::new(si));
+// the guide contains race starts for upto 3 hours, so each series may appear more
+// than once, each of its sessions is tracked separately. The series are in the order
+// they first appear in the guide.
+fn by_series(sessions: Vec<RaceGuideEntry>) -> Vec<(i64, Vec<RaceGuideEntry>)> {
+    let mut by_series: HashMap<i64, Vec<RaceGuideEntry>> = HashMap::new();
+    let mut order = Vec::new();
+    for e in sessions {
+        let sessions = by_series.entry(e.series_id).or_default();
+        if sessions.is_empty() {
+            order.push(e.series_id);
         }
+        sessions.push(e);
+    }
+    order
+        .into_iter()
+        .map(|id| (id, by_series.remove(&id).unwrap_or_default()))
+        .collect()
+}
+
+async fn update_series_info<A: IrApi>(
+    client: &A,
+    series_state: &mut HashMap<i64, SeriesReg>,
+    tx: &mut UnboundedSender<RaceGuideEvent>,
+    state: Arc<HandlerState>,
+) -> anyhow::Result<()> {
+    println!("checking for updated series/season info");
+    let (seasons, seasons_changed) = client.seasons().await?;
+    let (series, series_changed) = client.series().await?;
+    if !seasons_changed && !series_changed {
+        println!("series/season info unchanged");
+        return Ok(());
+    }
+    let infos = season_infos(client, seasons, series).await;
+    let resume = series_state.is_empty();
+    let db = state.db();
+    let (season_infos, mut saved, inactive, new_seasons) = db
+        .call(move |db| -> rusqlite::Result<_> {
+            // series that have moved onto a new season since we last looked.
+            let before = db.get_series()?;
+            let new_seasons: HashSet<i64> = infos
+                .iter()
+                .filter(|si| {
+                    before
+                        .get(&si.series_id)
+                        .is_some_and(|b| b.season_id != 0 && b.season_id != si.season_id)
+                })
+                .map(|si| si.series_id)
+                .collect();
+            let mut updater = db.start_series_update()?;
+            for si in &infos {
+                updater.upsert(si)?;
+            }
+            updater.commit()?;
+            let season_infos = db.get_series()?;
+            // pick up where we left off if we were restarted recently,
+            // and check for watches that we'll never announce.
+            let (saved, inactive) = if resume {
+                (
+                    db.watch_state(Duration::minutes(MAX_WATCH_STATE_AGE_MINS))?,
+                    db.inactive_regs()?,
+                )
+            } else {
+                (HashMap::new(), Vec::new())
+            };
+            Ok((season_infos, saved, inactive, new_seasons))
+        })
+        .await?;
+    if !saved.is_empty() {
+        println!("Resuming from saved state for {} series", saved.len());
+    }
+    let mut week_opens = HashMap::new();
+    let mut new_weeks = HashMap::new();
+    let mut season_starts = HashMap::new();
+    for si in season_infos.values() {
+        let sr = series_state
+            .entry(si.series_id)
+            .or_insert_with(|| SeriesReg::restore(si, saved.remove(&si.series_id)));
+        if new_seasons.contains(&si.series_id) {
+            println!("{} has started {}", si.name, si.season_label());
+            if let Some(rg) = sr.race_guide() {
+                season_starts.insert(
+                    si.series_id,
+                    Announcement::new(
+                        si.clone(),
+                        rg.clone(),
+                        rg.clone(),
+                        AnnouncementType::NewSeason,
+                    ),
+                );
+            }
+            // the sessions we were tracking are from the old season, comparing against
+            // them would make for bogus open/closed announcements.
+            *sr = SeriesReg::new(si);
+            continue;
+        }
+        if sr.series.week != si.week {
+            // announcements are about a race guide entry, so we can only say something
+            // about series we've seen in the race guide.
+            if let Some(rg) = sr.race_guide() {
+                for (anns, ann_type) in [
+                    (&mut week_opens, AnnouncementType::WeekOpen),
+                    (&mut new_weeks, AnnouncementType::NewWeek),
+                ] {
+                    anns.insert(
+                        si.series_id,
+                        Announcement::new(si.clone(), rg.clone(), rg.clone(), ann_type),
+                    );
+                }
+            }
+        }
+        sr.series = si.clone();
     }
     println!("Sending {} series to discord bot", season_infos.len());
-    if let Err(err) = tx.send(RaceGuideEvent::Seasons(season_infos)).await {
+    if let Err(err) = tx.send(RaceGuideEvent::Seasons(season_infos)) {
         println!("Error sending Seasons to channel {:?}", err);
     }
+    // these are separate batches as there's only one announcement per series in a batch.
+    for anns in [season_starts, week_opens, new_weeks] {
+        if !anns.is_empty() {
+            if let Err(err) = tx.send(RaceGuideEvent::Announcements(anns)) {
+                println!("Error sending week announcements to channel {:?}", err);
+            }
+        }
+    }
+    if !inactive.is_empty() {
+        let mut msg = format!(
+            "\u{26a0} {} watches are for series that aren't running this season",
+            inactive.len()
+        );
+        for r in &inactive {
+            msg.push_str(&format!(
+                "\n\u{2981} <#{}> {} ({})",
+                r.channel, r.series_name, r.series_id
+            ));
+        }
+        println!("{}", msg);
+        if let Err(err) = tx.send(RaceGuideEvent::OpsWarning(msg)) {
+            println!("Error sending OpsWarning to channel {:?}", err);
+        }
+    }
     Ok(())
 }
 async fn iracing_loop(
     series_state: &mut HashMap<i64, SeriesReg>,
-    user: &str,
-    password: &str,
-    tx: &mut Sender<RaceGuideEvent>,
-    state: Arc<Mutex<HandlerState>>,
+    config: &Config,
+    tx: &mut UnboundedSender<RaceGuideEvent>,
+    state: Arc<HandlerState>,
+    health: &Health,
+    recorder: Option<Arc<Recorder>>,
+) -> anyhow::Result<()> {
+    match &config.ir_fixtures {
+        Some(dir) => {
+            println!("Using iRacing fixtures from {}", dir);
+            let api = MockIrApi::load(Path::new(dir))?;
+            watch_loop(&api, series_state, config, tx, state, health).await
+        }
+        None => {
+            let client = IrClient::new(&config.ir_user, &config.ir_password)
+                .await?
+                .with_recorder(recorder);
+            health.ir_logged_in();
+            watch_loop(&client, series_state, config, tx, state, health).await
+        }
+    }
+}
+async fn watch_loop<A: IrApi>(
+    client: &A,
+    series_state: &mut HashMap<i64, SeriesReg>,
+    config: &Config,
+    tx: &mut UnboundedSender<RaceGuideEvent>,
+    state: Arc<HandlerState>,
+    health: &Health,
 ) -> anyhow::Result<()> {
-    let loop_interval = tokio::time::Duration::from_secs(61);
-    let client = IrClient::new(user, password).await?;
-    //
+    let mut timing = PollTiming::new(config);
+    // only work through the series that someone is watching, for small deployments
+    // that don't need the rest, and optionally only keep their history too.
+    let watched_only = config.feature("watched_only", false);
+    let watched_history = config.feature("watched_history", false);
     let mut series_updated = Utc::now();
-    update_series_info(&client, series_state, tx, state.clone()).await?;
+    // series_id -> when it was fetched & the estimated SOF.
+    let mut sofs: HashMap<i64, (DateTime<Utc>, Option<i64>)> = HashMap::new();
+    let mut results_jobs: DelayedJobs<ResultsJob> = DelayedJobs::default();
+    update_series_info(client, series_state, tx, state.clone()).await?;
+    report_schema_drift(client, tx, health).await;
     loop {
+        if tx.is_closed() {
+            return Err(anyhow!("race guide listener has stopped"));
+        }
         let now_utc = Utc::now();
         if now_utc.date_naive() != series_updated.date_naive() {
-            update_series_info(&client, series_state, tx, state.clone()).await?;
+            update_series_info(client, series_state, tx, state.clone()).await?;
             series_updated = now_utc;
+            report_schema_drift(client, tx, health).await;
         }
+        health.heartbeat(POLLER_TASK);
         println!("checking for race guide updates");
         let start = Instant::now();
         let guide = client.race_guide().await?;
-        // the guide contains race starts for upto 3 hours, so each series may appear more than once
-        // so we need to keep track of which ones we've seen and only process the first one for each series.
-        let mut seen = HashSet::new();
-        let mut announcements = HashMap::new();
-        for e in guide.sessions {
-            if seen.insert(e.series_id) {
-                if let Some(sr) = series_state.get_mut(&e.series_id) {
-                    if let Some(msg) = sr.update(e) {
-                        announcements.insert(sr.series.series_id, msg);
+        health.poll_succeeded();
+        state.set_guide(guide.sessions.clone());
+        let db = state.db();
+        // read every poll, so a new watch is picked up on the next one.
+        let mut watched = match db.call(|db| db.watched_series()).await {
+            Ok(w) => Some(w),
+            Err(e) => {
+                println!("Failed to read watched series {:?}", e);
+                None
+            }
+        };
+        if let Some(w) = watched.as_mut() {
+            w.extend(&timing.telegram_series);
+        }
+        // if we can't tell, it's better to say too much than miss something.
+        let is_watched = |series_id: &i64| match &watched {
+            Some(w) => w.contains(series_id),
+            None => true,
+        };
+        let sessions: Vec<RaceGuideEntry> = guide
+            .sessions
+            .iter()
+            .filter(|e| !watched_history || is_watched(&e.series_id))
+            .cloned()
+            .collect();
+        if let Err(e) = db.call(move |db| db.record_sessions(&sessions)).await {
+            println!("Failed to record session history {:?}", e);
+        }
+        report_schema_drift(client, tx, health).await;
+        let starting_soon = guide.sessions.iter().any(|e| {
+            watched.as_ref().is_some_and(|w| w.contains(&e.series_id))
+                && e.session_id.is_some()
+                && e.start_time - now_utc <= Duration::minutes(FAST_POLL_MINS)
+        });
+        // a batch has at most one announcement per series, so when a series has more than
+        // one thing to say they go in successive batches.
+        let mut batches: Vec<HashMap<i64, Announcement>> = Vec::new();
+        let mut changed: Vec<(i64, WatchState)> = Vec::new();
+        let mut latest = HashMap::new();
+        for (series_id, sessions) in by_series(guide.sessions) {
+            latest.insert(series_id, sessions[0].clone());
+            if let Some(sr) = series_state.get_mut(&series_id) {
+                if watched_only && !is_watched(&series_id) {
+                    // start afresh if it gets watched, rather than announcing what changed
+                    // while it wasn't.
+                    sr.sessions.clear();
+                    continue;
+                }
+                let before = sr.race_guide().cloned();
+                for (i, msg) in sr.update(sessions, now_utc).into_iter().enumerate() {
+                    if batches.len() <= i {
+                        batches.push(HashMap::new());
+                    }
+                    batches[i].insert(series_id, msg);
+                }
+                if let Some(rg) = sr.race_guide() {
+                    if before.as_ref() != Some(rg) {
+                        changed.push((series_id, (rg.clone(), sr.opened_at())));
                     }
                 }
-                continue;
             }
         }
-        let ann_count = announcements.len();
-        if !announcements.is_empty() {
-            match tx.send(RaceGuideEvent::Announcements(announcements)).await {
-                Err(err) => println!("Failed to send RaceGuideEvent to channel {:?}", err),
-                _ => {}
+        let db = state.db();
+        if let Err(e) = db.call(move |db| db.save_watch_state(&changed)).await {
+            println!("Failed to save watch state {:?}", e);
+        }
+        schedule_results(&mut results_jobs, &batches, &state).await;
+        add_sof(client, &mut sofs, &mut batches, now_utc).await;
+        let ann_count: usize = batches.iter().map(|b| b.len()).sum();
+        for announcements in batches {
+            if let Err(err) = tx.send(RaceGuideEvent::Announcements(announcements)) {
+                println!("Failed to send RaceGuideEvent to channel {:?}", err)
+            }
+        }
+        let predictions = predict(series_state, &state, now_utc).await;
+        if !predictions.is_empty() {
+            if let Err(err) = tx.send(RaceGuideEvent::Announcements(predictions)) {
+                println!("Failed to send predictions to channel {:?}", err);
+            }
+        }
+        for results in fetch_results(client, &mut results_jobs, series_state, now_utc).await {
+            if let Err(err) = tx.send(RaceGuideEvent::Announcements(results)) {
+                println!("Failed to send results to channel {:?}", err);
+            }
+        }
+        let last_days: HashMap<i64, Announcement> = series_state
+            .values_mut()
+            .filter(|sr| !watched_only || is_watched(&sr.series.series_id))
+            .filter_map(|sr| sr.last_day(now_utc).map(|a| (sr.series.series_id, a)))
+            .collect();
+        if !last_days.is_empty() {
+            if let Err(err) = tx.send(RaceGuideEvent::Announcements(last_days)) {
+                println!("Failed to send last day announcements to channel {:?}", err);
             }
         }
+        if let Err(err) = tx.send(RaceGuideEvent::Status(latest)) {
+            println!("Failed to send status to channel {:?}", err);
+        }
+        let delay = timing.next_poll(client, Utc::now(), starting_soon);
         println!(
-            "all done for this time, sent {} announcements, took {}ms",
+            "all done for this time, sent {} announcements, took {}ms, next poll in {}s",
             ann_count,
-            (Instant::now() - start).as_millis()
+            (Instant::now() - start).as_millis(),
+            delay.num_seconds()
+        );
+        tokio::time::sleep_until(start + delay.to_std().unwrap_or_default()).await;
+    }
+}
+
+// announces the open sessions that look like they'll go official, going by how the
+// series' past sessions filled up, for the series that have watches that want to know.
+async fn predict(
+    series_state: &mut HashMap<i64, SeriesReg>,
+    state: &HandlerState,
+    now: DateTime<Utc>,
+) -> HashMap<i64, Announcement> {
+    let candidates: Vec<RaceGuideEntry> = series_state
+        .values()
+        .filter_map(|sr| sr.prediction_candidate(now).cloned())
+        .collect();
+    if candidates.is_empty() {
+        return HashMap::new();
+    }
+    let db = state.db();
+    let samples = db
+        .call(
+            move |db| -> rusqlite::Result<Vec<(RaceGuideEntry, Vec<forecast::Sample>)>> {
+                let wanted = db.predict_series()?;
+                let mut res = Vec::new();
+                for e in candidates {
+                    if wanted.contains(&e.series_id) {
+                        let mins = forecast::sample_mins(e.start_time, now);
+                        let samples = db.entry_samples(e.series_id, mins, now)?;
+                        res.push((e, samples));
+                    }
+                }
+                Ok(res)
+            },
+        )
+        .await;
+    let samples = match samples {
+        Err(e) => {
+            println!("Failed to read entry samples {:?}", e);
+            return HashMap::new();
+        }
+        Ok(s) => s,
+    };
+    let mut res = HashMap::new();
+    for (e, samples) in samples {
+        let sr = match series_state.get_mut(&e.series_id) {
+            Some(sr) => sr,
+            None => continue,
+        };
+        let p = match forecast::predict(e.start_time, e.entry_count, &samples) {
+            Some(p) if p >= sr.series.reg_official => p,
+            _ => continue,
+        };
+        sr.predicted = Some(e.start_time);
+        let mut a = Announcement::new(
+            sr.series.clone(),
+            e.clone(),
+            e.clone(),
+            AnnouncementType::Predicted,
         );
-        tokio::time::sleep_until(start + loop_interval).await;
+        a.predicted = Some(p);
+        res.insert(e.series_id, a);
+    }
+    res
+}
+
+// fills in the estimated SOF on the Count & Closed announcements, from the series'
+// recent official races. Results are cached per series, and the searches are spread
+// out over polls so they don't eat into the rate limit the race guide needs.
+async fn add_sof<A: IrApi>(
+    client: &A,
+    sofs: &mut HashMap<i64, (DateTime<Utc>, Option<i64>)>,
+    batches: &mut [HashMap<i64, Announcement>],
+    now: DateTime<Utc>,
+) {
+    sofs.retain(|_, (at, _)| now - *at < Duration::minutes(SOF_CACHE_MINS));
+    let mut fetches = 0;
+    for a in batches.iter_mut().flat_map(|b| b.values_mut()) {
+        if !matches!(
+            a.ann_type,
+            AnnouncementType::Count | AnnouncementType::Closed
+        ) {
+            continue;
+        }
+        let series_id = a.series.series_id;
+        if let Some((_, sof)) = sofs.get(&series_id) {
+            a.sof = *sof;
+            continue;
+        }
+        if fetches >= SOF_MAX_FETCHES
+            || matches!(client.rate_limit_remaining(), Some(r) if r < SPARE_RATE_LIMIT)
+        {
+            continue;
+        }
+        fetches += 1;
+        let since = now - Duration::hours(SOF_HISTORY_HOURS);
+        let sof = match client.search_series(series_id, since).await {
+            Ok(results) => estimate_sof(&results),
+            Err(e) => {
+                println!("Failed to fetch results for series {}: {}", series_id, e);
+                None
+            }
+        };
+        sofs.insert(series_id, (now, sof));
+        a.sof = sof;
+    }
+}
+
+// ResultsJob is a race that's started, for a series that has watches that want its results.
+struct ResultsJob {
+    session: RaceGuideEntry,
+    attempts: u32,
+}
+
+// queues up a results job for each official race that just started, for the series
+// that have watches that want results. They're due a little after the race ends.
+async fn schedule_results(
+    jobs: &mut DelayedJobs<ResultsJob>,
+    batches: &[HashMap<i64, Announcement>],
+    state: &HandlerState,
+) {
+    let started: Vec<&RaceGuideEntry> = batches
+        .iter()
+        .flat_map(|b| b.values())
+        .filter(|a| matches!(a.ann_type, AnnouncementType::Closed))
+        .filter(|a| a.prev.entry_count >= a.series.reg_official)
+        .map(|a| &a.prev)
+        .collect();
+    if started.is_empty() {
+        return;
+    }
+    let db = state.db();
+    let wanted = match db.call(|db| db.results_series()).await {
+        Ok(w) => w,
+        Err(e) => {
+            println!("Failed to read results watches {:?}", e);
+            return;
+        }
+    };
+    for s in started {
+        if !wanted.contains(&s.series_id) || jobs.contains(|j| j.session.session_id == s.session_id)
+        {
+            continue;
+        }
+        let ends = DateTime::parse_from_rfc3339(&s.end_time)
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|_| s.start_time + Duration::minutes(DEFAULT_SESSION_MINS));
+        jobs.add(
+            ends + Duration::minutes(RESULTS_DELAY_MINS),
+            ResultsJob {
+                session: s.clone(),
+                attempts: 0,
+            },
+        );
+    }
+}
+
+// fetches the results of the races that are due, the ones that aren't in yet go back
+// for another try later. Returns the Results announcements in batches, as a series
+// can have more than one race finish at once.
+async fn fetch_results<A: IrApi>(
+    client: &A,
+    jobs: &mut DelayedJobs<ResultsJob>,
+    series_state: &HashMap<i64, SeriesReg>,
+    now: DateTime<Utc>,
+) -> Vec<HashMap<i64, Announcement>> {
+    let mut batches: Vec<HashMap<i64, Announcement>> = Vec::new();
+    for mut job in jobs.due(now) {
+        let series_id = job.session.series_id;
+        let series = match series_state.get(&series_id) {
+            Some(sr) => sr.series.clone(),
+            None => continue,
+        };
+        if matches!(client.rate_limit_remaining(), Some(r) if r < SPARE_RATE_LIMIT) {
+            jobs.add(now + Duration::minutes(RESULTS_RETRY_MINS), job);
+            continue;
+        }
+        let results = match race_results(client, &job.session).await {
+            Ok(Some(r)) => r,
+            res => {
+                if let Err(e) = res {
+                    println!("Failed to fetch results for series {}: {}", series_id, e);
+                }
+                job.attempts += 1;
+                if job.attempts < RESULTS_MAX_ATTEMPTS {
+                    jobs.add(now + Duration::minutes(RESULTS_RETRY_MINS), job);
+                } else {
+                    println!(
+                        "Giving up on results for series {} session {:?}",
+                        series_id, job.session.session_id
+                    );
+                }
+                continue;
+            }
+        };
+        let mut a = Announcement::new(
+            series,
+            job.session.clone(),
+            job.session,
+            AnnouncementType::Results,
+        );
+        a.results = Some(results);
+        match batches.iter_mut().find(|b| !b.contains_key(&series_id)) {
+            Some(b) => {
+                b.insert(series_id, a);
+            }
+            None => batches.push(HashMap::from([(series_id, a)])),
+        }
+    }
+    batches
+}
+
+// the results of a finished race, None if they're not in yet. The splits and their SOF
+// come from the series search, and the winners from the top split's full results.
+async fn race_results<A: IrApi>(
+    client: &A,
+    session: &RaceGuideEntry,
+) -> Result<Option<RaceResults>, IrError> {
+    let splits: Vec<SeriesResult> = client
+        .search_series(session.series_id, session.start_time - Duration::minutes(1))
+        .await?
+        .into_iter()
+        .filter(|r| Some(r.session_id) == session.session_id)
+        .collect();
+    let top = match splits.iter().max_by_key(|r| r.event_strength_of_field) {
+        Some(t) => t,
+        None => return Ok(None),
+    };
+    let winners = client
+        .results(top.subsession_id)
+        .await?
+        .class_winners()
+        .into_iter()
+        .map(|d| (d.car_class_short_name.clone(), d.display_name.clone()))
+        .collect();
+    Ok(Some(RaceResults {
+        splits: splits.len() as i64,
+        drivers: splits.iter().map(|r| r.num_drivers).sum(),
+        sof: top.event_strength_of_field,
+        winners,
+    }))
+}
+
+// the top split is what people care about, so its the highest SOF of each race, and
+// then the median of those so one unusual race doesn't skew it.
+fn estimate_sof(results: &[SeriesResult]) -> Option<i64> {
+    let mut by_session: HashMap<i64, i64> = HashMap::new();
+    for r in results.iter().filter(|r| r.event_strength_of_field > 0) {
+        let sof = by_session.entry(r.session_id).or_default();
+        *sof = (*sof).max(r.event_strength_of_field);
+    }
+    let mut sofs: Vec<i64> = by_session.into_values().collect();
+    if sofs.is_empty() {
+        return None;
+    }
+    sofs.sort_unstable();
+    Some(sofs[sofs.len() / 2])
+}
+
+async fn report_schema_drift<A: IrApi>(
+    client: &A,
+    tx: &mut UnboundedSender<RaceGuideEvent>,
+    health: &Health,
+) {
+    health.schema_drift(client.schema().drift_count());
+    let warnings = client.schema().take_warnings();
+    if warnings.is_empty() {
+        return;
+    }
+    let msg = format!(
+        "\u{26a0} iRacing API changes detected\n{}",
+        warnings.join("\n")
+    );
+    if let Err(err) = tx.send(RaceGuideEvent::OpsWarning(msg)) {
+        println!("Error sending OpsWarning to channel {:?}", err);
     }
 }
 
@@ -131,6 +953,33 @@ pub enum AnnouncementType {
     Open,
     Count,
     Closed,
+    // the series has moved to a new race week, so time trials & open qualifying are open.
+    WeekOpen,
+    // the series has moved to a new race week, just the new track.
+    NewWeek,
+    // the series has started a new season.
+    NewSeason,
+    // the last day of the race week has started.
+    LastDay,
+    // the session is on course to go official, see forecast.
+    Predicted,
+    // the race has finished and its results are in.
+    Results,
+}
+impl AnnouncementType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnnouncementType::Open => "open",
+            AnnouncementType::Count => "count",
+            AnnouncementType::Closed => "closed",
+            AnnouncementType::WeekOpen => "week open",
+            AnnouncementType::NewWeek => "new week",
+            AnnouncementType::NewSeason => "new season",
+            AnnouncementType::LastDay => "last day",
+            AnnouncementType::Predicted => "predicted",
+            AnnouncementType::Results => "results",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -139,6 +988,42 @@ pub struct Announcement {
     pub prev: RaceGuideEntry,
     pub curr: RaceGuideEntry,
     pub ann_type: AnnouncementType,
+    // when we saw registration open for the session, None if it was already open at startup.
+    pub opened_at: Option<DateTime<Utc>>,
+    // when registration last closed for the series, if we've seen it close.
+    pub closed_at: Option<DateTime<Utc>>,
+    // the start time of the previous session we saw registration open for.
+    pub prev_open: Option<DateTime<Utc>>,
+    // the entry count the session is expected to get to, for Predicted.
+    pub predicted: Option<i64>,
+    // the estimated strength of field, for Count & Closed when we have results to go on.
+    pub sof: Option<i64>,
+    // how the race went, for Results.
+    pub results: Option<RaceResults>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RaceResults {
+    pub splits: i64,
+    pub drivers: i64,
+    // the strength of field of the top split.
+    pub sof: i64,
+    // the (class, name) of the winner of each class in the top split.
+    pub winners: Vec<(String, String)>,
+}
+impl RaceResults {
+    // e.g. "Jane Doe" or "Jane Doe (GTP), John Smith (GT3)"
+    pub fn winner_text(&self) -> String {
+        match &self.winners[..] {
+            [] => "?".to_string(),
+            [(_, name)] => name.clone(),
+            w => w
+                .iter()
+                .map(|(class, name)| format!("{} ({})", name, class))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
 }
 impl Announcement {
     fn new(
@@ -152,15 +1037,109 @@ impl Announcement {
             prev,
             curr,
             ann_type,
+            opened_at: None,
+            closed_at: None,
+            prev_open: None,
+            predicted: None,
+            sof: None,
+            results: None,
         }
     }
+    // returns a made up announcement of each type for the series, to preview the styles with.
+    pub fn samples(series: &SeasonInfo) -> Vec<Announcement> {
+        let entry = |entry_count, mins| RaceGuideEntry {
+            season_id: 0,
+            start_time: Utc::now() + Duration::minutes(mins),
+            super_session: false,
+            series_id: series.series_id,
+            race_week_num: series.week,
+            end_time: String::new(),
+            session_id: Some(0),
+            entry_count,
+        };
+        let official = series.reg_official.max(1);
+        [
+            (entry(0, 45), entry(0, 45), AnnouncementType::Open),
+            (
+                entry(official - 1, 12),
+                entry(official, 12),
+                AnnouncementType::Count,
+            ),
+            (
+                entry(official + 3, 0),
+                entry(0, 120),
+                AnnouncementType::Closed,
+            ),
+            (entry(0, 45), entry(0, 45), AnnouncementType::WeekOpen),
+            (entry(0, 45), entry(0, 45), AnnouncementType::NewWeek),
+            (entry(0, 45), entry(0, 45), AnnouncementType::NewSeason),
+            (entry(0, 45), entry(0, 45), AnnouncementType::LastDay),
+            (
+                entry(official / 2, 30),
+                entry(official / 2, 30),
+                AnnouncementType::Predicted,
+            ),
+            (
+                entry(official + 3, -60),
+                entry(official + 3, -60),
+                AnnouncementType::Results,
+            ),
+        ]
+        .into_iter()
+        .map(|(prev, curr, t)| {
+            let mut a = Announcement::new(series.clone(), prev, curr, t);
+            match a.ann_type {
+                AnnouncementType::Predicted => a.predicted = Some(official + 2),
+                AnnouncementType::Count | AnnouncementType::Closed => a.sof = Some(1850),
+                AnnouncementType::Results => {
+                    a.results = Some(RaceResults {
+                        splits: 2,
+                        drivers: official + 3,
+                        sof: 2140,
+                        winners: vec![(String::new(), "Jane Doe".to_string())],
+                    })
+                }
+                _ => {}
+            }
+            a
+        })
+        .collect()
+    }
     // returns true if the number of splits has changed
     pub fn splits_changed(&self) -> bool {
         self.prev.num_splits(self.series.reg_split) != self.curr.num_splits(self.series.reg_split)
     }
 }
-impl Display for Announcement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Announcement {
+    fn sof_text(&self, locale: Locale) -> String {
+        match self.sof {
+            Some(sof) => format!(" ({})", tr(locale, Msg::EstSof, &[&sof])),
+            None => String::new(),
+        }
+    }
+    // a copy with the names that come from iRacing escaped, for rendering into a discord
+    // message. Everything else (telegram, the logs) gets them as they are.
+    pub fn escaped(&self) -> Announcement {
+        let mut ann = self.clone();
+        let s = &mut ann.series;
+        for t in [
+            &mut s.name,
+            &mut s.short_name,
+            &mut s.track_name,
+            &mut s.track_config,
+        ] {
+            *t = escape_markdown(t);
+        }
+        if let Some(r) = &mut ann.results {
+            for (class, name) in &mut r.winners {
+                *class = escape_markdown(class);
+                *name = escape_markdown(name);
+            }
+        }
+        ann
+    }
+    // render the announcement text in the requested language.
+    pub fn render(&self, locale: Locale) -> String {
         let off = Duration::seconds(29);
         let to_start = self.curr.start_time - Utc::now();
         let split_text = |rge: &RaceGuideEntry| {
@@ -168,104 +1147,364 @@ impl Display for Announcement {
             if rge.entry_count < self.series.reg_official {
                 "".to_string()
             } else if split_count < 2 {
-                "Official! ".to_string()
+                text(locale, Msg::Official).to_string()
             } else {
-                format!("{} splits! ", split_count)
+                tr(locale, Msg::Splits, &[&split_count])
             }
         };
+        let track = if self.series.track_config.is_empty() {
+            self.series.track_name.clone()
+        } else {
+            format!(
+                "{} ({})",
+                &self.series.track_name, &self.series.track_config
+            )
+        };
         match self.ann_type {
-            AnnouncementType::Open => write!(
-                f,
-                "{}: Registration open!, {} minutes til race time",
-                &self.series.name,
-                (to_start + off).num_minutes()
+            AnnouncementType::Open => tr(
+                locale,
+                Msg::RegOpen,
+                &[&self.series.name, &(to_start + off).num_minutes()],
             ),
             AnnouncementType::Count => {
+                let mins = (to_start + off).num_minutes();
                 let starts_in = if to_start.num_minutes() < 1 {
-                    "less than a minute! \u{1f3ce}".to_string()
+                    text(locale, Msg::StartsVerySoon).to_string()
+                } else if mins == 1 {
+                    tr(locale, Msg::Minute, &[&mins])
                 } else {
-                    format!(
-                        "{} minute{}",
-                        (to_start + off).num_minutes(),
-                        if (to_start + off).num_minutes() == 1 {
-                            ""
-                        } else {
-                            "s"
-                        }
-                    )
+                    tr(locale, Msg::Minutes, &[&mins])
                 };
-                write!(
-                    f,
-                    "{}: {} registered. {}Session starts in {}",
-                    &self.series.name,
-                    self.curr.entry_count,
-                    split_text(&self.curr),
-                    starts_in
-                )
+                tr(
+                    locale,
+                    Msg::RegCount,
+                    &[
+                        &self.series.name,
+                        &self.curr.entry_count,
+                        &split_text(&self.curr),
+                        &starts_in,
+                    ],
+                ) + &self.sof_text(locale)
             }
             AnnouncementType::Closed => {
-                write!(
-                    f,
-                    "{}: registration closed \u{26d4} {} registered {}.",
+                tr(
+                    locale,
+                    Msg::RegClosed,
+                    &[
+                        &self.series.name,
+                        &self.prev.entry_count,
+                        &split_text(&self.prev),
+                    ],
+                ) + &self.sof_text(locale)
+            }
+            AnnouncementType::WeekOpen => {
+                tr(
+                    locale,
+                    Msg::WeekOpen,
+                    &[&self.series.name, &(self.series.week + 1), &track],
+                ) + text(locale, Msg::WeekOpenTail)
+            }
+            AnnouncementType::NewSeason => tr(
+                locale,
+                Msg::NewSeason,
+                &[
                     &self.series.name,
-                    self.prev.entry_count,
-                    split_text(&self.prev)
-                )
+                    &self.series.season_label(),
+                    &(self.series.week + 1),
+                    &track,
+                ],
+            ),
+            AnnouncementType::NewWeek => tr(
+                locale,
+                Msg::NewWeek,
+                &[&self.series.name, &(self.series.week + 1), &track],
+            ),
+            AnnouncementType::LastDay => {
+                tr(
+                    locale,
+                    Msg::LastDay,
+                    &[&self.series.name, &(self.series.week + 1), &track],
+                ) + " \u{1f3c1}"
             }
+            AnnouncementType::Predicted => tr(
+                locale,
+                Msg::Predicted,
+                &[
+                    &self.series.name,
+                    &self.curr.entry_count,
+                    &self.predicted.unwrap_or(self.series.reg_official),
+                    &(to_start + off).num_minutes(),
+                ],
+            ),
+            AnnouncementType::Results => match &self.results {
+                None => String::new(),
+                Some(r) if r.splits > 1 => tr(
+                    locale,
+                    Msg::ResultsSplits,
+                    &[
+                        &self.series.name,
+                        &r.winner_text(),
+                        &r.splits,
+                        &r.sof,
+                        &r.drivers,
+                    ],
+                ),
+                Some(r) => tr(
+                    locale,
+                    Msg::Results,
+                    &[&self.series.name, &r.winner_text(), &r.sof, &r.drivers],
+                ),
+            },
         }
     }
 }
+impl Display for Announcement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(Locale::En))
+    }
+}
+
+// SessionState is what we know about one session of a series in the race guide.
+#[derive(Debug, Clone)]
+struct SessionState {
+    entry: RaceGuideEntry,
+    // when we saw registration open, None if it was already open when we first saw it.
+    opened_at: Option<DateTime<Utc>>,
+}
 
 struct SeriesReg {
     series: SeasonInfo,
-    race_guide: Option<RaceGuideEntry>,
+    // the series' sessions in the race guide by start time, the first is the next to start.
+    sessions: BTreeMap<DateTime<Utc>, SessionState>,
+    closed_at: Option<DateTime<Utc>>,
+    // the start time of the last session we saw registration open for.
+    last_open: Option<DateTime<Utc>>,
+    // the race week we've said is on its last day.
+    last_day_week: Option<i64>,
+    // the start time of the last session we predicted would go official.
+    predicted: Option<DateTime<Utc>>,
 }
 impl SeriesReg {
     fn new(s: &SeasonInfo) -> Self {
         SeriesReg {
             series: s.clone(),
-            race_guide: None,
+            sessions: BTreeMap::new(),
+            closed_at: None,
+            last_open: None,
+            predicted: None,
+            // if we start up on the last day, it's probably already been announced.
+            last_day_week: if s.is_last_day(Utc::now()) {
+                Some(s.week)
+            } else {
+                None
+            },
         }
     }
-    #[inline]
-    fn update(&mut self, e: RaceGuideEntry) -> Option<Announcement> {
-        if self.race_guide.is_none() {
-            self.race_guide = Some(e);
+    fn restore(s: &SeasonInfo, saved: Option<WatchState>) -> Self {
+        match saved {
+            None => SeriesReg::new(s),
+            Some((rg, opened_at)) => SeriesReg {
+                sessions: BTreeMap::from([(
+                    rg.start_time,
+                    SessionState {
+                        entry: rg,
+                        opened_at,
+                    },
+                )]),
+                ..SeriesReg::new(s)
+            },
+        }
+    }
+    // the race guide entry for the next session to start.
+    fn race_guide(&self) -> Option<&RaceGuideEntry> {
+        self.sessions.values().next().map(|s| &s.entry)
+    }
+    fn opened_at(&self) -> Option<DateTime<Utc>> {
+        self.sessions.values().next().and_then(|s| s.opened_at)
+    }
+    // the next session, if it's open, hasn't gone official yet and hasn't been predicted.
+    fn prediction_candidate(&self, now: DateTime<Utc>) -> Option<&RaceGuideEntry> {
+        let rg = self.race_guide()?;
+        (rg.session_id.is_some()
+            && rg.entry_count > 0
+            && rg.entry_count < self.series.reg_official
+            && self.predicted != Some(rg.start_time)
+            && rg.start_time - now >= Duration::minutes(PREDICT_MIN_MINS))
+        .then_some(rg)
+    }
+    // returns an announcement if the last day of the race week has started since we last looked.
+    fn last_day(&mut self, now: DateTime<Utc>) -> Option<Announcement> {
+        if !self.series.is_last_day(now) || self.last_day_week == Some(self.series.week) {
             return None;
         }
-        // reg open
-        let prev = self.race_guide.take().unwrap();
-        let ann = if prev.session_id.is_none() && e.session_id.is_some() {
-            Some(Announcement::new(
-                self.series.clone(),
-                prev,
-                e.clone(),
-                AnnouncementType::Open,
-            ))
-        // reg count changed.
-        } else if prev.session_id.is_some()
-            && e.session_id.is_some()
-            && prev.entry_count != e.entry_count
-            && (prev.entry_count > 0 || e.entry_count > 0)
-        {
-            Some(Announcement::new(
-                self.series.clone(),
-                prev,
-                e.clone(),
-                AnnouncementType::Count,
-            ))
-        // reg closed
-        } else if prev.session_id.is_some() && e.session_id.is_none() && prev.entry_count > 0 {
-            Some(Announcement::new(
-                self.series.clone(),
-                prev,
-                e.clone(),
-                AnnouncementType::Closed,
-            ))
-        } else {
-            None
+        self.last_day_week = Some(self.series.week);
+        let rg = self.race_guide()?;
+        Some(Announcement::new(
+            self.series.clone(),
+            rg.clone(),
+            rg.clone(),
+            AnnouncementType::LastDay,
+        ))
+    }
+    // updates the sessions from the series' entries in the latest race guide, returning
+    // the announcements for any changes, earliest session first.
+    fn update(&mut self, entries: Vec<RaceGuideEntry>, now: DateTime<Utc>) -> Vec<Announcement> {
+        let prev_open = self.last_open;
+        let closed_at = self.closed_at;
+        let mut old = std::mem::take(&mut self.sessions);
+        let mut anns = Vec::new();
+        let announce = |prev, curr, ann_type, opened_at| {
+            let mut a = Announcement::new(self.series.clone(), prev, curr, ann_type);
+            a.opened_at = opened_at;
+            a.closed_at = closed_at;
+            a.prev_open = prev_open;
+            a
         };
-        self.race_guide = Some(e);
-        ann
+        // sessions that have dropped out of the guide have started, so registration for
+        // them has closed, if it hadn't already.
+        let current: HashSet<DateTime<Utc>> = entries.iter().map(|e| e.start_time).collect();
+        let gone: Vec<DateTime<Utc>> = old
+            .keys()
+            .filter(|t| !current.contains(t))
+            .cloned()
+            .collect();
+        for t in gone {
+            let s = old.remove(&t).unwrap();
+            if let Some(change) = announce_rules::dropped(&s.entry) {
+                self.closed_at = Some(now);
+                if let Some(ann_type) = change.announcement() {
+                    let curr = RaceGuideEntry {
+                        session_id: None,
+                        ..s.entry.clone()
+                    };
+                    anns.push(announce(s.entry, curr, ann_type, s.opened_at));
+                }
+            }
+        }
+        for e in entries {
+            // a session we haven't seen before, there's nothing to compare it with yet.
+            let prev = match old.remove(&e.start_time) {
+                Some(p) => p,
+                None => {
+                    self.sessions.insert(
+                        e.start_time,
+                        SessionState {
+                            entry: e,
+                            opened_at: None,
+                        },
+                    );
+                    continue;
+                }
+            };
+            let mut opened_at = prev.opened_at;
+            let p = prev.entry;
+            let change = announce_rules::transition(&p, &e);
+            match change {
+                Some(Transition::Opened) => {
+                    opened_at = Some(now);
+                    self.last_open = Some(e.start_time);
+                }
+                Some(Transition::Closed { .. }) => self.closed_at = Some(now),
+                _ => {}
+            }
+            if let Some(t) = change.and_then(Transition::announcement) {
+                anns.push(announce(p, e.clone(), t, opened_at));
+            }
+            if e.session_id.is_none() {
+                opened_at = None;
+            }
+            self.sessions.insert(
+                e.start_time,
+                SessionState {
+                    entry: e,
+                    opened_at,
+                },
+            );
+        }
+        anns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixtures() -> MockIrApi {
+        MockIrApi::load(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/replay")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_season_infos() {
+        let client = fixtures();
+        let (seasons, changed) = client.seasons().await.unwrap();
+        assert!(changed);
+        let (series, _) = client.series().await.unwrap();
+        let infos = season_infos(&client, seasons, series).await;
+        assert_eq!(infos.len(), 1);
+        let si = &infos[0];
+        assert_eq!((si.series_id, si.name.as_str()), (231, "Test Series"));
+        assert_eq!((si.reg_official, si.reg_split), (8, 20));
+        assert_eq!(si.week, 2);
+        assert_eq!(si.track_name, "Circuit de Spa-Francorchamps");
+        assert_eq!(si.track_config, "Grand Prix Pits");
+        assert_eq!(si.season_label(), "2026 S3");
+        // the season data only changes the first time.
+        assert!(!client.seasons().await.unwrap().1);
+    }
+
+    #[tokio::test]
+    async fn test_season_infos_missing_series() {
+        let client = fixtures();
+        let (seasons, _) = client.seasons().await.unwrap();
+        // a season without its series is skipped, not a panic.
+        assert!(season_infos(&client, seasons, Vec::new()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_series_reg_update() {
+        let client = fixtures();
+        let (seasons, _) = client.seasons().await.unwrap();
+        let (series, _) = client.series().await.unwrap();
+        let mut sr = SeriesReg::new(&season_infos(&client, seasons, series).await[0]);
+        let mut anns = Vec::new();
+        for _ in 0..client.race_guide_count() {
+            let guide = client.race_guide().await.unwrap();
+            let at = DateTime::parse_from_rfc3339(&guide.block_begin_time)
+                .unwrap()
+                .with_timezone(&Utc);
+            for (_, sessions) in by_series(guide.sessions) {
+                anns.extend(sr.update(sessions, at).into_iter().map(|a| {
+                    (
+                        a.ann_type.name(),
+                        a.prev.entry_count,
+                        a.curr.entry_count,
+                        a.opened_at.map(|t| t.format("%H:%M").to_string()),
+                    )
+                }));
+            }
+        }
+        let opened = Some("12:10".to_string());
+        assert_eq!(
+            anns,
+            vec![
+                ("count", 2, 5, None),
+                ("open", 0, 0, opened.clone()),
+                ("count", 5, 6, None),
+                ("count", 0, 4, opened),
+                ("closed", 6, 6, None),
+            ]
+        );
+        // the mock sticks on the last race guide, so nothing changes from here.
+        let guide = client.race_guide().await.unwrap();
+        let at = DateTime::parse_from_rfc3339(&guide.block_begin_time)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(sr.update(guide.sessions, at).is_empty());
+        assert_eq!(sr.race_guide().map(|e| e.entry_count), Some(4));
+        assert_eq!(
+            sr.opened_at().map(|t| t.format("%H:%M").to_string()),
+            Some("12:10".to_string())
+        );
     }
 }
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+// DelayedJobs holds work that can't be done until some later time, like fetching a
+// race's results once it's finished. Jobs due at the same time come out in the order
+// they were added. It's only in memory, so anything pending is lost on a restart.
+pub struct DelayedJobs<T> {
+    jobs: BTreeMap<(DateTime<Utc>, u64), T>,
+    seq: u64,
+}
+impl<T> Default for DelayedJobs<T> {
+    fn default() -> Self {
+        DelayedJobs {
+            jobs: BTreeMap::new(),
+            seq: 0,
+        }
+    }
+}
+impl<T> DelayedJobs<T> {
+    pub fn add(&mut self, at: DateTime<Utc>, job: T) {
+        self.seq += 1;
+        self.jobs.insert((at, self.seq), job);
+    }
+    // removes and returns the jobs that are due at now, oldest first.
+    pub fn due(&mut self, now: DateTime<Utc>) -> Vec<T> {
+        let later = self.jobs.split_off(&(now, u64::MAX));
+        std::mem::replace(&mut self.jobs, later)
+            .into_values()
+            .collect()
+    }
+    // returns true if there's already a job that matches f.
+    pub fn contains(&self, f: impl Fn(&T) -> bool) -> bool {
+        self.jobs.values().any(f)
+    }
+}
@@ -1,68 +1,398 @@
-use cmds::{ACommand, HelpCommand, ListCommand, RegCommand, RemoveCommand};
-use db::{Db, Reg, SeasonInfo};
-use ir_watcher::Announcement;
-use ir_watcher::{iracing_loop_task, RaceGuideEvent};
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use cli::{Cli, CliCommand};
+use cmds::{
+    announcement_buttons, group_commands, with_outcome, ACommand, AnnouncementCommand,
+    BlockCommand, BoostCommand, BotStatsCommand, CopyWatchesCommand, DailyDigestCommand,
+    DefaultsCommand, DigestCommand, ExportCommand, ForgetMeCommand, HelpCommand, HistoryCommand,
+    ImportCommand, LanguageCommand, LicensesCommand, ListAllCommand, ListCommand, MuteCommand,
+    NowCommand, PollCommand, PopularCommand, PreviewStylesCommand, PrivateRepliesCommand,
+    RegCommand, RemindMeCommand, RemoveCommand, RestrictWatchesCommand, SeriesButtons,
+    SetupStatusCommand, SnoozeCommand, StatusBoardCommand, StyleCommand, TimezoneCommand,
+    UndoCommand, WatchEventCommand, WatchLeagueCommand, WatchManyCommand, WatchMessageCommand,
+    WebhookCommand, WeeklyPostCommand, MAX_BUTTON_ROWS, REMIND_BEFORE_MINS,
+};
+use config::Config;
+use db::{AnnounceLog, ChannelSettings, DbHandle, Reg, SeasonInfo, DELETED_REG_DAYS};
+use error::BotError;
+use futures::stream::{self, StreamExt};
+use health::Health;
+use i18n::{text, tr, Locale, Msg};
+use ir::RaceGuideEntry;
+use ir_watcher::{coalesce, iracing_loop_task, RaceGuideEvent, POLLER_TASK};
+use ir_watcher::{Announcement, AnnouncementType};
+use recorder::Recorder;
+use reqwest::StatusCode;
 use serenity::async_trait;
-use serenity::http::Http;
+use serenity::builder::CreateApplicationCommands;
+use serenity::client::bridge::gateway::event::ShardStageUpdateEvent;
+use serenity::gateway::ConnectionStage;
+use serenity::http::{Http, HttpError};
 use serenity::model::application::interaction::Interaction;
 use serenity::model::gateway::Ready;
-use serenity::model::prelude::{ChannelId, Guild, GuildChannel, GuildId, UnavailableGuild};
+use serenity::model::prelude::command::Command;
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::model::prelude::{
+    Channel, ChannelId, ChannelType, Guild, GuildChannel, GuildId, Message, MessageId,
+    UnavailableGuild, UserId,
+};
 use serenity::prelude::Context;
 use serenity::prelude::EventHandler;
 use serenity::prelude::GatewayIntents;
 use serenity::Client;
-use std::collections::HashMap;
-use std::env;
-use std::sync::Arc;
-use std::sync::Mutex;
+use sha2::{Digest, Sha256};
+use sink::Sink;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Instant;
+use style::{escape_markdown, Layout};
+use supervisor::supervise;
 use tokio::spawn;
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use webhook::{WebhookFormat, WebhookTarget};
 
+mod announce_rules;
+mod api;
+mod backup;
+mod cli;
 mod cmds;
+mod config;
+mod dashboard;
 mod db;
+mod error;
+mod forecast;
+mod health;
+mod hosted;
+mod i18n;
 mod ir;
+mod ir_mock;
+mod ir_schema;
 mod ir_watcher;
+mod jobs;
+mod presence;
+mod recorder;
+mod schedule;
+mod sink;
+mod style;
+mod supervisor;
+mod telegram;
+mod webhook;
 
+// HandlerState is what the commands, the watcher and the API share. Each part has its
+// own lock, and the seasons & guide are swapped out whole as snapshots, so a command
+// reading them never waits on the watcher updating them. The db has its own threads,
+// see DbHandle.
+// A lock poisoned by a panic elsewhere still holds a whole snapshot, so it's used anyway
+// rather than taking everything else down too.
 pub struct HandlerState {
-    seasons: HashMap<i64, SeasonInfo>,
-    db: Db,
+    seasons: RwLock<Arc<HashMap<i64, SeasonInfo>>>,
+    db: DbHandle,
+    // every session in the latest race guide.
+    guide: RwLock<Arc<Vec<RaceGuideEntry>>>,
+    // announcement channels, messages sent to them get published to following servers.
+    news: RwLock<HashSet<ChannelId>>,
+    // when each announcement channel had a message published in the last hour.
+    published: Mutex<HashMap<ChannelId, Vec<DateTime<Utc>>>>,
+}
+impl HandlerState {
+    fn new(db: DbHandle) -> Self {
+        HandlerState {
+            seasons: RwLock::new(Arc::new(HashMap::new())),
+            db,
+            guide: RwLock::new(Arc::new(Vec::new())),
+            news: RwLock::new(HashSet::new()),
+            published: Mutex::new(HashMap::new()),
+        }
+    }
+    // the current seasons, by series_id.
+    pub fn seasons(&self) -> Arc<HashMap<i64, SeasonInfo>> {
+        self.seasons
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+    fn set_seasons(&self, s: HashMap<i64, SeasonInfo>) {
+        *self.seasons.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(s);
+    }
+    // every session in the latest race guide.
+    pub fn guide(&self) -> Arc<Vec<RaceGuideEntry>> {
+        self.guide
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+    pub fn set_guide(&self, g: Vec<RaceGuideEntry>) {
+        *self.guide.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(g);
+    }
+    pub fn db(&self) -> DbHandle {
+        self.db.clone()
+    }
+    fn news(&self) -> HashSet<ChannelId> {
+        self.news
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+    // the announcement channels that haven't used up discord's limit of PUBLISH_PER_HOUR.
+    fn publishable(&self) -> HashSet<ChannelId> {
+        let since = Utc::now() - chrono::Duration::hours(1);
+        let published = self
+            .published
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.news()
+            .into_iter()
+            .filter(|ch| {
+                published
+                    .get(ch)
+                    .map_or(0, |t| t.iter().filter(|t| **t > since).count())
+                    < PUBLISH_PER_HOUR
+            })
+            .collect()
+    }
+    fn set_published(&self, chs: &[ChannelId]) {
+        let now = Utc::now();
+        let since = now - chrono::Duration::hours(1);
+        let mut published = self
+            .published
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        published.retain(|_, t| {
+            t.retain(|t| *t > since);
+            !t.is_empty()
+        });
+        for ch in chs {
+            published.entry(*ch).or_default().push(now);
+        }
+    }
+    fn update_channel(&self, ch: &GuildChannel) {
+        let mut news = self.news.write().unwrap_or_else(PoisonError::into_inner);
+        if ch.kind == ChannelType::News {
+            news.insert(ch.id);
+        } else {
+            news.remove(&ch.id);
+        }
+    }
 }
 
 struct Handler {
-    state: Arc<Mutex<HandlerState>>,
+    state: Arc<HandlerState>,
     commands: Vec<Box<dyn ACommand>>,
+    health: Arc<Health>,
+    // install commands once globally rather than in each guild.
+    global_commands: bool,
 }
 
+const GLOBAL_COMMANDS_HASH: &str = "global_commands_hash";
+// commands taking longer than this get logged.
+const SLOW_COMMAND_MS: i64 = 2000;
+// how many channels to send announcements to at once.
+const ANNOUNCE_CONCURRENCY: usize = 8;
+// discord lets a channel publish this many messages an hour.
+const PUBLISH_PER_HOUR: usize = 10;
+// announcements that couldn't be sent are retried for this long, after that they're stale.
+const OUTBOX_EXPIRY_MINS: i64 = 10;
+// how often to retry sending messages in the outbox.
+const OUTBOX_POLL_SECS: u64 = 30;
+// how often to check for reminders that are due.
+const REMINDER_POLL_SECS: u64 = 30;
+// how often to look for removed guilds and watches that are past their grace period.
+const GUILD_PURGE_POLL_SECS: u64 = 60 * 60;
+// the name of the listener in the health status.
+const LISTENER_TASK: &str = "race guide listener";
+
 impl Handler {
-    fn listen_for_race_guide(&self, token: String, rx: Receiver<RaceGuideEvent>) {
-        let state = self.state.clone();
-        spawn(Self::listen_task(state, token, rx));
-    }
     async fn listen_task(
-        state: Arc<Mutex<HandlerState>>,
-        token: String,
-        mut rx: Receiver<RaceGuideEvent>,
+        state: Arc<HandlerState>,
+        config: Arc<Config>,
+        rx: Arc<tokio::sync::Mutex<UnboundedReceiver<RaceGuideEvent>>>,
+        health: Arc<Health>,
+        presence: Option<UnboundedSender<RaceGuideEvent>>,
     ) {
-        let http = Http::new(&token);
-        loop {
-            let e = rx.recv().await;
-            if let Some(evt) = e {
+        let http = Http::new(&config.discord_token);
+        let hooks = webhook::client();
+        let mut rx = rx.lock().await;
+        while let Some(evt) = rx.recv().await {
+            health.heartbeat(LISTENER_TASK);
+            // if we've fallen behind, catch up with everything that's waiting in one go.
+            let mut events = vec![evt];
+            while let Ok(e) = rx.try_recv() {
+                events.push(e);
+            }
+            if events.len() > 1 {
+                println!("catching up with {} race guide events", events.len());
+            }
+            for evt in coalesce(events) {
                 match evt {
-                    RaceGuideEvent::Announcements(msgs) => {
-                        let reg;
+                    RaceGuideEvent::Announcements(mut msgs) => {
+                        msgs.retain(|id, _| !config.blocked_series.contains(id));
+                        // (session_id, series_id) of the sessions that closed, for their poll results
+                        let closed: Vec<(i64, i64)> = msgs
+                            .values()
+                            .filter_map(|msg| match (&msg.ann_type, msg.prev.session_id) {
+                                (AnnouncementType::Closed, Some(sid)) => {
+                                    Some((sid, msg.series.series_id))
+                                }
+                                _ => None,
+                            })
+                            .collect();
+                        let (db, news) = (state.db(), state.publishable());
+                        let read = db
+                            .call(move |db| -> Result<_, BotError> {
+                                let reg = db.regs()?;
+                                let settings = db.channel_settings().unwrap_or_else(|e| {
+                                    println!("Failed to read channel settings {:?}", e);
+                                    HashMap::new()
+                                });
+                                let webhooks = db.webhooks().unwrap_or_else(|e| {
+                                    println!("Failed to read webhooks {:?}", e);
+                                    HashMap::new()
+                                });
+                                let mut polls = HashMap::new();
+                                for (sid, series_id) in closed {
+                                    match db.take_poll_results(sid) {
+                                        Ok(r) => {
+                                            for (ch, votes) in r {
+                                                polls.insert((ch, series_id), votes);
+                                            }
+                                        }
+                                        Err(e) => println!("Failed to read poll results {:?}", e),
+                                    }
+                                }
+                                Ok((reg, settings, webhooks, polls))
+                            })
+                            .await;
+                        // without the watches there's nothing to announce to, skip this batch
+                        // rather than stop, the next poll will bring newer ones.
+                        let (reg, settings, mut webhooks, polls) = match read {
+                            Ok(r) => r,
+                            Err(e) => {
+                                println!("Failed to read watches, skipping announcements {}", e);
+                                ops_alert(
+                                    &http,
+                                    &config,
+                                    &format!(
+                                        "Skipped {} announcements, unable to read the watches: {}",
+                                        msgs.len(),
+                                        e
+                                    ),
+                                )
+                                .await;
+                                continue;
+                            }
+                        };
+                        // a host the operator has since taken out of webhook_hosts doesn't get
+                        // any more, the channel's announcements are posted as usual instead.
+                        webhooks.retain(|_, t| {
+                            WebhookFormat::for_url(&t.url, &config.webhook_hosts) == Some(t.format)
+                        });
+                        let Announced {
+                            announced,
+                            retry,
+                            delivered,
+                            refused,
+                            counted,
+                            log,
+                            published,
+                            ..
+                        } = announce(
+                            &http,
+                            &hooks,
+                            config.max_message_len,
+                            config.max_channel_messages,
+                            reg,
+                            settings,
+                            &news,
+                            &webhooks,
+                            &msgs,
+                            polls,
+                        )
+                        .await;
+                        telegram::announce(&hooks, &config, &msgs).await;
+                        state.set_published(&published);
+                        health.announced(announced);
+                        if !log.is_empty() {
+                            if let Err(e) = db.call(move |db| db.log_announcements(&log)).await {
+                                println!("Failed to record announce log {:?}", e);
+                            }
+                        }
+                        if !counted.is_empty() {
+                            let now = Utc::now();
+                            if let Err(e) = db.call(move |db| db.record_counts(&counted, now)).await
+                            {
+                                println!("Failed to record count announcements {:?}", e);
+                            }
+                        }
+                        let max_failures = config.max_send_failures;
+                        match db
+                            .call(move |db| db.record_sends(&delivered, &refused, max_failures))
+                            .await
                         {
-                            let st = state.lock().expect("Unable to lock state");
-                            reg = st.db.regs().expect("query failed");
+                            Err(e) => println!("Failed to record send failures {:?}", e),
+                            Ok(disabled) => notify_disabled(&http, disabled).await,
+                        }
+                        if !retry.is_empty() {
+                            let expires =
+                                Utc::now() + chrono::Duration::minutes(OUTBOX_EXPIRY_MINS);
+                            if let Err(e) =
+                                db.call(move |db| db.queue_outbound(&retry, expires)).await
+                            {
+                                println!("Failed to queue messages for retry {:?}", e);
+                            }
                         }
-                        announce(&http, reg, msgs).await;
                     }
                     RaceGuideEvent::Seasons(s) => {
-                        let mut st = state.lock().expect("Unable to lock state");
-                        st.seasons = s;
+                        let mut s = s;
+                        // blocked series can't be picked for a watch.
+                        s.retain(|id, _| !config.blocked_series.contains(id));
+                        state.set_seasons(s);
                     }
+                    RaceGuideEvent::Status(entries) => {
+                        if let Some(p) = &presence {
+                            if p.send(RaceGuideEvent::Status(entries.clone())).is_err() {
+                                println!("presence updater has stopped");
+                            }
+                        }
+                        let db = state.db();
+                        let read = db
+                            .call(|db| -> Result<_, BotError> {
+                                Ok((db.channel_settings()?, db.regs()?))
+                            })
+                            .await;
+                        // an empty board would wipe everyone's status message, leave them be.
+                        let (settings, regs) = match read {
+                            Ok(r) => r,
+                            Err(e) => {
+                                println!("Failed to read status boards {}", e);
+                                continue;
+                            }
+                        };
+                        let boards: Vec<(ChannelId, Option<MessageId>, String)> = {
+                            let seasons = state.seasons();
+                            settings
+                                .iter()
+                                .filter(|(_, s)| s.status)
+                                .map(|(ch, s)| {
+                                    let regs = regs.get(ch).map(|r| r.as_slice()).unwrap_or(&[]);
+                                    let text = status_board(
+                                        regs,
+                                        &entries,
+                                        &seasons,
+                                        config.max_message_len,
+                                    );
+                                    (*ch, s.status_msg, text)
+                                })
+                                .collect()
+                        };
+                        for (ch, msg_id, text) in boards {
+                            update_status_board(&http, &db, ch, msg_id, &text).await;
+                        }
+                    }
+                    RaceGuideEvent::OpsWarning(msg) => ops_alert(&http, &config, &msg).await,
                 }
             }
         }
+        println!("race guide channel closed, listener stopping");
     }
     async fn install_commands(&self, ctx: &Context, guild_id: GuildId) {
         println!("Installing commands for guild {}", guild_id);
@@ -78,6 +408,44 @@ impl Handler {
             println!("Failed to install commands {:?}", e);
         }
     }
+    // installs the commands globally, but only if they've changed since the last time we did it.
+    async fn install_global_commands(&self, ctx: &Context) {
+        let mut defs = CreateApplicationCommands::default();
+        for c in &self.commands {
+            c.create(&mut defs);
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_string(&defs.0).unwrap_or_default());
+        let hash = base64::encode(hasher.finalize());
+        let db = self.state.db();
+        let prev_hash = db
+            .call(|db| db.get_meta(GLOBAL_COMMANDS_HASH))
+            .await
+            .unwrap_or_default();
+        if prev_hash.as_deref() == Some(hash.as_str()) {
+            println!("Global commands are up to date");
+            return;
+        }
+        println!("Installing global commands");
+        let res = Command::set_global_application_commands(&ctx.http, |commands| {
+            for c in &self.commands {
+                c.create(commands);
+            }
+            commands
+        })
+        .await;
+        match res {
+            Err(e) => println!("Failed to install global commands {:?}", e),
+            Ok(_) => {
+                let res = db
+                    .call(move |db| db.set_meta(GLOBAL_COMMANDS_HASH, &hash))
+                    .await;
+                if let Err(e) = res {
+                    println!("Failed to save global commands hash {:?}", e);
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -93,7 +461,38 @@ impl EventHandler for Handler {
         } else if let Interaction::ApplicationCommand(command) = interaction {
             for c in &self.commands {
                 if command.data.name == c.name() {
-                    c.execute(ctx, command).await;
+                    let name = command.data.name.clone();
+                    let (guild_id, user_id) = (command.guild_id, command.user.id);
+                    let start = Instant::now();
+                    let outcome = with_outcome(c.execute(ctx, command)).await;
+                    let ms = start.elapsed().as_millis() as i64;
+                    // discord wants a response within 3 seconds
+                    if ms > SLOW_COMMAND_MS {
+                        println!("slow command /{} took {}ms, {}", name, ms, outcome);
+                    }
+                    let db = self.state.db();
+                    let res = db
+                        .call(move |db| db.record_command(&name, guild_id, user_id, ms, outcome))
+                        .await;
+                    if let Err(e) = res {
+                        println!("Failed to record command {:?}", e);
+                    }
+                    break;
+                }
+            }
+        } else if let Interaction::MessageComponent(comp) = interaction {
+            let prefix = comp.data.custom_id.split(':').next().unwrap_or_default();
+            for c in &self.commands {
+                if c.handles(prefix) {
+                    c.component(ctx, comp).await;
+                    break;
+                }
+            }
+        } else if let Interaction::ModalSubmit(m) = interaction {
+            let prefix = m.data.custom_id.split(':').next().unwrap_or_default();
+            for c in &self.commands {
+                if c.handles(prefix) {
+                    c.modal(ctx, m).await;
                     break;
                 }
             }
@@ -105,25 +504,45 @@ impl EventHandler for Handler {
         incomplete: UnavailableGuild,
         _full: Option<Guild>,
     ) {
-        // delete any reg for this guild if the unavailable flag is false.
+        // the unavailable flag is false if we were removed from the guild, rather than it
+        // being unreachable. Its regs are kept for guild_grace_days in case we're invited
+        // back, see guild_purge_task.
         println!(
             "guild delete guild_id:{} / incomplete:{}",
             incomplete.id, incomplete.unavailable
         );
         if !incomplete.unavailable {
-            let mut st = self.state.lock().expect("Unable to locks state");
-            if let Err(e) = st.db.delete_guild(incomplete.id) {
-                println!("Failed to delete guild {} :{:?}", incomplete.id, e);
+            let db = self.state.db();
+            let guild_id = incomplete.id;
+            if let Err(e) = db
+                .call(move |db| db.mark_guild_removed(guild_id, Utc::now()))
+                .await
+            {
+                println!("Failed to mark guild {} removed :{:?}", incomplete.id, e);
             }
         }
     }
+    async fn channel_create(&self, _ctx: Context, channel: &GuildChannel) {
+        self.state.update_channel(channel);
+    }
+    async fn channel_update(&self, _ctx: Context, _old: Option<Channel>, new: Channel) {
+        if let Channel::Guild(ch) = new {
+            self.state.update_channel(&ch);
+        }
+    }
     async fn channel_delete(&self, _ctx: Context, _channel: &GuildChannel) {
+        self.state
+            .news
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&_channel.id);
         println!(
             "channel delete guild {} channel{}",
             _channel.guild_id, _channel.id
         );
-        let mut st = self.state.lock().expect("Unable to lock state");
-        if let Err(e) = st.db.delete_channel(_channel.id) {
+        let db = self.state.db();
+        let channel_id = _channel.id;
+        if let Err(e) = db.call(move |db| db.delete_channel(channel_id)).await {
             println!(
                 "Failed to delete reg entries for channel id {} {:?}",
                 _channel.id, e
@@ -133,114 +552,1113 @@ impl EventHandler for Handler {
     async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: bool) {
         // create commands in guild
         println!("guild create {}/{}", guild.id, _is_new);
-        self.install_commands(&ctx, guild.id).await;
+        let guild_id = guild.id;
+        match self
+            .state
+            .db()
+            .call(move |db| db.guild_returned(guild_id))
+            .await
+        {
+            Ok(true) => println!("guild {} is back, restored its watches", guild.id),
+            Ok(false) => {}
+            Err(e) => println!("Failed to restore guild {} :{:?}", guild.id, e),
+        }
+        for ch in guild.channels.values() {
+            if let Channel::Guild(ch) = ch {
+                self.state.update_channel(ch);
+            }
+        }
+        if !self.global_commands {
+            self.install_commands(&ctx, guild.id).await;
+        }
     }
 
-    async fn ready(&self, _ctx: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        self.health.shard_connected(ctx.shard_id, true);
+        self.health
+            .shard_count(ready.shard.map_or(1, |[_, total]| total));
+        println!(
+            "{} is connected on shard {}!",
+            ready.user.name, ctx.shard_id
+        );
         println!("{:?}", ready.guilds);
+        // global commands only need installing once, not once per shard.
+        if self.global_commands && ctx.shard_id == 0 {
+            self.install_global_commands(&ctx).await;
+        }
+    }
+
+    async fn shard_stage_update(&self, _ctx: Context, evt: ShardStageUpdateEvent) {
+        println!("shard {} now {:?}", evt.shard_id.0, evt.new);
+        self.health.shard_connected(
+            evt.shard_id.0,
+            matches!(evt.new, ConnectionStage::Connected),
+        );
+    }
+}
+
+pub fn all_commands(
+    state: Arc<HandlerState>,
+    config: &Config,
+    health: Arc<Health>,
+) -> Vec<Box<dyn ACommand>> {
+    let commands: Vec<Box<dyn ACommand>> = vec![
+        Box::new(RegCommand::new(
+            state.clone(),
+            config.presets.clone(),
+            config.watch_limits(),
+            config.owner_id,
+        )),
+        Box::new(WatchManyCommand::new(
+            state.clone(),
+            config.presets.clone(),
+            config.watch_limits(),
+        )),
+        Box::new(ListCommand::new(state.clone())),
+        Box::new(ListAllCommand::new(state.clone())),
+        Box::new(RemoveCommand::new(state.clone())),
+        Box::new(UndoCommand::new(state.clone(), config.watch_limits())),
+        Box::new(WatchMessageCommand::new(state.clone())),
+        Box::new(SnoozeCommand::new(state.clone())),
+        Box::new(BoostCommand::new(state.clone())),
+        Box::new(MuteCommand::new(state.clone())),
+        Box::new(StyleCommand::new(state.clone())),
+        Box::new(TimezoneCommand::new(state.clone())),
+        Box::new(LanguageCommand::new(state.clone())),
+        Box::new(RestrictWatchesCommand::new(state.clone())),
+        Box::new(PrivateRepliesCommand::new(state.clone())),
+        Box::new(BlockCommand::new(state.clone())),
+        Box::new(PreviewStylesCommand::new(state.clone())),
+        Box::new(StatusBoardCommand::new(state.clone())),
+        Box::new(DigestCommand::new(state.clone())),
+        Box::new(SetupStatusCommand::new(state.clone())),
+        Box::new(PollCommand::new(state.clone())),
+        Box::new(AnnouncementCommand::new(state.clone())),
+        Box::new(RemindMeCommand::new(state.clone())),
+        Box::new(NowCommand::new(state.clone())),
+        Box::new(PopularCommand::new(state.clone())),
+        Box::new(LicensesCommand::new(state.clone())),
+        Box::new(DefaultsCommand::new(state.clone())),
+        Box::new(WatchEventCommand::new(state.clone())),
+        Box::new(WatchLeagueCommand::new(state.clone())),
+        Box::new(WeeklyPostCommand::new(state.clone())),
+        Box::new(DailyDigestCommand::new(state.clone())),
+        Box::new(WebhookCommand::new(
+            state.clone(),
+            config.webhook_hosts.clone(),
+        )),
+        Box::new(HistoryCommand::new(state.clone())),
+        Box::new(ExportCommand::new(state.clone())),
+        Box::new(ImportCommand::new(state.clone(), config.watch_limits())),
+        Box::new(CopyWatchesCommand::new(
+            state.clone(),
+            config.watch_limits(),
+        )),
+        Box::new(BotStatsCommand::new(state.clone(), health, config.owner_id)),
+        Box::new(ForgetMeCommand::new(state.clone())),
+        Box::new(HelpCommand::new(state)),
+    ];
+    if config.feature("command_group", false) {
+        group_commands(commands)
+    } else {
+        commands
     }
 }
 
 #[tokio::main]
 async fn main() {
-    // Configure the client with your Discord bot token in the environment.
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
-    let ir_user = env::var("IRUSER").expect("Expected an iRacing username in the environment");
-    let ir_pwd = env::var("IRPWD").expect("Expected an iRacing password in the environment");
+    let cli = Cli::parse();
+    // Configure the client with your Discord bot token in the config file or environment.
+    let config = match Config::load() {
+        Err(e) => {
+            println!("Failed to load config {:?}", e);
+            std::process::exit(1);
+        }
+        Ok(c) => Arc::new(c),
+    };
+    let res = match cli.command.unwrap_or(CliCommand::Run) {
+        CliCommand::Run => {
+            run(config).await;
+            Ok(())
+        }
+        CliCommand::CheckConfig => cli::check_config(&config),
+        CliCommand::ExportWatches { output } => cli::export_watches(&config, output),
+        CliCommand::ImportWatches { file } => cli::import_watches(&config, file),
+        CliCommand::RegisterCommands { global, guild } => {
+            cli::register_commands(&config, global, guild).await
+        }
+        CliCommand::MigrateDb => cli::migrate_db(&config),
+        CliCommand::PurgeGuild { guild } => cli::purge_guild(&config, guild),
+        CliCommand::CommandStats { days } => cli::command_stats(&config, days),
+        CliCommand::ExportData { dir, from, to } => cli::export_data(&config, dir, from, to),
+        CliCommand::Restore { file } => cli::restore(&config, file),
+        CliCommand::Replay {
+            dir,
+            preset,
+            step,
+            expect,
+        } => cli::replay(&config, dir, preset, step, expect).await,
+    };
+    if let Err(e) = res {
+        println!("{:?}", e);
+        std::process::exit(1);
+    }
+}
 
+async fn run(config: Arc<Config>) {
     // Build our client.
-    let db = Db::new("regbot.db");
+    // opening the db brings the schema up to date, which can take a while, so keep it off
+    // the runtime's threads like all the other db work.
+    let path = config.db_path.clone();
+    let db = tokio::task::spawn_blocking(move || DbHandle::open(&path))
+        .await
+        .expect("db open task failed");
     if let Err(e) = db {
         println!("Failed to open db {:?}", e);
         return;
     }
-    let state = Arc::new(Mutex::new(HandlerState {
-        seasons: HashMap::new(),
-        db: db.unwrap(),
-    }));
+    let health = Arc::new(Health::default());
+    let state = Arc::new(HandlerState::new(db.unwrap()));
+    // the official and hosted pollers share the recorder, so they share its size limit.
+    let recorder = Recorder::from_config(&config);
     let handler = Handler {
         state: state.clone(),
-        commands: vec![
-            Box::new(RegCommand::new(state.clone())),
-            Box::new(ListCommand::new(state.clone())),
-            Box::new(RemoveCommand::new(state.clone())),
-            Box::new(HelpCommand),
-        ],
+        commands: all_commands(state.clone(), &config, health.clone()),
+        health: health.clone(),
+        global_commands: config.global_commands,
     };
-    let (tx, rx) = tokio::sync::mpsc::channel::<RaceGuideEvent>(2);
-    handler.listen_for_race_guide(token.clone(), rx);
-    spawn(iracing_loop_task(ir_user, ir_pwd, tx, state.clone()));
+    let (presence_tx, presence_rx) = if config.feature("presence", true) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<RaceGuideEvent>();
+        (Some(tx), Some(rx))
+    } else {
+        (None, None)
+    };
+    spawn(supervise_race_guide(
+        config.clone(),
+        state.clone(),
+        health.clone(),
+        presence_tx,
+        recorder.clone(),
+    ));
+    if let Some(addr) = &config.health_addr {
+        spawn(health::serve(addr.clone(), health.clone()));
+    }
+    if let Some(addr) = &config.api_addr {
+        spawn(api::serve(
+            addr.clone(),
+            config.api_token.clone().unwrap_or_default(),
+            config.dashboard_token.clone(),
+            state.clone(),
+            Arc::new(Http::new(&config.discord_token)),
+            config.watch_limits(),
+        ));
+    }
+    if config.feature("watchdog", true) {
+        spawn(health::watchdog_task(health.clone()));
+    }
+    spawn(backup::backup_task(config.clone()));
+    spawn(outbox_task(config.clone(), state.db()));
+    spawn(reminder_task(config.clone(), state.db()));
+    spawn(guild_purge_task(config.clone(), state.db()));
+    spawn(hosted::hosted_loop_task(
+        config.clone(),
+        state.db(),
+        recorder,
+    ));
+    spawn(schedule::schedule_task(config.clone(), state.db()));
 
-    let mut client = Client::builder(token, GatewayIntents::non_privileged())
+    let mut client = Client::builder(&config.discord_token, GatewayIntents::non_privileged())
         .event_handler(handler)
         .await
         .expect("Error creating client");
+    if let Some(rx) = presence_rx {
+        spawn(presence::presence_task(client.shard_manager.clone(), rx));
+    }
 
-    // Finally, start a single shard, and start listening to events.
+    // Finally, start the shards, and start listening to events. Discord insists on
+    // sharding once the bot is in enough servers.
     //
     // Shards will automatically attempt to reconnect, and will perform
     // exponential backoff until it reconnects.
-    if let Err(why) = client.start().await {
+    let started = match config.shards {
+        0 => client.start_autosharded().await,
+        1 => client.start().await,
+        n => client.start_shards(n).await,
+    };
+    if let Err(why) = started {
         println!("Client error: {:?}", why);
     }
 }
 
+// the text of the status message for a channel, a line per watched series.
+fn status_board(
+    regs: &[Reg],
+    entries: &HashMap<i64, RaceGuideEntry>,
+    seasons: &HashMap<i64, SeasonInfo>,
+    max_len: usize,
+) -> String {
+    let mut text = format!(
+        "**Registration status**, updated <t:{}:R>\n",
+        Utc::now().timestamp()
+    );
+    if regs.is_empty() {
+        text.push_str("Nothing is being watched in this channel.");
+    }
+    let mut regs: Vec<&Reg> = regs.iter().collect();
+    regs.sort_by(|a, b| a.series_name.cmp(&b.series_name));
+    for reg in regs {
+        let name = escape_markdown(&reg.series_name);
+        let line = match entries.get(&reg.series_id) {
+            None => format!("{}: no races coming up\n", name),
+            Some(e) if e.session_id.is_some() => {
+                let splits = match seasons.get(&reg.series_id) {
+                    Some(si) if e.entry_count >= si.reg_official => {
+                        match e.num_splits(si.reg_split) {
+                            1 => ", official".to_string(),
+                            n => format!(", {} splits", n),
+                        }
+                    }
+                    _ => String::new(),
+                };
+                format!(
+                    "{}: {} registered{}, starts <t:{}:R>\n",
+                    name,
+                    e.entry_count,
+                    splits,
+                    e.start_time.timestamp()
+                )
+            }
+            Some(e) => format!("{}: next race <t:{}:R>\n", name, e.start_time.timestamp()),
+        };
+        if text.len() + line.len() > max_len {
+            break;
+        }
+        text.push_str(&line);
+    }
+    text
+}
+
+// edits the channel's status message, posting (and pinning) a new one if there isn't one yet.
+async fn update_status_board(
+    http: &Http,
+    db: &DbHandle,
+    ch: ChannelId,
+    msg_id: Option<MessageId>,
+    text: &str,
+) {
+    if let Some(id) = msg_id {
+        match ch.edit_message(http, id, |m| m.content(text)).await {
+            Ok(_) => return,
+            // someone deleted it, post a new one.
+            Err(serenity::Error::Http(e)) if matches!(&*e, HttpError::UnsuccessfulRequest(r) if r.status_code.as_u16() == 404) =>
+                {}
+            Err(e) => {
+                println!("Failed to update status message in {} {:?}", ch, e);
+                return;
+            }
+        }
+    }
+    match ch.send_message(http, |m| m.content(text)).await {
+        Err(e) => println!("Failed to post status message to {} {:?}", ch, e),
+        Ok(msg) => {
+            if let Err(e) = msg.pin(http).await {
+                println!("Failed to pin status message in {} {:?}", ch, e);
+            }
+            let id = msg.id;
+            if let Err(e) = db.call(move |db| db.set_status_msg(ch, Some(id))).await {
+                println!("Failed to save status message id {:?}", e);
+            }
+        }
+    }
+}
+
+// sends msg to the ops channel, if there is one.
+async fn ops_alert(http: &Http, config: &Config, msg: &str) {
+    if let Some(ch) = config.ops_channel {
+        let mut msger = Messenger::new(ChannelId(ch), http, config.max_message_len);
+        let lines: Vec<&str> = msg.lines().collect();
+        sink::send_lines(&mut msger, &lines).await;
+    }
+}
+
+// runs the iRacing poller and the race guide listener, each under a supervisor that
+// restarts it if it stops. The channel between them outlives both, so either one can be
+// restarted without losing what the other has sent.
+async fn supervise_race_guide(
+    config: Arc<Config>,
+    state: Arc<HandlerState>,
+    health: Arc<Health>,
+    presence: Option<UnboundedSender<RaceGuideEvent>>,
+    recorder: Option<Arc<Recorder>>,
+) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<RaceGuideEvent>();
+    let rx = Arc::new(tokio::sync::Mutex::new(rx));
+    let listener = {
+        let (config, state, health) = (config.clone(), state.clone(), health.clone());
+        supervise(LISTENER_TASK, health.clone(), move || {
+            Handler::listen_task(
+                state.clone(),
+                config.clone(),
+                rx.clone(),
+                health.clone(),
+                presence.clone(),
+            )
+        })
+    };
+    let poller = {
+        let health = health.clone();
+        supervise(POLLER_TASK, health.clone(), move || {
+            iracing_loop_task(
+                config.clone(),
+                tx.clone(),
+                state.clone(),
+                health.clone(),
+                recorder.clone(),
+            )
+        })
+    };
+    tokio::join!(listener, poller);
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn announce(
     http: impl AsRef<Http>,
+    hooks: &reqwest::Client,
+    max_message_len: usize,
+    max_messages: usize,
     reg: HashMap<ChannelId, Vec<Reg>>,
-    msgs: HashMap<i64, Announcement>,
-) {
+    settings: HashMap<ChannelId, ChannelSettings>,
+    news: &HashSet<ChannelId>,
+    webhooks: &HashMap<ChannelId, WebhookTarget>,
+    msgs: &HashMap<i64, Announcement>,
+    polls: HashMap<(ChannelId, i64), (i64, i64)>,
+) -> Announced {
     // many reg may want the same series_id. and we can message a number of msgs to a single channel at once.
+    // The channels are done concurrently, serenity's http client takes care of discord's rate limits,
+    // and a channel that gets a lot at once only gets max_messages of them now, the rest go via the outbox.
+    let http = http.as_ref();
     let reg_len = reg.len();
-    let mut sent = 0;
-    for (ch, regs) in reg {
-        let mut msger = Messenger::new(ch, http.as_ref());
-        for reg in &regs {
-            if let Some(msg) = msgs.get(&reg.series_id) {
-                if reg.wants(msg) {
-                    msger.add(&msg.to_string()).await;
-                    sent += 1;
-                }
-            }
+    let results: Vec<Announced> = stream::iter(reg.into_iter().map(|(ch, regs)| {
+        let cs = settings.get(&ch).cloned().unwrap_or_default();
+        let polls = &polls;
+        let out = match webhooks.get(&ch) {
+            Some(t) => Output::Webhook(hooks, t),
+            None => Output::Channel {
+                publish: news.contains(&ch),
+            },
+        };
+        async move {
+            announce_channel(
+                http,
+                max_message_len,
+                max_messages,
+                ch,
+                out,
+                &regs,
+                &cs,
+                msgs,
+                polls,
+            )
+            .await
         }
-        msger.flush().await;
+    }))
+    .buffer_unordered(ANNOUNCE_CONCURRENCY)
+    .collect()
+    .await;
+    let mut res = Announced::default();
+    for r in results {
+        res.sent += r.sent;
+        res.announced += r.announced;
+        res.stats += r.stats;
+        res.retry.extend(r.retry);
+        res.delivered.extend(r.delivered);
+        res.refused.extend(r.refused);
+        res.counted.extend(r.counted);
+        res.log.extend(r.log);
+        res.published.extend(r.published);
     }
+    let mut failed: Vec<String> = res.retry.iter().map(|(ch, _)| ch.to_string()).collect();
+    failed.dedup();
     println!(
-        "{} announcements, {} channels with watches, sent {} announcements",
+        "{} announcements, {} channels with watches, delivered {} of {} announcements in {} messages, {} messages queued, {} failed",
         msgs.len(),
         reg_len,
-        sent,
+        res.announced,
+        res.sent,
+        res.stats.messages,
+        res.stats.queued,
+        res.stats.failed,
     );
+    if !failed.is_empty() {
+        println!(
+            "held back or failed to send announcements to {} channels, will retry: {}",
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+    if !res.refused.is_empty() {
+        println!("{} channels refused announcements", res.refused.len());
+    }
+    res
+}
+
+// Announced is the outcome of sending out a batch of announcements.
+#[derive(Debug, Default)]
+struct Announced {
+    // the number of announcements the watches wanted.
+    sent: usize,
+    // the number of those that made it into a message discord accepted.
+    announced: usize,
+    // what happened to the messages they were sent in.
+    stats: SendStats,
+    // messages that failed to send but are worth trying again.
+    retry: Vec<(ChannelId, String)>,
+    // channels that accepted messages.
+    delivered: Vec<ChannelId>,
+    // channels that refused messages, usually because of missing permissions.
+    refused: Vec<ChannelId>,
+    // the (channel, series_id) of watches with a cooldown that had a count announced.
+    counted: Vec<(ChannelId, i64)>,
+    // the announcements that were delivered, for the announce_log.
+    log: Vec<AnnounceLog>,
+    // announcement channels that had a message published.
+    published: Vec<ChannelId>,
+}
+
+// Output is where a channel's announcements go.
+enum Output<'a> {
+    // the channel itself, publishing the first message if it's an announcement channel
+    // that hasn't used up its publishing for the hour.
+    Channel { publish: bool },
+    // a webhook the channel set up with /webhook.
+    Webhook(&'a reqwest::Client, &'a WebhookTarget),
+}
+
+// sends the announcements the regs want to the channel.
+#[allow(clippy::too_many_arguments)]
+async fn announce_channel(
+    http: &Http,
+    max_message_len: usize,
+    max_messages: usize,
+    ch: ChannelId,
+    out: Output<'_>,
+    regs: &[Reg],
+    settings: &ChannelSettings,
+    msgs: &HashMap<i64, Announcement>,
+    polls: &HashMap<(ChannelId, i64), (i64, i64)>,
+) -> Announced {
+    // all the watches in a channel are from the same server.
+    let locale = regs.first().map(|r| r.locale).unwrap_or_default();
+    let mut sent = 0;
+    let mut new_polls = Vec::new();
+    // (series name, text) of everything to say in the channel.
+    let mut items: Vec<(String, String)> = Vec::new();
+    // the buttons for each series in items, by series name.
+    let mut buttons: HashMap<String, SeriesButtons> = HashMap::new();
+    let mut counted = Vec::new();
+    // the announcements in items, for the announce_log once they're delivered.
+    let mut said: Vec<&Announcement> = Vec::new();
+    for reg in regs {
+        if let Some(msg) = msgs.get(&reg.series_id) {
+            buttons
+                .entry(msg.series.name.clone())
+                .or_insert_with(|| series_buttons(msg));
+            if reg.wants(msg) {
+                items.push((
+                    msg.series.name.clone(),
+                    settings.style.render(&msg.escaped(), locale),
+                ));
+                said.push(msg);
+                if reg.cooldown_mins > 0 && matches!(msg.ann_type, AnnouncementType::Count) {
+                    counted.push((ch, reg.series_id));
+                }
+                sent += 1;
+            }
+            if reg.poll && !reg.snoozed && !reg.disabled {
+                match msg.ann_type {
+                    AnnouncementType::Open => new_polls.push(msg),
+                    AnnouncementType::Closed => {
+                        if let Some((yes, no)) = polls.get(&(ch, reg.series_id)) {
+                            items.push((
+                                msg.series.name.clone(),
+                                tr(
+                                    locale,
+                                    Msg::PollResults,
+                                    &[&escape_markdown(&msg.series.name), yes, no],
+                                ),
+                            ));
+                        }
+                    }
+                    AnnouncementType::Count
+                    | AnnouncementType::WeekOpen
+                    | AnnouncementType::NewWeek
+                    | AnnouncementType::NewSeason
+                    | AnnouncementType::LastDay
+                    | AnnouncementType::Predicted
+                    | AnnouncementType::Results => {}
+                }
+            }
+        }
+    }
+    let guild = regs.first().and_then(|r| r.guild);
+    let now = Utc::now();
+    let log_entry = |msg: &Announcement, message_id| AnnounceLog {
+        at: now,
+        guild,
+        channel: ch,
+        series_id: msg.series.series_id,
+        series_name: msg.series.name.clone(),
+        ann_type: msg.ann_type.name().to_string(),
+        entry_count: msg.curr.entry_count,
+        message_id,
+    };
+    let publish = match out {
+        Output::Channel { publish } => publish,
+        // there's no buttons or polls over a webhook, just the announcements.
+        Output::Webhook(client, target) => {
+            let mut res = Announced {
+                sent,
+                counted,
+                ..Default::default()
+            };
+            if !items.is_empty() {
+                match webhook::send(client, target, ch, &items, max_message_len).await {
+                    Ok(_) => {
+                        res.delivered.push(ch);
+                        res.announced = said.len();
+                        res.log = said.iter().map(|m| log_entry(m, None)).collect();
+                    }
+                    Err(e) => {
+                        println!("Failed to send to the webhook for channel {}: {:?}", ch, e);
+                        // the webhook's gone, or never existed. Being rate limited (429) or
+                        // anything else is worth trying again next time.
+                        if e.status().is_some_and(|s| {
+                            matches!(
+                                s,
+                                StatusCode::UNAUTHORIZED
+                                    | StatusCode::FORBIDDEN
+                                    | StatusCode::NOT_FOUND
+                            )
+                        }) {
+                            res.refused.push(ch);
+                        }
+                    }
+                }
+            }
+            return res;
+        }
+    };
+    let mut msger = Messenger::new(ch, http, max_message_len);
+    msger.publish = publish;
+    msger.max_messages = max_messages;
+    msger.locale = locale;
+    match settings.layout {
+        Layout::Lines => {
+            for (name, line) in &items {
+                msger.add_for(line, &buttons[name]).await;
+            }
+            msger.flush().await;
+        }
+        Layout::Digest => {
+            for page in style::digest_pages(&items) {
+                // a digest is one message however many series are in it, the buttons only
+                // go on one that has few enough series for a row each.
+                let mut page_buttons: Vec<SeriesButtons> = page
+                    .iter()
+                    .filter_map(|(name, _)| buttons.get(name).cloned())
+                    .collect();
+                if page_buttons.len() > MAX_BUTTON_ROWS {
+                    page_buttons.clear();
+                }
+                msger
+                    .send_digest(text(locale, Msg::DigestTitle), page, &page_buttons)
+                    .await;
+            }
+        }
+    }
+    for msg in new_polls {
+        send_poll(http, ch, msg, locale).await;
+    }
+    let log: Vec<AnnounceLog> = said
+        .iter()
+        .filter_map(|m| {
+            msger
+                .posted
+                .iter()
+                .find(|(id, _)| *id == m.series.series_id)
+                .map(|(_, msg_id)| log_entry(m, Some(*msg_id)))
+        })
+        .collect();
+    Announced {
+        sent,
+        announced: log.len(),
+        stats: msger.stats,
+        retry: msger.retry.into_iter().map(|m| (ch, m)).collect(),
+        delivered: if msger.delivered { vec![ch] } else { vec![] },
+        refused: if msger.refused { vec![ch] } else { vec![] },
+        counted,
+        log,
+        published: if msger.published { vec![ch] } else { vec![] },
+    }
+}
+
+// the buttons to go with an announcement, the reminder is only offered when there's
+// time for it.
+fn series_buttons(msg: &Announcement) -> SeriesButtons {
+    let upcoming = matches!(
+        msg.ann_type,
+        AnnouncementType::Open | AnnouncementType::Count
+    ) && msg.curr.start_time - Utc::now()
+        > chrono::Duration::minutes(REMIND_BEFORE_MINS);
+    SeriesButtons {
+        series_id: msg.series.series_id,
+        short_name: msg.series.short_name.clone(),
+        starts_at: upcoming.then_some(msg.curr.start_time),
+    }
+}
+
+// lets the people that created watches know that they've been disabled because
+// the bot can't post in the channel.
+async fn notify_disabled(http: &Http, disabled: Vec<(Option<UserId>, Reg)>) {
+    let mut by_user: HashMap<UserId, Vec<Reg>> = HashMap::new();
+    for (user, reg) in disabled {
+        println!(
+            "Disabled watch for {} in channel {}, too many failed sends",
+            reg.series_name, reg.channel
+        );
+        if let Some(u) = user {
+            by_user.entry(u).or_default().push(reg);
+        }
+    }
+    for (user, regs) in by_user {
+        let mut msg = String::from("I've turned off these watches because I keep getting refused when posting to the channel:\n");
+        for r in &regs {
+            msg.push_str(&format!("{} in <#{}>\n", r.series_name, r.channel));
+        }
+        msg.push_str("Check I have permission to send messages there, then use /watch again to turn them back on.");
+        let res = match user.create_dm_channel(http).await {
+            Ok(dm) => dm.say(http, &msg).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            println!("Failed to DM user {} about disabled watches {:?}", user, e);
+        }
+    }
+}
+
+// guild_purge_task deletes everything for the guilds we were removed from more than
+// guild_grace_days ago, and the watches that were removed too long ago to /undo.
+async fn guild_purge_task(config: Arc<Config>, db: DbHandle) {
+    let mut interval =
+        tokio::time::interval(tokio::time::Duration::from_secs(GUILD_PURGE_POLL_SECS));
+    loop {
+        interval.tick().await;
+        let before = Utc::now() - chrono::Duration::days(config.guild_grace_days);
+        match db.call(move |db| db.purge_removed_guilds(before)).await {
+            Err(e) => println!("Failed to purge removed guilds {:?}", e),
+            Ok(guilds) => {
+                for g in guilds {
+                    println!("purged guild {}, it's been gone a while", g);
+                }
+            }
+        }
+        let before = Utc::now() - chrono::Duration::days(DELETED_REG_DAYS);
+        if let Err(e) = db.call(move |db| db.purge_deleted_regs(before)).await {
+            println!("Failed to purge deleted watches {:?}", e);
+        }
+    }
+}
+
+// outbox_task retries sending messages that failed because of a discord hiccup.
+async fn outbox_task(config: Arc<Config>, db: DbHandle) {
+    let http = Http::new(&config.discord_token);
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(OUTBOX_POLL_SECS));
+    loop {
+        interval.tick().await;
+        let due = match db.call(|db| db.due_outbound()).await {
+            Err(e) => {
+                println!("Failed to read outbox {:?}", e);
+                continue;
+            }
+            Ok(d) => d,
+        };
+        // messages sent to each channel this time round, and channels that hit a rate limit,
+        // whatever's left stays due for the next tick.
+        let mut sent: HashMap<ChannelId, usize> = HashMap::new();
+        let mut limited: HashSet<ChannelId> = HashSet::new();
+        for m in due {
+            let id = m.id;
+            let count = sent.entry(m.channel).or_default();
+            if *count >= config.max_channel_messages || limited.contains(&m.channel) {
+                continue;
+            }
+            *count += 1;
+            let res = match m.channel.say(&http, &m.content).await {
+                Ok(_) => db.call(move |db| db.remove_outbound(id)).await,
+                Err(e) if is_transient(&e) => {
+                    if is_rate_limited(&e) {
+                        limited.insert(m.channel);
+                    }
+                    println!(
+                        "Retry {} to channel {} failed {:?}",
+                        m.attempts + 1,
+                        m.channel,
+                        e
+                    );
+                    // back off a bit more each time
+                    let next = Utc::now()
+                        + chrono::Duration::seconds(OUTBOX_POLL_SECS as i64 * (1 << m.attempts));
+                    db.call(move |db| db.retry_outbound(id, next)).await
+                }
+                Err(e) => {
+                    println!("Giving up sending to channel {} {:?}", m.channel, e);
+                    db.call(move |db| db.remove_outbound(id)).await
+                }
+            };
+            if let Err(e) = res {
+                println!("Failed to update outbox {:?}", e);
+            }
+        }
+    }
+}
+
+// reminder_task sends the reminders people asked for with /remindme as they come due.
+// They're kept in the db so that they survive a restart.
+async fn reminder_task(config: Arc<Config>, db: DbHandle) {
+    let http = Http::new(&config.discord_token);
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(REMINDER_POLL_SECS));
+    loop {
+        interval.tick().await;
+        let due = match db.call(|db| db.due_reminders()).await {
+            Err(e) => {
+                println!("Failed to read reminders {:?}", e);
+                continue;
+            }
+            Ok(d) => d,
+        };
+        for r in due {
+            let starts = format!("<t:{}:R>", r.starts_at.timestamp());
+            let msg = tr(r.locale, Msg::ReminderDue, &[&r.series_name, &starts]);
+            let res = match r.user.create_dm_channel(&http).await {
+                Ok(dm) => dm.say(&http, &msg).await.map(|_| ()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                println!("Failed to send reminder to {} {:?}", r.user, e);
+            }
+            let id = r.id;
+            if let Err(e) = db.call(move |db| db.remove_reminder(id)).await {
+                println!("Failed to remove reminder {:?}", e);
+            }
+        }
+    }
+}
+
+// true if discord said we're not allowed to do that, e.g. missing permissions in a channel.
+fn is_refused(e: &serenity::Error) -> bool {
+    matches!(e, serenity::Error::Http(e) if matches!(&**e, HttpError::UnsuccessfulRequest(r) if r.status_code.as_u16() == 403))
+}
+
+// true if the error is discord saying we're sending too fast.
+fn is_rate_limited(e: &serenity::Error) -> bool {
+    match e {
+        serenity::Error::Http(e) => matches!(
+            &**e,
+            HttpError::UnsuccessfulRequest(r) if r.status_code.as_u16() == 429
+        ),
+        _ => false,
+    }
+}
+
+// true if the error is something that might work if tried again later, e.g. discord
+// having a bad moment or rate limiting us.
+fn is_transient(e: &serenity::Error) -> bool {
+    match e {
+        serenity::Error::Http(e) => match &**e {
+            HttpError::UnsuccessfulRequest(r) => {
+                r.status_code.is_server_error() || r.status_code.as_u16() == 429
+            }
+            HttpError::Request(_) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// posts a "Racing this one?" message with buttons for the session that just opened.
+async fn send_poll(http: &Http, ch: ChannelId, msg: &Announcement, locale: Locale) {
+    let session_id = match msg.curr.session_id {
+        None => return,
+        Some(s) => s,
+    };
+    let res = ch
+        .send_message(http, |m| {
+            m.content(tr(locale, Msg::PollQuestion, &[&msg.series.name]))
+                .components(|c| {
+                    c.create_action_row(|row| {
+                        row.create_button(|b| {
+                            b.custom_id(format!("poll:1:{}:{}", msg.series.series_id, session_id))
+                                .label(text(locale, Msg::PollYes))
+                                .style(ButtonStyle::Success)
+                        })
+                        .create_button(|b| {
+                            b.custom_id(format!("poll:0:{}:{}", msg.series.series_id, session_id))
+                                .label(text(locale, Msg::PollNo))
+                                .style(ButtonStyle::Secondary)
+                        })
+                    })
+                })
+        })
+        .await;
+    if let Err(e) = res {
+        println!("Failed to send poll to channel {}: {:?}", ch, e);
+    }
 }
 
 pub struct Messenger<'a> {
     http: &'a Http,
     ch: ChannelId,
     buf: String,
+    // the buttons for the series in buf.
+    buttons: Vec<SeriesButtons>,
+    max_len: usize,
+    // messages that failed to send, that are worth trying again.
+    pub retry: Vec<String>,
+    // true if any message was sent.
+    pub delivered: bool,
+    // true if discord refused to let us post in the channel.
+    pub refused: bool,
+    // publish (crosspost) the next message, for announcement channels. Discord only allows
+    // a few an hour, so only the first message of a batch is published.
+    pub publish: bool,
+    // true if a message was published.
+    pub published: bool,
+    // the language for the buttons on announcements.
+    pub locale: Locale,
+    // the (series_id, message) of each series with buttons that made it into a message.
+    pub posted: Vec<(i64, MessageId)>,
+    // the most messages to send to the channel, the rest go to the outbox for later.
+    pub max_messages: usize,
+    // set once discord says we're sending too fast, everything after that waits for the outbox.
+    limited: bool,
+    pub stats: SendStats,
 }
+
+// SendStats is what a Messenger did with the messages it was given.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SendStats {
+    // messages discord accepted.
+    pub messages: usize,
+    // messages held back for the outbox, because of the message limit or a rate limit.
+    pub queued: usize,
+    // messages that failed and were dropped.
+    pub failed: usize,
+}
+
+impl std::ops::AddAssign for SendStats {
+    fn add_assign(&mut self, o: SendStats) {
+        self.messages += o.messages;
+        self.queued += o.queued;
+        self.failed += o.failed;
+    }
+}
+
 impl<'a> Messenger<'a> {
-    pub fn new(ch: ChannelId, http: &'a Http) -> Self {
+    pub fn new(ch: ChannelId, http: &'a Http, max_len: usize) -> Self {
         Messenger {
             ch,
             http,
             buf: String::new(),
+            buttons: Vec::new(),
+            max_len,
+            retry: Vec::new(),
+            delivered: false,
+            refused: false,
+            publish: false,
+            published: false,
+            locale: Locale::default(),
+            posted: Vec::new(),
+            max_messages: usize::MAX,
+            limited: false,
+            stats: SendStats::default(),
+        }
+    }
+    // true if the next message should wait for the outbox rather than be sent now.
+    fn holding(&self) -> bool {
+        self.limited || self.stats.messages >= self.max_messages
+    }
+    // queues content for the outbox, adding it to the last queued message if there's
+    // room so that a burst goes out as few messages as possible.
+    fn hold(&mut self, content: String) {
+        match self.retry.last_mut() {
+            Some(last) if last.len() + 1 + content.len() <= self.max_len => {
+                if !last.ends_with('\n') {
+                    last.push('\n');
+                }
+                last.push_str(content.trim_end());
+            }
+            _ => {
+                self.retry.push(content);
+                self.stats.queued += 1;
+            }
         }
     }
     pub async fn add(&mut self, line: &str) {
-        if self.buf.len() + 1 + line.len() > 1950 {
+        // a line that's too long for a message on its own would be refused, and dropped.
+        for part in split_line(line, self.max_len - 1) {
+            if self.buf.len() + 1 + part.len() > self.max_len {
+                self.flush().await;
+            }
+            self.buf.push_str(part);
+            self.buf.push('\n')
+        }
+    }
+    // adds a line about a series, the message it ends up in gets buttons for the series.
+    // A message only has room for so many rows of buttons, so another series starts a new
+    // message once it's full.
+    pub async fn add_for(&mut self, line: &str, buttons: &SeriesButtons) {
+        let new = !self
+            .buttons
+            .iter()
+            .any(|b| b.series_id == buttons.series_id);
+        if new && self.buttons.len() == MAX_BUTTON_ROWS {
             self.flush().await;
         }
-        //      if !self.buf.is_empty() {}
-        self.buf.push_str(line);
-        self.buf.push('\n')
+        self.add(line).await;
+        if new {
+            self.buttons.push(buttons.clone());
+        }
     }
     pub async fn flush(&mut self) {
         if !self.buf.is_empty() {
-            if let Err(e) = self.ch.say(self.http, &self.buf).await {
+            let buttons = std::mem::take(&mut self.buttons);
+            if self.holding() {
+                // the outbox sends plain text, so the buttons don't go with it.
+                let content = std::mem::take(&mut self.buf);
+                self.hold(content);
+                return;
+            }
+            let res = if buttons.is_empty() {
+                self.ch.say(self.http, &self.buf).await
+            } else {
+                self.ch
+                    .send_message(self.http, |m| {
+                        m.content(&self.buf)
+                            .components(|c| announcement_buttons(c, &buttons, self.locale))
+                    })
+                    .await
+            };
+            let content = std::mem::take(&mut self.buf);
+            if let Some(id) = self.sent(res, content).await {
+                self.posted
+                    .extend(buttons.iter().map(|b| (b.series_id, id)));
+            }
+        }
+    }
+    // sends a single embed with a field per (name, value).
+    pub async fn send_digest(
+        &mut self,
+        title: &str,
+        fields: Vec<(String, String)>,
+        buttons: &[SeriesButtons],
+    ) {
+        // if it needs retrying, it goes as plain text.
+        let content = fields
+            .iter()
+            .map(|(_, v)| v.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.holding() {
+            self.hold(content);
+            return;
+        }
+        let res = self
+            .ch
+            .send_message(self.http, |m| {
+                m.embed(|e| {
+                    e.title(title);
+                    for (name, value) in &fields {
+                        e.field(name, value, false);
+                    }
+                    e
+                });
+                if !buttons.is_empty() {
+                    m.components(|c| announcement_buttons(c, buttons, self.locale));
+                }
+                m
+            })
+            .await;
+        if let Some(id) = self.sent(res, content).await {
+            self.posted
+                .extend(buttons.iter().map(|b| (b.series_id, id)));
+        }
+    }
+    // returns the id of the message if it was sent.
+    async fn sent(&mut self, res: serenity::Result<Message>, content: String) -> Option<MessageId> {
+        match res {
+            Ok(msg) => {
+                self.delivered = true;
+                self.stats.messages += 1;
+                if self.publish {
+                    self.publish = false;
+                    match self.ch.crosspost(self.http, msg.id).await {
+                        Ok(_) => self.published = true,
+                        Err(e) => {
+                            println!("Failed to publish message in channel {}: {:?}", self.ch, e)
+                        }
+                    }
+                }
+                Some(msg.id)
+            }
+            Err(e) => {
                 println!("Failed to send message to channel {}: {:?}", self.ch, e);
+                if is_rate_limited(&e) {
+                    // serenity already waited on the limit it knew about, so sending more
+                    // now will just get the same answer.
+                    self.limited = true;
+                }
+                if is_transient(&e) {
+                    self.hold(content);
+                } else {
+                    self.stats.failed += 1;
+                    if is_refused(&e) {
+                        self.refused = true;
+                    }
+                }
+                None
             }
-            self.buf.clear();
         }
     }
 }
+
+// splits line into pieces of no more than max bytes, at the last space before the limit
+// where there is one so that words, mentions and <t:..> timestamps stay in one piece.
+// Long lines are rare, so this doesn't try to keep **bold** etc. from being split.
+pub fn split_line(line: &str, max: usize) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut rest = line;
+    while rest.len() > max {
+        let mut end = max;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let at = match rest[..end].rfind(char::is_whitespace) {
+            Some(i) if i > 0 => i,
+            _ => end,
+        };
+        res.push(rest[..at].trim_end());
+        rest = rest[at..].trim_start();
+    }
+    res.push(rest);
+    res
+}
+
+#[async_trait]
+impl Sink for Messenger<'_> {
+    async fn add(&mut self, line: &str) {
+        Messenger::add(self, line).await
+    }
+    async fn flush(&mut self) {
+        Messenger::flush(self).await
+    }
+}
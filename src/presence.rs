@@ -0,0 +1,83 @@
+use serenity::client::bridge::gateway::ShardManager;
+use serenity::model::gateway::Activity;
+use serenity::prelude::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{Duration, Instant};
+
+use crate::ir::RaceGuideEntry;
+use crate::ir_watcher::RaceGuideEvent;
+
+// discord limits how often a shard can change its presence, so don't go faster than this.
+const PRESENCE_MIN_SECS: u64 = 60;
+// the presence is sent again this often even if it hasn't changed, so shards that
+// (re)connected since the last update pick it up.
+const PRESENCE_REFRESH_MINS: u64 = 10;
+
+// presence_task sets the bot's activity on every shard from the race guide status,
+// e.g. "Watching 37 series · 1,204 entries live".
+pub async fn presence_task(
+    shards: Arc<Mutex<ShardManager>>,
+    mut rx: UnboundedReceiver<RaceGuideEvent>,
+) {
+    let mut last: Option<(String, Instant)> = None;
+    while let Some(evt) = rx.recv().await {
+        let entries = match evt {
+            RaceGuideEvent::Status(entries) => entries,
+            _ => continue,
+        };
+        let text = presence_text(&entries);
+        let due = match &last {
+            None => true,
+            Some((prev, at)) => {
+                at.elapsed() >= Duration::from_secs(PRESENCE_MIN_SECS)
+                    && (*prev != text
+                        || at.elapsed() >= Duration::from_secs(PRESENCE_REFRESH_MINS * 60))
+            }
+        };
+        if !due {
+            continue;
+        }
+        {
+            let manager = shards.lock().await;
+            let runners = manager.runners.lock().await;
+            for runner in runners.values() {
+                runner
+                    .runner_tx
+                    .set_activity(Some(Activity::watching(&text)));
+            }
+        }
+        last = Some((text, Instant::now()));
+    }
+    println!("presence channel closed, presence updates stopping");
+}
+
+// the series with registration open, and how many are registered for them.
+fn presence_text(entries: &HashMap<i64, RaceGuideEntry>) -> String {
+    let live: Vec<&RaceGuideEntry> = entries
+        .values()
+        .filter(|e| e.session_id.is_some())
+        .collect();
+    let count: i64 = live.iter().map(|e| e.entry_count).sum();
+    format!(
+        "{} series · {} entries live",
+        live.len(),
+        with_commas(count)
+    )
+}
+
+fn with_commas(n: i64) -> String {
+    let digits = n.abs().to_string();
+    let mut res = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            res.push(',');
+        }
+        res.push(c);
+    }
+    if n < 0 {
+        res.insert(0, '-');
+    }
+    res
+}
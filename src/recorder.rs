@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::config::Config;
+
+// Recorder keeps a copy of every iRacing API response in a directory per day, for
+// debugging and for replaying later. The files are named the way MockIrApi expects, so
+// a day's directory can be used as ir_fixtures or with regbot replay, as long as the
+// seasons & series were fetched that day. Once the recordings take up more than
+// max_bytes the oldest are deleted.
+pub struct Recorder {
+    dir: PathBuf,
+    max_bytes: u64,
+    // roughly how much is in dir, it's counted properly when it looks to be over.
+    used: AtomicU64,
+}
+
+impl Recorder {
+    // the recorder from the config, None if recording isn't turned on.
+    pub fn from_config(config: &Config) -> Option<Arc<Recorder>> {
+        let dir = PathBuf::from(config.record_dir.as_ref()?);
+        let used = files(&dir).iter().map(|(_, _, len)| len).sum();
+        Some(Arc::new(Recorder {
+            dir,
+            max_bytes: config.record_max_mb * 1024 * 1024,
+            used: AtomicU64::new(used),
+        }))
+    }
+
+    // saves the response body for the api path, the file writes happen on the blocking pool.
+    pub fn record(self: &Arc<Self>, path: &str, body: &str) {
+        let now = Utc::now();
+        let file = self
+            .dir
+            .join(now.format("%Y-%m-%d").to_string())
+            .join(file_name(path, now));
+        let (rec, body) = (self.clone(), body.to_string());
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = rec.write(&file, &body) {
+                println!("Failed to record {} {:?}", file.display(), e);
+            }
+        });
+    }
+
+    fn write(&self, file: &Path, body: &str) -> std::io::Result<()> {
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file, body)?;
+        let used = self.used.fetch_add(body.len() as u64, Ordering::SeqCst) + body.len() as u64;
+        if used > self.max_bytes {
+            self.rotate();
+        }
+        Ok(())
+    }
+
+    // deletes the oldest files until the recordings are back under 90% of max_bytes,
+    // so it's not doing this after every response.
+    fn rotate(&self) {
+        let mut all = files(&self.dir);
+        all.sort();
+        let mut used: u64 = all.iter().map(|(_, _, len)| len).sum();
+        let target = self.max_bytes / 10 * 9;
+        for (_, f, len) in all {
+            if used <= target {
+                break;
+            }
+            match std::fs::remove_file(&f) {
+                Ok(_) => used -= len,
+                Err(e) => println!("Failed to remove old recording {} {:?}", f.display(), e),
+            }
+            // tidy up the day's directory once it's empty, this fails if it isn't.
+            if let Some(parent) = f.parent() {
+                let _ = std::fs::remove_dir(parent);
+            }
+        }
+        self.used.store(used, Ordering::SeqCst);
+    }
+}
+
+// the fixture file name for the api path, see MockIrApi. Things there's only one of each
+// day are overwritten by the latest, the race guides are kept in time order.
+fn file_name(path: &str, at: DateTime<Utc>) -> String {
+    let (payload, query) = path.split_once('?').unwrap_or((path, ""));
+    let time = at.format("%H%M%S");
+    match payload {
+        "series/seasons" => "seasons.json".to_string(),
+        "series/get" => "series.json".to_string(),
+        "season/list" => "season_list.json".to_string(),
+        "carclass/get" => "carclass.json".to_string(),
+        "car/get" => "car.json".to_string(),
+        "hosted/combined_sessions" => "hosted.json".to_string(),
+        "season/race_guide" => format!("race_guide_{}.json", time),
+        "results/get" => format!(
+            "results_{}.json",
+            query.trim_start_matches("subsession_id=")
+        ),
+        // time first, so these don't look like fixtures to MockIrApi.
+        p => format!("{}_{}.json", time, p.replace(['/', ' '], "_")),
+    }
+}
+
+// every file under dir as (modified, path, size).
+fn files(dir: &Path) -> Vec<(std::time::SystemTime, PathBuf, u64)> {
+    let mut res = Vec::new();
+    let days = match std::fs::read_dir(dir) {
+        Ok(d) => d,
+        Err(_) => return res,
+    };
+    for day in days.flatten() {
+        let entries = match std::fs::read_dir(day.path()) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for f in entries.flatten() {
+            if let Ok(meta) = f.metadata() {
+                if meta.is_file() {
+                    res.push((
+                        meta.modified().unwrap_or(std::time::UNIX_EPOCH),
+                        f.path(),
+                        meta.len(),
+                    ));
+                }
+            }
+        }
+    }
+    res
+}
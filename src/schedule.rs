@@ -0,0 +1,239 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Utc};
+use serenity::http::Http;
+use serenity::model::prelude::ChannelId;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::db::{DbHandle, Reg, SeasonInfo};
+use crate::i18n::{text, tr, Locale, Msg};
+use crate::style::{digest_pages, escape_markdown};
+use crate::Messenger;
+
+// how often to check for posts that are due.
+const SCHEDULE_POLL_SECS: u64 = 60;
+
+// schedule_task sends the regular posts that channels asked for, the weekly post with
+// this week's track for each series they watch from /weeklypost, and the summary of
+// yesterday's racing from /dailydigest.
+pub async fn schedule_task(config: Arc<Config>, db: DbHandle) {
+    let http = Http::new(&config.discord_token);
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(SCHEDULE_POLL_SECS));
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        if let Err(e) = send_weekly_posts(&http, &config, &db, now).await {
+            println!("Failed to send weekly posts {:?}", e);
+        }
+        if let Err(e) = send_daily_digests(&http, &config, &db, now).await {
+            println!("Failed to send daily digests {:?}", e);
+        }
+    }
+}
+
+async fn send_weekly_posts(
+    http: &Http,
+    config: &Config,
+    db: &DbHandle,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let due: Vec<_> = db
+        .call(|db| db.weekly_posts())
+        .await?
+        .into_iter()
+        .filter(|p| {
+            let due = last_due(Some(p.weekday), p.hour, p.utc_offset_mins, now);
+            p.last_sent.is_none_or(|t| t < due)
+        })
+        .collect();
+    if due.is_empty() {
+        return Ok(());
+    }
+    let seasons = db.call(|db| db.get_series()).await?;
+    for p in due {
+        let ch = p.channel;
+        let regs = db.call(move |db| db.channel_regs(ch)).await?;
+        let locale = regs.first().map(|r| r.locale).unwrap_or_default();
+        let fields = week_schedule(&regs, &seasons, locale);
+        send_fields(
+            http,
+            config,
+            ch,
+            text(locale, Msg::WeekScheduleTitle),
+            &fields,
+        )
+        .await;
+        db.call(move |db| db.weekly_post_sent(ch, now)).await?;
+    }
+    Ok(())
+}
+
+async fn send_daily_digests(
+    http: &Http,
+    config: &Config,
+    db: &DbHandle,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let due: Vec<_> = db
+        .call(|db| db.daily_digests())
+        .await?
+        .into_iter()
+        .filter(|d| {
+            let due = last_due(None, d.hour, d.utc_offset_mins, now);
+            d.last_sent.is_none_or(|t| t < due)
+        })
+        .collect();
+    if due.is_empty() {
+        return Ok(());
+    }
+    let seasons = db.call(|db| db.get_series()).await?;
+    for d in due {
+        let ch = d.channel;
+        // yesterday is by the server's clock.
+        let tz = offset(d.utc_offset_mins);
+        let today = now.with_timezone(&tz).date_naive();
+        let to = midnight(today, &tz);
+        let from = midnight(today - Duration::days(1), &tz);
+        let (regs, history) = db
+            .call(move |db| {
+                Ok::<_, rusqlite::Error>((db.channel_regs(ch)?, db.session_history(from, to)?))
+            })
+            .await?;
+        let locale = regs.first().map(|r| r.locale).unwrap_or_default();
+        let fields = day_activity(&regs, &seasons, &history, locale);
+        send_fields(
+            http,
+            config,
+            ch,
+            text(locale, Msg::DailyDigestTitle),
+            &fields,
+        )
+        .await;
+        db.call(move |db| db.daily_digest_sent(ch, now)).await?;
+    }
+    Ok(())
+}
+
+async fn send_fields(
+    http: &Http,
+    config: &Config,
+    ch: ChannelId,
+    title: &str,
+    fields: &[(String, String)],
+) {
+    if fields.is_empty() {
+        return;
+    }
+    let mut msger = Messenger::new(ch, http, config.max_message_len);
+    for page in digest_pages(fields) {
+        msger.send_digest(title, page, &[]).await;
+    }
+}
+
+fn offset(utc_offset_mins: i64) -> FixedOffset {
+    FixedOffset::east_opt(utc_offset_mins as i32 * 60).unwrap_or_else(|| FixedOffset::east(0))
+}
+
+fn midnight(date: NaiveDate, tz: &FixedOffset) -> DateTime<Utc> {
+    tz.from_local_datetime(&date.and_hms(0, 0, 0))
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+// the most recent time at or before now that a post was supposed to go out, at hour
+// on weekday (0 is Monday) in the server's timezone, or every day for None.
+fn last_due(
+    weekday: Option<i64>,
+    hour: i64,
+    utc_offset_mins: i64,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let tz = offset(utc_offset_mins);
+    let local = now.with_timezone(&tz);
+    let days_back = match weekday {
+        Some(d) => (local.weekday().num_days_from_monday() as i64 - d).rem_euclid(7),
+        None => 0,
+    };
+    let date = local.date_naive() - Duration::days(days_back);
+    let due = date
+        .and_hms_opt(hour as u32, 0, 0)
+        .and_then(|t| tz.from_local_datetime(&t).single())
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or(now);
+    if due <= now {
+        due
+    } else if weekday.is_some() {
+        due - Duration::days(7)
+    } else {
+        due - Duration::days(1)
+    }
+}
+
+// the series the channel is watching, skipping the ones it wouldn't announce anyway.
+fn watched_series<'a>(regs: &[Reg], seasons: &'a HashMap<i64, SeasonInfo>) -> Vec<&'a SeasonInfo> {
+    let mut series: Vec<&SeasonInfo> = regs
+        .iter()
+        .filter(|r| !(r.muted || r.blocked || r.inactive || r.disabled))
+        .filter_map(|r| seasons.get(&r.series_id))
+        .collect();
+    series.sort_by(|a, b| a.name.cmp(&b.name));
+    series.dedup_by_key(|s| s.series_id);
+    series
+}
+
+// a (series name, track) field for each series the channel is watching.
+fn week_schedule(
+    regs: &[Reg],
+    seasons: &HashMap<i64, SeasonInfo>,
+    locale: Locale,
+) -> Vec<(String, String)> {
+    watched_series(regs, seasons)
+        .into_iter()
+        .map(|s| {
+            let mut track = format!(
+                "{} {}: {}",
+                text(locale, Msg::TickerWeek),
+                s.week + 1,
+                escape_markdown(&s.track_name)
+            );
+            if !s.track_config.is_empty() {
+                track.push_str(&format!(" ({})", escape_markdown(&s.track_config)));
+            }
+            (s.name.clone(), track)
+        })
+        .collect()
+}
+
+// a (series name, summary) field for each watched series that raced in the history,
+// busiest first.
+fn day_activity(
+    regs: &[Reg],
+    seasons: &HashMap<i64, SeasonInfo>,
+    history: &[(i64, i64)],
+    locale: Locale,
+) -> Vec<(String, String)> {
+    let mut res = Vec::new();
+    for s in watched_series(regs, seasons) {
+        let counts: Vec<i64> = history
+            .iter()
+            .filter(|(id, _)| *id == s.series_id)
+            .map(|(_, c)| *c)
+            .collect();
+        let peak = match counts.iter().max() {
+            Some(p) if *p > 0 => *p,
+            _ => continue,
+        };
+        let splits = 1 + (peak - 1) / s.reg_split.max(1);
+        let official = counts.iter().filter(|c| **c >= s.reg_official).count();
+        let line = tr(
+            locale,
+            Msg::DailyDigestLine,
+            &[&peak, &splits, &official, &counts.len()],
+        );
+        res.push((peak, s.name.clone(), line));
+    }
+    res.sort_by_key(|r| std::cmp::Reverse(r.0));
+    res.into_iter()
+        .map(|(_, name, line)| (name, line))
+        .collect()
+}
@@ -0,0 +1,17 @@
+use serenity::async_trait;
+
+// Sink is somewhere lines of text can be sent, e.g. a discord channel or a telegram chat.
+// Lines are batched up into as few messages as they fit in until flush.
+#[async_trait]
+pub trait Sink: Send {
+    async fn add(&mut self, line: &str);
+    async fn flush(&mut self);
+}
+
+// sends all the lines to the sink.
+pub async fn send_lines<S: Sink, L: AsRef<str> + Sync>(sink: &mut S, lines: &[L]) {
+    for line in lines {
+        sink.add(line.as_ref()).await;
+    }
+    sink.flush().await;
+}
@@ -0,0 +1,294 @@
+use crate::i18n::{text, Locale, Msg};
+use crate::ir::RaceGuideEntry;
+use crate::ir_watcher::{Announcement, AnnouncementType};
+use anyhow::anyhow;
+use chrono::{Duration, Utc};
+use std::str::FromStr;
+
+// Style controls how announcements are written out for a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    // a sentence per announcement.
+    #[default]
+    Standard,
+    // a short single line per announcement, for busy feeds.
+    Ticker,
+}
+
+impl Style {
+    pub const ALL: [Style; 2] = [Style::Standard, Style::Ticker];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Style::Standard => "standard",
+            Style::Ticker => "ticker",
+        }
+    }
+    pub fn render(&self, ann: &Announcement, locale: Locale) -> String {
+        match self {
+            Style::Standard => ann.render(locale),
+            Style::Ticker => ticker(ann, locale),
+        }
+    }
+}
+
+// Layout controls how a channel's announcements from one poll are put into messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    // a line per announcement, packed into as few messages as they fit in.
+    #[default]
+    Lines,
+    // a single digest embed with a field per series.
+    Digest,
+}
+
+impl Layout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Lines => "lines",
+            Layout::Digest => "digest",
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Layout::Lines, Layout::Digest]
+            .into_iter()
+            .find(|l| l.name() == s)
+            .ok_or_else(|| anyhow!("unknown layout {}", s))
+    }
+}
+
+// escapes the characters discord would treat as formatting, for text that comes from
+// iRacing or its members, like series, track & session names. @ gets a zero width space
+// after it so that a name can't mention everyone.
+pub fn escape_markdown(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '*' | '_' | '~' | '`' | '|' | '<' | '>' | '[' | ']' | '#' => {
+                res.push('\\');
+                res.push(c);
+            }
+            '@' => res.push_str("@\u{200b}"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+// discord's limits for embeds.
+const EMBED_MAX_FIELDS: usize = 25;
+const EMBED_MAX_FIELD_LEN: usize = 1024;
+const EMBED_MAX_LEN: usize = 5800;
+
+// groups the (series name, line) items into embed fields, with the lines for a series
+// in one field. Returns the fields for each embed needed to stay within discord's limits.
+pub fn digest_pages(items: &[(String, String)]) -> Vec<Vec<(String, String)>> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for (name, line) in items {
+        match fields.last_mut() {
+            Some((n, v)) if n == name => {
+                v.push('\n');
+                v.push_str(line);
+            }
+            _ => fields.push((name.clone(), line.clone())),
+        }
+    }
+    let mut pages = vec![Vec::new()];
+    let mut len = 0;
+    for (name, value) in fields {
+        let name: String = name.chars().take(256).collect();
+        let value: String = value.chars().take(EMBED_MAX_FIELD_LEN).collect();
+        let page = pages.last_mut().unwrap();
+        if !page.is_empty()
+            && (page.len() == EMBED_MAX_FIELDS || len + name.len() + value.len() > EMBED_MAX_LEN)
+        {
+            pages.push(Vec::new());
+            len = 0;
+        }
+        len += name.len() + value.len();
+        pages.last_mut().unwrap().push((name, value));
+    }
+    pages.retain(|p| !p.is_empty());
+    pages
+}
+
+impl FromStr for Style {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Style::ALL
+            .into_iter()
+            .find(|st| st.name() == s)
+            .ok_or_else(|| anyhow!("unknown style {}", s))
+    }
+}
+
+// e.g. "📈 IMSA 84 ×2 · 12m"
+fn ticker(ann: &Announcement, locale: Locale) -> String {
+    let mins = (ann.curr.start_time - Utc::now() + Duration::seconds(29))
+        .num_minutes()
+        .max(0);
+    let name = &ann.series.short_name;
+    match ann.ann_type {
+        AnnouncementType::Open => format!(
+            "\u{1f7e2} {} {} \u{b7} {}m",
+            name,
+            text(locale, Msg::TickerOpen),
+            mins
+        ),
+        AnnouncementType::Count => format!(
+            "\u{1f4c8} {} {}{} \u{b7} {}m{}",
+            name,
+            ann.curr.entry_count,
+            ticker_splits(ann, &ann.curr),
+            mins,
+            ticker_sof(ann)
+        ),
+        AnnouncementType::Closed => format!(
+            "\u{26d4} {} {}{} {}{}",
+            name,
+            ann.prev.entry_count,
+            ticker_splits(ann, &ann.prev),
+            text(locale, Msg::TickerClosed),
+            ticker_sof(ann)
+        ),
+        AnnouncementType::WeekOpen => format!(
+            "\u{23f1} {} {}{} {}",
+            name,
+            text(locale, Msg::TickerWeek),
+            ann.series.week + 1,
+            ann.series.track_name
+        ),
+        AnnouncementType::NewSeason => format!(
+            "\u{1f389} {} {} {}{} {}",
+            name,
+            ann.series.season_label(),
+            text(locale, Msg::TickerWeek),
+            ann.series.week + 1,
+            ann.series.track_name
+        ),
+        AnnouncementType::NewWeek => format!(
+            "\u{1f5d3} {} {}{} {}",
+            name,
+            text(locale, Msg::TickerWeek),
+            ann.series.week + 1,
+            ann.series.track_name
+        ),
+        AnnouncementType::LastDay => {
+            format!(
+                "\u{1f3c1} {} {}{} {}",
+                name,
+                text(locale, Msg::TickerWeek),
+                ann.series.week + 1,
+                text(locale, Msg::TickerLastDay)
+            )
+        }
+        // e.g. "🔮 IMSA 8 → ~14 · 25m"
+        AnnouncementType::Predicted => format!(
+            "\u{1f52e} {} {} \u{2192} ~{} \u{b7} {}m",
+            name,
+            ann.curr.entry_count,
+            ann.predicted.unwrap_or(ann.series.reg_official),
+            mins
+        ),
+        // e.g. "🏆 IMSA Jane Doe ×2 · SOF 2140"
+        AnnouncementType::Results => match &ann.results {
+            None => format!("\u{1f3c6} {}", name),
+            Some(r) => format!(
+                "\u{1f3c6} {} {}{} \u{b7} SOF {}",
+                name,
+                r.winner_text(),
+                if r.splits > 1 {
+                    format!(" \u{d7}{}", r.splits)
+                } else {
+                    String::new()
+                },
+                r.sof
+            ),
+        },
+    }
+}
+
+fn ticker_splits(ann: &Announcement, rge: &RaceGuideEntry) -> String {
+    let split_count = rge.num_splits(ann.series.reg_split);
+    if rge.entry_count < ann.series.reg_official {
+        "".to_string()
+    } else if split_count < 2 {
+        " \u{2714}".to_string()
+    } else {
+        format!(" \u{d7}{}", split_count)
+    }
+}
+
+fn ticker_sof(ann: &Announcement) -> String {
+    match ann.sof {
+        Some(sof) => format!(" \u{b7} SOF ~{}", sof),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::series;
+
+    #[test]
+    fn test_ticker() {
+        let samples = Announcement::samples(&series(1, "IMSA"));
+        let got: Vec<(&str, String)> = samples
+            .iter()
+            .map(|a| (a.ann_type.name(), ticker(a, Locale::En)))
+            .collect();
+        let want = [
+            (AnnouncementType::Open, "\u{1f7e2} IMSA open \u{b7} 45m"),
+            (
+                AnnouncementType::Count,
+                "\u{1f4c8} IMSA 8 \u{2714} \u{b7} 12m \u{b7} SOF ~1850",
+            ),
+            (
+                AnnouncementType::Closed,
+                "\u{26d4} IMSA 11 \u{2714} closed \u{b7} SOF ~1850",
+            ),
+            (AnnouncementType::WeekOpen, "\u{23f1} IMSA wk3 Spa"),
+            (AnnouncementType::NewWeek, "\u{1f5d3} IMSA wk3 Spa"),
+            (
+                AnnouncementType::NewSeason,
+                "\u{1f389} IMSA 2026 S3 wk3 Spa",
+            ),
+            (AnnouncementType::LastDay, "\u{1f3c1} IMSA wk3 last day"),
+            (
+                AnnouncementType::Predicted,
+                "\u{1f52e} IMSA 4 \u{2192} ~10 \u{b7} 30m",
+            ),
+            (
+                AnnouncementType::Results,
+                "\u{1f3c6} IMSA Jane Doe \u{d7}2 \u{b7} SOF 2140",
+            ),
+        ];
+        assert_eq!(got.len(), want.len());
+        for (ann_type, text) in want {
+            let (_, g) = got.iter().find(|(t, _)| *t == ann_type.name()).unwrap();
+            assert_eq!(g, text, "{:?}", ann_type);
+        }
+    }
+
+    #[test]
+    fn test_ticker_splits_and_locale() {
+        let mut ann = Announcement::samples(&series(1, "IMSA"))
+            .into_iter()
+            .find(|a| matches!(a.ann_type, AnnouncementType::Closed))
+            .unwrap();
+        ann.prev.entry_count = 45;
+        ann.sof = None;
+        assert_eq!(ticker(&ann, Locale::En), "\u{26d4} IMSA 45 \u{d7}3 closed");
+        assert_eq!(
+            ticker(&ann, Locale::De),
+            "\u{26d4} IMSA 45 \u{d7}3 geschlossen"
+        );
+        ann.prev.entry_count = 5;
+        assert_eq!(ticker(&ann, Locale::En), "\u{26d4} IMSA 5 closed");
+    }
+}
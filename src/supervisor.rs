@@ -0,0 +1,35 @@
+use std::future::Future;
+use std::sync::Arc;
+use tokio::spawn;
+use tokio::time::{Duration, Instant};
+
+use crate::health::Health;
+
+const DEF_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(120);
+// a task that ran for this long before stopping gets restarted straight away.
+const RESET_BACKOFF_AFTER: Duration = Duration::from_secs(600);
+
+// supervise runs the task that start makes, and when it stops or panics, starts a new
+// one after a backoff, forever. The task should call health.heartbeat(name) as it goes
+// so that a task that's stuck rather than stopped shows up in the health status.
+pub async fn supervise<F, Fut>(name: &'static str, health: Arc<Health>, mut start: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut backoff = DEF_BACKOFF;
+    loop {
+        let started = Instant::now();
+        match spawn(start()).await {
+            Err(e) if e.is_panic() => println!("{} panicked, restarting", name),
+            _ => println!("{} stopped, restarting", name),
+        }
+        health.restarted(name);
+        if started.elapsed() > RESET_BACKOFF_AFTER {
+            backoff = DEF_BACKOFF;
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,108 @@
+use serde_json::json;
+use serenity::async_trait;
+use serenity::model::prelude::ChannelId;
+use std::collections::HashMap;
+
+use crate::config::{Config, Preset};
+use crate::db::{Reg, SeasonInfo};
+use crate::ir_watcher::Announcement;
+use crate::sink::Sink;
+use crate::style::Style;
+
+// telegram won't take messages over 4096 chars.
+const TELEGRAM_MAX_LEN: usize = 4096;
+
+// TelegramSink sends lines to a telegram chat via the bot API.
+pub struct TelegramSink<'a> {
+    client: &'a reqwest::Client,
+    token: &'a str,
+    chat_id: &'a str,
+    buf: String,
+}
+
+impl<'a> TelegramSink<'a> {
+    pub fn new(client: &'a reqwest::Client, token: &'a str, chat_id: &'a str) -> Self {
+        TelegramSink {
+            client,
+            token,
+            chat_id,
+            buf: String::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for TelegramSink<'_> {
+    async fn add(&mut self, line: &str) {
+        if !self.buf.is_empty() && self.buf.len() + 1 + line.len() > TELEGRAM_MAX_LEN {
+            self.flush().await;
+        }
+        self.buf.push_str(line);
+        self.buf.push('\n');
+    }
+    async fn flush(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let text = std::mem::take(&mut self.buf);
+        let res = self
+            .client
+            .post(format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                self.token
+            ))
+            .json(&json!({ "chat_id": self.chat_id, "text": text }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        // the error has the url in it, which has the token in it.
+        if let Err(e) = res {
+            println!(
+                "Failed to send to telegram chat {}: status {:?} timeout {}",
+                self.chat_id,
+                e.status(),
+                e.is_timeout()
+            );
+        }
+    }
+}
+
+// sends the announcements each telegram chat in the config wants.
+pub async fn announce(
+    client: &reqwest::Client,
+    config: &Config,
+    msgs: &HashMap<i64, Announcement>,
+) {
+    let token = match &config.telegram_token {
+        Some(t) => t,
+        None => return,
+    };
+    for chat in &config.telegram {
+        let preset = chat
+            .preset
+            .as_ref()
+            .and_then(|p| config.presets.get(p))
+            .cloned()
+            .unwrap_or_default();
+        let mut sink = TelegramSink::new(client, token, &chat.chat_id);
+        // msgs are as they came from iRacing, the names are only escaped for discord.
+        for msg in chat.series.iter().filter_map(|id| msgs.get(id)) {
+            if chat_reg(&msg.series, &preset).wants(msg) {
+                sink.add(&Style::Standard.render(msg, chat.locale())).await;
+            }
+        }
+        sink.flush().await;
+    }
+}
+
+// the watch a telegram chat has on the series, from its preset the same way /watch does.
+pub fn chat_reg(series: &SeasonInfo, p: &Preset) -> Reg {
+    // telegram chats don't have a discord channel.
+    let mut reg = Reg::new(None, ChannelId(0), series);
+    p.apply(&mut reg);
+    // there's no buttons to vote with.
+    reg.poll = false;
+    // without a db row there's nowhere to remember the last count.
+    reg.cooldown_mins = 0;
+    reg
+}
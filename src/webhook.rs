@@ -0,0 +1,193 @@
+use anyhow::anyhow;
+use serde_json::json;
+use serenity::model::prelude::ChannelId;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::split_line;
+
+// how long to wait for a webhook before giving up on it.
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+// WebhookFormat is how announcements are posted to a webhook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    // a discord webhook, the announcements are the message content.
+    Discord,
+    // a JSON object with a list of announcements, for feeding other systems.
+    Json,
+}
+
+impl WebhookFormat {
+    pub const ALL: [WebhookFormat; 2] = [WebhookFormat::Discord, WebhookFormat::Json];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookFormat::Discord => "discord",
+            WebhookFormat::Json => "json",
+        }
+    }
+    // the format to send to url in, discord webhooks get messages and the operator's
+    // json_hosts get JSON. None for anywhere else, it's not somewhere /webhook can send to.
+    pub fn for_url(url: &str, json_hosts: &[String]) -> Option<Self> {
+        if is_discord_webhook(url) {
+            return Some(WebhookFormat::Discord);
+        }
+        let u = reqwest::Url::parse(url).ok()?;
+        let host = u.host_str()?;
+        (u.scheme() == "https"
+            && u.port().is_none()
+            && json_hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+        .then_some(WebhookFormat::Json)
+    }
+}
+
+// true if url is a discord webhook. Other than these /webhook only takes the hosts the
+// operator lists, otherwise anyone with admin in a server could have the bot POST to any
+// host it can reach.
+pub fn is_discord_webhook(url: &str) -> bool {
+    match reqwest::Url::parse(url) {
+        Ok(u) => {
+            u.scheme() == "https"
+                && matches!(u.host_str(), Some("discord.com") | Some("discordapp.com"))
+                && u.port().is_none()
+                && u.path().starts_with("/api/webhooks/")
+        }
+        Err(_) => false,
+    }
+}
+
+impl FromStr for WebhookFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WebhookFormat::ALL
+            .into_iter()
+            .find(|f| f.name() == s)
+            .ok_or_else(|| anyhow!("unknown webhook format {}", s))
+    }
+}
+
+// WebhookTarget is where a channel's announcements go instead of the channel itself.
+#[derive(Debug, Clone)]
+pub struct WebhookTarget {
+    pub url: String,
+    pub format: WebhookFormat,
+}
+
+pub fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+        // a webhook has no reason to send us anywhere else.
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap_or_default()
+}
+
+// sends the (series name, text) items for the channel to the webhook, discord webhooks
+// get as many messages as it takes to stay under max_len.
+pub async fn send(
+    client: &reqwest::Client,
+    target: &WebhookTarget,
+    ch: ChannelId,
+    items: &[(String, String)],
+    max_len: usize,
+) -> reqwest::Result<()> {
+    match target.format {
+        WebhookFormat::Discord => {
+            let mut buf = String::new();
+            for line in items.iter().flat_map(|(_, l)| split_line(l, max_len - 1)) {
+                if !buf.is_empty() && buf.len() + 1 + line.len() > max_len {
+                    post(client, &target.url, &json!({ "content": buf })).await?;
+                    buf.clear();
+                }
+                buf.push_str(line);
+                buf.push('\n');
+            }
+            if !buf.is_empty() {
+                post(client, &target.url, &json!({ "content": buf })).await?;
+            }
+            Ok(())
+        }
+        WebhookFormat::Json => {
+            let anns: Vec<_> = items
+                .iter()
+                .map(|(series, text)| json!({ "series": series, "text": text }))
+                .collect();
+            let body = json!({ "channel_id": ch.0.to_string(), "announcements": anns });
+            post(client, &target.url, &body).await
+        }
+    }
+}
+
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    body: &serde_json::Value,
+) -> reqwest::Result<()> {
+    client
+        .post(url)
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_discord_webhook() {
+        for (url, ok) in [
+            ("https://discord.com/api/webhooks/123/abc", true),
+            ("https://discordapp.com/api/webhooks/123/abc", true),
+            ("http://discord.com/api/webhooks/123/abc", false),
+            ("https://discord.com/api/channels/123", false),
+            ("https://discord.com:8443/api/webhooks/123/abc", false),
+            (
+                "https://discord.com.example.org/api/webhooks/123/abc",
+                false,
+            ),
+            (
+                "https://example.org/discord.com/api/webhooks/123/abc",
+                false,
+            ),
+            ("https://169.254.169.254/api/webhooks/1", false),
+            ("not a url", false),
+        ] {
+            assert_eq!(is_discord_webhook(url), ok, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_for_url() {
+        let hosts = vec!["hooks.example.org".to_string()];
+        for (url, want) in [
+            (
+                "https://discord.com/api/webhooks/123/abc",
+                Some(WebhookFormat::Discord),
+            ),
+            (
+                "https://hooks.example.org/regbot",
+                Some(WebhookFormat::Json),
+            ),
+            (
+                "https://HOOKS.example.org/regbot",
+                Some(WebhookFormat::Json),
+            ),
+            ("http://hooks.example.org/regbot", None),
+            ("https://hooks.example.org:8443/regbot", None),
+            ("https://other.example.org/regbot", None),
+            ("https://hooks.example.org.evil.net/regbot", None),
+            ("not a url", None),
+        ] {
+            assert_eq!(WebhookFormat::for_url(url, &hosts), want, "{}", url);
+        }
+        // without any hosts it's discord only.
+        assert_eq!(
+            WebhookFormat::for_url("https://hooks.example.org/regbot", &[]),
+            None
+        );
+    }
+}